@@ -5,9 +5,17 @@ use std::io::{self, BufRead};
 use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
 
-use flate2::read::GzDecoder;
 use reqwest::blocking::get;
-use tar::Archive;
+
+#[path = "build/archive.rs"]
+mod archive;
+
+/// The capabilities that differ across supported libddwaf releases, as `cfg` names along with the
+/// function whose presence in `ddwaf.h` indicates them.
+///
+/// Each detected capability is enabled as a `cfg` for this crate, and forwarded to dependents
+/// through the `links` metadata (as `DEP_DDWAF_<CFG>`).
+const CAPABILITIES: &[(&str, &str)] = &[("ddwaf_has_from_json", "ddwaf_object_from_json")];
 
 fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
@@ -48,18 +56,32 @@ fn main() {
     rustls::crypto::CryptoProvider::install_default(rustls::crypto::aws_lc_rs::default_provider())
         .expect("Failed to set rustls default crypto provider");
 
-    // Read the Rust crate version from the environment variable set by Cargo
-    let version =
-        env::var("CARGO_PKG_VERSION").expect("CARGO_PKG_VERSION environment variable not set");
+    // Use the libddwaf release matching the Rust crate version, unless another one is requested
+    let version = match env::var("LIBDDWAF_VERSION") {
+        Ok(version) if !version.is_empty() => version,
+        _ => env::var("CARGO_PKG_VERSION").expect("CARGO_PKG_VERSION environment variable not set"),
+    };
+    println!("cargo::rerun-if-env-changed=LIBDDWAF_VERSION");
 
     // Check if a custom libddwaf installation prefix is provided
     let (include_dir, lib_dir, soname) = if let Some(prefix) = env::var_os("LIBDDWAF_PREFIX") {
         from_installed_libddwaf(&prefix)
     } else {
+        // Dependents can tell which release they are linked against (installed ones are unknown)
+        println!("cargo::metadata=version={version}");
         from_github_release(&version, &out_dir)
     };
     println!("cargo::rerun-if-env-changed=LIBDDWAF_PREFIX");
 
+    let header = fs::read_to_string(include_dir.join("ddwaf.h")).expect("Failed to read ddwaf.h");
+    for (cfg, function) in CAPABILITIES {
+        println!("cargo::rustc-check-cfg=cfg({cfg})");
+        if declares_function(&header, function) {
+            println!("cargo::rustc-cfg={cfg}");
+            println!("cargo::metadata={cfg}=1");
+        }
+    }
+
     // Add library search path and link directive
     println!(
         "cargo::rustc-link-search=native={}",
@@ -135,6 +157,17 @@ fn main() {
     println!("cargo::rerun-if-changed=build.rs");
 }
 
+/// Returns true if the `header` contents declare a function with the provided name.
+fn declares_function(header: &str, function: &str) -> bool {
+    header.match_indices(function).any(|(idx, _)| {
+        let preceded_by_ident = header[..idx]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+        !preceded_by_ident && header[idx + function.len()..].trim_start().starts_with('(')
+    })
+}
+
 fn from_installed_libddwaf(prefix: impl AsRef<OsStr>) -> (PathBuf, PathBuf, &'static str) {
     println!(
         "cargo::warning=Using libddwaf installation from prefix: {:?}",
@@ -168,89 +201,80 @@ fn from_github_release(version: &str, out_dir: &Path) -> (PathBuf, PathBuf, &'st
     // Target triple for the current build
     let target = env::var("TARGET").expect("TARGET environment variable not set");
 
-    // Output directory
-    let download_dir = out_dir.join("download").join(&target);
+    // Output directory, specific to the release so that changing it does not reuse stale files
+    let download_dir = out_dir.join("download").join(version).join(&target);
     let include_dir = download_dir.join("include");
     let lib_dir = download_dir.join("lib");
 
-    let (archive, soname, is_override) = {
-        // Base URL for downloading the library
-        let base_url = "https://github.com/DataDog/libddwaf/releases/download";
-
-        // Map the target triple to the correct library archive
-        let (archive_name, soname) = match target.as_str() {
-            "x86_64-unknown-linux-gnu" => (
-                format!("libddwaf-{version}-x86_64-linux-musl.tar.gz"),
-                "libddwaf.so",
-            ),
-            // "x86_64-alpine-linux-musl" is Alpine's own (non-rustup) cargo/rustc
-            // reporting its host triple with an "alpine" vendor instead of "unknown".
-            "x86_64-unknown-linux-musl" | "x86_64-alpine-linux-musl" => (
-                format!("libddwaf-{version}-x86_64-linux-musl.tar.gz"),
-                "libddwaf.so",
-            ),
-            "aarch64-unknown-linux-gnu" => (
-                format!("libddwaf-{version}-aarch64-linux-musl.tar.gz"),
-                "libddwaf.so",
-            ),
-            "aarch64-unknown-linux-musl" | "aarch64-alpine-linux-musl" => (
-                format!("libddwaf-{version}-aarch64-linux-musl.tar.gz"),
-                "libddwaf.so",
-            ),
-            "armv7-unknown-linux-musleabihf" => (
-                format!("libddwaf-{version}-armv7-linux-musl.tar.gz"),
-                "libddwaf.so",
-            ),
-            "aarch64-apple-darwin" => (
-                format!("libddwaf-{version}-darwin-arm64.tar.gz"),
-                "libddwaf.dylib",
-            ),
-            "x86_64-apple-darwin" => (
-                format!("libddwaf-{version}-darwin-x86_64.tar.gz"),
-                "libddwaf.dylib",
-            ),
-            target => panic!("Unsupported target platform: {target}"),
-        };
+    // Base URL for downloading the library, which can point to a mirror of the GitHub releases
+    let base_url = match env::var("LIBDDWAF_DOWNLOAD_BASE_URL") {
+        Ok(url) if !url.is_empty() => url,
+        _ => archive::DEFAULT_BASE_URL.to_string(),
+    };
+    println!("cargo::rerun-if-env-changed=LIBDDWAF_DOWNLOAD_BASE_URL");
+    // Expected checksum of the archive, if integrity checking is requested
+    let checksum_var = archive::sha256_env_var(&target);
+    println!("cargo::rerun-if-env-changed={checksum_var}");
+
+    // Map the target triple to the correct library archive
+    let (archive_name, soname) = match target.as_str() {
+        "x86_64-unknown-linux-gnu" => (
+            format!("libddwaf-{version}-x86_64-linux-musl.tar.gz"),
+            "libddwaf.so",
+        ),
+        // "x86_64-alpine-linux-musl" is Alpine's own (non-rustup) cargo/rustc
+        // reporting its host triple with an "alpine" vendor instead of "unknown".
+        "x86_64-unknown-linux-musl" | "x86_64-alpine-linux-musl" => (
+            format!("libddwaf-{version}-x86_64-linux-musl.tar.gz"),
+            "libddwaf.so",
+        ),
+        "aarch64-unknown-linux-gnu" => (
+            format!("libddwaf-{version}-aarch64-linux-musl.tar.gz"),
+            "libddwaf.so",
+        ),
+        "aarch64-unknown-linux-musl" | "aarch64-alpine-linux-musl" => (
+            format!("libddwaf-{version}-aarch64-linux-musl.tar.gz"),
+            "libddwaf.so",
+        ),
+        "armv7-unknown-linux-musleabihf" => (
+            format!("libddwaf-{version}-armv7-linux-musl.tar.gz"),
+            "libddwaf.so",
+        ),
+        "aarch64-apple-darwin" => (
+            format!("libddwaf-{version}-darwin-arm64.tar.gz"),
+            "libddwaf.dylib",
+        ),
+        "x86_64-apple-darwin" => (
+            format!("libddwaf-{version}-darwin-x86_64.tar.gz"),
+            "libddwaf.dylib",
+        ),
+        target => panic!("Unsupported target platform: {target}"),
+    };
 
+    // Download and extract the archive, unless this was already done by a previous build
+    let ar = env::var("AR").unwrap_or("ar".to_string());
+    if !include_dir.exists() || !lib_dir.exists() {
         // Construct the download URL
-        let archive_url = format!("{base_url}/{version}/{archive_name}");
+        let archive_url = archive::archive_url(&base_url, version, &archive_name);
         let response = get(&archive_url).expect("Failed to download archive");
         assert!(
             response.status().is_success(),
             "Failed to download archive from {archive_url}: {status}",
             status = response.status()
         );
-        (response, soname, false)
-    };
+        let archive = response.bytes().expect("Failed to download archive");
 
-    // Extract the archive
-    let ar = env::var("AR").unwrap_or("ar".to_string());
-    if is_override || !include_dir.exists() || !lib_dir.exists() {
-        fs::create_dir_all(&download_dir).expect("Failed to create extraction directory");
-
-        let reader = GzDecoder::new(archive);
-        let mut tar = Archive::new(reader);
-        for entry in tar.entries().expect("Failed to get tar archive entries") {
-            let mut entry = entry.expect("Failed to get tar archive entry");
-            if entry.header().entry_type().is_dir() {
-                continue;
+        if let Some(expected) = env::var(&checksum_var).ok().filter(|e| !e.is_empty()) {
+            if let Err(error) = archive::verify_sha256(&archive, &expected) {
+                panic!("Failed to verify {archive_url} against {checksum_var}: {error}");
             }
+        }
 
-            let path = entry.path().expect("Failed to get tar archive entry path");
-            let mut components = path.components();
-            if components.next().is_none() {
-                continue;
-            }
-            let out_path = download_dir.join(components.as_path());
-            let out_dir = out_path
-                .parent()
-                .expect("Failed to compute dir name of output file");
-            fs::create_dir_all(out_dir).expect("Failed to create directory for archive entry");
-            let mut file =
-                File::create(&out_path).expect("Failed to create file for archive entry");
-            io::copy(&mut entry, &mut file)
-                .expect("Failed to write archive entry contents to file");
+        fs::create_dir_all(&download_dir).expect("Failed to create extraction directory");
 
+        let extracted =
+            archive::extract(&archive[..], &download_dir).expect("Failed to extract archive");
+        for out_path in extracted {
             if out_path.extension() == Some(OsStr::new("a")) {
                 // We remove the `Unwind*` objects from the static archives, as they are the LLVM `libunwind` unwinder,
                 // which conflicts with the unwinder provided by the rust standard library (there can only be one
@@ -0,0 +1,109 @@
+//! Verification and extraction of the `libddwaf` release archives.
+//!
+//! This is used by the build script, and is also included by the `build_archive` tests so that it
+//! can be exercised against a fixture archive, without network access.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use tar::Archive;
+
+/// The URL releases are downloaded from, unless `LIBDDWAF_DOWNLOAD_BASE_URL` is set (e.g, to use a
+/// mirror that follows the same layout).
+pub const DEFAULT_BASE_URL: &str = "https://github.com/DataDog/libddwaf/releases/download";
+
+/// Returns the URL of the `archive_name` archive of the `version` release, under `base_url`.
+pub fn archive_url(base_url: &str, version: &str, archive_name: &str) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    format!("{base_url}/{version}/{archive_name}")
+}
+
+/// Returns the name of the environment variable holding the expected SHA-256 checksum of the
+/// archive used for `target` (e.g, `LIBDDWAF_SHA256_X86_64_UNKNOWN_LINUX_GNU`).
+pub fn sha256_env_var(target: &str) -> String {
+    let suffix: String = target
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("LIBDDWAF_SHA256_{suffix}")
+}
+
+/// Returns the SHA-256 checksum of `data`, in lowercase hexadecimal.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Checks that `data` has the `expected` SHA-256 checksum (in hexadecimal, in any case).
+pub fn verify_sha256(data: &[u8], expected: &str) -> Result<(), String> {
+    let actual = sha256_hex(data);
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Checksum mismatch: expected SHA-256 {expected}, got {actual}"
+        ))
+    }
+}
+
+/// Extracts the `.tar.gz` archive read from `reader` into `dest`, and returns the paths of the
+/// extracted files.
+///
+/// The first component of the path of each entry (the top-level directory of the release
+/// archives) is stripped. Entries with an absolute path or a `..` component are rejected, so that
+/// nothing is ever written outside of `dest`.
+pub fn extract(reader: impl Read, dest: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut archive = Archive::new(GzDecoder::new(reader));
+    let mut extracted = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+
+        let path = entry.path()?.into_owned();
+        let Some(relative) = strip_top_level(&path) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Refusing to extract archive entry {}", path.display()),
+            ));
+        };
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+
+        let out_path = dest.join(relative);
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = File::create(&out_path)?;
+        io::copy(&mut entry, &mut file)?;
+        extracted.push(out_path);
+    }
+    Ok(extracted)
+}
+
+/// Returns `path` without its first component, or [`None`] if it is not a relative path made of
+/// plain components only.
+fn strip_top_level(path: &Path) -> Option<PathBuf> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => components.push(name),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(components.into_iter().skip(1).collect())
+}
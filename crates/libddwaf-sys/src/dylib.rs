@@ -64,9 +64,10 @@ fn init() -> Option<ddwaf> {
 /// library could not be loaded.
 macro_rules! reexport {
     (
-        $($vis:vis unsafe fn $name:ident($($arg_name:ident: $arg_type: ty),*) $(-> $ret_type:ty)? { $($fallback:expr)? })*
+        $($(#[$attr:meta])* $vis:vis unsafe fn $name:ident($($arg_name:ident: $arg_type: ty),*) $(-> $ret_type:ty)? { $($fallback:expr)? })*
     ) => {
         $(
+            $(#[$attr])*
             $vis unsafe extern "C" fn $name($($arg_name: $arg_type),*) $(-> $ret_type)? {
                 unsafe { LIBRARY.$name($($arg_name),*) }
             }
@@ -76,13 +77,14 @@ macro_rules! reexport {
             #[cold]
             fn default() -> Self {
                 $(
+                    $(#[$attr])*
                     #[cold]
                     unsafe extern "C" fn $name($($arg_name: $arg_type),*) $(-> $ret_type)? {$($fallback)?}
                 )*
 
                 Self {
                     __library: unsafe { libloading::os::unix::Library::from_raw(std::ptr::null_mut()) }.into(),
-                    $($name),*
+                    $($(#[$attr])* $name),*
                 }
             }
         }
@@ -116,6 +118,7 @@ reexport! {
     pub unsafe fn ddwaf_object_clone(source: *const ddwaf_object, destination: *mut ddwaf_object, alloc: ddwaf_allocator) -> *mut ddwaf_object { std::ptr::null_mut() }
     pub unsafe fn ddwaf_object_destroy(object: *mut ddwaf_object, alloc: ddwaf_allocator) {}
     pub unsafe fn ddwaf_object_find(object: *const ddwaf_object, key: *const ::std::os::raw::c_char, length: usize) -> *const ddwaf_object { std::ptr::null() }
+    #[cfg(ddwaf_has_from_json)]
     pub unsafe fn ddwaf_object_from_json(output: *mut ddwaf_object, json_str: *const std::os::raw::c_char, length: u32, alloc: ddwaf_allocator) -> bool { false }
     pub unsafe fn ddwaf_object_get_bool(object: *const ddwaf_object) -> bool { false }
     pub unsafe fn ddwaf_object_get_float(object: *const ddwaf_object) -> f64 { 0.0 }
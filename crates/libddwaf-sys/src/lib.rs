@@ -12,6 +12,42 @@ use std::slice;
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+/// Checks the preconditions of the unsafe helpers below. Like [`debug_assert!`] and
+/// [`debug_assert_eq!`], but also checked in release builds when the `strict-asserts` feature is
+/// enabled.
+macro_rules! strict_assert {
+    ($($arg:tt)*) => {
+        if cfg!(any(debug_assertions, feature = "strict-asserts")) {
+            assert!($($arg)*);
+        }
+    };
+}
+macro_rules! strict_assert_eq {
+    ($($arg:tt)*) => {
+        if cfg!(any(debug_assertions, feature = "strict-asserts")) {
+            assert_eq!($($arg)*);
+        }
+    };
+}
+
+/// The number of [`std::alloc::alloc`]ated buffers backing [`ddwaf_object`]s that have not been
+/// released yet, maintained when the `leak-check` feature is enabled.
+///
+/// The buffers released by the `drop_*` helpers of [`ddwaf_object`] are accounted for here, and
+/// the code allocating them is responsible for accounting for their allocation.
+#[cfg(feature = "leak-check")]
+pub static LIVE_RUST_ALLOCATIONS: std::sync::atomic::AtomicIsize =
+    std::sync::atomic::AtomicIsize::new(0);
+
+/// Records the release of a buffer in [`LIVE_RUST_ALLOCATIONS`], when the `leak-check` feature is
+/// enabled.
+macro_rules! track_dealloc {
+    () => {
+        #[cfg(feature = "leak-check")]
+        LIVE_RUST_ALLOCATIONS.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+    };
+}
+
 #[cfg(feature = "dynamic")]
 mod dylib;
 #[cfg(feature = "dynamic")]
@@ -33,7 +69,7 @@ impl ddwaf_object {
     ///   with [`ddwaf_object::drop_object`].
     #[allow(clippy::missing_panics_doc)]
     pub unsafe fn drop_array(&mut self) {
-        debug_assert_eq!(self.obj_type(), DDWAF_OBJ_ARRAY);
+        strict_assert_eq!(self.obj_type(), DDWAF_OBJ_ARRAY);
         let array = unsafe { self.via.array };
         if array.capacity == 0 {
             return;
@@ -48,6 +84,7 @@ impl ddwaf_object {
         // given that capacity is limited to u16::MAX.
         let layout = Layout::array::<ddwaf_object>(array.capacity as usize).unwrap();
         unsafe { std::alloc::dealloc(array.ptr.cast(), layout) };
+        track_dealloc!();
     }
 
     /// Drops the map data associated with the receiving [`ddwaf_object`].
@@ -59,7 +96,7 @@ impl ddwaf_object {
     ///   both [`ddwaf_object::drop_object`] and [`ddwaf_object::drop_key`].
     #[allow(clippy::missing_panics_doc)]
     pub unsafe fn drop_map(&mut self) {
-        debug_assert_eq!(self.obj_type(), DDWAF_OBJ_MAP);
+        strict_assert_eq!(self.obj_type(), DDWAF_OBJ_MAP);
         let map = unsafe { self.via.map };
         if map.capacity == 0 {
             return;
@@ -72,6 +109,7 @@ impl ddwaf_object {
         }
         let layout = Layout::array::<_ddwaf_object_kv>(map.capacity as usize).unwrap();
         unsafe { std::alloc::dealloc(map.ptr.cast(), layout) };
+        track_dealloc!();
     }
 
     /// Drops the value associated with the receiving [`ddwaf_object`].
@@ -99,7 +137,7 @@ impl ddwaf_object {
     ///   size indicated by the [`_ddwaf_object_string::size`] field done with [`std::alloc::alloc`].
     #[allow(clippy::missing_panics_doc)]
     pub unsafe fn drop_string(&mut self) {
-        debug_assert_eq!(self.obj_type(), DDWAF_OBJ_STRING);
+        strict_assert_eq!(self.obj_type(), DDWAF_OBJ_STRING);
         let sval = unsafe { self.via.str_.ptr };
         if sval.is_null() {
             return;
@@ -110,6 +148,7 @@ impl ddwaf_object {
                 Layout::array::<::std::os::raw::c_char>(self.via.str_.size as usize).unwrap(),
             );
         }
+        track_dealloc!();
     }
 
     /// Returns the type of the [`ddwaf_object`]
@@ -124,12 +163,37 @@ impl ddwaf_object {
         (self.obj_type() & DDWAF_OBJ_STRING) != 0
     }
 
+    /// Returns the size claimed by the receiving [`ddwaf_object`] if it is a string, array, or map
+    /// with a non-zero size but a null data pointer; and [`None`] otherwise.
+    ///
+    /// Such objects can't be read from without causing undefined behavior.
+    fn degenerate_size(&self) -> Option<u32> {
+        let (size, is_null) = match self.obj_type() {
+            DDWAF_OBJ_STRING | DDWAF_OBJ_LITERAL_STRING => {
+                let str = unsafe { self.via.str_ };
+                (str.size, str.ptr.is_null())
+            }
+            DDWAF_OBJ_ARRAY => {
+                let array = unsafe { self.via.array };
+                (u32::from(array.size), array.ptr.is_null())
+            }
+            DDWAF_OBJ_MAP => {
+                let map = unsafe { self.via.map };
+                (u32::from(map.size), map.ptr.is_null())
+            }
+            _ => return None,
+        };
+        (size != 0 && is_null).then_some(size)
+    }
+
     /// Returns a slice of the bytes from the string associated with the receiving [`ddwaf_object`].
     ///
     /// # Safety
     /// - The [`ddwaf_object`] must be a valid representation of a string.
+    /// - The [`ddwaf_object`] must not be degenerate (see [`ddwaf_object::degenerate_size`]).
     unsafe fn string_vec(&self) -> &[u8] {
-        debug_assert!(self.is_string());
+        strict_assert!(self.is_string());
+        strict_assert!(self.degenerate_size().is_none());
 
         if self.obj_type() == DDWAF_OBJ_STRING || self.obj_type() == DDWAF_OBJ_LITERAL_STRING {
             let str = unsafe { self.via.str_ };
@@ -146,8 +210,16 @@ impl ddwaf_object {
     }
 }
 
+/// Structural equality: arrays are equal when their elements are equal in order, and maps when
+/// their entries have equal keys and equal values in order, at every level of nesting.
 impl std::cmp::PartialEq<ddwaf_object> for ddwaf_object {
     fn eq(&self, other: &ddwaf_object) -> bool {
+        match (self.degenerate_size(), other.degenerate_size()) {
+            (None, None) => {}
+            // Degenerate objects are only equal to identically-degenerate objects
+            (left, right) => return left == right && self.obj_type() == other.obj_type(),
+        }
+
         if self.is_string() && other.is_string() {
             let left = unsafe { self.string_vec() };
             let right = unsafe { other.string_vec() };
@@ -207,6 +279,13 @@ impl std::cmp::PartialEq<ddwaf_object> for ddwaf_object {
 impl std::fmt::Debug for ddwaf_object {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut dbg = f.debug_struct("ddwaf_object");
+        if let Some(size) = self.degenerate_size() {
+            return dbg
+                .field("type", &self.obj_type())
+                .field("size", &size)
+                .field("ptr", &"<null>")
+                .finish_non_exhaustive();
+        }
         match self.obj_type() {
             DDWAF_OBJ_BOOL => dbg
                 .field("type", &stringify!(DDWAF_OBJ_BOOL))
@@ -241,15 +320,21 @@ impl std::fmt::Debug for ddwaf_object {
             }
             DDWAF_OBJ_ARRAY => {
                 let array = unsafe { self.via.array };
-                let array: &[ddwaf_object] =
-                    unsafe { slice::from_raw_parts(array.ptr.cast(), array.size as usize) };
+                let array: &[ddwaf_object] = if array.size == 0 {
+                    &[]
+                } else {
+                    unsafe { slice::from_raw_parts(array.ptr.cast(), array.size as usize) }
+                };
                 dbg.field("type", &stringify!(DDWAF_OBJ_ARRAY))
                     .field("array", &array)
             }
             DDWAF_OBJ_MAP => {
                 let map = unsafe { self.via.map };
-                let map: &[_ddwaf_object_kv] =
-                    unsafe { slice::from_raw_parts(map.ptr.cast(), map.size as usize) };
+                let map: &[_ddwaf_object_kv] = if map.size == 0 {
+                    &[]
+                } else {
+                    unsafe { slice::from_raw_parts(map.ptr.cast(), map.size as usize) }
+                };
                 dbg.field("type", &stringify!(DDWAF_OBJ_MAP))
                     .field("map", &map)
             }
@@ -0,0 +1,113 @@
+#![cfg(not(miri))]
+
+//! Tests for the archive handling of the build script, which do not need network access nor
+//! `libddwaf` itself.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+#[path = "../build/archive.rs"]
+mod archive;
+
+const FIXTURE: &[u8] = include_bytes!("fixtures/libddwaf-fixture.tar.gz");
+const FIXTURE_SHA256: &str = "57a8aa217228b6c8653be979d652d67aebb42b12b29e1e48c610f98f2d3aec23";
+
+/// Returns an empty directory named after `test`, under the target's temporary directory.
+fn scratch_dir(test: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn archive_url() {
+    let expected = "https://mirror.example/libddwaf/1.2.3/libddwaf-1.2.3-darwin-arm64.tar.gz";
+    for base_url in [
+        "https://mirror.example/libddwaf",
+        "https://mirror.example/libddwaf/",
+    ] {
+        assert_eq!(
+            archive::archive_url(base_url, "1.2.3", "libddwaf-1.2.3-darwin-arm64.tar.gz"),
+            expected
+        );
+    }
+    assert!(
+        archive::archive_url(archive::DEFAULT_BASE_URL, "1.2.3", "archive.tar.gz")
+            .starts_with("https://github.com/DataDog/libddwaf/releases/download/1.2.3/")
+    );
+}
+
+#[test]
+fn sha256_env_var() {
+    assert_eq!(
+        archive::sha256_env_var("x86_64-unknown-linux-gnu"),
+        "LIBDDWAF_SHA256_X86_64_UNKNOWN_LINUX_GNU"
+    );
+    assert_eq!(
+        archive::sha256_env_var("armv7-unknown-linux-musleabihf"),
+        "LIBDDWAF_SHA256_ARMV7_UNKNOWN_LINUX_MUSLEABIHF"
+    );
+}
+
+#[test]
+fn verify_sha256() {
+    assert_eq!(archive::sha256_hex(FIXTURE), FIXTURE_SHA256);
+    assert!(archive::verify_sha256(FIXTURE, FIXTURE_SHA256).is_ok());
+    assert!(archive::verify_sha256(FIXTURE, &FIXTURE_SHA256.to_uppercase()).is_ok());
+    assert!(archive::verify_sha256(FIXTURE, &format!(" {FIXTURE_SHA256}\n")).is_ok());
+
+    let mut tampered = FIXTURE.to_vec();
+    tampered[FIXTURE.len() / 2] ^= 0xFF;
+    let error = archive::verify_sha256(&tampered, FIXTURE_SHA256).unwrap_err();
+    assert!(error.contains(FIXTURE_SHA256));
+    assert!(error.contains(&archive::sha256_hex(&tampered)));
+}
+
+#[test]
+fn extract_fixture() {
+    let dest = scratch_dir("extract_fixture");
+    let mut extracted = archive::extract(FIXTURE, &dest).unwrap();
+    extracted.sort();
+    assert_eq!(
+        extracted,
+        [
+            dest.join("include").join("ddwaf.h"),
+            dest.join("lib").join("libddwaf.so"),
+        ]
+    );
+    let header = fs::read_to_string(dest.join("include").join("ddwaf.h")).unwrap();
+    assert!(header.contains("ddwaf_get_version"));
+    assert_eq!(
+        fs::read_to_string(dest.join("lib").join("libddwaf.so")).unwrap(),
+        "not really a shared library\n"
+    );
+}
+
+#[test]
+fn extract_rejects_escaping_paths() {
+    for path in ["libddwaf/../../escaped.txt", "/tmp/escaped.txt"] {
+        // The tar crate refuses to create such entries, so the name is written directly.
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut tar = tar::Builder::new(Vec::new());
+        tar.append(&header, &b"evil"[..]).unwrap();
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&tar.into_inner().unwrap()).unwrap();
+        let archive = gz.finish().unwrap();
+
+        let root = scratch_dir("extract_rejects_escaping_paths");
+        let dest = root.join("a").join("b");
+        let error = archive::extract(&archive[..], &dest).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(!root.join("escaped.txt").exists());
+        assert!(!dest.exists());
+    }
+}
@@ -0,0 +1,124 @@
+//! Tests for objects that claim a non-zero size but carry a null data pointer. These do not call
+//! into `libddwaf`, and are hence able to run under miri.
+
+use std::ptr::null_mut;
+
+use libddwaf_sys::*;
+
+fn string(type_: DDWAF_OBJ_TYPE, size: u32) -> ddwaf_object {
+    let mut obj = ddwaf_object::default();
+    obj.via.str_ = _ddwaf_object_string {
+        type_: type_ as u8,
+        size,
+        ptr: null_mut(),
+    };
+    obj
+}
+
+fn array(size: u16) -> ddwaf_object {
+    let mut obj = ddwaf_object::default();
+    obj.via.array = _ddwaf_object_array {
+        type_: DDWAF_OBJ_ARRAY as u8,
+        size,
+        capacity: size,
+        ptr: null_mut(),
+    };
+    obj
+}
+
+fn map(size: u16) -> ddwaf_object {
+    let mut obj = ddwaf_object::default();
+    obj.via.map = _ddwaf_object_map {
+        type_: DDWAF_OBJ_MAP as u8,
+        size,
+        capacity: size,
+        ptr: null_mut(),
+    };
+    obj
+}
+
+#[test]
+fn test_eq_degenerate_string() {
+    assert_eq!(string(DDWAF_OBJ_STRING, 5), string(DDWAF_OBJ_STRING, 5));
+    assert_ne!(string(DDWAF_OBJ_STRING, 5), string(DDWAF_OBJ_STRING, 6));
+    assert_ne!(
+        string(DDWAF_OBJ_STRING, 5),
+        string(DDWAF_OBJ_LITERAL_STRING, 5)
+    );
+    // Empty strings with a null pointer are well-formed
+    assert_ne!(string(DDWAF_OBJ_STRING, 5), string(DDWAF_OBJ_STRING, 0));
+    assert_eq!(
+        string(DDWAF_OBJ_STRING, 0),
+        string(DDWAF_OBJ_LITERAL_STRING, 0)
+    );
+
+    let mut small = ddwaf_object::default();
+    small.via.sstr = _ddwaf_object_small_string {
+        type_: DDWAF_OBJ_SMALL_STRING as u8,
+        size: 5,
+        data: [0; 14],
+    };
+    assert_ne!(string(DDWAF_OBJ_STRING, 5), small);
+    assert_ne!(small, string(DDWAF_OBJ_STRING, 5));
+}
+
+#[test]
+fn test_eq_degenerate_array_and_map() {
+    assert_eq!(array(3), array(3));
+    assert_ne!(array(3), array(2));
+    assert_ne!(array(3), array(0));
+    assert_ne!(array(0), array(3));
+    assert_eq!(map(3), map(3));
+    assert_ne!(map(3), map(2));
+    assert_ne!(map(3), map(0));
+    assert_ne!(array(3), map(3));
+}
+
+fn single_entry_map(entry: &mut _ddwaf_object_kv) -> ddwaf_object {
+    let mut obj = ddwaf_object::default();
+    obj.via.map = _ddwaf_object_map {
+        type_: DDWAF_OBJ_MAP as u8,
+        size: 1,
+        capacity: 1,
+        ptr: entry,
+    };
+    obj
+}
+
+#[test]
+fn test_eq_degenerate_map_entries() {
+    let mut left_entry = _ddwaf_object_kv {
+        key: string(DDWAF_OBJ_STRING, 3),
+        val: array(2),
+    };
+    let mut right_entry = left_entry;
+    let mut other_entry = _ddwaf_object_kv {
+        key: string(DDWAF_OBJ_STRING, 4),
+        val: array(2),
+    };
+
+    let left = single_entry_map(&mut left_entry);
+    let right = single_entry_map(&mut right_entry);
+    let other = single_entry_map(&mut other_entry);
+    assert_eq!(left, right);
+    assert_ne!(left, other);
+}
+
+#[test]
+fn test_debug_degenerate() {
+    let dbg = format!("{:?}", string(DDWAF_OBJ_STRING, 5));
+    assert!(dbg.contains("size: 5"), "{dbg}");
+    assert!(dbg.contains("<null>"), "{dbg}");
+
+    let dbg = format!("{:?}", array(2));
+    assert!(dbg.contains("size: 2"), "{dbg}");
+    assert!(dbg.contains("<null>"), "{dbg}");
+
+    let dbg = format!("{:?}", map(7));
+    assert!(dbg.contains("size: 7"), "{dbg}");
+    assert!(dbg.contains("<null>"), "{dbg}");
+
+    // Empty containers with a null pointer are formatted normally
+    assert!(format!("{:?}", array(0)).contains("array: []"));
+    assert!(format!("{:?}", map(0)).contains("map: []"));
+}
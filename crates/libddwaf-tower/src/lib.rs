@@ -0,0 +1,515 @@
+#![deny(
+    clippy::correctness,
+    clippy::pedantic,
+    clippy::perf,
+    clippy::style,
+    clippy::suspicious
+)]
+
+//! [Tower](https://docs.rs/tower) middleware protecting HTTP services with the
+//! [`libddwaf` library](https://github.com/DataDog/libddwaf).
+//!
+//! The [`WafLayer`] wraps a service with a [`WafService`], which evaluates each request with a
+//! [`RequestEvaluation`] created from a shared [`Handle`]:
+//! 1. The request method, URI, query, headers and cookies are evaluated before the inner service
+//!    is called; if the rules block the request, the inner service is not called at all.
+//! 2. If enabled with [`WafLayerConfig::with_request_body`], JSON, multipart and URL-encoded
+//!    bodies of a known size within [`ConversionLimits::max_body_size`] are buffered and
+//!    evaluated, before being handed to the inner service unchanged.
+//! 3. The response status and headers are evaluated once the inner service responded; if the
+//!    rules block the request at this point, the response is replaced.
+//!
+//! Blocked requests receive the response described by the blocking [`Action`]: the configured
+//! HTML or JSON template (with the status code of the action) for `block_request`, or a redirect
+//! for `redirect_request`. Once the request has been evaluated, its [`FinalOutcome`] (including
+//! the events and attributes to attach to the request's trace) is reported to the callback set
+//! with [`WafLayerConfig::on_outcome`].
+//!
+//! ```rust,no_run
+//! use std::sync::Arc;
+//!
+//! use libddwaf::{Builder, Config};
+//! use libddwaf_tower::{WafLayer, WafLayerConfig};
+//! # let ruleset = libddwaf::object::WafMap::new(0);
+//!
+//! let mut builder = Builder::new(Some(&Config::default())).expect("Failed to create builder");
+//! assert!(builder.add_or_update_config("datadog/0/ASM_DD/0/rules", &ruleset, None));
+//! let handle = Arc::new(builder.build().expect("Failed to build WAF instance"));
+//!
+//! let layer = WafLayer::new(
+//!     handle,
+//!     WafLayerConfig::default().on_outcome(|outcome| {
+//!         if !outcome.events.is_empty() {
+//!             // Tag the current trace with the events and attributes...
+//!         }
+//!     }),
+//! );
+//! // Then, for example with `axum`: `Router::new().route(...).layer(layer)`
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use http::uri::PathAndQuery;
+use http::{request, Request, Response, StatusCode};
+use http_body::Body;
+use http_body_util::{BodyExt, Either, Full};
+use libddwaf::addresses::AddressMapBuilder;
+use libddwaf::http::{body_json_to_waf, body_multipart_to_waf, ConversionLimits};
+use libddwaf::object::{WafArray, WafMap, WafObject};
+use libddwaf::phases::{Action, FinalOutcome, PhaseOutcome, RequestEvaluation, DEFAULT_BUDGET};
+use libddwaf::Handle;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The default HTML blocking template.
+pub const DEFAULT_HTML_TEMPLATE: &str = concat!(
+    "<!DOCTYPE html><html lang=\"en\"><head><meta charset=\"UTF-8\">",
+    "<title>You've been blocked</title></head><body><h1>Sorry, you cannot access this page.</h1>",
+    "<p>Please contact the customer service team.</p><p>Security provided by Datadog.</p>",
+    "</body></html>"
+);
+
+/// The default JSON blocking template.
+pub const DEFAULT_JSON_TEMPLATE: &str = concat!(
+    r#"{"errors":[{"title":"You've been blocked","detail":"Sorry, you cannot access this page. "#,
+    r#"Please contact the customer service team. Security provided by Datadog."}]}"#
+);
+
+type OutcomeCallback = Arc<dyn Fn(&FinalOutcome) + Send + Sync>;
+
+/// The configuration of a [`WafLayer`].
+#[derive(Clone)]
+pub struct WafLayerConfig {
+    budget: Duration,
+    body_limits: Option<ConversionLimits>,
+    html_template: Bytes,
+    json_template: Bytes,
+    on_outcome: Option<OutcomeCallback>,
+}
+impl WafLayerConfig {
+    /// Sets the time budget shared by all the phases of a request (defaults to
+    /// [`DEFAULT_BUDGET`]).
+    #[must_use]
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Enables the evaluation of request bodies, which are converted with the provided limits.
+    ///
+    /// Only bodies with a JSON, multipart or URL-encoded content type are buffered, and only when
+    /// their size is known to be at most [`ConversionLimits::max_body_size`] (for example with a
+    /// `Content-Length` header); other bodies are streamed to the inner service without being
+    /// evaluated.
+    #[must_use]
+    pub fn with_request_body(mut self, limits: ConversionLimits) -> Self {
+        self.body_limits = Some(limits);
+        self
+    }
+
+    /// Sets the body of HTML blocking responses (defaults to [`DEFAULT_HTML_TEMPLATE`]).
+    #[must_use]
+    pub fn with_html_template(mut self, template: impl Into<Bytes>) -> Self {
+        self.html_template = template.into();
+        self
+    }
+
+    /// Sets the body of JSON blocking responses (defaults to [`DEFAULT_JSON_TEMPLATE`]).
+    #[must_use]
+    pub fn with_json_template(mut self, template: impl Into<Bytes>) -> Self {
+        self.json_template = template.into();
+        self
+    }
+
+    /// Sets the callback receiving the [`FinalOutcome`] of each request, once all of its phases
+    /// have been evaluated, typically to attach its events and attributes to the request's trace.
+    ///
+    /// The callback is also invoked for blocked requests, and for requests whose inner service
+    /// returned an error.
+    #[must_use]
+    pub fn on_outcome(mut self, callback: impl Fn(&FinalOutcome) + Send + Sync + 'static) -> Self {
+        self.on_outcome = Some(Arc::new(callback));
+        self
+    }
+
+    fn report(&self, outcome: &FinalOutcome) {
+        if let Some(callback) = &self.on_outcome {
+            callback(outcome);
+        }
+    }
+
+    /// Returns the response replacing the one of the inner service, if the phase blocked the
+    /// request.
+    fn blocking_response(
+        &self,
+        phase: &PhaseOutcome,
+        accept: Option<&HeaderValue>,
+    ) -> Option<Response<Full<Bytes>>> {
+        let (status, response_type) = match phase.blocking_action()? {
+            Action::Redirect {
+                status_code,
+                location,
+            } => match HeaderValue::try_from(location.as_str()) {
+                Ok(location) if !location.is_empty() => {
+                    let status = StatusCode::from_u16(*status_code)
+                        .ok()
+                        .filter(StatusCode::is_redirection)
+                        .unwrap_or(StatusCode::SEE_OTHER);
+                    let mut response = Response::new(Full::default());
+                    *response.status_mut() = status;
+                    response.headers_mut().insert(header::LOCATION, location);
+                    return Some(response);
+                }
+                // Redirects without a valid location block the request instead.
+                _ => (StatusCode::FORBIDDEN, "auto"),
+            },
+            Action::Block {
+                status_code,
+                response_type,
+                ..
+            } => (
+                StatusCode::from_u16(*status_code).unwrap_or(StatusCode::FORBIDDEN),
+                response_type.as_str(),
+            ),
+            _ => return None,
+        };
+
+        let html = match response_type {
+            "html" => true,
+            "json" => false,
+            "none" => {
+                let mut response = Response::new(Full::default());
+                *response.status_mut() = status;
+                return Some(response);
+            }
+            _ => prefers_html(accept),
+        };
+        let (content_type, body) = if html {
+            ("text/html; charset=utf-8", self.html_template.clone())
+        } else {
+            ("application/json", self.json_template.clone())
+        };
+        let mut response = Response::new(Full::new(body));
+        *response.status_mut() = status;
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        Some(response)
+    }
+}
+impl Default for WafLayerConfig {
+    fn default() -> Self {
+        Self {
+            budget: DEFAULT_BUDGET,
+            body_limits: None,
+            html_template: Bytes::from_static(DEFAULT_HTML_TEMPLATE.as_bytes()),
+            json_template: Bytes::from_static(DEFAULT_JSON_TEMPLATE.as_bytes()),
+            on_outcome: None,
+        }
+    }
+}
+impl fmt::Debug for WafLayerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WafLayerConfig")
+            .field("budget", &self.budget)
+            .field("body_limits", &self.body_limits)
+            .field("html_template", &self.html_template)
+            .field("json_template", &self.json_template)
+            .field("on_outcome", &self.on_outcome.is_some())
+            .finish()
+    }
+}
+
+/// A [`Layer`] wrapping services with a [`WafService`].
+#[derive(Clone)]
+pub struct WafLayer {
+    handle: Arc<Handle>,
+    config: Arc<WafLayerConfig>,
+}
+impl WafLayer {
+    /// Creates a new [`WafLayer`] evaluating requests with contexts created from `handle`.
+    #[must_use]
+    pub fn new(handle: Arc<Handle>, config: WafLayerConfig) -> Self {
+        Self {
+            handle,
+            config: Arc::new(config),
+        }
+    }
+}
+impl<S> Layer<S> for WafLayer {
+    type Service = WafService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        WafService {
+            inner,
+            handle: Arc::clone(&self.handle),
+            config: Arc::clone(&self.config),
+        }
+    }
+}
+
+/// The body of the responses of a [`WafService`]: either the body of the inner service's
+/// response, or the body of a blocking response.
+pub type WafResponseBody<B> = Either<B, Full<Bytes>>;
+
+/// The future returned by a [`WafService`].
+pub type WafFuture<B, E> =
+    Pin<Box<dyn Future<Output = Result<Response<WafResponseBody<B>>, E>> + Send>>;
+
+/// A [`Service`] evaluating requests with `libddwaf` before passing them to the inner service.
+///
+/// See the [crate documentation](crate) for the phases of the evaluation. Buffered request bodies
+/// are handed to the inner service as a new body created from the buffered [`Bytes`]; requests
+/// whose body fails to be read while buffered are answered with `400 Bad Request`.
+#[derive(Clone)]
+pub struct WafService<S> {
+    inner: S,
+    handle: Arc<Handle>,
+    config: Arc<WafLayerConfig>,
+}
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for WafService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Body + From<Bytes> + Send + 'static,
+    ReqBody::Data: Send,
+    ResBody: 'static,
+{
+    type Response = Response<WafResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = WafFuture<ResBody, S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        // The service that was polled ready is the one that must be called.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let handle = Arc::clone(&self.handle);
+        let config = Arc::clone(&self.config);
+
+        Box::pin(async move {
+            let mut evaluation = RequestEvaluation::new(&handle).with_budget(config.budget);
+            let (parts, body) = req.into_parts();
+            let accept = parts.headers.get(header::ACCEPT).cloned();
+
+            let blocked = config.blocking_response(
+                &evaluation.on_addresses(request_addresses(&parts)),
+                accept.as_ref(),
+            );
+            if let Some(response) = blocked {
+                config.report(&evaluation.finish());
+                return Ok(response.map(Either::Right));
+            }
+
+            let buffered = config.body_limits.as_ref().and_then(|limits| {
+                let kind = BodyKind::of(&parts.headers)?;
+                let size = body.size_hint().upper()?;
+                (usize::try_from(size).ok()? <= limits.max_body_size).then_some((kind, limits))
+            });
+            let req = if let Some((kind, limits)) = buffered {
+                let Ok(collected) = body.collect().await else {
+                    config.report(&evaluation.finish());
+                    let mut response = Response::new(Either::Right(Full::default()));
+                    *response.status_mut() = StatusCode::BAD_REQUEST;
+                    return Ok(response);
+                };
+                let bytes = collected.to_bytes();
+                if let Some(data) = kind.convert(&parts.headers, &bytes, limits) {
+                    let blocked = config
+                        .blocking_response(&evaluation.on_request_body(data), accept.as_ref());
+                    if let Some(response) = blocked {
+                        config.report(&evaluation.finish());
+                        return Ok(response.map(Either::Right));
+                    }
+                }
+                Request::from_parts(parts, ReqBody::from(bytes))
+            } else {
+                Request::from_parts(parts, body)
+            };
+
+            let response = match inner.call(req).await {
+                Ok(response) => response,
+                Err(error) => {
+                    config.report(&evaluation.finish());
+                    return Err(error);
+                }
+            };
+
+            let (parts, body) = response.into_parts();
+            let blocked = config.blocking_response(
+                &evaluation.on_response(
+                    parts.status.as_u16(),
+                    headers_to_waf(&parts.headers, &header::SET_COOKIE),
+                ),
+                accept.as_ref(),
+            );
+            config.report(&evaluation.finish());
+            Ok(match blocked {
+                Some(response) => response.map(Either::Right),
+                None => Response::from_parts(parts, Either::Left(body)),
+            })
+        })
+    }
+}
+
+/// The request body formats that are evaluated.
+#[derive(Clone, Copy)]
+enum BodyKind {
+    Json,
+    Multipart,
+    UrlEncoded,
+}
+impl BodyKind {
+    fn of(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+        match mime.as_str() {
+            "application/x-www-form-urlencoded" => Some(Self::UrlEncoded),
+            "multipart/form-data" => Some(Self::Multipart),
+            _ if mime == "application/json" || mime.ends_with("+json") => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    /// Converts the body into the value of the
+    /// [`REQUEST_BODY`](libddwaf::addresses::REQUEST_BODY) address, if it is well-formed.
+    fn convert(
+        self,
+        headers: &HeaderMap,
+        bytes: &[u8],
+        limits: &ConversionLimits,
+    ) -> Option<WafObject> {
+        match self {
+            Self::Json => body_json_to_waf(bytes, limits).ok(),
+            Self::Multipart => {
+                let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+                body_multipart_to_waf(content_type, bytes, limits)
+                    .ok()
+                    .map(Into::into)
+            }
+            Self::UrlEncoded => Some(multi_map(form_urlencoded::parse(bytes)).into()),
+        }
+    }
+}
+
+/// Returns the addresses of the request headers phase.
+fn request_addresses(parts: &request::Parts) -> WafMap {
+    let uri = parts
+        .uri
+        .path_and_query()
+        .map_or(parts.uri.path(), PathAndQuery::as_str);
+    let mut builder = AddressMapBuilder::new()
+        .request_method(parts.method.as_str())
+        .request_uri(uri)
+        .request_headers(headers_to_waf(&parts.headers, &header::COOKIE));
+    if let Some(query) = parts.uri.query() {
+        builder = builder.request_query(multi_map(form_urlencoded::parse(query.as_bytes())));
+    }
+    let cookies = parts
+        .headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='));
+    let cookies = multi_map(cookies);
+    if !cookies.is_empty() {
+        builder = builder.request_cookies(cookies);
+    }
+    builder.build()
+}
+
+/// Converts headers into a map from (lower-cased) names to values, leaving out `excluded`.
+/// Headers with several values are converted into arrays.
+fn headers_to_waf(headers: &HeaderMap, excluded: &HeaderName) -> WafMap {
+    let entries: Vec<(&str, WafObject)> = headers
+        .keys()
+        .filter(|name| *name != excluded)
+        .map(|name| {
+            let values: Vec<&[u8]> = headers
+                .get_all(name)
+                .iter()
+                .map(HeaderValue::as_bytes)
+                .collect();
+            let value = match values.as_slice() {
+                [value] => WafObject::from(*value),
+                values => WafArray::from(values).into(),
+            };
+            (name.as_str(), value)
+        })
+        .collect();
+    WafMap::from(entries)
+}
+
+/// Groups key-value pairs into a map from keys to the arrays of their values.
+fn multi_map<K: AsRef<str>, V: AsRef<str>>(pairs: impl IntoIterator<Item = (K, V)>) -> WafMap {
+    // Keys are looked up through an index rather than by scanning `entries`, as the number of
+    // pairs is controlled by the client.
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut entries: Vec<(String, Vec<String>)> = Vec::new();
+    for (key, value) in pairs {
+        let value = value.as_ref().to_string();
+        match index.get(key.as_ref()) {
+            Some(&i) => entries[i].1.push(value),
+            None => {
+                index.insert(key.as_ref().to_string(), entries.len());
+                entries.push((key.as_ref().to_string(), vec![value]));
+            }
+        }
+    }
+    WafMap::from(
+        entries
+            .into_iter()
+            .map(|(key, values)| (key, WafArray::from(values)))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Returns true if the `Accept` header lists `text/html` before `application/json`.
+fn prefers_html(accept: Option<&HeaderValue>) -> bool {
+    let Some(accept) = accept.and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let accept = accept.to_ascii_lowercase();
+    match (accept.find("text/html"), accept.find("application/json")) {
+        (Some(html), Some(json)) => html < json,
+        (html, _) => html.is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libddwaf::{waf_array, waf_map};
+
+    use super::{multi_map, WafArray};
+
+    #[test]
+    fn multi_map_groups_values_in_order() {
+        let map = multi_map([("b", "1"), ("a", "2"), ("b", "3"), ("c", "4"), ("a", "5")]);
+        assert_eq!(
+            map,
+            waf_map!(
+                ("b", waf_array!["1", "3"]),
+                ("a", waf_array!["2", "5"]),
+                ("c", waf_array!["4"]),
+            )
+        );
+
+        let map = multi_map((0..10_000).map(|i| (format!("key{}", i % 100), i.to_string())));
+        assert_eq!(map.len(), 100);
+        assert!(map
+            .iter()
+            .all(|entry| entry.as_type::<WafArray>().map(|values| values.len()) == Some(100)));
+    }
+}
@@ -0,0 +1,109 @@
+#![cfg(not(miri))]
+
+use std::sync::{Arc, Mutex};
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::routing::{get, post};
+use axum::Router;
+use libddwaf::http::ConversionLimits;
+use libddwaf::test_util::fixtures;
+use libddwaf::Builder;
+use libddwaf_tower::{WafLayer, WafLayerConfig, DEFAULT_HTML_TEMPLATE, DEFAULT_JSON_TEMPLATE};
+use tower::ServiceExt;
+
+/// Returns a router protected by the `arachni_rule` fixture, and the number of events reported
+/// for each request seen by the outcome callback.
+fn protected_router() -> (Router, Arc<Mutex<Vec<usize>>>) {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("ruleset", &fixtures::arachni_rule(), None));
+    let handle = Arc::new(builder.build().expect("Failed to build WAF instance"));
+
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let config = WafLayerConfig::default()
+        .with_request_body(ConversionLimits::default())
+        .on_outcome({
+            let reported = Arc::clone(&reported);
+            move |outcome| reported.lock().unwrap().push(outcome.events.len())
+        });
+    let router = Router::new()
+        .route("/", get(|| async { "Hello, world!" }))
+        .route("/echo", post(|body: String| async move { body }))
+        .layer(WafLayer::new(handle, config));
+    (router, reported)
+}
+
+async fn send(router: &Router, request: Request<Body>) -> (StatusCode, Option<String>, String) {
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (
+        status,
+        content_type,
+        String::from_utf8(body.to_vec()).unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn normal_traffic_passes_through() {
+    let (router, reported) = protected_router();
+
+    let request = Request::get("/?q=search")
+        .header(header::USER_AGENT, "Mozilla/5.0")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _, body) = send(&router, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "Hello, world!");
+
+    // Buffered bodies are handed to the inner service unchanged.
+    let request = Request::post("/echo")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"name":"alice"}"#))
+        .unwrap();
+    let (status, _, body) = send(&router, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, r#"{"name":"alice"}"#);
+
+    assert_eq!(*reported.lock().unwrap(), [0, 0]);
+}
+
+#[tokio::test]
+async fn scanner_is_blocked() {
+    let (router, reported) = protected_router();
+
+    let request = Request::get("/")
+        .header(header::USER_AGENT, "Arachni/v1")
+        .body(Body::empty())
+        .unwrap();
+    let (status, content_type, body) = send(&router, request).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(content_type.as_deref(), Some("application/json"));
+    assert_eq!(body, DEFAULT_JSON_TEMPLATE);
+
+    // The template follows the `Accept` header of the request.
+    let request = Request::get("/")
+        .header(header::USER_AGENT, "Arachni/v1")
+        .header(header::ACCEPT, "text/html,application/json;q=0.9")
+        .body(Body::empty())
+        .unwrap();
+    let (status, content_type, body) = send(&router, request).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(content_type.as_deref(), Some("text/html; charset=utf-8"));
+    assert_eq!(body, DEFAULT_HTML_TEMPLATE);
+
+    // Scanners are also detected in request bodies.
+    let request = Request::post("/echo")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"payload":"Arachni"}"#))
+        .unwrap();
+    let (status, _, body) = send(&router, request).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body, DEFAULT_JSON_TEMPLATE);
+
+    assert_eq!(*reported.lock().unwrap(), [1, 1, 1]);
+}
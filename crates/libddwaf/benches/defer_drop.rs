@@ -0,0 +1,91 @@
+//! Measures how long releasing a large [`RunOutput`] blocks the request thread, when it is dropped
+//! inline and when it is handed to [`defer_drop`].
+//!
+//! Run with `cargo bench -p libddwaf --bench defer_drop`; when built by `cargo test`, a single
+//! round is run to check that the benchmark still works.
+
+use std::time::{Duration, Instant};
+
+use libddwaf::object::{defer_drop, flush_deferred_drops, WafArray, WafMap};
+use libddwaf::{waf_array, waf_map, Builder, Handle, RunOutput, RunResult, RunnableContext};
+
+/// The number of rules in the ruleset, each of which reports an event for every request.
+const RULES: u16 = 500;
+const ROUNDS: usize = 200;
+
+/// Returns a ruleset of [`RULES`] rules matching any value of `server.request.query`.
+fn ruleset() -> WafMap {
+    let mut rules = WafArray::new(RULES);
+    for (i, rule) in rules.iter_mut().enumerate() {
+        *rule = waf_map! {
+            ("id", format!("rule-{i}")),
+            ("name", "Matches anything"),
+            ("tags", waf_map!{ ("type", "test"), ("category", "test") }),
+            ("conditions", waf_array![
+                waf_map!{
+                    ("operator", "match_regex"),
+                    ("parameters", waf_map!{
+                        ("inputs", waf_array![waf_map!{ ("address", "server.request.query") }]),
+                        ("regex", "."),
+                    }),
+                },
+            ]),
+        }
+        .into();
+    }
+    waf_map! { ("version", "2.1"), ("rules", rules) }
+}
+
+/// Returns the output of a request matching every rule, whose events each hold a copy of a
+/// kilobyte-long matched value.
+fn large_output(handle: &Handle) -> RunOutput {
+    let mut context = handle.new_context();
+    let data = waf_map!(("server.request.query", waf_map!(("q", "x".repeat(1024)))));
+    match context.run(data, Duration::from_secs(10)) {
+        Ok(RunResult::Match(output)) => output,
+        res => panic!("Unexpected result: {res:?}"),
+    }
+}
+
+/// Returns the 99th percentile of `samples`.
+fn p99(mut samples: Vec<Duration>) -> Duration {
+    samples.sort_unstable();
+    samples[(samples.len() * 99).div_ceil(100) - 1]
+}
+
+fn main() {
+    let rounds = if std::env::args().any(|arg| arg == "--bench") {
+        ROUNDS
+    } else {
+        1
+    };
+
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", &ruleset(), None));
+    let handle = builder.build().expect("Failed to build WAF instance");
+
+    // Spawn the drop thread before measuring.
+    defer_drop(());
+    flush_deferred_drops();
+
+    let mut inline = Vec::with_capacity(rounds);
+    let mut deferred = Vec::with_capacity(rounds);
+    for _ in 0..rounds {
+        let output = large_output(&handle);
+        let start = Instant::now();
+        drop(output);
+        inline.push(start.elapsed());
+
+        let output = large_output(&handle);
+        let start = Instant::now();
+        defer_drop(output);
+        deferred.push(start.elapsed());
+        flush_deferred_drops();
+    }
+
+    println!(
+        "p99 request-thread drop time of a {RULES}-event RunOutput over {rounds} rounds: inline {:?}, deferred {:?}",
+        p99(inline),
+        p99(deferred),
+    );
+}
@@ -0,0 +1,112 @@
+/// The capabilities detected by `libddwaf-sys` in the libddwaf release it is built against (see its
+/// build script), which are forwarded as `cfg`s of the same name.
+const CAPABILITIES: &[&str] = &["ddwaf_has_from_json"];
+
+fn main() {
+    for cfg in CAPABILITIES {
+        println!("cargo::rustc-check-cfg=cfg({cfg})");
+        if std::env::var_os(format!("DEP_DDWAF_{}", cfg.to_uppercase())).is_some() {
+            println!("cargo::rustc-cfg={cfg}");
+        }
+    }
+    // The libddwaf release downloaded by `libddwaf-sys`, if it did not use an installed one
+    if let Ok(version) = std::env::var("DEP_DDWAF_VERSION") {
+        println!("cargo::rustc-env=LIBDDWAF_RELEASE_VERSION={version}");
+    }
+    #[cfg(feature = "bundled-ruleset")]
+    ruleset::bundle();
+    println!("cargo::rerun-if-changed=build.rs");
+}
+
+#[cfg(feature = "bundled-ruleset")]
+mod ruleset {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use sha2::{Digest, Sha256};
+
+    /// The release of the recommended ruleset that is embedded, unless another one is requested
+    /// with `LIBDDWAF_RULESET_VERSION`.
+    const DEFAULT_VERSION: &str = "1.13.3";
+    /// The SHA-256 checksum of the `recommended.json` document of [`DEFAULT_VERSION`], which must be
+    /// updated along with it.
+    // FIXME: pin the checksum of the 1.13.3 document; until then, downloading it fails, reporting
+    // its actual checksum.
+    const DEFAULT_SHA256: &str = "";
+
+    /// Writes the recommended ruleset to `$OUT_DIR/recommended.json`, for the `ruleset` module.
+    ///
+    /// The ruleset is read from `LIBDDWAF_RULESET_PATH` if it is set, which allows offline and
+    /// vendored builds; otherwise it is downloaded from the `DataDog/appsec-event-rules` releases,
+    /// and checked against [`DEFAULT_SHA256`], or against `LIBDDWAF_RULESET_SHA256` (which is then
+    /// required) if another release is requested with `LIBDDWAF_RULESET_VERSION`.
+    pub(super) fn bundle() {
+        let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("recommended.json");
+        println!("cargo::rerun-if-env-changed=LIBDDWAF_RULESET_PATH");
+        println!("cargo::rerun-if-env-changed=LIBDDWAF_RULESET_VERSION");
+        println!("cargo::rerun-if-env-changed=LIBDDWAF_RULESET_SHA256");
+
+        let ruleset = match env::var_os("LIBDDWAF_RULESET_PATH") {
+            Some(path) if !path.is_empty() => {
+                println!("cargo::rerun-if-changed={}", PathBuf::from(&path).display());
+                fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {path:?}: {e}"))
+            }
+            _ => {
+                let (version, expected) = match env::var("LIBDDWAF_RULESET_VERSION") {
+                    Ok(version) if !version.is_empty() => {
+                        let expected = env::var("LIBDDWAF_RULESET_SHA256")
+                            .ok()
+                            .filter(|e| !e.is_empty())
+                            .expect("LIBDDWAF_RULESET_SHA256 must be set along with LIBDDWAF_RULESET_VERSION");
+                        (version, expected)
+                    }
+                    _ => (DEFAULT_VERSION.to_string(), DEFAULT_SHA256.to_string()),
+                };
+                let ruleset = download(&version);
+                if let Err(error) = verify_sha256(&ruleset, &expected) {
+                    panic!("Failed to verify the recommended ruleset {version}: {error}");
+                }
+                ruleset
+            }
+        };
+        fs::write(&out_path, ruleset).expect("Failed to write the recommended ruleset");
+    }
+
+    fn download(version: &str) -> Vec<u8> {
+        // Ensure reqwest is able to use a crypto provider (see the libddwaf-sys build script). This
+        // fails if one was already installed, which is fine.
+        let _ = rustls::crypto::CryptoProvider::install_default(
+            rustls::crypto::aws_lc_rs::default_provider(),
+        );
+
+        let url = format!(
+            "https://raw.githubusercontent.com/DataDog/appsec-event-rules/{version}/build/recommended.json"
+        );
+        let response = reqwest::blocking::get(&url).expect("Failed to download the ruleset");
+        assert!(
+            response.status().is_success(),
+            "Failed to download the ruleset from {url}: {status}",
+            status = response.status()
+        );
+        response
+            .bytes()
+            .expect("Failed to download the ruleset")
+            .to_vec()
+    }
+
+    /// Checks that `data` has the `expected` SHA-256 checksum (in hexadecimal, in any case).
+    fn verify_sha256(data: &[u8], expected: &str) -> Result<(), String> {
+        let actual: String = Sha256::digest(data)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        if actual.eq_ignore_ascii_case(expected.trim()) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Checksum mismatch: expected SHA-256 {expected}, got {actual}"
+            ))
+        }
+    }
+}
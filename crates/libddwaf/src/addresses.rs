@@ -0,0 +1,233 @@
+//! Names of the addresses documented by `libddwaf`, and an [`AddressMapBuilder`] producing address
+//! data in the shapes rulesets expect.
+//!
+//! Not all addresses are used by every ruleset; [`Handle::uses_address`](crate::Handle::uses_address)
+//! tells whether collecting data for a given address is worthwhile.
+
+use std::net::IpAddr;
+
+use crate::object::{WafMap, WafObject};
+
+/// The HTTP method of the request, as a string.
+pub const REQUEST_METHOD: &str = "server.request.method";
+/// The raw, undecoded URI of the request (path and query string), as a string.
+pub const REQUEST_URI_RAW: &str = "server.request.uri.raw";
+/// The request headers, excluding cookies, as a map from lower-cased header names to values.
+pub const REQUEST_HEADERS_NO_COOKIES: &str = "server.request.headers.no_cookies";
+/// The request cookies, as a map from cookie names to values.
+pub const REQUEST_COOKIES: &str = "server.request.cookies";
+/// The decoded query string parameters of the request, as a map.
+pub const REQUEST_QUERY: &str = "server.request.query";
+/// The path parameters extracted by the framework's router, as a map.
+pub const REQUEST_PATH_PARAMS: &str = "server.request.path_params";
+/// The parsed request body.
+pub const REQUEST_BODY: &str = "server.request.body";
+/// The names of the files uploaded with the request, as an array of strings.
+pub const REQUEST_BODY_FILENAMES: &str = "server.request.body.filenames";
+/// The names of the multipart fields carrying uploaded files, as an array of strings.
+pub const REQUEST_BODY_FILES_FIELD_NAMES: &str = "server.request.body.files_field_names";
+/// The request trailers, as a map.
+pub const REQUEST_TRAILERS: &str = "server.request.trailers";
+
+/// The status code of the response, as a string.
+pub const RESPONSE_STATUS: &str = "server.response.status";
+/// The response headers, excluding cookies, as a map from lower-cased header names to values.
+pub const RESPONSE_HEADERS_NO_COOKIES: &str = "server.response.headers.no_cookies";
+/// The parsed response body.
+pub const RESPONSE_BODY: &str = "server.response.body";
+
+/// The full name of the gRPC method being called, as a string.
+pub const GRPC_SERVER_METHOD: &str = "grpc.server.method";
+/// The gRPC request message(s).
+pub const GRPC_SERVER_REQUEST_MESSAGE: &str = "grpc.server.request.message";
+/// The gRPC request metadata, as a map.
+pub const GRPC_SERVER_REQUEST_METADATA: &str = "grpc.server.request.metadata";
+/// The gRPC response message(s).
+pub const GRPC_SERVER_RESPONSE_MESSAGE: &str = "grpc.server.response.message";
+/// The gRPC response metadata headers, as a map.
+pub const GRPC_SERVER_RESPONSE_METADATA_HEADERS: &str = "grpc.server.response.metadata.headers";
+/// The gRPC response metadata trailers, as a map.
+pub const GRPC_SERVER_RESPONSE_METADATA_TRAILERS: &str = "grpc.server.response.metadata.trailers";
+
+/// The arguments of all the resolvers of a GraphQL query, as a map from resolver names to
+/// arrays of argument maps.
+pub const GRAPHQL_SERVER_ALL_RESOLVERS: &str = "graphql.server.all_resolvers";
+/// The arguments of a single GraphQL resolver, as a map from the resolver name to its arguments.
+pub const GRAPHQL_SERVER_RESOLVER: &str = "graphql.server.resolver";
+
+/// The IP address of the client, as a string.
+pub const HTTP_CLIENT_IP: &str = "http.client_ip";
+/// The identifier of the authenticated user, as a string.
+pub const USER_ID: &str = "usr.id";
+/// The login of the authenticated user, as a string.
+pub const USER_LOGIN: &str = "usr.login";
+/// The identifier of the user's session, as a string.
+pub const USER_SESSION_ID: &str = "usr.session_id";
+/// Set (to any value) when a user login succeeded.
+pub const LOGIN_SUCCESS: &str = "server.business_logic.users.login.success";
+/// Set (to any value) when a user login failed.
+pub const LOGIN_FAILURE: &str = "server.business_logic.users.login.failure";
+
+/// The URL of an outgoing network request, as a string.
+pub const IO_NET_URL: &str = "server.io.net.url";
+/// The path of a file being accessed, as a string.
+pub const IO_FS_FILE: &str = "server.io.fs.file";
+/// The statement of a database query, as a string.
+pub const DB_STATEMENT: &str = "server.db.statement";
+/// The database management system a query is sent to (such as `postgresql`), as a string.
+pub const DB_SYSTEM: &str = "server.db.system";
+/// A shell command being executed, as a string.
+pub const SYS_SHELL_CMD: &str = "server.sys.shell.cmd";
+/// A command being executed without a shell, as an array of strings.
+pub const SYS_EXEC_CMD: &str = "server.sys.exec.cmd";
+
+/// Settings for the WAF's processors, as a map (for example, `{"extract-schema": true}` enables
+/// API schema extraction, and `{"fingerprint": true}` enables fingerprinting).
+pub const WAF_CONTEXT_PROCESSOR: &str = "waf.context.processor";
+
+/// Accumulates address data into a [`WafMap`] suitable for [`RunnableContext::run`](crate::RunnableContext::run),
+/// using the address names and value shapes documented by `libddwaf`.
+///
+/// Setting the same address more than once replaces the previous value, so each address appears
+/// at most once in the resulting map.
+///
+/// # Example
+/// ```
+/// use libddwaf::addresses::AddressMapBuilder;
+/// use libddwaf::waf_map;
+///
+/// let data = AddressMapBuilder::new()
+///     .request_method("GET")
+///     .request_headers(waf_map!(("user-agent", "Arachni/v1")))
+///     .response_status(404)
+///     .build();
+/// assert_eq!(data.len(), 3);
+/// assert_eq!(data.get_str("server.response.status").and_then(|s| s.to_str()), Some("404"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AddressMapBuilder {
+    entries: Vec<(&'static str, WafObject)>,
+}
+impl AddressMapBuilder {
+    /// Creates a new, empty [`AddressMapBuilder`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value of an arbitrary `address`, replacing any previous value.
+    #[must_use]
+    pub fn set(mut self, address: &'static str, value: impl Into<WafObject>) -> Self {
+        let value = value.into();
+        match self.entries.iter_mut().find(|(key, _)| *key == address) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((address, value)),
+        }
+        self
+    }
+
+    /// Sets [`REQUEST_METHOD`].
+    #[must_use]
+    pub fn request_method(self, method: &str) -> Self {
+        self.set(REQUEST_METHOD, method)
+    }
+
+    /// Sets [`REQUEST_URI_RAW`].
+    #[must_use]
+    pub fn request_uri(self, uri: &str) -> Self {
+        self.set(REQUEST_URI_RAW, uri)
+    }
+
+    /// Sets [`REQUEST_HEADERS_NO_COOKIES`].
+    #[must_use]
+    pub fn request_headers(self, headers: WafMap) -> Self {
+        self.set(REQUEST_HEADERS_NO_COOKIES, headers)
+    }
+
+    /// Sets [`REQUEST_COOKIES`].
+    #[must_use]
+    pub fn request_cookies(self, cookies: WafMap) -> Self {
+        self.set(REQUEST_COOKIES, cookies)
+    }
+
+    /// Sets [`REQUEST_QUERY`].
+    #[must_use]
+    pub fn request_query(self, query: WafMap) -> Self {
+        self.set(REQUEST_QUERY, query)
+    }
+
+    /// Sets [`REQUEST_PATH_PARAMS`].
+    #[must_use]
+    pub fn request_path_params(self, params: WafMap) -> Self {
+        self.set(REQUEST_PATH_PARAMS, params)
+    }
+
+    /// Sets [`REQUEST_BODY`].
+    #[must_use]
+    pub fn request_body(self, body: impl Into<WafObject>) -> Self {
+        self.set(REQUEST_BODY, body)
+    }
+
+    /// Sets [`RESPONSE_STATUS`], formatting the status code as a string.
+    #[must_use]
+    pub fn response_status(self, status: u16) -> Self {
+        self.set(RESPONSE_STATUS, status.to_string())
+    }
+
+    /// Sets [`RESPONSE_HEADERS_NO_COOKIES`].
+    #[must_use]
+    pub fn response_headers(self, headers: WafMap) -> Self {
+        self.set(RESPONSE_HEADERS_NO_COOKIES, headers)
+    }
+
+    /// Sets [`RESPONSE_BODY`].
+    #[must_use]
+    pub fn response_body(self, body: impl Into<WafObject>) -> Self {
+        self.set(RESPONSE_BODY, body)
+    }
+
+    /// Sets [`HTTP_CLIENT_IP`], formatting the address as a string.
+    #[must_use]
+    pub fn client_ip(self, ip: IpAddr) -> Self {
+        self.set(HTTP_CLIENT_IP, ip.to_string())
+    }
+
+    /// Sets [`USER_ID`].
+    #[must_use]
+    pub fn user_id(self, id: &str) -> Self {
+        self.set(USER_ID, id)
+    }
+
+    /// Sets [`USER_LOGIN`].
+    #[must_use]
+    pub fn user_login(self, login: &str) -> Self {
+        self.set(USER_LOGIN, login)
+    }
+
+    /// Sets [`USER_SESSION_ID`].
+    #[must_use]
+    pub fn session_id(self, id: &str) -> Self {
+        self.set(USER_SESSION_ID, id)
+    }
+
+    /// Returns true if no address has been set.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Produces the [`WafMap`] containing the addresses that were set, in the order they were
+    /// first set.
+    ///
+    /// Only the first [`u16::MAX`] addresses are included; any extra addresses are ignored.
+    #[must_use]
+    pub fn build(self) -> WafMap {
+        let len = self.entries.len().min(usize::from(u16::MAX));
+        #[allow(clippy::cast_possible_truncation)] // Bounded by the min above
+        let mut map = WafMap::new(len as u16);
+        for (i, (address, value)) in self.entries.into_iter().take(len).enumerate() {
+            map[i] = (address, value).into();
+        }
+        map
+    }
+}
@@ -1,26 +1,63 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::ops::ControlFlow;
 use std::ptr::null_mut;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::object::{AsRawMutObject, WafArray, WafMap, WafOwnedDefaultAllocator};
-use crate::{Config, Handle};
+use crate::object::raw::AsRawMutObject;
+use crate::object::{
+    Keyed, UncheckedAsWafObject, WafArray, WafMap, WafObject, WafObjectType,
+    WafOwnedDefaultAllocator,
+};
+use crate::{Config, Handle, LengthError, RuleInfo};
 
 /// A builder for [`Handle`]s.
 ///
 /// This is used to maintain a live view over mutable configuration, and is best
 /// suited for cases where the Waf's configuration evolves regularly, such as
 /// through remote configuration.
-#[repr(transparent)]
+///
+/// The time spent in each [`Builder::add_or_update_config`], [`Builder::remove_config`] and
+/// [`Builder::build`] call is recorded, and can be retrieved using
+/// [`Builder::last_operation_duration`] and [`Builder::total_build_time`].
+///
+/// Mutating a [`Builder`] requires exclusive access to it; see [`SyncBuilder`] for a variant that
+/// can be shared between threads.
+///
+/// A summary of the last [`Handle`] built is retained, so that [`Builder::build_with_delta`] can
+/// report how the WAF's capabilities changed from one build to the next. A copy of each
+/// configuration is retained as well, for [`Builder::export_merged_config`].
 pub struct Builder {
     raw: libddwaf_sys::ddwaf_builder,
+    last_operation_duration: Option<Duration>,
+    total_build_time: Duration,
+    rules: BTreeMap<String, Vec<RuleInfo>>,
+    inventory: HashMap<String, ConfigEntry>,
+    configs: BTreeMap<String, WafObject>,
+    last_build: BuildSummary,
+    generation: u64,
 }
 impl Builder {
     const OBFUSCATOR_KEY: &str = "datadog/0/ASM_DD/0/config";
 
     /// Creates a new [`Builder`] instance using the provided [`Config`]. Returns [`None`] if the
     /// builder's initialization fails.
+    ///
+    /// The [`Config`] is only borrowed for the duration of this call: its contents are copied
+    /// into `libddwaf`, so it may be dropped as soon as this returns. See [`Builder::with_config`]
+    /// for a variant that takes ownership of it.
     #[must_use]
     pub fn new(config: Option<&Config>) -> Option<Self> {
         let mut builder = Builder {
             raw: unsafe { libddwaf_sys::ddwaf_builder_init() },
+            last_operation_duration: None,
+            total_build_time: Duration::ZERO,
+            rules: BTreeMap::new(),
+            inventory: HashMap::new(),
+            configs: BTreeMap::new(),
+            last_build: BuildSummary::default(),
+            generation: 0,
         };
         if builder.raw.is_null() {
             return None;
@@ -29,8 +66,8 @@ impl Builder {
         if let Some(config) = config {
             let config_obj = config.as_waf_object();
             let res = builder.add_or_update_config(Self::OBFUSCATOR_KEY, &config_obj, None);
+            strict_assert!(res, "Failed to add or update obfuscator config");
             if !res {
-                debug_assert!(false, "Failed to add or update obfuscator config");
                 return None;
             }
         }
@@ -38,21 +75,79 @@ impl Builder {
         Some(builder)
     }
 
+    /// Creates a new [`Builder`] instance using the provided [`Config`], which is consumed. Returns
+    /// [`None`] if the builder's initialization fails.
+    ///
+    /// This is equivalent to [`Builder::new`], and is convenient when the [`Config`] is built
+    /// inline: its contents (including the [`Obfuscator`][crate::Obfuscator]) are copied into
+    /// `libddwaf`, so the [`Builder`] does not depend on it after this returns.
+    #[must_use]
+    #[allow(clippy::needless_pass_by_value)] // Consuming the Config is the purpose of this function
+    pub fn with_config(config: Config) -> Option<Self> {
+        Self::new(Some(&config))
+    }
+
+    /// Returns the underlying `libddwaf` builder, for use with other code linked against
+    /// `libddwaf`.
+    ///
+    /// Ownership is retained by this [`Builder`]: the returned pointer must not be destroyed, and
+    /// must not be used after this [`Builder`] has been dropped. It must not be used to add or
+    /// remove configurations either, as this [`Builder`] would not be aware of such changes (see
+    /// [`Builder::rules`] and [`Builder::config_inventory`]).
+    #[must_use]
+    pub fn as_raw(&self) -> libddwaf_sys::ddwaf_builder {
+        self.raw
+    }
+
     /// Adds or updates the configuration for the given path.
     ///
     /// Returns true if the ruleset was successfully added or updated. Any warning/error information
-    /// is conveyed through the provided diagnostics object.
+    /// is conveyed through the provided diagnostics object. The same diagnostics object can be
+    /// reused across calls, as its previous contents are released (see [`WafOwned::reset`][crate::object::WafOwned::reset]):
+    ///
+    /// ```no_run
+    /// # use libddwaf::{object::*, Builder};
+    /// # let mut builder = Builder::new(None).unwrap();
+    /// # let configs: Vec<(&str, WafMap)> = Vec::new();
+    /// let mut diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+    /// for (path, config) in &configs {
+    ///     if !builder.add_or_update_config(path, config, Some(&mut diagnostics)) {
+    ///         eprintln!("Failed to load {path}: {diagnostics:?}");
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// The configuration can be any object that is a map, including a [`WafObject`] obtained by
+    /// parsing a JSON document, without converting it into a [`WafMap`] first. In debug builds,
+    /// this panics if it is not a map; otherwise, `libddwaf` rejects it.
     ///
     /// # Panics
-    /// Panics if the provided `path` is longer than [`u32::MAX`] bytes.
+    /// Panics if the provided `path` is longer than [`u32::MAX`] bytes. See
+    /// [`Builder::try_add_or_update_config`] for a variant that returns an error instead.
     #[must_use]
     pub fn add_or_update_config(
         &mut self,
         path: &str,
         ruleset: &impl AsRef<libddwaf_sys::ddwaf_object>,
-        mut diagnostics: Option<&mut WafOwnedDefaultAllocator<WafMap>>,
+        diagnostics: Option<&mut WafOwnedDefaultAllocator<WafMap>>,
     ) -> bool {
-        debug_assert!(
+        self.try_add_or_update_config(path, ruleset, diagnostics)
+            .expect("path is too long")
+    }
+
+    /// Adds or updates the configuration for the given path, like
+    /// [`Builder::add_or_update_config`].
+    ///
+    /// # Errors
+    /// Returns an error if the provided `path` is longer than [`u32::MAX`] bytes, in which case
+    /// the configuration is left unchanged.
+    pub fn try_add_or_update_config(
+        &mut self,
+        path: &str,
+        ruleset: &impl AsRef<libddwaf_sys::ddwaf_object>,
+        mut diagnostics: Option<&mut WafOwnedDefaultAllocator<WafMap>>,
+    ) -> Result<bool, LengthError> {
+        strict_assert!(
             !path.is_empty(),
             concat!(
                 "path cannot be empty (",
@@ -60,19 +155,128 @@ impl Builder {
                 " would always fail)"
             )
         );
-        let path_len = u32::try_from(path.len()).expect("path is too long");
+        strict_assert!(
+            ruleset.as_ref().as_object_ref().object_type() == WafObjectType::Map,
+            "ruleset must be a map, but is {:?}",
+            ruleset.as_ref().as_object_ref().object_type()
+        );
+        let path_len = LengthError::checked_len("path", path.len())?;
         if let Some(ref mut diagnostics) = diagnostics {
-            // drop the old diagnostics if we're reusing it
-            let _ = std::mem::take(*diagnostics);
+            // release the old diagnostics if we're reusing it
+            diagnostics.reset();
         }
-        unsafe {
+        // Diagnostics are needed to determine which rules were loaded
+        let mut own_diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+        let diagnostics = diagnostics.unwrap_or(&mut own_diagnostics);
+        let start = Instant::now();
+        let res = unsafe {
             libddwaf_sys::ddwaf_builder_add_or_update_config(
                 self.raw,
                 path.as_ptr().cast(),
                 path_len,
                 ruleset.as_ref(),
-                diagnostics.map_or(null_mut(), |o| std::ptr::from_mut(o.as_raw_mut()).cast()),
+                std::ptr::from_mut(diagnostics.as_raw_mut()),
             )
+        };
+        self.record_operation(start.elapsed());
+        if res {
+            let ruleset = ruleset.as_ref().as_object_ref();
+            let rules = RuleInfo::loaded_from(ruleset, diagnostics);
+            self.inventory.insert(
+                path.to_string(),
+                ConfigEntry {
+                    path: path.to_string(),
+                    fingerprint: ruleset.content_hash(),
+                    added_at: SystemTime::now(),
+                    rules_loaded: rules.len(),
+                },
+            );
+            self.configs.insert(path.to_string(), ruleset.clone());
+            if rules.is_empty() {
+                self.rules.remove(path);
+            } else {
+                self.rules.insert(path.to_string(), rules);
+            }
+        } else if self.inventory.contains_key(path)
+            && !self.config_paths(None).iter().any(|p| p.to_str() == Some(path))
+        {
+            // The previous configuration for this path was dropped by the failed update
+            self.inventory.remove(path);
+            self.configs.remove(path);
+            self.rules.remove(path);
+        }
+        Ok(res)
+    }
+
+    /// Adds or updates the configuration for the given path like
+    /// [`Builder::add_or_update_config`], but only keeps it if the rules it contains load in a way
+    /// that satisfies the provided [`AcceptancePolicy`].
+    ///
+    /// If the configuration is rejected, it is removed from this [`Builder`], and the previous
+    /// configuration for the same path (if any) is restored in its place. The `diagnostics`
+    /// always describe the configuration that was submitted.
+    ///
+    /// # Errors
+    /// Returns [`Rejected`] if `libddwaf` failed to load the configuration, or if it does not
+    /// satisfy the `policy`.
+    ///
+    /// # Panics
+    /// Panics if the provided `path` is longer than [`u32::MAX`] bytes.
+    pub fn add_or_update_config_with_policy(
+        &mut self,
+        path: &str,
+        ruleset: &impl AsRef<libddwaf_sys::ddwaf_object>,
+        policy: &AcceptancePolicy<'_>,
+        diagnostics: Option<&mut WafOwnedDefaultAllocator<WafMap>>,
+    ) -> Result<Accepted, Rejected> {
+        let previous = self.configs.get(path).cloned();
+        let mut own_diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+        let diagnostics = diagnostics.unwrap_or(&mut own_diagnostics);
+        let added = self.add_or_update_config(path, ruleset, Some(&mut *diagnostics));
+        let report = RuleLoadReport::from_diagnostics(diagnostics);
+        let reason = if added {
+            policy.violation(&report)
+        } else {
+            Some(RejectionReason::NotLoaded)
+        };
+        let Some(reason) = reason else {
+            return Ok(Accepted { report });
+        };
+        let restored = match previous {
+            Some(previous) => self.add_or_update_config(path, &previous, None),
+            None => {
+                self.remove_config(path);
+                false
+            }
+        };
+        Err(Rejected {
+            reason,
+            report,
+            restored,
+        })
+    }
+
+    /// Adds the recommended Datadog ruleset embedded with the `bundled-ruleset` feature (see
+    /// [`ruleset::recommended_parsed`][crate::ruleset::recommended_parsed]) under
+    /// [`ruleset::RECOMMENDED_PATH`][crate::ruleset::RECOMMENDED_PATH], like
+    /// [`Builder::add_or_update_config`].
+    ///
+    /// # Errors
+    /// Returns the diagnostics reported by `libddwaf` in both cases, as an error if it failed to
+    /// load the ruleset.
+    #[cfg(feature = "bundled-ruleset")]
+    pub fn with_recommended_ruleset(
+        &mut self,
+    ) -> Result<WafOwnedDefaultAllocator<WafMap>, WafOwnedDefaultAllocator<WafMap>> {
+        let mut diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+        if self.add_or_update_config(
+            crate::ruleset::RECOMMENDED_PATH,
+            crate::ruleset::recommended_parsed(),
+            Some(&mut diagnostics),
+        ) {
+            Ok(diagnostics)
+        } else {
+            Err(diagnostics)
         }
     }
 
@@ -81,68 +285,574 @@ impl Builder {
     /// Returns true if some configuration was indeed removed.
     ///
     /// # Panics
-    /// Panics if the provided `path` is longer than [`u32::MAX`] bytes.
+    /// Panics if the provided `path` is longer than [`u32::MAX`] bytes. See
+    /// [`Builder::try_remove_config`] for a variant that returns an error instead.
     pub fn remove_config(&mut self, path: &str) -> bool {
-        let path_len = u32::try_from(path.len()).expect("path is too long");
-        unsafe {
+        self.try_remove_config(path).expect("path is too long")
+    }
+
+    /// Removes the configuration for the given path if some exists, like
+    /// [`Builder::remove_config`].
+    ///
+    /// # Errors
+    /// Returns an error if the provided `path` is longer than [`u32::MAX`] bytes.
+    pub fn try_remove_config(&mut self, path: &str) -> Result<bool, LengthError> {
+        let path_len = LengthError::checked_len("path", path.len())?;
+        let start = Instant::now();
+        let res = unsafe {
             libddwaf_sys::ddwaf_builder_remove_config(self.raw, path.as_ptr().cast(), path_len)
+        };
+        self.record_operation(start.elapsed());
+        if res {
+            self.rules.remove(path);
+            self.inventory.remove(path);
+            self.configs.remove(path);
         }
+        Ok(res)
+    }
+
+    /// Removes all configurations whose path starts with `prefix`, such as all the configurations
+    /// received from a given remote configuration product (e.g, `datadog/2/ASM/`).
+    ///
+    /// Returns the number of configurations that were removed.
+    pub fn remove_config_matching(&mut self, prefix: &str) -> u32 {
+        let paths: Vec<String> = self
+            .config_paths(None)
+            .iter()
+            .filter_map(WafObject::to_str)
+            .filter(|path| path.starts_with(prefix))
+            .map(str::to_string)
+            .collect();
+        let mut removed = 0;
+        for path in paths {
+            if self.remove_config(&path) {
+                removed += 1;
+            }
+        }
+        removed
     }
 
     /// Returns the number of configuration paths currently loaded in this [`Builder`], optionally
     /// filtered by a regular expression.
     ///
+    /// This only requires a shared reference, so it can be called from several threads at once:
+    ///
+    /// ```no_run
+    /// # use libddwaf::Builder;
+    /// let builder = Builder::new(None).unwrap();
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| builder.config_paths_count(Some("^datadog/")));
+    ///     s.spawn(|| builder.config_paths(None));
+    /// });
+    /// ```
+    ///
     /// # Panics
-    /// Panics if the provided `filter` regular expression is longer than [`u32::MAX`] bytes.
+    /// Panics if the provided `filter` regular expression is longer than [`u32::MAX`] bytes. See
+    /// [`Builder::try_config_paths_count`] for a variant that returns an error instead.
     #[must_use]
-    pub fn config_paths_count(&mut self, filter: Option<&'_ str>) -> u32 {
+    pub fn config_paths_count(&self, filter: Option<&'_ str>) -> u32 {
+        self.try_config_paths_count(filter)
+            .expect("filter is too long")
+    }
+
+    /// Returns the number of configuration paths currently loaded in this [`Builder`], like
+    /// [`Builder::config_paths_count`].
+    ///
+    /// # Errors
+    /// Returns an error if the provided `filter` regular expression is longer than [`u32::MAX`]
+    /// bytes.
+    pub fn try_config_paths_count(&self, filter: Option<&'_ str>) -> Result<u32, LengthError> {
         let filter = filter.unwrap_or("");
-        let filter_len = u32::try_from(filter.len()).expect("filter is too long");
-        unsafe {
+        let filter_len = LengthError::checked_len("filter", filter.len())?;
+        Ok(unsafe {
             libddwaf_sys::ddwaf_builder_get_config_paths(
-                self.raw,
+                self.raw_for_read(),
                 null_mut(),
                 filter.as_ptr().cast(),
                 filter_len,
             )
-        }
+        })
     }
 
     /// Returns the configuration paths currently loaded in this [`Builder`], optionally filtered by
     /// a regular expression.
     ///
     /// # Panics
-    /// Panics if the provided `filter` regular expression is longer than [`u32::MAX`] bytes.
+    /// Panics if the provided `filter` regular expression is longer than [`u32::MAX`] bytes. See
+    /// [`Builder::try_config_paths`] for a variant that returns an error instead.
     #[must_use]
-    pub fn config_paths(&mut self, filter: Option<&'_ str>) -> WafOwnedDefaultAllocator<WafArray> {
+    pub fn config_paths(&self, filter: Option<&'_ str>) -> WafOwnedDefaultAllocator<WafArray> {
+        self.try_config_paths(filter).expect("filter is too long")
+    }
+
+    /// Returns the configuration paths currently loaded in this [`Builder`], like
+    /// [`Builder::config_paths`].
+    ///
+    /// # Errors
+    /// Returns an error if the provided `filter` regular expression is longer than [`u32::MAX`]
+    /// bytes.
+    pub fn try_config_paths(
+        &self,
+        filter: Option<&'_ str>,
+    ) -> Result<WafOwnedDefaultAllocator<WafArray>, LengthError> {
+        let filter = filter.unwrap_or("");
+        let filter_len = LengthError::checked_len("filter", filter.len())?;
         // SAFETY: ddwaf_builder_get_config_paths uses the default allocator
         let mut res = WafOwnedDefaultAllocator::<WafArray>::default();
-        let filter = filter.unwrap_or("");
-        let filter_len = u32::try_from(filter.len()).expect("filter is too long");
         let _ = unsafe {
             libddwaf_sys::ddwaf_builder_get_config_paths(
-                self.raw,
+                self.raw_for_read(),
                 res.as_raw_mut(),
                 filter.as_ptr().cast(),
                 filter_len,
             )
         };
-        res
+        Ok(res)
+    }
+
+    /// Calls `f` with the configuration paths currently loaded in this [`Builder`], optionally
+    /// filtered by a regular expression, in chunks of at most `chunk_size` paths (at least one).
+    ///
+    /// `libddwaf` returns all paths at once, so they are all converted into [`String`]s first; the
+    /// array returned by `libddwaf` is released before `f` is first called, and the [`String`]s of
+    /// each chunk are released as soon as `f` returns. The peak memory usage is thus that of
+    /// [`Builder::config_paths`] plus the converted paths, but only the converted paths are held
+    /// while `f` runs.
+    ///
+    /// Returning [`ControlFlow::Break`] from `f` stops the iteration, and is returned by this
+    /// function; the remaining paths are released without being converted again.
+    ///
+    /// # Panics
+    /// Panics if the provided `filter` regular expression is longer than [`u32::MAX`] bytes.
+    pub fn config_paths_chunked(
+        &self,
+        filter: Option<&'_ str>,
+        chunk_size: usize,
+        mut f: impl FnMut(&[String]) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let paths: Vec<String> = self
+            .config_paths(filter)
+            .iter()
+            .filter_map(WafObject::to_str)
+            .map(str::to_string)
+            .collect();
+        let mut paths = paths.into_iter();
+        loop {
+            let chunk: Vec<String> = paths.by_ref().take(chunk_size.max(1)).collect();
+            if chunk.is_empty() {
+                return ControlFlow::Continue(());
+            }
+            f(&chunk)?;
+        }
     }
 
     /// Builds a new [`Handle`] from the current configuration in this [`Builder`].
     ///
     /// Returns [`None`] if the builder fails to create a new [`Handle`], meaning the current
     /// configuration contains no active instructions (no rules nor processors are available).
+    ///
+    /// This requires an exclusive reference, as `libddwaf` updates the builder's internal state
+    /// (caching the ruleset it assembled from the current configurations).
+    ///
+    /// Each [`Handle`] built is assigned the next [`Handle::generation`] of this [`Builder`].
     #[must_use]
     pub fn build(&mut self) -> Option<Handle> {
+        self.build_with_delta().map(|(handle, _)| handle)
+    }
+
+    /// Builds a new [`Handle`] like [`Builder::build`], also returning a [`BuildDelta`] describing
+    /// how the new [`Handle`] differs from the previous one built by this [`Builder`] (or from an
+    /// empty ruleset, if this is the first successful build).
+    ///
+    /// `libddwaf` does not report diagnostics when building; this allows observing the effects
+    /// of configurations that do not add or remove rules, such as rule overrides or exclusions.
+    #[must_use]
+    pub fn build_with_delta(&mut self) -> Option<(Handle, BuildDelta)> {
+        self.debug_assert_inventory_consistent();
+        let start = Instant::now();
         let raw = unsafe { libddwaf_sys::ddwaf_builder_build_instance(self.raw) };
+        let build_duration = start.elapsed();
+        self.record_operation(build_duration);
         if raw.is_null() {
             return None;
         }
-        Some(Handle { raw })
+        self.generation += 1;
+        let handle = Handle::new(
+            raw,
+            build_duration,
+            self.generation,
+            self.rules.values().flatten().cloned().collect(),
+        );
+        let summary = BuildSummary::of(&handle);
+        let delta = BuildDelta::between(&self.last_build, &summary);
+        self.last_build = summary;
+        Some((handle, delta))
+    }
+
+    /// Returns metadata about the rules that were successfully loaded from the configurations
+    /// currently present in this [`Builder`].
+    pub fn rules(&self) -> impl Iterator<Item = &RuleInfo> {
+        self.rules.values().flatten()
+    }
+
+    /// Returns information about each configuration currently present in this [`Builder`], sorted
+    /// by path.
+    ///
+    /// This can be used to verify which version of each configuration is loaded, by comparing the
+    /// [`ConfigEntry::fingerprint`] with that of the expected content.
+    #[must_use]
+    pub fn config_inventory(&self) -> Vec<ConfigEntry> {
+        let mut res: Vec<_> = self.inventory.values().cloned().collect();
+        res.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+        res
+    }
+
+    /// Returns a single configuration merging all the configurations currently present in this
+    /// [`Builder`], for debugging purposes.
+    ///
+    /// `libddwaf` does not expose the ruleset it assembles, so this is reconstructed from copies
+    /// of the configurations that were added, visited in path order: array sections (such as
+    /// `rules`, `exclusions` or `actions`) are concatenated, and for any other section the value
+    /// from the last path wins. This approximates what `libddwaf` does, but does not reproduce
+    /// its handling of conflicts: for example, entries that `libddwaf` rejected (see the
+    /// diagnostics of [`Builder::add_or_update_config`]) or duplicate identifiers are kept
+    /// as-is. Sections and arrays are truncated to [`u16::MAX`] entries.
+    #[must_use]
+    pub fn export_merged_config(&self) -> WafMap {
+        let mut sections: Vec<(&[u8], Vec<&WafObject>)> = Vec::new();
+        let configs = self.configs.values().filter_map(WafObject::as_type::<WafMap>);
+        for entry in configs.flat_map(WafMap::iter) {
+            let Ok(key) = entry.key_bytes() else {
+                continue;
+            };
+            let value: &WafObject = entry;
+            match sections.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, values)) => values.push(value),
+                None => sections.push((key, vec![value])),
+            }
+        }
+
+        let len = sections.len().min(usize::from(u16::MAX));
+        #[allow(clippy::cast_possible_truncation)] // Bounded by the min above
+        let mut merged = WafMap::new(len as u16);
+        for (i, (key, values)) in sections.into_iter().take(len).enumerate() {
+            merged[i] = (key, merge_section(&values)).into();
+        }
+        merged
+    }
+
+    /// Returns the time spent in the most recent [`Builder::add_or_update_config`],
+    /// [`Builder::remove_config`] or [`Builder::build`] call, or [`None`] if no such call was made
+    /// yet.
+    ///
+    /// This includes the call made by [`Builder::new`] to install the obfuscator configuration,
+    /// if one was provided.
+    #[must_use]
+    pub fn last_operation_duration(&self) -> Option<Duration> {
+        self.last_operation_duration
+    }
+
+    /// Returns the cumulative time spent in [`Builder::add_or_update_config`],
+    /// [`Builder::remove_config`] and [`Builder::build`] calls over the lifetime of this
+    /// [`Builder`].
+    #[must_use]
+    pub fn total_build_time(&self) -> Duration {
+        self.total_build_time
+    }
+
+    fn record_operation(&mut self, duration: Duration) {
+        self.last_operation_duration = Some(duration);
+        self.total_build_time = self.total_build_time.saturating_add(duration);
+    }
+
+    /// Returns the raw builder pointer, for use with `libddwaf` functions that do not modify the
+    /// builder.
+    ///
+    /// The `libddwaf` API has no `const` builder type, so such functions receive the same mutable
+    /// pointer as those that modify it. Funnelling read-only calls through this helper keeps it
+    /// auditable that only non-modifying functions are called through a shared reference.
+    fn raw_for_read(&self) -> libddwaf_sys::ddwaf_builder {
+        self.raw
+    }
+
+    /// Checks, in debug builds, that the configuration inventory lists the same paths as
+    /// `libddwaf`. This lists all loaded paths, so it only runs when building rather than after
+    /// each addition or removal, which would make loading many configurations quadratic.
+    fn debug_assert_inventory_consistent(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        let paths = self.config_paths(None);
+        let mut loaded: Vec<_> = paths.iter().filter_map(WafObject::to_str).collect();
+        loaded.sort_unstable();
+        let mut known: Vec<_> = self.inventory.keys().map(String::as_str).collect();
+        known.sort_unstable();
+        debug_assert_eq!(
+            loaded, known,
+            "configuration inventory is out of sync with libddwaf"
+        );
+    }
+}
+
+/// Merges the values of a configuration section found in several configurations, for
+/// [`Builder::export_merged_config`].
+fn merge_section(values: &[&WafObject]) -> WafObject {
+    let arrays: Option<Vec<&WafArray>> = values.iter().map(|v| v.as_type::<WafArray>()).collect();
+    let Some(arrays) = arrays else {
+        // Not all values are arrays, so the last one wins
+        return values.last().map_or_else(WafObject::default, |v| (*v).clone());
+    };
+    let items: Vec<&WafObject> = arrays.into_iter().flat_map(WafArray::iter).collect();
+    let len = items.len().min(usize::from(u16::MAX));
+    #[allow(clippy::cast_possible_truncation)] // Bounded by the min above
+    let mut merged = WafArray::new(len as u16);
+    for (i, item) in items.into_iter().take(len).enumerate() {
+        merged[i] = item.clone();
     }
+    merged.into()
 }
+
+/// The conditions a configuration must meet to be accepted by
+/// [`Builder::add_or_update_config_with_policy`].
+///
+/// The [`Default`] policy accepts any configuration that `libddwaf` loads.
+#[derive(Clone, Debug, Default)]
+pub struct AcceptancePolicy<'a> {
+    /// The maximum number of rules that may fail to load.
+    pub max_failed_rules: Option<usize>,
+    /// The maximum proportion (between `0.0` and `1.0`) of the rules that may fail to load.
+    pub max_failed_ratio: Option<f32>,
+    /// The identifiers of the rules that must be loaded.
+    pub required_rule_ids: &'a [&'a str],
+}
+impl AcceptancePolicy<'_> {
+    fn violation(&self, report: &RuleLoadReport) -> Option<RejectionReason> {
+        let missing: Vec<String> = self
+            .required_rule_ids
+            .iter()
+            .filter(|id| !report.loaded.iter().any(|loaded| loaded == *id))
+            .map(|id| (*id).to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Some(RejectionReason::MissingRequiredRules(missing));
+        }
+        let failed = report.failed.len();
+        if let Some(max) = self.max_failed_rules.filter(|&max| failed > max) {
+            return Some(RejectionReason::TooManyFailedRules { failed, max });
+        }
+        let ratio = report.failed_ratio();
+        if let Some(max) = self.max_failed_ratio.filter(|&max| ratio > max) {
+            return Some(RejectionReason::FailedRatioExceeded { ratio, max });
+        }
+        None
+    }
+}
+
+/// The identifiers of the rules (and custom rules) that loaded, or failed to load, from a
+/// configuration; as reported by the diagnostics of [`Builder::add_or_update_config`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RuleLoadReport {
+    /// The identifiers of the rules that were loaded.
+    pub loaded: Vec<String>,
+    /// The identifiers of the rules that failed to load.
+    pub failed: Vec<String>,
+}
+impl RuleLoadReport {
+    /// Returns the proportion of the rules that failed to load, or `0.0` if there are no rules.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // Rule counts are far below the precision limit
+    pub fn failed_ratio(&self) -> f32 {
+        let total = self.loaded.len() + self.failed.len();
+        if total == 0 {
+            return 0.0;
+        }
+        self.failed.len() as f32 / total as f32
+    }
+
+    fn from_diagnostics(diagnostics: &WafMap) -> Self {
+        let mut report = Self::default();
+        for key in ["rules", "custom_rules"] {
+            let Some(section) = diagnostics
+                .get_str(key)
+                .and_then(Keyed::<WafObject>::as_type::<WafMap>)
+            else {
+                continue;
+            };
+            for (status, ids) in [
+                ("loaded", &mut report.loaded),
+                ("failed", &mut report.failed),
+            ] {
+                let list = section
+                    .get_str(status)
+                    .and_then(Keyed::<WafObject>::as_type::<WafArray>);
+                ids.extend(
+                    list.into_iter()
+                        .flat_map(Keyed::<WafArray>::iter)
+                        .filter_map(WafObject::to_str)
+                        .map(str::to_string),
+                );
+            }
+        }
+        report
+    }
+}
+
+/// The result of a configuration accepted by [`Builder::add_or_update_config_with_policy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Accepted {
+    /// The rules that loaded, or failed to load, from the configuration.
+    pub report: RuleLoadReport,
+}
+
+/// The error returned when a configuration is rejected by
+/// [`Builder::add_or_update_config_with_policy`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rejected {
+    /// Why the configuration was rejected.
+    pub reason: RejectionReason,
+    /// The rules that loaded, or failed to load, from the rejected configuration.
+    pub report: RuleLoadReport,
+    /// Whether a previous configuration for the same path was restored. When false, there was no
+    /// previous configuration for this path (or it could no longer be loaded), and the path was
+    /// left empty.
+    pub restored: bool,
+}
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The configuration was rejected: {}", self.reason)
+    }
+}
+impl std::error::Error for Rejected {}
+
+/// The reason a configuration was rejected by [`Builder::add_or_update_config_with_policy`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum RejectionReason {
+    /// `libddwaf` failed to load the configuration.
+    NotLoaded,
+    /// More rules than [`AcceptancePolicy::max_failed_rules`] failed to load.
+    TooManyFailedRules {
+        /// The number of rules that failed to load.
+        failed: usize,
+        /// The maximum number of rules allowed to fail to load.
+        max: usize,
+    },
+    /// The proportion of rules that failed to load exceeds
+    /// [`AcceptancePolicy::max_failed_ratio`].
+    FailedRatioExceeded {
+        /// The proportion of rules that failed to load.
+        ratio: f32,
+        /// The maximum proportion of rules allowed to fail to load.
+        max: f32,
+    },
+    /// Some of the [`AcceptancePolicy::required_rule_ids`] were not loaded.
+    MissingRequiredRules(Vec<String>),
+}
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectionReason::NotLoaded => write!(f, "it could not be loaded"),
+            RejectionReason::TooManyFailedRules { failed, max } => {
+                write!(f, "{failed} rules failed to load (at most {max} allowed)")
+            }
+            RejectionReason::FailedRatioExceeded { ratio, max } => {
+                write!(
+                    f,
+                    "{ratio} of the rules failed to load (at most {max} allowed)"
+                )
+            }
+            RejectionReason::MissingRequiredRules(ids) => {
+                write!(f, "required rules were not loaded: {}", ids.join(", "))
+            }
+        }
+    }
+}
+
+/// The differences between two consecutive [`Handle`]s built by the same [`Builder`], as returned
+/// by [`Builder::build_with_delta`].
+///
+/// All lists are sorted.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BuildDelta {
+    /// The addresses used by the new [`Handle`] that were not used by the previous one.
+    pub added_addresses: Vec<String>,
+    /// The addresses used by the previous [`Handle`] that are no longer used by the new one.
+    pub removed_addresses: Vec<String>,
+    /// The action types that may be produced by the new [`Handle`] but not by the previous one.
+    pub added_actions: Vec<String>,
+    /// The action types that may be produced by the previous [`Handle`] but no longer by the new
+    /// one.
+    pub removed_actions: Vec<String>,
+    /// The number of rules loaded in the previous [`Handle`] (see [`Handle::rules`]).
+    pub previous_rule_count: usize,
+    /// The number of rules loaded in the new [`Handle`] (see [`Handle::rules`]).
+    pub rule_count: usize,
+}
+impl BuildDelta {
+    /// Returns true if the new [`Handle`] uses the same addresses, produces the same actions, and
+    /// has the same number of rules as the previous one.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added_addresses.is_empty()
+            && self.removed_addresses.is_empty()
+            && self.added_actions.is_empty()
+            && self.removed_actions.is_empty()
+            && self.previous_rule_count == self.rule_count
+    }
+
+    fn between(previous: &BuildSummary, current: &BuildSummary) -> Self {
+        let difference = |left: &BTreeSet<String>, right: &BTreeSet<String>| {
+            left.difference(right).cloned().collect()
+        };
+        Self {
+            added_addresses: difference(&current.addresses, &previous.addresses),
+            removed_addresses: difference(&previous.addresses, &current.addresses),
+            added_actions: difference(&current.actions, &previous.actions),
+            removed_actions: difference(&previous.actions, &current.actions),
+            previous_rule_count: previous.rule_count,
+            rule_count: current.rule_count,
+        }
+    }
+}
+
+/// The properties of a [`Handle`] that are compared by [`BuildDelta`].
+#[derive(Default)]
+struct BuildSummary {
+    addresses: BTreeSet<String>,
+    actions: BTreeSet<String>,
+    rule_count: usize,
+}
+impl BuildSummary {
+    fn of(handle: &Handle) -> Self {
+        let to_strings = |list: Vec<&std::ffi::CStr>| {
+            list.into_iter()
+                .map(|s| s.to_string_lossy().into_owned())
+                .collect()
+        };
+        Self {
+            addresses: to_strings(handle.known_addresses()),
+            actions: to_strings(handle.known_actions()),
+            rule_count: handle.rules().len(),
+        }
+    }
+}
+
+/// Information about a configuration present in a [`Builder`], as returned by
+/// [`Builder::config_inventory`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigEntry {
+    /// The path the configuration was added at.
+    pub path: String,
+    /// The [`WafObject::content_hash`] of the configuration, captured when it was added or last
+    /// updated.
+    pub fingerprint: u64,
+    /// The time at which the configuration was added or last updated.
+    pub added_at: SystemTime,
+    /// The number of rules that were successfully loaded from the configuration.
+    pub rules_loaded: usize,
+}
+
 impl Drop for Builder {
     fn drop(&mut self) {
         unsafe { libddwaf_sys::ddwaf_builder_destroy(self.raw) }
@@ -151,5 +861,115 @@ impl Drop for Builder {
 
 // SAFETY: no thread-local data and no data can be changed under us if we have an owning handle
 unsafe impl Send for Builder {}
-// SAFETY: changes are only made through exclusive references
+// SAFETY: changes are only made through exclusive references, and methods taking a shared reference
+// only call functions that do not modify the builder (see `Builder::raw_for_read`)
 unsafe impl Sync for Builder {}
+
+/// A [`Builder`] that synchronizes access internally, so that it can be shared between threads
+/// (typically behind an [`Arc`](std::sync::Arc)) without additional external locking.
+///
+/// A single lock guards the whole [`Builder`], and each method holds it for the entire duration of
+/// the underlying [`Builder`] call. Concurrent calls are hence serialized, and a call to
+/// [`SyncBuilder::build`] always observes either all or none of the changes made by any given
+/// [`SyncBuilder::add_or_update_config`] or [`SyncBuilder::remove_config`] call. Sequences of
+/// operations that must not be interleaved with other threads' can be performed on the guard
+/// returned by [`SyncBuilder::lock`].
+pub struct SyncBuilder {
+    inner: Mutex<Builder>,
+}
+impl SyncBuilder {
+    /// Creates a new [`SyncBuilder`] instance using the provided [`Config`]. Returns [`None`] if
+    /// the builder's initialization fails.
+    ///
+    /// See [`Builder::new`] for more information.
+    #[must_use]
+    pub fn new(config: Option<&Config>) -> Option<Self> {
+        Builder::new(config).map(Self::from)
+    }
+
+    /// Adds or updates the configuration for the given path.
+    ///
+    /// See [`Builder::add_or_update_config`] for more information.
+    ///
+    /// # Panics
+    /// Panics if the provided `path` is longer than [`u32::MAX`] bytes.
+    #[must_use]
+    pub fn add_or_update_config(
+        &self,
+        path: &str,
+        ruleset: &impl AsRef<libddwaf_sys::ddwaf_object>,
+        diagnostics: Option<&mut WafOwnedDefaultAllocator<WafMap>>,
+    ) -> bool {
+        self.lock()
+            .add_or_update_config(path, ruleset, diagnostics)
+    }
+
+    /// Removes the configuration for the given path if some exists.
+    ///
+    /// See [`Builder::remove_config`] for more information.
+    ///
+    /// # Panics
+    /// Panics if the provided `path` is longer than [`u32::MAX`] bytes.
+    pub fn remove_config(&self, path: &str) -> bool {
+        self.lock().remove_config(path)
+    }
+
+    /// Removes all configurations whose path starts with `prefix`.
+    ///
+    /// See [`Builder::remove_config_matching`] for more information.
+    pub fn remove_config_matching(&self, prefix: &str) -> u32 {
+        self.lock().remove_config_matching(prefix)
+    }
+
+    /// Returns the number of configuration paths currently loaded, optionally filtered by a
+    /// regular expression.
+    ///
+    /// See [`Builder::config_paths_count`] for more information.
+    ///
+    /// # Panics
+    /// Panics if the provided `filter` regular expression is longer than [`u32::MAX`] bytes.
+    #[must_use]
+    pub fn config_paths_count(&self, filter: Option<&'_ str>) -> u32 {
+        self.lock().config_paths_count(filter)
+    }
+
+    /// Builds a new [`Handle`] from the current configuration.
+    ///
+    /// See [`Builder::build`] for more information.
+    #[must_use]
+    pub fn build(&self) -> Option<Handle> {
+        self.lock().build()
+    }
+
+    /// Builds a new [`Handle`] from the current configuration, along with its [`BuildDelta`].
+    ///
+    /// See [`Builder::build_with_delta`] for more information.
+    #[must_use]
+    pub fn build_with_delta(&self) -> Option<(Handle, BuildDelta)> {
+        self.lock().build_with_delta()
+    }
+
+    /// Acquires exclusive access to the underlying [`Builder`], blocking the current thread until
+    /// it is available.
+    ///
+    /// A panic while the lock is held does not leave the [`Builder`] in an inconsistent state, so
+    /// lock poisoning is ignored.
+    pub fn lock(&self) -> MutexGuard<'_, Builder> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Consumes this [`SyncBuilder`], returning the underlying [`Builder`].
+    #[must_use]
+    pub fn into_inner(self) -> Builder {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+impl From<Builder> for SyncBuilder {
+    fn from(builder: Builder) -> Self {
+        Self {
+            inner: Mutex::new(builder),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::RunError;
+
+/// The policy of the circuit breaker configured with
+/// [`Handle::set_circuit_breaker`][crate::Handle::set_circuit_breaker].
+///
+/// The circuit breaker opens once `error_threshold` evaluations have failed with
+/// [`RunError::InternalError`] within `window`. While it is open, evaluations are skipped and fail
+/// with [`RunError::CircuitOpen`]; after `open_duration`, a single evaluation is let through to
+/// probe the WAF, which closes the circuit breaker if it succeeds, or re-opens it otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitBreakerPolicy {
+    /// The number of internal errors that opens the circuit breaker. A value of `0` is treated
+    /// as `1`.
+    pub error_threshold: u32,
+    /// The time window within which `error_threshold` internal errors must occur.
+    pub window: Duration,
+    /// How long the circuit breaker stays open before probing the WAF again.
+    pub open_duration: Duration,
+}
+impl CircuitBreakerPolicy {
+    /// Creates a new [`CircuitBreakerPolicy`].
+    #[must_use]
+    pub fn new(error_threshold: u32, window: Duration, open_duration: Duration) -> Self {
+        Self {
+            error_threshold,
+            window,
+            open_duration,
+        }
+    }
+}
+
+/// The state of a circuit breaker, as returned by
+/// [`Handle::circuit_breaker_state`][crate::Handle::circuit_breaker_state].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Evaluations are performed normally.
+    Closed,
+    /// Evaluations are skipped.
+    Open,
+    /// The open duration has elapsed, and an evaluation is probing the WAF.
+    HalfOpen,
+}
+impl CircuitState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            CLOSED => Self::Closed,
+            OPEN => Self::Open,
+            _ => Self::HalfOpen,
+        }
+    }
+}
+
+const CLOSED: u8 = 0;
+const OPEN: u8 = 1;
+const HALF_OPEN: u8 = 2;
+
+/// The state of a circuit breaker, shared by a [`Handle`][crate::Handle] and its contexts.
+///
+/// Timestamps are stored as nanoseconds elapsed since `origin`, so that they fit in atomics.
+pub(crate) struct CircuitBreaker {
+    policy: CircuitBreakerPolicy,
+    origin: Instant,
+    state: AtomicU8,
+    errors: AtomicU32,
+    window_start: AtomicU64,
+    opened_at: AtomicU64,
+}
+impl CircuitBreaker {
+    pub(crate) fn new(policy: CircuitBreakerPolicy) -> Self {
+        Self {
+            policy,
+            origin: Instant::now(),
+            state: AtomicU8::new(CLOSED),
+            errors: AtomicU32::new(0),
+            window_start: AtomicU64::new(0),
+            opened_at: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn state(&self) -> CircuitState {
+        CircuitState::from_u8(self.state.load(Ordering::Acquire))
+    }
+
+    /// Runs `eval` unless the circuit breaker is open, and records its outcome.
+    pub(crate) fn guard<T>(
+        &self,
+        eval: impl FnOnce() -> Result<T, RunError>,
+    ) -> Result<T, RunError> {
+        if !self.allow(self.now()) {
+            return Err(RunError::CircuitOpen);
+        }
+        let res = eval();
+        self.record(self.now(), matches!(res, Err(RunError::InternalError)));
+        res
+    }
+
+    fn now(&self) -> u64 {
+        self.origin
+            .elapsed()
+            .as_nanos()
+            .try_into()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Returns true if an evaluation may be performed at `now`.
+    fn allow(&self, now: u64) -> bool {
+        match self.state.load(Ordering::Acquire) {
+            CLOSED => true,
+            OPEN => {
+                let opened_at = self.opened_at.load(Ordering::Acquire);
+                now.saturating_sub(opened_at) >= duration_nanos(self.policy.open_duration)
+                    // Only the evaluation that half-opens the breaker probes the WAF.
+                    && self
+                        .state
+                        .compare_exchange(OPEN, HALF_OPEN, Ordering::AcqRel, Ordering::Acquire)
+                        .is_ok()
+            }
+            _ => false,
+        }
+    }
+
+    /// Records the outcome of an evaluation that was allowed at `now`.
+    fn record(&self, now: u64, internal_error: bool) {
+        let state = self.state.load(Ordering::Acquire);
+        if state == HALF_OPEN {
+            if internal_error {
+                self.open(now, HALF_OPEN);
+            } else {
+                self.errors.store(0, Ordering::Release);
+                self.window_start.store(now, Ordering::Release);
+                self.state.store(CLOSED, Ordering::Release);
+            }
+            return;
+        }
+        if state != CLOSED || !internal_error {
+            return;
+        }
+
+        let window_start = self.window_start.load(Ordering::Acquire);
+        let errors = if now.saturating_sub(window_start) > duration_nanos(self.policy.window) {
+            self.window_start.store(now, Ordering::Release);
+            self.errors.store(1, Ordering::Release);
+            1
+        } else {
+            self.errors.fetch_add(1, Ordering::AcqRel).saturating_add(1)
+        };
+        if errors >= self.policy.error_threshold.max(1) {
+            self.open(now, CLOSED);
+        }
+    }
+
+    fn open(&self, now: u64, from: u8) {
+        self.opened_at.store(now, Ordering::Release);
+        if self
+            .state
+            .compare_exchange(from, OPEN, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.errors.store(0, Ordering::Release);
+        }
+    }
+}
+
+fn duration_nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerPolicy::new(
+            3,
+            Duration::from_secs(10),
+            Duration::from_secs(5),
+        ))
+    }
+
+    #[test]
+    fn test_trip_and_recover() {
+        let breaker = breaker();
+        for now in 0..3 {
+            assert!(breaker.allow(now * SECOND));
+            assert_eq!(breaker.state(), CircuitState::Closed);
+            breaker.record(now * SECOND, true);
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow(3 * SECOND));
+        assert!(!breaker.allow(6 * SECOND));
+
+        // Once the open duration elapsed, a single probe is let through.
+        assert!(breaker.allow(7 * SECOND));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.allow(7 * SECOND));
+
+        // A failed probe re-opens the breaker for another open duration.
+        breaker.record(7 * SECOND, true);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow(11 * SECOND));
+        assert!(breaker.allow(12 * SECOND));
+
+        // A successful probe closes it.
+        breaker.record(12 * SECOND, false);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.allow(12 * SECOND));
+        breaker.record(12 * SECOND, true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_errors_outside_window() {
+        let breaker = breaker();
+        breaker.record(0, true);
+        breaker.record(SECOND, true);
+        // The window has elapsed, so error counting starts over.
+        breaker.record(11 * SECOND, true);
+        breaker.record(12 * SECOND, true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.record(13 * SECOND, true);
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn test_guard() {
+        let breaker = CircuitBreaker::new(CircuitBreakerPolicy::new(
+            1,
+            Duration::from_secs(30),
+            Duration::from_secs(30),
+        ));
+        assert!(matches!(breaker.guard(|| Ok(())), Ok(())));
+        assert!(matches!(
+            breaker.guard(|| Err::<(), _>(RunError::InvalidObject)),
+            Err(RunError::InvalidObject)
+        ));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(matches!(
+            breaker.guard(|| Err::<(), _>(RunError::InternalError)),
+            Err(RunError::InternalError)
+        ));
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let mut called = false;
+        let res = breaker.guard(|| {
+            called = true;
+            Ok(())
+        });
+        assert!(matches!(res, Err(RunError::CircuitOpen)));
+        assert!(!called);
+    }
+}
@@ -0,0 +1,82 @@
+//! Deprecated aliases for code written against the `WAF*` names of the original `libddwaf` crate,
+//! available with the `compat-naming` feature.
+//!
+//! Glob-importing this module in place of the old crate's object module lets existing code build
+//! unchanged while it is migrated, with a deprecation warning pointing at the new name of each
+//! type:
+//!
+//! ```rust
+//! # #![allow(deprecated)]
+//! use libddwaf::compat::*;
+//!
+//! let mut map = WAFMap::new(1);
+//! map[0] = ("key", "value").into();
+//! let obj: WAFObject = map.into();
+//! assert_eq!(obj.object_type(), WAFObjectType::Map);
+//! ```
+//!
+//! # Migration notes
+//!
+//! Besides the names, the following differences may require changes when migrating:
+//! - Map keys can be replaced with [`Keyed::set_key_boxed`](crate::object::Keyed::set_key_boxed),
+//!   [`Keyed::set_key_checked`](crate::object::Keyed::set_key_checked) and
+//!   [`Keyed::try_set_key_str`](crate::object::Keyed::try_set_key_str), which are public.
+//! - JSON documents can be parsed directly with [`WafObject::from_json`], instead of being
+//!   deserialized with `serde_json`.
+//! - [`Builder::config_paths`](crate::Builder::config_paths) only requires a shared reference to
+//!   the [`Builder`](crate::Builder).
+
+#![allow(deprecated)]
+
+use crate::object::{
+    RustAllocator, WafArray, WafBool, WafFloat, WafInvalid, WafMap, WafNull, WafObject,
+    WafObjectType, WafOwned, WafSigned, WafString, WafUnsigned,
+};
+
+/// The type of a [`WAFObject`], whose variants are those of [`WafObjectType`].
+#[deprecated(note = "renamed to `WafObjectType`")]
+pub type WAFObjectType = WafObjectType;
+
+/// See [`WafObject`].
+#[deprecated(note = "renamed to `WafObject`")]
+pub type WAFObject = WafObject;
+
+/// See [`WafInvalid`].
+#[deprecated(note = "renamed to `WafInvalid`")]
+pub type WAFInvalid = WafInvalid;
+
+/// See [`WafSigned`].
+#[deprecated(note = "renamed to `WafSigned`")]
+pub type WAFSigned = WafSigned;
+
+/// See [`WafUnsigned`].
+#[deprecated(note = "renamed to `WafUnsigned`")]
+pub type WAFUnsigned = WafUnsigned;
+
+/// See [`WafString`].
+#[deprecated(note = "renamed to `WafString`")]
+pub type WAFString = WafString;
+
+/// See [`WafArray`].
+#[deprecated(note = "renamed to `WafArray`")]
+pub type WAFArray = WafArray;
+
+/// See [`WafMap`].
+#[deprecated(note = "renamed to `WafMap`")]
+pub type WAFMap = WafMap;
+
+/// See [`WafBool`].
+#[deprecated(note = "renamed to `WafBool`")]
+pub type WAFBool = WafBool;
+
+/// See [`WafFloat`].
+#[deprecated(note = "renamed to `WafFloat`")]
+pub type WAFFloat = WafFloat;
+
+/// See [`WafNull`].
+#[deprecated(note = "renamed to `WafNull`")]
+pub type WAFNull = WafNull;
+
+/// See [`WafOwned`].
+#[deprecated(note = "renamed to `WafOwned`")]
+pub type WAFOwned<T, A = RustAllocator> = WafOwned<T, A>;
@@ -2,6 +2,10 @@ use crate::object::WafMap;
 use crate::waf_map;
 
 /// The configuration for a new [`Builder`](crate::Builder).
+///
+/// The limits applied by `libddwaf` when evaluating data (such as the maximum container size and
+/// depth) are fixed by the library and cannot be configured; data that is built or deserialized
+/// within the default `serde::ContainerLimits` (with the `serde` feature) is evaluated in full.
 #[derive(Clone, Default, Debug)]
 pub struct Config {
     obfuscator: Obfuscator,
@@ -13,6 +17,12 @@ impl Config {
         Self { obfuscator }
     }
 
+    /// Returns the [`Obfuscator`] configured in this [`Config`].
+    #[must_use]
+    pub fn obfuscator(&self) -> &Obfuscator {
+        &self.obfuscator
+    }
+
     #[must_use]
     pub fn as_waf_object(&self) -> WafMap {
         let mut map = WafMap::new(2);
@@ -57,6 +67,24 @@ impl Obfuscator {
         }
     }
 
+    /// Creates a new [`Obfuscator`] with the source patterns of the provided, already compiled,
+    /// key and value regular expressions (as returned by [`regex::Regex::as_str`]).
+    ///
+    /// Compiling the expressions beforehand ensures they are valid, but note that `libddwaf`
+    /// compiles them again using the [RE2](https://github.com/google/re2/wiki/Syntax) syntax, which
+    /// does not support every construct of the [`regex`] crate.
+    #[cfg(feature = "regex")]
+    #[must_use]
+    pub fn from_regex(
+        key_regex: Option<&regex::Regex>,
+        value_regex: Option<&regex::Regex>,
+    ) -> Self {
+        Self::new(
+            key_regex.map(regex::Regex::as_str),
+            value_regex.map(regex::Regex::as_str),
+        )
+    }
+
     /// Returns the regular expression used to determine key data to be obfuscated, if one has been
     /// set.
     #[must_use]
@@ -1,10 +1,19 @@
+#![deny(clippy::unwrap_used, clippy::expect_used)]
+
+use std::collections::HashMap;
 use std::error;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::circuit_breaker::CircuitBreaker;
+use crate::log::{log_internal, Level};
+
 use crate::object::get_default_allocator;
 use crate::object::WafOwnedOutputAllocator;
-use crate::object::{AsRawMutObject, Keyed, WafArray, WafMap, WafObject};
+use crate::object::raw::AsRawMutObject;
+use crate::object::{Keyed, WafArray, WafMap, WafObject, WafObjectStats, WafView};
 
 /// A WAF Context that can be used to evaluate the configured ruleset against address data.
 ///
@@ -12,6 +21,17 @@ use crate::object::{AsRawMutObject, Keyed, WafArray, WafMap, WafObject};
 /// be used to handle data for a single request.
 pub struct Context {
     pub(crate) raw: libddwaf_sys::ddwaf_context,
+    generation: u64,
+    run_count: u64,
+    total_duration: Duration,
+    keepalive_len: usize,
+    keepalive_limit: Option<usize>,
+    keepalive_bytes: usize,
+    max_memory: Option<usize>,
+    /// The addresses submitted so far, with the order in which they were first submitted, if
+    /// [`Context::track_persistent_addresses`] is enabled.
+    persistent_addresses: Option<HashMap<Box<str>, usize>>,
+    breaker: Option<Arc<CircuitBreaker>>,
 }
 
 /// Subcontexts are type of [`Context`] that inherit the data from their parents,
@@ -22,6 +42,7 @@ pub struct Context {
 /// They are obtained by calling [`Context::new_subcontext`][crate::Context::new_subcontext].
 pub struct Subcontext {
     pub(crate) raw: libddwaf_sys::ddwaf_subcontext,
+    breaker: Option<Arc<CircuitBreaker>>,
 }
 
 /// Common waf evaluation interface for [`Context`] and [`Subcontext`].
@@ -29,9 +50,12 @@ pub trait RunnableContext {
     /// Evaluates the configured ruleset against the provided address data, and returns the result
     /// of this evaluation.
     ///
+    /// The `timeout` is converted to microseconds as documented by [`Context::effective_timeout`].
+    ///
     /// # Errors
     /// Returns an error if the WAF encountered an internal error, invalid object, or invalid argument while processing
-    /// the request.
+    /// the request, or if the evaluation was skipped because the
+    /// [circuit breaker][crate::Handle::set_circuit_breaker] is open.
     fn run(&mut self, data: WafMap, timeout: Duration) -> Result<RunResult, RunError>;
 
     /// Evaluates multiple batches of address data in sequence, and returns a combined result.
@@ -43,6 +67,31 @@ pub trait RunnableContext {
     /// Returns an error if the WAF encountered an internal error, invalid object, or invalid argument while processing
     /// the request.
     fn run_batches(&mut self, data: WafArray, timeout: Duration) -> Result<RunResult, RunError>;
+
+    /// Evaluates the configured ruleset against the provided address/value pairs, as
+    /// [`RunnableContext::run`] would with a [`WafMap`] containing them.
+    ///
+    /// Only the first [`u16::MAX`] pairs are used; any extra pairs are ignored.
+    ///
+    /// # Errors
+    /// Returns an error if the WAF encountered an internal error, invalid object, or invalid argument while processing
+    /// the request.
+    fn run_pairs<'a, V: Into<WafObject>>(
+        &mut self,
+        data: impl IntoIterator<Item = (&'a str, V)>,
+        timeout: Duration,
+    ) -> Result<RunResult, RunError>
+    where
+        Self: Sized,
+    {
+        let pairs: Vec<_> = data.into_iter().take(usize::from(u16::MAX)).collect();
+        #[allow(clippy::cast_possible_truncation)] // Bounded by the take above
+        let mut map = WafMap::new(pairs.len() as u16);
+        for (i, (address, value)) in pairs.into_iter().enumerate() {
+            map[i] = (address, value.into()).into();
+        }
+        self.run(map, timeout)
+    }
 }
 
 type RunFunc<S> = unsafe extern "C" fn(
@@ -53,7 +102,22 @@ type RunFunc<S> = unsafe extern "C" fn(
     u64,
 ) -> libddwaf_sys::DDWAF_RET_CODE;
 
+/// Evaluates `data` using `func`, unless the provided circuit `breaker` is open.
 fn run<S>(
+    breaker: Option<&CircuitBreaker>,
+    raw_self: S,
+    func: RunFunc<S>,
+    func_name: &'static str,
+    data: impl AsRawMutObject,
+    timeout: Duration,
+) -> Result<RunResult, RunError> {
+    match breaker {
+        Some(breaker) => breaker.guard(|| eval(raw_self, func, func_name, data, timeout)),
+        None => eval(raw_self, func, func_name, data, timeout),
+    }
+}
+
+fn eval<S>(
     raw_self: S,
     func: RunFunc<S>,
     func_name: &'static str,
@@ -70,7 +134,7 @@ fn run<S>(
             data_ptr,
             get_default_allocator().into(),
             res.as_mut_ptr().cast(),
-            timeout.as_micros().try_into().unwrap_or(u64::MAX),
+            Context::effective_timeout(timeout),
         )
     };
     match status {
@@ -89,11 +153,14 @@ fn run<S>(
         libddwaf_sys::DDWAF_OK => {
             // We need to keep the persistent data alive (now owned by the WAF)
             std::mem::forget(data);
+            // The output was populated by libddwaf, rather than created by `WafOwned::default`
+            track!(WAF_OWNED_OBJECTS, 1);
             Ok(RunResult::NoMatch(unsafe { res.assume_init() }))
         }
         libddwaf_sys::DDWAF_MATCH => {
             // We need to keep the persistent data alive (now owned by the WAF)
             std::mem::forget(data);
+            track!(WAF_OWNED_OBJECTS, 1);
             Ok(RunResult::Match(unsafe { res.assume_init() }))
         }
         unknown => unreachable!(
@@ -105,26 +172,391 @@ fn run<S>(
 }
 impl RunnableContext for Context {
     fn run(&mut self, data: WafMap, timeout: Duration) -> Result<RunResult, RunError> {
-        run(
+        if self.keepalive_full() {
+            return self.run_ephemeral(|subcontext| subcontext.run(data, timeout));
+        }
+        let incoming = self.reserve_memory(data.as_object().stats())?;
+        let addresses = self.new_addresses([&data]);
+        let res = run(
+            self.breaker.as_deref(),
             self.raw,
             libddwaf_sys::ddwaf_context_eval,
             stringify!(libddwaf_sys::ddwaf_context_eval),
             data,
             timeout,
-        )
+        );
+        self.record_run(&res, addresses, incoming);
+        res
     }
 
     fn run_batches(&mut self, data: WafArray, timeout: Duration) -> Result<RunResult, RunError> {
-        run(
+        if self.keepalive_full() {
+            return self.run_ephemeral(|subcontext| subcontext.run_batches(data, timeout));
+        }
+        let incoming = self.reserve_memory(data.as_object().stats())?;
+        let addresses = self.new_addresses(data.iter().filter_map(WafObject::as_type::<WafMap>));
+        let res = run(
+            self.breaker.as_deref(),
             self.raw,
             libddwaf_sys::ddwaf_context_multieval,
             stringify!(libddwaf_sys::ddwaf_context_multieval),
             data,
             timeout,
-        )
+        );
+        self.record_run(&res, addresses, incoming);
+        res
     }
 }
 impl Context {
+    pub(crate) fn new(
+        raw: libddwaf_sys::ddwaf_context,
+        generation: u64,
+        breaker: Option<Arc<CircuitBreaker>>,
+        max_memory: Option<usize>,
+    ) -> Self {
+        track!(CONTEXTS, 1);
+        Self {
+            raw,
+            generation,
+            run_count: 0,
+            total_duration: Duration::ZERO,
+            keepalive_len: 0,
+            keepalive_limit: None,
+            keepalive_bytes: 0,
+            max_memory,
+            persistent_addresses: None,
+            breaker,
+        }
+    }
+
+    /// Returns the [`Handle::generation`][crate::Handle::generation] of the instance this
+    /// [`Context`] was created from.
+    ///
+    /// A [`Context`] keeps evaluating data using the ruleset of the instance it was created from,
+    /// even after a newer one has been built; comparing generations allows detecting this:
+    ///
+    /// ```no_run
+    /// # use libddwaf::{Builder, Handle};
+    /// # fn f(builder: &mut Builder, current: &Handle) {
+    /// let ctx = current.new_context();
+    /// let latest = builder.build().unwrap();
+    /// assert!(ctx.generation() < latest.generation());
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the timeout value, in microseconds, that is passed to `libddwaf` when evaluating
+    /// with the provided `duration` as a timeout.
+    ///
+    /// `libddwaf` expresses timeouts in microseconds, so the `duration` is converted as follows:
+    /// - a zero `duration` maps to `0`,
+    /// - a non-zero `duration` shorter than one microsecond is rounded up to `1`, as truncating it
+    ///   would cause the evaluation to time out immediately,
+    /// - any other `duration` is truncated to whole microseconds, and clamped to [`u64::MAX`].
+    #[must_use]
+    pub fn effective_timeout(duration: Duration) -> u64 {
+        match duration.as_micros() {
+            0 if !duration.is_zero() => 1,
+            micros => micros.try_into().unwrap_or(u64::MAX),
+        }
+    }
+
+    /// Returns the underlying `libddwaf` context, for use with other code linked against
+    /// `libddwaf`.
+    ///
+    /// Ownership is retained by this [`Context`]: the returned pointer must not be destroyed, and
+    /// must not be used after this [`Context`] has been dropped. Like this [`Context`], it must not
+    /// be used to evaluate data concurrently from multiple threads.
+    #[must_use]
+    pub fn as_raw(&self) -> libddwaf_sys::ddwaf_context {
+        self.raw
+    }
+
+    /// Returns the number of successful evaluations performed on this [`Context`] using
+    /// [`RunnableContext::run`] or [`RunnableContext::run_batches`] (evaluations performed on its
+    /// [`Subcontext`]s are not included).
+    #[must_use]
+    pub fn run_count(&self) -> u64 {
+        self.run_count
+    }
+
+    /// Returns true if at least one successful evaluation was performed on this [`Context`],
+    /// meaning it may hold persistent address data.
+    #[must_use]
+    pub fn has_run(&self) -> bool {
+        self.run_count != 0
+    }
+
+    /// Returns the sum of the [`RunOutput::duration`]s of the evaluations counted by
+    /// [`Context::run_count`].
+    #[must_use]
+    pub fn total_duration(&self) -> Duration {
+        self.total_duration
+    }
+
+    /// Returns how much of a `total` time budget, shared by all evaluations of this [`Context`],
+    /// is left after the evaluations performed so far (see [`Context::total_duration`]), for use as
+    /// the timeout of the next one. This is [`Duration::ZERO`] once the budget is exhausted.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use libddwaf::{object::WafMap, Context, RunnableContext};
+    /// # fn f(ctx: &mut Context, phases: Vec<WafMap>) {
+    /// let budget = Duration::from_millis(5);
+    /// for data in phases {
+    ///     let timeout = ctx.budget_remaining(budget);
+    ///     let _ = ctx.run(data, timeout);
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn budget_remaining(&self, total: Duration) -> Duration {
+        total.saturating_sub(self.total_duration)
+    }
+
+    /// Evaluates the configured ruleset against the provided address data like
+    /// [`RunnableContext::run`] does, but returns the complete result map produced by `libddwaf`
+    /// (which [`RunOutput`] otherwise wraps), for example to forward it verbatim.
+    ///
+    /// # Errors
+    /// Returns the same errors as [`RunnableContext::run`].
+    pub fn run_raw_result(
+        &mut self,
+        data: WafMap,
+        timeout: Duration,
+    ) -> Result<WafOwnedOutputAllocator<WafMap>, RunError> {
+        match self.run(data, timeout)? {
+            RunResult::NoMatch(output) | RunResult::Match(output) => Ok(output.into_data()),
+        }
+    }
+
+    /// Evaluates several independent pieces of ephemeral address data (for example, one per
+    /// GraphQL resolver) against the data of this [`Context`].
+    ///
+    /// If `persistent` data is provided, it is first evaluated in this [`Context`], and the result
+    /// of this evaluation is the first item of the returned results; should it fail, no further
+    /// evaluation is performed. Each of the `ephemerals` is then evaluated in a fresh
+    /// [`Subcontext`], so that they do not affect this [`Context`] nor each other, and their
+    /// results follow in the same order.
+    ///
+    /// The `timeout` applies to each evaluation separately, so the whole batch may take up to
+    /// `timeout` times the number of evaluations.
+    pub fn run_batch(
+        &mut self,
+        persistent: Option<WafMap>,
+        ephemerals: Vec<WafMap>,
+        timeout: Duration,
+    ) -> Vec<Result<RunResult, RunError>> {
+        let mut results = Vec::with_capacity(ephemerals.len() + 1);
+        if let Some(persistent) = persistent {
+            let res = self.run(persistent, timeout);
+            let failed = res.is_err();
+            results.push(res);
+            if failed {
+                return results;
+            }
+        }
+        for ephemeral in ephemerals {
+            results.push(match self.new_subcontext() {
+                Ok(mut subcontext) => subcontext.run(ephemeral, timeout),
+                Err(InternalError {}) => Err(RunError::InternalError),
+            });
+        }
+        results
+    }
+
+    /// Evaluates the configured ruleset against the provided address data like
+    /// [`RunnableContext::run`] does, but on Tokio's blocking thread pool, so that the evaluation
+    /// does not block the asynchronous runtime.
+    ///
+    /// The evaluation needs exclusive access to this [`Context`], which is moved to the blocking
+    /// task and handed back together with the result once it completes.
+    ///
+    /// # Panics
+    /// Panics if called outside of a Tokio runtime, or if the runtime is shutting down.
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(
+        mut self,
+        data: WafMap,
+        timeout: Duration,
+    ) -> (Self, Result<RunResult, RunError>) {
+        let task = tokio::task::spawn_blocking(move || {
+            let res = self.run(data, timeout);
+            (self, res)
+        });
+        match task.await {
+            Ok(res) => res,
+            Err(err) => match err.try_into_panic() {
+                Ok(payload) => std::panic::resume_unwind(payload),
+                Err(err) => panic!("The WAF evaluation task did not complete: {err}"),
+            },
+        }
+    }
+
+    /// Drops this [`Context`], along with the address data it retains, on a background thread
+    /// (see [`defer_drop`][crate::object::defer_drop]).
+    pub fn into_deferred_drop(self) {
+        crate::object::defer_drop(self);
+    }
+
+    /// Enables or disables the tracking of the addresses reported by
+    /// [`Context::persistent_addresses`].
+    ///
+    /// Tracking is disabled by default, as it copies the addresses of every evaluation. Only the
+    /// evaluations performed while it is enabled are tracked, and disabling it forgets the
+    /// addresses tracked so far.
+    pub fn track_persistent_addresses(&mut self, enabled: bool) {
+        if enabled {
+            self.persistent_addresses.get_or_insert_with(HashMap::new);
+        } else {
+            self.persistent_addresses = None;
+        }
+    }
+
+    /// Returns the addresses of the persistent address data submitted to this [`Context`] by
+    /// successful evaluations so far, in the order they were first submitted.
+    ///
+    /// This is intended for debugging evaluations spanning several calls to
+    /// [`RunnableContext::run`] or [`RunnableContext::run_batches`], and requires enabling
+    /// [`Context::track_persistent_addresses`] beforehand; it is empty otherwise. Addresses that
+    /// are not valid UTF-8 are not reported.
+    #[must_use]
+    pub fn persistent_addresses(&self) -> Vec<&str> {
+        let Some(known) = &self.persistent_addresses else {
+            return Vec::new();
+        };
+        let mut addresses: Vec<_> = known.iter().collect();
+        addresses.sort_unstable_by_key(|(_, order)| **order);
+        addresses
+            .into_iter()
+            .map(|(address, _)| address.as_ref())
+            .collect()
+    }
+
+    /// Limits the number of evaluations whose address data this [`Context`] retains, or removes
+    /// the limit if `limit` is [`None`].
+    ///
+    /// Address data submitted to [`RunnableContext::run`] or [`RunnableContext::run_batches`] is
+    /// owned by `libddwaf` until the [`Context`] is dropped, as later evaluations may still refer
+    /// to it; it cannot be released earlier. Once `limit` evaluations have been retained, further
+    /// evaluations are instead performed in a fresh [`Subcontext`], so that their address data is
+    /// released as soon as they complete. Such data is therefore not visible to later evaluations,
+    /// and its addresses are not reported by [`Context::persistent_addresses`].
+    ///
+    /// The limit does not apply retroactively: a [`Context`] that already retains more than
+    /// `limit` evaluations keeps all of them.
+    pub fn set_keepalive_limit(&mut self, limit: Option<usize>) {
+        self.keepalive_limit = limit;
+    }
+
+    /// Returns the limit set by [`Context::set_keepalive_limit`], if any.
+    #[must_use]
+    pub fn keepalive_limit(&self) -> Option<usize> {
+        self.keepalive_limit
+    }
+
+    /// Returns the number of evaluations whose address data is retained by this [`Context`].
+    #[must_use]
+    pub fn keepalive_len(&self) -> usize {
+        self.keepalive_len
+    }
+
+    /// Returns an estimate of the memory used by the address data retained by this [`Context`], in
+    /// bytes.
+    ///
+    /// This is the sum of the [`WafObjectStats::estimated_bytes`] of the data of each evaluation
+    /// counted by [`Context::keepalive_len`], computed once when the data is submitted.
+    #[must_use]
+    pub fn keepalive_bytes(&self) -> usize {
+        self.keepalive_bytes
+    }
+
+    /// Returns the memory limit that was set on the [`Handle`][crate::Handle] (see
+    /// [`Handle::set_max_context_memory`][crate::Handle::set_max_context_memory]) when this
+    /// [`Context`] was created, if any.
+    #[must_use]
+    pub fn max_memory(&self) -> Option<usize> {
+        self.max_memory
+    }
+
+    /// Returns the estimated size of data that is about to be retained, or an error if retaining
+    /// it would exceed [`Context::max_memory`].
+    fn reserve_memory(&self, stats: WafObjectStats) -> Result<usize, RunError> {
+        let incoming = stats.estimated_bytes();
+        match self.max_memory {
+            Some(max) if self.keepalive_bytes.saturating_add(incoming) > max => {
+                Err(RunError::MemoryLimitExceeded {
+                    held: self.keepalive_bytes,
+                    incoming,
+                })
+            }
+            _ => Ok(incoming),
+        }
+    }
+
+    fn keepalive_full(&self) -> bool {
+        self.keepalive_limit
+            .is_some_and(|limit| self.keepalive_len >= limit)
+    }
+
+    /// Performs an evaluation that must not retain its address data, in a fresh [`Subcontext`].
+    fn run_ephemeral(
+        &mut self,
+        eval: impl FnOnce(&mut Subcontext) -> Result<RunResult, RunError>,
+    ) -> Result<RunResult, RunError> {
+        let res = match self.new_subcontext() {
+            Ok(mut subcontext) => eval(&mut subcontext),
+            Err(InternalError {}) => Err(RunError::InternalError),
+        };
+        if let Ok(result) = &res {
+            self.count_run(result);
+        }
+        res
+    }
+
+    fn count_run(&mut self, result: &RunResult) {
+        self.run_count = self.run_count.saturating_add(1);
+        self.total_duration = self
+            .total_duration
+            .saturating_add(result.output().duration());
+    }
+
+    fn record_run(
+        &mut self,
+        res: &Result<RunResult, RunError>,
+        addresses: Vec<Box<str>>,
+        bytes: usize,
+    ) {
+        if let Ok(result) = res {
+            self.count_run(result);
+            self.keepalive_len = self.keepalive_len.saturating_add(1);
+            self.keepalive_bytes = self.keepalive_bytes.saturating_add(bytes);
+            if let Some(known) = &mut self.persistent_addresses {
+                for address in addresses {
+                    let order = known.len();
+                    known.entry(address).or_insert(order);
+                }
+            }
+        }
+    }
+
+    /// Returns the addresses of the provided `data` that have not been submitted yet, if
+    /// [`Context::track_persistent_addresses`] is enabled.
+    fn new_addresses<'a>(&self, data: impl IntoIterator<Item = &'a WafMap>) -> Vec<Box<str>> {
+        let Some(known) = &self.persistent_addresses else {
+            return Vec::new();
+        };
+        data.into_iter()
+            .flat_map(WafMap::iter)
+            .filter_map(|entry| entry.key_str().ok())
+            .filter(|address| !known.contains_key(*address))
+            .map(Box::from)
+            .collect()
+    }
+
     /// Creates a new [`Subcontext`] from this [`Context`].
     ///
     /// # Errors
@@ -135,13 +567,18 @@ impl Context {
         if raw.is_null() {
             Err(InternalError {})
         } else {
-            Ok(Subcontext { raw })
+            track!(CONTEXTS, 1);
+            Ok(Subcontext {
+                raw,
+                breaker: self.breaker.clone(),
+            })
         }
     }
 }
 impl RunnableContext for Subcontext {
     fn run(&mut self, data: WafMap, timeout: Duration) -> Result<RunResult, RunError> {
         run(
+            self.breaker.as_deref(),
             self.raw,
             libddwaf_sys::ddwaf_subcontext_eval,
             stringify!(libddwaf_sys::ddwaf_subcontext_eval),
@@ -152,6 +589,7 @@ impl RunnableContext for Subcontext {
 
     fn run_batches(&mut self, data: WafArray, timeout: Duration) -> Result<RunResult, RunError> {
         run(
+            self.breaker.as_deref(),
             self.raw,
             libddwaf_sys::ddwaf_subcontext_multieval,
             stringify!(libddwaf_sys::ddwaf_subcontext_multieval),
@@ -162,12 +600,14 @@ impl RunnableContext for Subcontext {
 }
 impl Drop for Context {
     fn drop(&mut self) {
-        unsafe { libddwaf_sys::ddwaf_context_destroy(self.raw) }
+        unsafe { libddwaf_sys::ddwaf_context_destroy(self.raw) };
+        track!(CONTEXTS, -1);
     }
 }
 impl Drop for Subcontext {
     fn drop(&mut self) {
-        unsafe { libddwaf_sys::ddwaf_subcontext_destroy(self.raw) }
+        unsafe { libddwaf_sys::ddwaf_subcontext_destroy(self.raw) };
+        track!(CONTEXTS, -1);
     }
 }
 
@@ -195,6 +635,66 @@ pub enum RunResult {
     Match(RunOutput),
 }
 
+impl RunResult {
+    /// Returns true if this is a [`RunResult::Match`].
+    #[must_use]
+    pub fn is_match(&self) -> bool {
+        matches!(self, RunResult::Match(_))
+    }
+
+    /// Returns true if this is a [`RunResult::NoMatch`].
+    #[must_use]
+    pub fn is_no_match(&self) -> bool {
+        matches!(self, RunResult::NoMatch(_))
+    }
+
+    /// Returns the [`RunOutput`] of this evaluation, regardless of whether it produced a match.
+    #[must_use]
+    pub fn output(&self) -> &RunOutput {
+        match self {
+            RunResult::NoMatch(output) | RunResult::Match(output) => output,
+        }
+    }
+
+    /// Returns the [`RunOutput`] of this evaluation, regardless of whether it produced a match.
+    #[must_use]
+    pub fn into_output(self) -> RunOutput {
+        match self {
+            RunResult::NoMatch(output) | RunResult::Match(output) => output,
+        }
+    }
+
+    /// Returns the [`SamplingDecision`] resulting from this evaluation (see
+    /// [`RunOutput::sampling_decision`]).
+    #[must_use]
+    pub fn sampling_decision(&self) -> SamplingDecision {
+        match self {
+            RunResult::NoMatch(output) | RunResult::Match(output) => output.sampling_decision(),
+        }
+    }
+}
+
+/// How the trace of a request should be sampled after an evaluation, as reported by
+/// [`RunOutput::sampling_decision`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SamplingDecision {
+    /// The WAF has no opinion on the sampling of the trace, and the sampler should decide as usual.
+    NoOverride,
+    /// The trace should be kept, by overriding its sampling priority (typically to `USER_KEEP`).
+    Keep {
+        /// Whether the decision is due to some rules producing [events][RunOutput::events], as
+        /// opposed to rules or processors that only produce [attributes][RunOutput::attributes].
+        due_to_match: bool,
+    },
+}
+impl SamplingDecision {
+    /// Returns true if the trace should be kept.
+    #[must_use]
+    pub fn is_keep(self) -> bool {
+        matches!(self, SamplingDecision::Keep { .. })
+    }
+}
+
 /// The error that can occur during a [`RunnableContext::run`] operation.
 #[non_exhaustive]
 #[derive(Debug)]
@@ -205,6 +705,18 @@ pub enum RunError {
     InvalidObject,
     /// The WAF encountered an invalid argument while processing the request.
     InvalidArgument,
+    /// The evaluation was skipped, without calling into the WAF, because the
+    /// [circuit breaker][crate::Handle::set_circuit_breaker] is open.
+    CircuitOpen,
+    /// The evaluation was skipped, without calling into the WAF, because retaining its address
+    /// data would exceed the [memory limit][crate::Handle::set_max_context_memory] of the
+    /// [`Context`].
+    MemoryLimitExceeded {
+        /// The estimated memory used by the address data already retained, in bytes.
+        held: usize,
+        /// The estimated memory used by the address data that was rejected, in bytes.
+        incoming: usize,
+    },
 }
 impl fmt::Display for RunError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -212,6 +724,11 @@ impl fmt::Display for RunError {
             RunError::InternalError => write!(f, "The WAF encountered an internal error"),
             RunError::InvalidObject => write!(f, "The WAF encountered an invalid object"),
             RunError::InvalidArgument => write!(f, "The WAF encountered an invalid argument"),
+            RunError::CircuitOpen => write!(f, "The WAF circuit breaker is open"),
+            RunError::MemoryLimitExceeded { held, incoming } => write!(
+                f,
+                "The WAF context memory limit would be exceeded ({held} bytes held, {incoming} more)"
+            ),
         }
     }
 }
@@ -240,9 +757,7 @@ impl RunOutput {
     /// being evaluated.
     #[must_use]
     pub fn timeout(&self) -> bool {
-        debug_assert!(self.data.is_valid());
-        self.data
-            .get_bstr(b"timeout")
+        self.get(b"timeout")
             .and_then(|o| o.to_bool())
             .unwrap_or_default()
     }
@@ -251,30 +766,51 @@ impl RunOutput {
     /// overridden to ensure it is not dropped by the sampler.
     #[must_use]
     pub fn keep(&self) -> bool {
-        debug_assert!(self.data.is_valid());
-        self.data
-            .get_bstr(b"keep")
+        self.get(b"keep")
             .and_then(|o| o.to_bool())
             .unwrap_or_default()
     }
 
-    /// Returns the total time spent processing the request; excluding bindings overhead (which
+    /// Returns the [`SamplingDecision`] for the trace of this request, derived from
+    /// [`RunOutput::keep`] and whether any [`RunOutput::events`] were produced.
+    #[must_use]
+    pub fn sampling_decision(&self) -> SamplingDecision {
+        if !self.keep() {
+            return SamplingDecision::NoOverride;
+        }
+        SamplingDecision::Keep {
+            due_to_match: self.events().is_some_and(|events| !events.is_empty()),
+        }
+    }
+
+    /// Returns the time spent by the WAF on this evaluation, excluding bindings overhead (which
     /// ought to be trivial).
+    ///
+    /// This only covers the [`RunnableContext::run`] call that produced this [`RunOutput`], and not
+    /// the previous evaluations of the same [`Context`]: the total runtime of a request is the sum
+    /// of the durations of all of its evaluations, which [`Context::total_duration`] returns.
+    ///
+    /// The `duration` reported by `libddwaf` is a number of nanoseconds, which is accepted as an
+    /// unsigned integer, a signed integer (negative values being clamped to zero) or a float. If it
+    /// is missing, [`Duration::ZERO`] is returned. If it has any other type, [`Duration::ZERO`] is
+    /// returned as well, and a [`Level::Debug`] message is logged the first time this happens;
+    /// [`RunOutput::duration_raw`] gives access to the original value.
+    #[must_use]
     pub fn duration(&self) -> Duration {
-        debug_assert!(self.data.is_valid());
-        self.data
-            .get_bstr(b"duration")
-            .and_then(|o| o.to_u64())
-            .map(Duration::from_nanos)
-            .unwrap_or_default()
+        self.duration_raw().map(parse_duration).unwrap_or_default()
+    }
+
+    /// Returns the raw `duration` value reported by `libddwaf`, for diagnostics. Prefer
+    /// [`RunOutput::duration`] to obtain it as a [`Duration`].
+    #[must_use]
+    pub fn duration_raw(&self) -> Option<&WafObject> {
+        self.get(b"duration").map(Keyed::value)
     }
 
     /// Returns the number of input batches fully evaluated by the WAF.
     #[must_use]
     pub fn evaluated(&self) -> u64 {
-        debug_assert!(self.data.is_valid());
-        self.data
-            .get_bstr(b"evaluated")
+        self.get(b"evaluated")
             .and_then(|o| o.to_u64())
             .unwrap_or_default()
     }
@@ -283,31 +819,136 @@ impl RunOutput {
     ///
     /// This is only expected to be populated when [`Context::run`] returns [`RunResult::Match`].
     pub fn events(&self) -> Option<&Keyed<WafArray>> {
-        debug_assert!(self.data.is_valid());
-        self.data
-            .get_bstr(b"events")
-            .and_then(Keyed::<WafObject>::as_type)
+        self.get(b"events").and_then(Keyed::<WafObject>::as_type)
     }
 
     /// Returns the list of actions that were produced by this WAF run.
     ///
     /// This is only expected to be populated when [`Context::run`] returns [`RunResult::Match`].
     pub fn actions(&self) -> Option<&Keyed<WafMap>> {
-        debug_assert!(self.data.is_valid());
+        self.get(b"actions").and_then(Keyed::<WafObject>::as_type)
+    }
+
+    /// Returns the distinct addresses whose data matched the rules that produced the
+    /// [`RunOutput::events`], in the order they first appear.
+    ///
+    /// These are collected from the `address` of the parameters of each event's `rule_matches`.
+    #[must_use]
+    pub fn matched_addresses(&self) -> Vec<&str> {
+        fn maps(obj: Option<&Keyed<WafObject>>) -> impl Iterator<Item = &WafMap> {
+            obj.and_then(Keyed::<WafObject>::as_type::<WafArray>)
+                .into_iter()
+                .flat_map(Keyed::<WafArray>::iter)
+                .filter_map(WafObject::as_type::<WafMap>)
+        }
+
+        let mut addresses = Vec::new();
+        let Some(events) = self.events() else {
+            return addresses;
+        };
+        for event in events.iter().filter_map(WafObject::as_type::<WafMap>) {
+            for rule_match in maps(event.get_str("rule_matches")) {
+                for parameter in maps(rule_match.get_str("parameters")) {
+                    let address = parameter.get_str("address").and_then(|a| a.to_str());
+                    if let Some(address) = address.filter(|a| !addresses.contains(a)) {
+                        addresses.push(address);
+                    }
+                }
+            }
+        }
+        addresses
+    }
+
+    /// Returns a summary of this WAF run as key/value strings, suitable for tagging the span of
+    /// the request (under a prefix of the caller's choosing):
+    /// - `timeout`: [`RunOutput::timeout`], as `true` or `false`,
+    /// - `keep`: [`RunOutput::keep`], as `true` or `false`,
+    /// - `duration_ns`: [`RunOutput::duration`], in nanoseconds,
+    /// - `event_count`: the number of [`RunOutput::events`],
+    /// - `rule_ids`: the distinct identifiers of the rules that produced the events, in the order
+    ///   they first appear, separated by commas (empty if there are no events).
+    #[must_use]
+    pub fn to_tags(&self) -> Vec<(String, String)> {
+        let mut event_count = 0;
+        let mut rule_ids = Vec::new();
+        for event in self.events().into_iter().flat_map(Keyed::<WafArray>::iter) {
+            event_count += 1;
+            let Some(event) = event.as_type::<WafMap>() else {
+                continue;
+            };
+            let id = event
+                .get_str("rule")
+                .and_then(|rule| rule.as_type::<WafMap>()?.get_str("id")?.to_str());
+            if let Some(id) = id.filter(|id| !rule_ids.contains(id)) {
+                rule_ids.push(id);
+            }
+        }
+        vec![
+            ("timeout".to_string(), self.timeout().to_string()),
+            ("keep".to_string(), self.keep().to_string()),
+            (
+                "duration_ns".to_string(),
+                self.duration().as_nanos().to_string(),
+            ),
+            ("event_count".to_string(), event_count.to_string()),
+            ("rule_ids".to_string(), rule_ids.join(",")),
+        ]
+    }
+
+    /// Returns the raw data produced by this WAF run, which the accessors of this [`RunOutput`]
+    /// read from.
+    ///
+    /// Use [`WafOwned::into_owned`][crate::object::WafOwned::into_owned] to obtain a Rust-owned
+    /// copy of it.
+    #[must_use]
+    pub fn into_data(self) -> WafOwnedOutputAllocator<WafMap> {
         self.data
-            .get_bstr(b"actions")
-            .and_then(Keyed::<WafObject>::as_type)
     }
 
     /// Returns the list of attributes that were produced by this WAF run, and which should be
     /// attached to the surrounding trace.
     pub fn attributes(&self) -> Option<&Keyed<WafMap>> {
-        debug_assert!(self.data.is_valid());
-        self.data
-            .get_bstr(b"attributes")
+        self.get(b"attributes")
             .and_then(Keyed::<WafObject>::as_type)
     }
+
+    /// Returns the entry of the output with the provided key. An output that is not a map (which
+    /// `libddwaf` never produces) is treated as an empty one, rather than read as a map.
+    fn get(&self, key: &[u8]) -> Option<&Keyed<WafObject>> {
+        if !self.data.is_valid() {
+            return None;
+        }
+        self.data.get_bstr(key)
+    }
+}
+/// Converts the `duration` reported by `libddwaf` (in nanoseconds) into a [`Duration`], clamping
+/// negative values to zero and saturating values that are too large.
+fn parse_duration(raw: &WafObject) -> Duration {
+    static UNEXPECTED_TYPE_LOGGED: AtomicBool = AtomicBool::new(false);
+
+    match raw.view() {
+        WafView::Unsigned(nanos) => Duration::from_nanos(nanos),
+        WafView::Signed(nanos) => Duration::from_nanos(nanos.try_into().unwrap_or_default()),
+        WafView::Float(nanos) if nanos.is_nan() || nanos <= 0.0 => Duration::ZERO,
+        WafView::Float(nanos) => {
+            Duration::try_from_secs_f64(nanos / 1_000_000_000.0).unwrap_or(Duration::MAX)
+        }
+        _ => {
+            if !UNEXPECTED_TYPE_LOGGED.swap(true, Ordering::Relaxed) {
+                log_internal(
+                    Level::Debug,
+                    c"RunOutput::duration",
+                    &format!(
+                        "unexpected type for the duration of an evaluation: {:?}",
+                        raw.object_type()
+                    ),
+                );
+            }
+            Duration::ZERO
+        }
+    }
 }
+
 impl fmt::Debug for RunOutput {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RunOutput")
@@ -321,3 +962,75 @@ impl fmt::Debug for RunOutput {
             .finish()
     }
 }
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use super::*;
+    use crate::{waf_array, waf_map};
+
+    fn duration_of(result: &WafMap) -> Duration {
+        result
+            .get_str("duration")
+            .map(Keyed::value)
+            .map(parse_duration)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn duration_representations() {
+        let cases = [
+            (
+                waf_map!(("duration", 1_500_u64)),
+                Duration::from_nanos(1_500),
+            ),
+            (
+                waf_map!(("duration", u64::MAX)),
+                Duration::from_nanos(u64::MAX),
+            ),
+            (
+                waf_map!(("duration", 2_000_i64)),
+                Duration::from_nanos(2_000),
+            ),
+            (waf_map!(("duration", -2_000_i64)), Duration::ZERO),
+            (waf_map!(("duration", i64::MIN)), Duration::ZERO),
+            (waf_map!(("duration", 1_500.0)), Duration::from_nanos(1_500)),
+            (waf_map!(("duration", 2.5e9)), Duration::from_millis(2_500)),
+            (waf_map!(("duration", -1.0)), Duration::ZERO),
+            (waf_map!(("duration", f64::NAN)), Duration::ZERO),
+            (waf_map!(("duration", f64::INFINITY)), Duration::MAX),
+            (waf_map!(("duration", "1500")), Duration::ZERO),
+            (
+                waf_map!(("duration", waf_array![1_500_u64])),
+                Duration::ZERO,
+            ),
+            (waf_map!(("timeout", false)), Duration::ZERO),
+        ];
+        for (result, expected) in &cases {
+            assert_eq!(
+                duration_of(result),
+                *expected,
+                "{:?}",
+                result.get_str("duration")
+            );
+        }
+    }
+
+    #[test]
+    fn output_that_is_not_a_map() {
+        // `RunOutput` is a transparent wrapper around the object `libddwaf` produced, which is
+        // read as a map by its accessors. An unsigned integer would be read as a map with a bogus
+        // size and pointer if they did not check its type first.
+        let output: RunOutput = unsafe { std::mem::transmute(WafObject::from(42_u64)) };
+        assert!(!output.timeout());
+        assert!(!output.keep());
+        assert_eq!(output.sampling_decision(), SamplingDecision::NoOverride);
+        assert_eq!(output.duration(), Duration::ZERO);
+        assert!(output.duration_raw().is_none());
+        assert_eq!(output.evaluated(), 0);
+        assert!(output.events().is_none());
+        assert!(output.actions().is_none());
+        assert!(output.attributes().is_none());
+        assert!(output.matched_addresses().is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+//! Builders for the data configurations (such as the `ASM_DATA` IP and user denylists) that rules
+//! and exclusion filters refer to by identifier.
+//!
+//! A [`DenyList`] keeps its entries sorted, so that the documents it produces only depend on its
+//! contents, and can be compared (or fingerprinted) to detect no-op updates:
+//!
+//! ```rust
+//! use libddwaf::data_configs::{DenyList, DenyListType};
+//!
+//! let mut blocked_ips = DenyList::new("blocked_ips", DenyListType::Ip);
+//! blocked_ips.insert("192.168.1.1", None).unwrap();
+//! blocked_ips.insert("10.0.0.0/8", Some(1_900_000_000)).unwrap();
+//! let config = blocked_ips.to_config();
+//! // builder.add_or_update_config("ASM_DATA/blocked_ips", &config, None);
+//!
+//! let previous = blocked_ips.clone();
+//! blocked_ips.insert("192.168.1.1", None).unwrap();
+//! assert!(!blocked_ips.diff_against(&previous));
+//! ```
+
+use std::collections::BTreeMap;
+
+use crate::object::{Keyed, WafArray, WafMap, WafObject, WafString};
+use crate::LengthError;
+
+/// The type of the values held by a [`DenyList`], which determines the operators that can use it.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DenyListType {
+    /// IP addresses or CIDR ranges, used by the `ip_match` operator (`ip_with_expiration`).
+    Ip,
+    /// Arbitrary strings (such as user identifiers), used by the `exact_match` operator
+    /// (`data_with_expiration`).
+    Data,
+}
+impl DenyListType {
+    /// Returns the name of this type in data configurations.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ip => "ip_with_expiration",
+            Self::Data => "data_with_expiration",
+        }
+    }
+}
+
+/// A list of values with optional expirations, identified by the `data` parameter of the rules or
+/// exclusion filters that use it.
+///
+/// A [`DenyList`] holds at most [`u16::MAX`] values, the maximum length of a [`WafArray`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenyList {
+    id: String,
+    list_type: DenyListType,
+    entries: BTreeMap<String, Option<u64>>,
+}
+impl DenyList {
+    /// Creates a new, empty [`DenyList`] with the provided identifier and type.
+    #[must_use]
+    pub fn new(id: &str, list_type: DenyListType) -> Self {
+        Self {
+            id: id.to_string(),
+            list_type,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the identifier of this [`DenyList`].
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the type of this [`DenyList`].
+    #[must_use]
+    pub fn list_type(&self) -> DenyListType {
+        self.list_type
+    }
+
+    /// Returns the number of values in this [`DenyList`].
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if this [`DenyList`] contains no values.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns `true` if this [`DenyList`] contains the provided value.
+    #[must_use]
+    pub fn contains(&self, value: &str) -> bool {
+        self.entries.contains_key(value)
+    }
+
+    /// Adds a value to this [`DenyList`], or updates its expiration if it is already present.
+    ///
+    /// The expiration is a UNIX timestamp in seconds, after which `libddwaf` ignores the value;
+    /// [`None`] means the value never expires.
+    ///
+    /// Returns `true` if the value was not present before.
+    ///
+    /// # Errors
+    /// Returns an error if the value is not present, and this [`DenyList`] already holds
+    /// [`u16::MAX`] values.
+    pub fn insert(&mut self, value: &str, expiration: Option<u64>) -> Result<bool, LengthError> {
+        if let Some(current) = self.entries.get_mut(value) {
+            *current = expiration;
+            return Ok(false);
+        }
+        LengthError::checked_len::<u16>("deny list", self.entries.len() + 1)?;
+        self.entries.insert(value.to_string(), expiration);
+        Ok(true)
+    }
+
+    /// Removes a value from this [`DenyList`]. Returns `true` if it was present.
+    pub fn remove(&mut self, value: &str) -> bool {
+        self.entries.remove(value).is_some()
+    }
+
+    /// Returns `true` if this [`DenyList`] differs from `previous`, that is, if the configuration
+    /// it produces would change the behavior of the WAF. Updates for which this returns `false`
+    /// can be skipped.
+    #[must_use]
+    pub fn diff_against(&self, previous: &DenyList) -> bool {
+        self != previous
+    }
+
+    /// Returns the entry describing this [`DenyList`] in the `rules_data` or `exclusion_data`
+    /// list of a configuration.
+    #[must_use]
+    pub fn to_data(&self) -> WafMap {
+        let mut data = WafArray::new(u16::try_from(self.entries.len()).unwrap_or(u16::MAX));
+        for (slot, (value, expiration)) in data.iter_mut().zip(&self.entries) {
+            let mut entry = WafMap::new(2);
+            entry[0] = Keyed::with_static_key("value", WafString::from(value).into());
+            entry[1] = Keyed::with_static_key(
+                "expiration",
+                WafObject::from(expiration.unwrap_or_default()),
+            );
+            *slot = entry.into();
+        }
+
+        let mut map = WafMap::new(3);
+        map[0] = Keyed::with_static_key("id", WafString::from(self.id.as_str()).into());
+        map[1] = Keyed::with_static_key(
+            "type",
+            WafString::new_literal(self.list_type.as_str().as_bytes()).into(),
+        );
+        map[2] = Keyed::with_static_key("data", data.into());
+        map
+    }
+
+    /// Returns a configuration providing this [`DenyList`] to the rules that use it, suitable for
+    /// [`Builder::add_or_update_config`](crate::Builder::add_or_update_config).
+    #[must_use]
+    pub fn to_config(&self) -> WafMap {
+        self.wrap("rules_data")
+    }
+
+    /// Returns a configuration providing this [`DenyList`] to the exclusion filters that use it,
+    /// suitable for [`Builder::add_or_update_config`](crate::Builder::add_or_update_config).
+    #[must_use]
+    pub fn to_exclusion_config(&self) -> WafMap {
+        self.wrap("exclusion_data")
+    }
+
+    fn wrap(&self, key: &'static str) -> WafMap {
+        let mut list = WafArray::new(1);
+        list[0] = self.to_data().into();
+        let mut config = WafMap::new(1);
+        config[0] = Keyed::with_static_key(key, list.into());
+        config
+    }
+}
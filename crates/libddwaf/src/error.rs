@@ -0,0 +1,148 @@
+use std::error;
+use std::fmt;
+
+#[cfg(feature = "http")]
+use crate::http::BodyError;
+use crate::log::UnknownLogLevelError;
+#[cfg(feature = "encoding")]
+use crate::object::DecodeError;
+use crate::object::{FromJsonError, ObjectTypeError, SetPathError, UnknownObjectTypeError};
+use crate::{InternalError, LengthError, Rejected, RunError};
+
+/// A [`std::result::Result`] whose error type defaults to [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Any of the errors returned by the fallible APIs of this crate.
+///
+/// Each of these errors converts into an [`Error`], so that code dealing with several of them can
+/// use [`Result`] and the `?` operator. The [`Display`](fmt::Display) implementation describes the
+/// failed operation, and the original error is available as its
+/// [`source`](error::Error::source):
+///
+/// ```rust
+/// use std::error::Error as _;
+/// use libddwaf::object::WafMap;
+///
+/// fn set_user_id(map: &mut WafMap, id: &str) -> libddwaf::Result<()> {
+///     map.set_path(&["usr", "id"], id)?;
+///     Ok(())
+/// }
+///
+/// let mut map = WafMap::new(0);
+/// map.insert("usr", "not a map");
+/// let err = set_user_id(&mut map, "admin").unwrap_err();
+/// assert_eq!(err.to_string(), "Failed to set a value at a path");
+/// assert!(err.source().is_some());
+/// ```
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum Error {
+    /// An evaluation failed (see [`RunError`]).
+    Run(RunError),
+    /// `libddwaf` failed unexpectedly (see [`InternalError`]).
+    Internal(InternalError),
+    /// An input was longer than what `libddwaf` supports (see [`LengthError`]).
+    Length(LengthError),
+    /// A configuration was rejected (see [`Rejected`]).
+    Rejected(Rejected),
+    /// A JSON document could not be converted into a [`WafObject`](crate::object::WafObject)
+    /// (see [`FromJsonError`]).
+    FromJson(FromJsonError),
+    /// A value could not be set at a path of a [`WafMap`](crate::object::WafMap) (see
+    /// [`SetPathError`]).
+    SetPath(SetPathError),
+    /// An object did not have the expected type (see [`ObjectTypeError`]).
+    ObjectType(ObjectTypeError),
+    /// An object had an unknown type (see [`UnknownObjectTypeError`]).
+    UnknownObjectType(UnknownObjectTypeError),
+    /// A log level was not known (see [`UnknownLogLevelError`]).
+    UnknownLogLevel(UnknownLogLevelError),
+    /// A string was not valid UTF-8.
+    Utf8(std::str::Utf8Error),
+    /// A regular expression could not be compiled.
+    #[cfg(feature = "regex")]
+    Regex(regex::Error),
+    /// Encoded data could not be decoded into a [`WafString`](crate::object::WafString) (see
+    /// [`DecodeError`]).
+    #[cfg(feature = "encoding")]
+    Decode(DecodeError),
+    /// A request body could not be converted into address data (see [`BodyError`]).
+    #[cfg(feature = "http")]
+    Body(BodyError),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Run(_) => write!(f, "Failed to evaluate address data"),
+            Self::Internal(_) => write!(f, "The WAF failed unexpectedly"),
+            Self::Length(_) => write!(f, "An input is too long for the WAF"),
+            Self::Rejected(_) => write!(f, "Failed to add or update a configuration"),
+            Self::FromJson(_) => write!(f, "Failed to parse a JSON document"),
+            Self::SetPath(_) => write!(f, "Failed to set a value at a path"),
+            Self::ObjectType(_) => write!(f, "An object does not have the expected type"),
+            Self::UnknownObjectType(_) => write!(f, "An object has an unknown type"),
+            Self::UnknownLogLevel(_) => write!(f, "A log level is unknown"),
+            Self::Utf8(_) => write!(f, "A string is not valid UTF-8"),
+            #[cfg(feature = "regex")]
+            Self::Regex(_) => write!(f, "Failed to compile a regular expression"),
+            #[cfg(feature = "encoding")]
+            Self::Decode(_) => write!(f, "Failed to decode encoded data"),
+            #[cfg(feature = "http")]
+            Self::Body(_) => write!(f, "Failed to convert a request body"),
+        }
+    }
+}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        let source: &(dyn error::Error + 'static) = match self {
+            Self::Run(err) => err,
+            Self::Internal(err) => err,
+            Self::Length(err) => err,
+            Self::Rejected(err) => err,
+            Self::FromJson(err) => err,
+            Self::SetPath(err) => err,
+            Self::ObjectType(err) => err,
+            Self::UnknownObjectType(err) => err,
+            Self::UnknownLogLevel(err) => err,
+            Self::Utf8(err) => err,
+            #[cfg(feature = "regex")]
+            Self::Regex(err) => err,
+            #[cfg(feature = "encoding")]
+            Self::Decode(err) => err,
+            #[cfg(feature = "http")]
+            Self::Body(err) => err,
+        };
+        Some(source)
+    }
+}
+
+macro_rules! from_error {
+    ($($variant:ident($ty:ty)),* $(,)?) => {
+        $(
+            impl From<$ty> for Error {
+                fn from(err: $ty) -> Self {
+                    Self::$variant(err)
+                }
+            }
+        )*
+    };
+}
+
+from_error!(
+    Run(RunError),
+    Internal(InternalError),
+    Length(LengthError),
+    Rejected(Rejected),
+    FromJson(FromJsonError),
+    SetPath(SetPathError),
+    ObjectType(ObjectTypeError),
+    UnknownObjectType(UnknownObjectTypeError),
+    UnknownLogLevel(UnknownLogLevelError),
+    Utf8(std::str::Utf8Error),
+);
+#[cfg(feature = "regex")]
+from_error!(Regex(regex::Error));
+#[cfg(feature = "encoding")]
+from_error!(Decode(DecodeError));
+#[cfg(feature = "http")]
+from_error!(Body(BodyError));
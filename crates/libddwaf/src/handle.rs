@@ -1,29 +1,155 @@
-use std::ffi::CStr;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::{CStr, CString};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerPolicy, CircuitState};
+
+use crate::object::{Keyed, WafArray, WafMap, WafObject};
 use crate::{Context, object::get_default_allocator};
 
 /// A fully configured WAF instance.
 ///
 /// This is obtained by [`Builder::build`][crate::Builder::build] and provides facility to create new [`Context`]
 /// that use the underlying instance's configuration.
-#[repr(transparent)]
 pub struct Handle {
     pub(crate) raw: libddwaf_sys::ddwaf_handle,
+    pub(crate) build_duration: Duration,
+    generation: u64,
+    pub(crate) rules: Vec<RuleInfo>,
+    known: OnceLock<KnownLists>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    max_context_memory: Option<usize>,
 }
 impl Handle {
+    pub(crate) fn new(
+        raw: libddwaf_sys::ddwaf_handle,
+        build_duration: Duration,
+        generation: u64,
+        rules: Vec<RuleInfo>,
+    ) -> Self {
+        track!(HANDLES, 1);
+        Self {
+            raw,
+            build_duration,
+            generation,
+            rules,
+            known: OnceLock::new(),
+            breaker: None,
+            max_context_memory: None,
+        }
+    }
+
+    /// Returns the time it took for the [`Builder`][crate::Builder] to produce this instance.
+    #[must_use]
+    pub fn build_duration(&self) -> Duration {
+        self.build_duration
+    }
+
+    /// Returns the time it took to obtain the lists of known addresses and actions from
+    /// `libddwaf`, or [`None`] if they have not been needed yet.
+    ///
+    /// These lists are obtained on first use (by [`Handle::warm`], or by methods such as
+    /// [`Handle::uses_address`]), and never again for the lifetime of this [`Handle`].
+    #[must_use]
+    pub fn lazy_init_duration(&self) -> Option<Duration> {
+        self.known.get().map(|known| known.init_duration)
+    }
+
+    /// Performs the work that is otherwise deferred until this instance is first used, so that the
+    /// first request does not pay for it. This is mostly useful where startup latency matters, such
+    /// as on the cold start of a serverless function.
+    ///
+    /// This obtains the lists of known addresses and actions from `libddwaf` (see
+    /// [`Handle::lazy_init_duration`]), and creates and destroys a [`Context`]. Calling it again
+    /// has no further effect beyond creating another [`Context`].
+    pub fn warm(&self) {
+        self.known();
+        drop(self.new_context());
+    }
+
+    /// Returns the generation of this instance: successive [`Handle`]s built by the same
+    /// [`Builder`][crate::Builder] have increasing generations, starting at 1.
+    ///
+    /// This can be used to tell which version of the ruleset evaluated some data (see
+    /// [`Context::generation`]), or whether a [`Context`] was created from an outdated instance.
+    #[must_use]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the underlying `libddwaf` instance, for use with other code linked against
+    /// `libddwaf`.
+    ///
+    /// Ownership is retained by this [`Handle`]: the returned pointer must not be destroyed, and
+    /// must not be used after this [`Handle`] has been dropped.
+    #[must_use]
+    pub fn as_raw(&self) -> libddwaf_sys::ddwaf_handle {
+        self.raw
+    }
+
     /// Creates a new [`Context`] from this instance.
+    ///
+    /// The [`Context`] shares ownership of the ruleset with this [`Handle`] (`libddwaf` keeps it
+    /// alive for as long as contexts created from it exist), so it does not borrow the [`Handle`],
+    /// and may outlive it: dropping the [`Handle`] (for example, once a new one was built after a
+    /// configuration update) does not affect the requests that are still being evaluated.
     #[must_use]
     pub fn new_context(&self) -> Context {
-        Context {
-            raw: unsafe {
-                libddwaf_sys::ddwaf_context_init(self.raw, get_default_allocator().into())
-            },
-        }
+        Context::new(
+            unsafe { libddwaf_sys::ddwaf_context_init(self.raw, get_default_allocator().into()) },
+            self.generation,
+            self.breaker.clone(),
+            self.max_context_memory,
+        )
+    }
+
+    /// Enables a circuit breaker that skips evaluations when the WAF repeatedly fails with
+    /// [`RunError::InternalError`][crate::RunError::InternalError], according to the provided
+    /// [`CircuitBreakerPolicy`].
+    ///
+    /// The circuit breaker is shared by all [`Context`]s (and their
+    /// [`Subcontext`][crate::Subcontext]s) created from this [`Handle`] after this call; contexts
+    /// created before keep using the circuit breaker that was set at the time, if any. While it is
+    /// open, evaluations fail with [`RunError::CircuitOpen`][crate::RunError::CircuitOpen].
+    pub fn set_circuit_breaker(&mut self, policy: CircuitBreakerPolicy) {
+        self.breaker = Some(Arc::new(CircuitBreaker::new(policy)));
+    }
+
+    /// Limits the estimated memory (see [`Context::keepalive_bytes`]) of the address data that each
+    /// [`Context`] created from this [`Handle`] after this call may retain, or removes the limit if
+    /// `bytes` is [`None`].
+    ///
+    /// Once the limit would be exceeded, evaluations of new data fail with
+    /// [`RunError::MemoryLimitExceeded`][crate::RunError::MemoryLimitExceeded], without the data
+    /// being handed to `libddwaf`; the caller may then evaluate it as ephemeral data instead (for
+    /// example in a [`Subcontext`][crate::Subcontext]), or skip it.
+    pub fn set_max_context_memory(&mut self, bytes: Option<usize>) {
+        self.max_context_memory = bytes;
+    }
+
+    /// Returns the limit set by [`Handle::set_max_context_memory`], if any.
+    #[must_use]
+    pub fn max_context_memory(&self) -> Option<usize> {
+        self.max_context_memory
+    }
+
+    /// Returns the current state of the circuit breaker set with
+    /// [`Handle::set_circuit_breaker`], if any.
+    #[must_use]
+    pub fn circuit_breaker_state(&self) -> Option<CircuitState> {
+        self.breaker.as_ref().map(|b| b.state())
     }
 
     /// Returns the list of actions that may be produced by this instance's ruleset.
     pub fn known_actions(&self) -> Vec<&CStr> {
-        self.call_cstr_array_fn(libddwaf_sys::ddwaf_known_actions)
+        self.known().actions.as_c_strs()
+    }
+
+    /// Returns metadata about the rules that are loaded in this instance.
+    #[must_use]
+    pub fn rules(&self) -> &[RuleInfo] {
+        &self.rules
     }
 
     /// Returns the list of addresses that are used by this instance's ruleset.
@@ -31,7 +157,54 @@ impl Handle {
     /// Sending data for addresses not in this list to [`Context::run`] should be avoided as this
     /// data will never result in any side-effects.
     pub fn known_addresses(&self) -> Vec<&CStr> {
-        self.call_cstr_array_fn(libddwaf_sys::ddwaf_known_addresses)
+        self.known().addresses.as_c_strs()
+    }
+
+    /// Returns the list of addresses that are used by this instance's ruleset, like
+    /// [`Handle::known_addresses`], but without allocating.
+    ///
+    /// The list is obtained from `libddwaf` the first time it is needed, and is then retained for
+    /// the lifetime of this [`Handle`]; successive calls return the same slice.
+    #[must_use]
+    pub fn known_addresses_cached(&self) -> &[String] {
+        &self.known().addresses.names
+    }
+
+    /// Returns true if the provided address is used by this instance's ruleset, meaning it is
+    /// part of [`Handle::known_addresses`].
+    #[must_use]
+    pub fn uses_address(&self, addr: &str) -> bool {
+        self.known().addresses.lookup.contains(addr)
+    }
+
+    /// Returns which groups of addresses are used by this instance's ruleset.
+    ///
+    /// This can be used to avoid collecting data for addresses that would never be used; for
+    /// example, response data only needs to be collected if [`AddressPhases::response`] is set.
+    #[must_use]
+    pub fn address_phases(&self) -> AddressPhases {
+        self.known().address_phases
+    }
+
+    /// Returns the [`KnownLists`] of this instance, obtaining them from `libddwaf` on first use.
+    fn known(&self) -> &KnownLists {
+        self.known.get_or_init(|| {
+            let start = Instant::now();
+            let addresses =
+                KnownNames::new(self.call_cstr_array_fn(libddwaf_sys::ddwaf_known_addresses));
+            let actions =
+                KnownNames::new(self.call_cstr_array_fn(libddwaf_sys::ddwaf_known_actions));
+            let mut address_phases = AddressPhases::default();
+            for address in &addresses.names {
+                address_phases.add(address);
+            }
+            KnownLists {
+                addresses,
+                actions,
+                address_phases,
+                init_duration: start.elapsed(),
+            }
+        })
     }
 
     fn call_cstr_array_fn(
@@ -51,9 +224,142 @@ impl Handle {
         arr.iter().map(|&x| unsafe { CStr::from_ptr(x) }).collect()
     }
 }
+
+/// The addresses and actions known to a [`Handle`], which never change once it is built.
+struct KnownLists {
+    addresses: KnownNames,
+    actions: KnownNames,
+    address_phases: AddressPhases,
+    init_duration: Duration,
+}
+
+/// A list of names reported by `libddwaf`, in the representations the [`Handle`] APIs need.
+struct KnownNames {
+    c_names: Vec<CString>,
+    names: Vec<String>,
+    lookup: HashSet<Box<str>>,
+}
+impl KnownNames {
+    fn new(list: Vec<&CStr>) -> Self {
+        let names: Vec<String> = list
+            .iter()
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        Self {
+            c_names: list.into_iter().map(CStr::to_owned).collect(),
+            lookup: names.iter().map(|name| name.as_str().into()).collect(),
+            names,
+        }
+    }
+
+    fn as_c_strs(&self) -> Vec<&CStr> {
+        self.c_names.iter().map(CString::as_c_str).collect()
+    }
+}
+
+/// The groups of addresses used by a [`Handle`]'s ruleset, as returned by
+/// [`Handle::address_phases`].
+///
+/// Addresses are classified based on their documented prefixes; addresses that do not belong to
+/// any of these groups are not reflected here (see [`Handle::uses_address`] instead).
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // These are independent flags
+pub struct AddressPhases {
+    /// Whether some `server.request.*` addresses are used.
+    pub request: bool,
+    /// Whether some `server.response.*` addresses are used.
+    pub response: bool,
+    /// Whether some `grpc.server.*` addresses are used.
+    pub grpc: bool,
+    /// Whether some `graphql.server.*` addresses are used.
+    pub graphql: bool,
+}
+type AddressPhaseField = fn(&mut AddressPhases) -> &mut bool;
+impl AddressPhases {
+    /// The address prefixes identifying each group, along with the corresponding field.
+    const PREFIXES: &[(&str, AddressPhaseField)] = &[
+        ("server.request.", |p| &mut p.request),
+        ("server.response.", |p| &mut p.response),
+        ("grpc.server.", |p| &mut p.grpc),
+        ("graphql.server.", |p| &mut p.graphql),
+    ];
+
+    fn add(&mut self, address: &str) {
+        for (prefix, field) in Self::PREFIXES {
+            if address.starts_with(prefix) {
+                *field(self) = true;
+            }
+        }
+    }
+}
+
+/// Metadata about a rule loaded in a [`Handle`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleInfo {
+    /// The rule's unique identifier.
+    pub id: String,
+    /// The rule's human-readable name.
+    pub name: String,
+    /// The rule's tags (such as `type` and `category`).
+    pub tags: BTreeMap<String, String>,
+}
+impl RuleInfo {
+    /// Extracts the metadata of the rules from `ruleset` that are reported as loaded by the
+    /// `diagnostics` produced when adding it to a [`Builder`][crate::Builder].
+    pub(crate) fn loaded_from(ruleset: &WafObject, diagnostics: &WafMap) -> Vec<Self> {
+        let Some(ruleset) = ruleset.as_type::<WafMap>() else {
+            return Vec::new();
+        };
+        let mut res = Vec::new();
+        for key in ["rules", "custom_rules"] {
+            let Some(rules) = ruleset
+                .get_str(key)
+                .and_then(Keyed::<WafObject>::as_type::<WafArray>)
+            else {
+                continue;
+            };
+            let loaded = diagnostics
+                .get_str(key)
+                .and_then(Keyed::<WafObject>::as_type::<WafMap>)
+                .and_then(|m| m.get_str("loaded"))
+                .and_then(Keyed::<WafObject>::as_type::<WafArray>);
+            let Some(loaded) = loaded else {
+                continue;
+            };
+            for rule in rules.iter().filter_map(WafObject::as_type::<WafMap>) {
+                let Some(id) = rule.get_str("id").and_then(|o| o.to_str()) else {
+                    continue;
+                };
+                if !loaded.iter().any(|o| o.to_str() == Some(id)) {
+                    continue;
+                }
+                let name = rule.get_str("name").and_then(|o| o.to_str()).unwrap_or("");
+                let tags = rule
+                    .get_str("tags")
+                    .and_then(Keyed::<WafObject>::as_type::<WafMap>)
+                    .map(|tags| {
+                        tags.iter()
+                            .filter_map(|t| Some((t.key_str().ok()?, t.to_str()?)))
+                            .map(|(k, v)| (k.to_string(), v.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                res.push(Self {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    tags,
+                });
+            }
+        }
+        res
+    }
+}
+
 impl Drop for Handle {
     fn drop(&mut self) {
-        unsafe { libddwaf_sys::ddwaf_destroy(self.raw) }
+        unsafe { libddwaf_sys::ddwaf_destroy(self.raw) };
+        track!(HANDLES, -1);
     }
 }
 // SAFETY: ddwaf instances are effectively immutable
@@ -0,0 +1,245 @@
+//! Helpers converting HTTP request bodies into the values expected for the
+//! [`REQUEST_BODY`](crate::addresses::REQUEST_BODY) address.
+//!
+//! Bodies larger than [`ConversionLimits::max_body_size`] are rejected before being parsed, and
+//! the values extracted from them are subject to the same [`Limits`] as
+//! [`deserialize_with_limits`](crate::serde::deserialize_with_limits).
+//!
+//! ```rust
+//! use libddwaf::addresses::AddressMapBuilder;
+//! use libddwaf::http::{body_json_to_waf, ConversionLimits};
+//!
+//! let body = body_json_to_waf(br#"{"user": {"name": "admin"}}"#, &ConversionLimits::default())
+//!     .expect("the body is valid JSON");
+//! let data = AddressMapBuilder::new().request_body(body).build();
+//! ```
+
+use std::error;
+use std::fmt;
+
+use crate::object::{WafMap, WafObject, WafString};
+use crate::serde::{deserialize_with_limits, Limits};
+
+/// Default maximum body size (1 MiB).
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Limits applied when converting request bodies.
+#[derive(Debug, Clone)]
+pub struct ConversionLimits {
+    /// Bodies larger than this (in bytes) are rejected with [`BodyError::TooLarge`], without
+    /// being parsed.
+    pub max_body_size: usize,
+    /// The limits applied to the values extracted from the body.
+    pub values: Limits,
+}
+impl Default for ConversionLimits {
+    fn default() -> Self {
+        Self {
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            values: Limits::default(),
+        }
+    }
+}
+
+/// The error that is returned when a request body cannot be converted.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BodyError {
+    /// The body is larger than [`ConversionLimits::max_body_size`].
+    TooLarge {
+        /// The size of the body, in bytes.
+        len: usize,
+        /// The maximum size allowed, in bytes.
+        max_len: usize,
+    },
+    /// The body is not a valid JSON document.
+    InvalidJson,
+    /// The content type is not `multipart/form-data`, or has no boundary.
+    MissingBoundary,
+    /// The body is not a valid multipart document.
+    InvalidMultipart,
+}
+impl error::Error for BodyError {}
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { len, max_len } => write!(
+                f,
+                "Request body is too large ({len} bytes, at most {max_len} are supported)"
+            ),
+            Self::InvalidJson => write!(f, "Request body is not valid JSON"),
+            Self::MissingBoundary => write!(f, "Content type is not multipart with a boundary"),
+            Self::InvalidMultipart => write!(f, "Request body is not valid multipart data"),
+        }
+    }
+}
+
+/// Converts a JSON request body into a [`WafObject`].
+///
+/// # Errors
+/// Returns [`BodyError::TooLarge`] if the body is larger than
+/// [`ConversionLimits::max_body_size`], and [`BodyError::InvalidJson`] if it is not a single valid
+/// JSON document.
+pub fn body_json_to_waf(bytes: &[u8], limits: &ConversionLimits) -> Result<WafObject, BodyError> {
+    check_size(bytes, limits)?;
+    let mut de = serde_json::Deserializer::from_slice(bytes);
+    let result =
+        deserialize_with_limits(&mut de, &limits.values).map_err(|_| BodyError::InvalidJson)?;
+    de.end().map_err(|_| BodyError::InvalidJson)?;
+    Ok(result.value)
+}
+
+/// Converts a `multipart/form-data` request body into a [`WafMap`], from its `content_type`
+/// (which carries the boundary between parts).
+///
+/// Each text field is converted into an entry from the field name to its value. File parts are
+/// not included; instead, each is converted into an entry from the field name to a map holding
+/// its `filename` and `content_type` (when provided).
+/// At most [`Limits::max_elements`] parts are converted, and values are truncated to
+/// [`Limits::max_string_length`] bytes.
+///
+/// # Errors
+/// Returns [`BodyError::TooLarge`] if the body is larger than
+/// [`ConversionLimits::max_body_size`], [`BodyError::MissingBoundary`] if `content_type` is not
+/// `multipart/form-data` with a boundary, and [`BodyError::InvalidMultipart`] if the body is
+/// malformed.
+pub fn body_multipart_to_waf(
+    content_type: &str,
+    bytes: &[u8],
+    limits: &ConversionLimits,
+) -> Result<WafMap, BodyError> {
+    check_size(bytes, limits)?;
+    let boundary = multipart_boundary(content_type).ok_or(BodyError::MissingBoundary)?;
+    let delimiter = [&b"--"[..], boundary.as_bytes()].concat();
+    let max_len = usize::try_from(limits.values.max_string_length).unwrap_or(usize::MAX);
+    let truncated = |value: &[u8]| WafString::from(&value[..value.len().min(max_len)]);
+
+    let mut fields = Vec::new();
+    let start = find(bytes, &delimiter).ok_or(BodyError::InvalidMultipart)?;
+    let mut rest = &bytes[start + delimiter.len()..];
+    loop {
+        if rest.starts_with(b"--") {
+            // The closing delimiter
+            break;
+        }
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .ok_or(BodyError::InvalidMultipart)?;
+        let end = find(rest, &[&b"\r\n"[..], delimiter.as_slice()].concat())
+            .ok_or(BodyError::InvalidMultipart)?;
+        let part = parse_part(&rest[..end]).ok_or(BodyError::InvalidMultipart)?;
+        rest = &rest[end + 2 + delimiter.len()..];
+
+        if fields.len() >= limits.values.max_elements {
+            continue;
+        }
+        let value: WafObject = match part.filename {
+            Some(filename) => {
+                let mut metadata = vec![("filename", WafObject::from(truncated(filename)))];
+                if let Some(content_type) = part.content_type {
+                    metadata.push(("content_type", truncated(content_type).into()));
+                }
+                WafMap::from(metadata).into()
+            }
+            None => truncated(part.body).into(),
+        };
+        fields.push((part.name, value));
+    }
+    Ok(WafMap::from(fields))
+}
+
+fn check_size(bytes: &[u8], limits: &ConversionLimits) -> Result<(), BodyError> {
+    if bytes.len() > limits.max_body_size {
+        return Err(BodyError::TooLarge {
+            len: bytes.len(),
+            max_len: limits.max_body_size,
+        });
+    }
+    Ok(())
+}
+
+/// Returns the boundary of a `multipart/form-data` content type.
+fn multipart_boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| unquote(value.trim()))
+        .filter(|boundary| !boundary.is_empty())
+}
+
+/// A part of a multipart body.
+struct Part<'a> {
+    name: &'a [u8],
+    filename: Option<&'a [u8]>,
+    content_type: Option<&'a [u8]>,
+    body: &'a [u8],
+}
+
+/// Parses a part of a multipart body, made of headers and a body separated by an empty line.
+/// Returns [`None`] if it has no `Content-Disposition` header with a name.
+fn parse_part(part: &[u8]) -> Option<Part<'_>> {
+    let (headers, body) = match find(part, b"\r\n\r\n") {
+        Some(end) => (&part[..end], &part[end + 4..]),
+        // A part without a body
+        None => (part.strip_suffix(b"\r\n").unwrap_or(part), &[][..]),
+    };
+
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+    for header in headers.split(|&b| b == b'\n') {
+        let header = header.strip_suffix(b"\r").unwrap_or(header);
+        let Some(colon) = header.iter().position(|&b| b == b':') else {
+            continue;
+        };
+        let (header_name, value) = (&header[..colon], header[colon + 1..].trim_ascii());
+        if header_name.eq_ignore_ascii_case(b"content-type") {
+            content_type = Some(value);
+        } else if header_name.eq_ignore_ascii_case(b"content-disposition") {
+            for param in value.split(|&b| b == b';').skip(1) {
+                let Some(eq) = param.iter().position(|&b| b == b'=') else {
+                    continue;
+                };
+                let param_value = unquote_bytes(param[eq + 1..].trim_ascii());
+                match param[..eq].trim_ascii() {
+                    b"name" => name = Some(param_value),
+                    b"filename" => filename = Some(param_value),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Some(Part {
+        name: name?,
+        filename,
+        content_type,
+        body,
+    })
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn unquote_bytes(value: &[u8]) -> &[u8] {
+    value
+        .strip_prefix(b"\"")
+        .and_then(|value| value.strip_suffix(b"\""))
+        .unwrap_or(value)
+}
+
+/// Returns the position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
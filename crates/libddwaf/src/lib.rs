@@ -61,9 +61,9 @@
 //! let data = waf_map!{
 //!     ("arg1", "value1"),
 //! };
-//! match waf_ctx.run(data, std::time::Duration::from_millis(1)) {
+//! match waf_ctx.run(data, std::time::Duration::from_millis(1))? {
 //!     // Deal with the result as appropriate...
-//!     Ok(RunResult::Match(res)) => {
+//!     RunResult::Match(res) => {
 //!         assert!(!res.timeout());
 //!         assert!(res.keep());
 //!         assert!(res.duration() >= std::time::Duration::default());
@@ -71,19 +71,117 @@
 //!         assert_eq!(res.actions().expect("Expected actions").len(), 1);
 //!         assert_eq!(res.attributes().expect("Expected attributes").len(), 0);
 //!     },
-//!     Err(e) => panic!("Error while running the in-app WAF: {e}"),
-//!     _ => panic!("Unexpected result"),
+//!     RunResult::NoMatch(_) => panic!("Unexpected result"),
+//! }
+//! # Ok::<(), libddwaf::Error>(())
+//! ```
+//!
+//! The errors returned by the fallible APIs of this crate all convert into an [`Error`], so that
+//! they can be propagated with the `?` operator from functions returning a [`Result`].
+//!
+//! # Assertions
+//!
+//! Data that is malformed in a way that would otherwise lead to undefined behavior is always
+//! checked: for example, the accessors of a [`RunOutput`] that is not a map return their default
+//! values, and a [`WafString`](object::WafString) without data is empty.
+//!
+//! Misuses of the API that are otherwise handled gracefully (such as adding a configuration with
+//! an empty path, which `libddwaf` rejects) are only checked in debug builds, where they panic. The
+//! `strict-asserts` feature enables these checks in release builds too, for users who prefer to
+//! fail fast. Checks that are costly, or that run in callbacks invoked by `libddwaf` (where a
+//! panic would abort the process), are only done in debug builds.
+//!
+//! # Concurrency
+//!
+//! - A [`Handle`] is immutable once built, and can be shared between threads (typically in an
+//!   [`Arc`](std::sync::Arc)). When the configuration changes, a new [`Handle`] is built and
+//!   swapped in place of the previous one; [`Context`]s created from the previous [`Handle`]
+//!   remain valid (even after it is dropped) and keep using its ruleset until they are dropped.
+//!   [`Handle::generation`] and [`Context::generation`] tell them apart.
+//! - A [`Context`] accumulates the data of a single request. It can be moved between threads (for
+//!   example, across `.await` points of a multi-threaded executor), but evaluating data requires
+//!   exclusive access: sharing one between threads requires a lock such as a
+//!   [`Mutex`](std::sync::Mutex). With the `tokio` feature, `Context::run_async` performs the
+//!   (blocking) evaluation on Tokio's blocking thread pool.
+//! - A [`Builder`] is mutated through exclusive references only; [`SyncBuilder`] provides internal
+//!   locking for sharing one between threads.
+//!
+//! # Trace Sampling
+//!
+//! When [`RunOutput::keep`] is set, the trace of the request must not be dropped by the sampler,
+//! which tracers typically implement by overriding its sampling priority to `USER_KEEP`. This is
+//! the case when rules produced events, but also when rules or processors only produced
+//! [attributes][RunOutput::attributes], which tracers usually report under a different metric.
+//! [`RunResult::sampling_decision`] tells these cases apart:
+//!
+//! ```rust
+//! use libddwaf::{RunResult, SamplingDecision};
+//!
+//! /// A stand-in for the span API of a tracer.
+//! trait Span {
+//!     fn set_sampling_priority(&mut self, priority: i32);
+//!     fn increment_metric(&mut self, name: &str);
+//! }
+//!
+//! const USER_KEEP: i32 = 2;
+//!
+//! fn apply_sampling_decision(result: &RunResult, span: &mut impl Span) {
+//!     match result.sampling_decision() {
+//!         SamplingDecision::NoOverride => {}
+//!         SamplingDecision::Keep { due_to_match } => {
+//!             span.set_sampling_priority(USER_KEEP);
+//!             span.increment_metric(if due_to_match {
+//!                 "appsec.keep.match"
+//!             } else {
+//!                 "appsec.keep.attributes"
+//!             });
+//!         }
+//!     }
 //! }
 //! ```
 
 use std::ffi::CStr;
 
-#[cfg(feature = "serde")]
+/// Asserts an invariant whose violation is a programming error, and which is otherwise handled
+/// gracefully. Like [`debug_assert!`], but also checked in release builds when
+/// the `strict-asserts` feature is enabled.
+macro_rules! strict_assert {
+    ($($arg:tt)*) => {
+        if cfg!(any(debug_assertions, feature = "strict-asserts")) {
+            assert!($($arg)*);
+        }
+    };
+}
+
+/// Adds `delta` to one of the [`object::stats`] counters when the `leak-check` feature is enabled;
+/// does nothing otherwise.
+macro_rules! track {
+    ($counter:ident, $delta:expr) => {
+        #[cfg(feature = "leak-check")]
+        $crate::object::stats::$counter.fetch_add($delta, ::std::sync::atomic::Ordering::Relaxed);
+    };
+}
+
+#[cfg(feature = "compat-naming")]
+pub mod compat;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "regex")]
+pub mod redact;
+#[cfg(feature = "bundled-ruleset")]
+pub mod ruleset;
+#[cfg(any(feature = "serde-serialize", feature = "serde-deserialize"))]
 pub mod serde;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 
+pub mod addresses;
+pub mod data_configs;
 pub mod log;
 pub mod object;
+pub mod phases;
 mod private;
+pub mod raw_inspect;
 
 macro_rules! forward {
     ($($name:ident),*) => {
@@ -95,7 +193,15 @@ macro_rules! forward {
     };
 }
 
-forward!(builder, config, context, handle);
+forward!(
+    builder,
+    circuit_breaker,
+    config,
+    context,
+    error,
+    handle,
+    tester
+);
 
 /// Returns the version of the underlying `libddwaf` library.
 #[must_use]
@@ -108,20 +214,127 @@ pub fn version() -> &'static CStr {
     }
 }
 
+/// The error returned when an input is longer than what `libddwaf` supports.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LengthError {
+    /// A description of the input that was too long (e.g, `"path"`).
+    pub what: &'static str,
+    /// The length of the input, in bytes.
+    pub len: usize,
+    /// The maximum supported length, in bytes.
+    pub max: usize,
+}
+impl LengthError {
+    /// Returns `len` as the length type `T` used by `libddwaf` for the input described by
+    /// `what`, or a [`LengthError`] if it exceeds [`Length::MAX`].
+    ///
+    /// This is the single place where lengths are checked: the fallible (`try_*`) APIs return
+    /// this error, and the infallible conversions that are documented to truncate use
+    /// [`Length::MAX`] instead.
+    pub(crate) fn checked_len<T: Length>(what: &'static str, len: usize) -> Result<T, Self> {
+        T::try_from(len).map_err(|_| Self {
+            what,
+            len,
+            max: T::MAX,
+        })
+    }
+}
+
+/// The integer types `libddwaf` stores lengths in: [`u32`] for strings, and [`u16`] for the
+/// number of entries of arrays and maps.
+pub(crate) trait Length: TryFrom<usize> + Copy {
+    /// The largest length this type can represent.
+    const MAX: usize;
+
+    /// Returns this length as a [`usize`].
+    fn to_usize(self) -> usize;
+}
+impl Length for u16 {
+    const MAX: usize = u16::MAX as usize;
+
+    fn to_usize(self) -> usize {
+        usize::from(self)
+    }
+}
+impl Length for u32 {
+    const MAX: usize = u32::MAX as usize;
+
+    /// # Panics
+    /// Panics if the length does not fit in a [`usize`], which can only happen on 16-bit
+    /// platforms.
+    #[allow(clippy::expect_used)] // Documented panic
+    fn to_usize(self) -> usize {
+        usize::try_from(self).expect("length does not fit in usize on this platform")
+    }
+}
+impl std::fmt::Display for LengthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The {} is too long ({} bytes, at most {} are supported)",
+            self.what, self.len, self.max
+        )
+    }
+}
+impl std::error::Error for LengthError {}
+
 #[cfg(test)]
 mod tests {
+    use crate::{Length, LengthError};
+
+    #[test]
+    fn test_checked_len() {
+        assert_eq!(LengthError::checked_len::<u32>("path", 0), Ok(0));
+        assert_eq!(
+            LengthError::checked_len::<u32>("path", u32::MAX as usize),
+            Ok(u32::MAX)
+        );
+        assert_eq!(
+            LengthError::checked_len::<u16>("array", u16::MAX.into()),
+            Ok(u16::MAX)
+        );
+        assert_eq!(
+            LengthError::checked_len::<u16>("array", usize::from(u16::MAX) + 1),
+            Err(LengthError {
+                what: "array",
+                len: 65_536,
+                max: 65_535,
+            })
+        );
+        assert_eq!(u16::MAX.to_usize(), 65_535);
+        assert_eq!(u32::MAX.to_usize(), u32::MAX as usize);
+        #[cfg(target_pointer_width = "64")]
+        {
+            let err = LengthError::checked_len::<u32>("path", u32::MAX as usize + 1).unwrap_err();
+            assert_eq!(
+                err,
+                LengthError {
+                    what: "path",
+                    len: u32::MAX as usize + 1,
+                    max: u32::MAX as usize,
+                }
+            );
+            assert_eq!(
+                err.to_string(),
+                "The path is too long (4294967296 bytes, at most 4294967295 are supported)"
+            );
+            assert!(LengthError::checked_len::<u32>("filter", usize::MAX).is_err());
+        }
+    }
+
     #[test]
     #[cfg(not(miri))]
     fn test_version() {
         use crate::version;
 
-        if std::env::var("LIBDDWAF_PREFIX").is_ok() {
-            eprintln!("Skipping test_get_version: LIBDDWAF_PREFIX is set");
+        // Honors `LIBDDWAF_VERSION` overrides, and is unknown when `LIBDDWAF_PREFIX` is set.
+        let Some(expected) = option_env!("LIBDDWAF_RELEASE_VERSION") else {
+            eprintln!("Skipping test_version: linked against an installed libddwaf");
             return;
-        }
+        };
 
         assert_eq!(
-            env!("CARGO_PKG_VERSION"),
+            expected,
             version()
                 .to_str()
                 .expect("Failed to convert version to str")
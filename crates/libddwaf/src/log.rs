@@ -1,11 +1,40 @@
 //! Access to the in-app WAF's logging facility.
 
 use std::ffi::CStr;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
 use std::{error, fmt, slice};
 
 type LogCallback = Box<dyn Fn(Level, &'static CStr, &'static CStr, u32, &[u8])>;
 
 static mut LOG_CB: Option<LogCallback> = None;
+static mut LOG_OPTIONS: LogOptions = LogOptions::new();
+static mut LOG_LEVEL: Level = Level::Off;
+static RATE_LIMITER: RateLimiter = RateLimiter::new();
+
+/// Options controlling which of the WAF's log messages are forwarded to the log callback.
+#[derive(Clone, Debug, Default)]
+pub struct LogOptions {
+    /// The maximum number of messages forwarded per second, allowing bursts of as many messages
+    /// (up to 2^24 - 1). Excess messages are dropped, and the number of dropped messages is
+    /// reported by a single [`Level::Warn`] message once forwarding resumes, or when the log
+    /// callback is reset.
+    pub max_messages_per_second: Option<u32>,
+    /// Only messages whose source file name contains this substring are forwarded.
+    pub file_filter: Option<String>,
+}
+impl LogOptions {
+    /// Creates a new [`LogOptions`] that forwards all messages.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_messages_per_second: None,
+            file_filter: None,
+        }
+    }
+}
 
 /// Sets the log callback function.
 ///
@@ -17,6 +46,27 @@ pub unsafe fn set_log_cb(
     cb: impl Fn(Level, &'static CStr, &'static CStr, u32, &[u8]) + 'static,
     min_level: Level,
 ) {
+    unsafe { set_log_cb_with(cb, min_level, LogOptions::new()) };
+}
+
+/// Sets the log callback function, only forwarding the messages allowed by the provided
+/// [`LogOptions`].
+///
+/// # Safety
+///
+/// This function is unsafe because it writes to a static variable without synchronization.
+/// It should only be used during startup.
+pub unsafe fn set_log_cb_with(
+    cb: impl Fn(Level, &'static CStr, &'static CStr, u32, &[u8]) + 'static,
+    min_level: Level,
+    options: LogOptions,
+) {
+    RATE_LIMITER.reset(
+        now_millis(),
+        options.max_messages_per_second.unwrap_or_default(),
+    );
+    unsafe { LOG_OPTIONS = options };
+    unsafe { LOG_LEVEL = min_level };
     unsafe { LOG_CB = Some(Box::new(cb)) };
     unsafe { libddwaf_sys::ddwaf_set_log_cb(Some(bridge_log_cb), min_level.as_raw()) };
 }
@@ -29,7 +79,29 @@ pub unsafe fn set_log_cb(
 /// It should only be used during startup.
 pub unsafe fn reset_log_cb() {
     unsafe { libddwaf_sys::ddwaf_set_log_cb(None, Level::Off.as_raw()) };
+    // Messages dropped since the last refill would otherwise never be reported.
+    #[allow(static_mut_refs)]
+    if let Some(cb) = unsafe { &LOG_CB } {
+        report_dropped(cb, RATE_LIMITER.take_dropped());
+    }
     unsafe { LOG_CB = None };
+    unsafe { LOG_OPTIONS = LogOptions::new() };
+    unsafe { LOG_LEVEL = Level::Off };
+}
+
+/// Forwards a message emitted by these bindings (rather than by the WAF itself) to the log
+/// callback, if one is set and the message is at or above its minimum level. Such messages are
+/// reported from the `libddwaf-rust` file.
+pub(crate) fn log_internal(level: Level, function: &'static CStr, message: &str) {
+    unsafe {
+        #[allow(static_mut_refs)]
+        if let Some(cb) = &LOG_CB {
+            if LOG_LEVEL == Level::Off || level.as_raw() < LOG_LEVEL.as_raw() {
+                return;
+            }
+            cb(level, c"libddwaf-rust", function, 0, message.as_bytes());
+        }
+    }
 }
 
 /// Logging levels supported by the WAF.
@@ -101,8 +173,116 @@ impl fmt::Display for UnknownLogLevelError {
 }
 impl error::Error for UnknownLogLevelError {}
 
+/// The number of low bits of a [`RateLimiter`]'s state holding the tokens left.
+const TOKEN_BITS: u32 = 24;
+const TOKEN_MASK: u64 = (1 << TOKEN_BITS) - 1;
+
+/// A token bucket rate limiter, holding up to `rate` tokens and refilled at `rate` tokens per
+/// second.
+///
+/// The time of the last refill (in milliseconds) and the number of tokens left are packed in a
+/// single [`AtomicU64`], so that they are updated together by a single compare-and-swap. The
+/// current time is provided by the caller, which allows testing the limiter in isolation.
+struct RateLimiter {
+    state: AtomicU64,
+    dropped: AtomicU64,
+}
+impl RateLimiter {
+    const fn new() -> Self {
+        Self {
+            state: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Fills the bucket as of `now`, and forgets about previously dropped messages.
+    fn reset(&self, now: u64, rate: u32) {
+        self.state
+            .store(Self::pack(now, Self::capacity(rate)), Ordering::Release);
+        self.dropped.store(0, Ordering::Release);
+    }
+
+    /// Takes a token at time `now`, refilling the bucket first. Returns whether a token was
+    /// available (otherwise the message is counted as dropped), and the number of messages
+    /// dropped before a refill, which are reported once.
+    fn admit(&self, now: u64, rate: u32) -> (bool, u64) {
+        let capacity = Self::capacity(rate);
+        let mut current = self.state.load(Ordering::Acquire);
+        let (admitted, refilled) = loop {
+            let (mut last, mut tokens) = Self::unpack(current);
+            let refill = now.saturating_sub(last).saturating_mul(capacity) / 1_000;
+            if refill > 0 {
+                tokens = (tokens + refill).min(capacity);
+                // Only the time accounting for whole tokens is consumed, unless the bucket is full.
+                last = if tokens == capacity {
+                    now
+                } else {
+                    last + (refill * 1_000).div_ceil(capacity)
+                };
+            }
+            let admitted = tokens > 0;
+            let next = Self::pack(last, tokens - u64::from(admitted));
+            if next == current {
+                break (admitted, false);
+            }
+            match self.state.compare_exchange_weak(
+                current,
+                next,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break (admitted, refill > 0),
+                Err(actual) => current = actual,
+            }
+        };
+        if !admitted {
+            self.dropped.fetch_add(1, Ordering::AcqRel);
+        }
+        let dropped = if refilled { self.take_dropped() } else { 0 };
+        (admitted, dropped)
+    }
+
+    /// Returns the number of messages dropped since this was last called.
+    fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::AcqRel)
+    }
+
+    /// Returns the size of the bucket for `rate`, which is capped to what the state can hold.
+    fn capacity(rate: u32) -> u64 {
+        u64::from(rate).min(TOKEN_MASK)
+    }
+
+    const fn pack(last: u64, tokens: u64) -> u64 {
+        (last << TOKEN_BITS) | tokens
+    }
+
+    const fn unpack(state: u64) -> (u64, u64) {
+        (state >> TOKEN_BITS, state & TOKEN_MASK)
+    }
+}
+
+/// Returns the number of milliseconds elapsed since this was first called, for [`RATE_LIMITER`].
+fn now_millis() -> u64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    u64::try_from(EPOCH.get_or_init(Instant::now).elapsed().as_millis()).unwrap_or(u64::MAX)
+}
+
+/// A [`fmt::Write`] implementation over a fixed-size buffer, silently truncating its output.
+struct BufWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+impl Write for BufWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
 /// Wraps the log callback function (stored in [`LOG_CB`]) to convert the raw pointers provided by the C/C++ library into
-/// somewhat easier to consume types.
+/// somewhat easier to consume types, and applies the filtering set up in [`LOG_OPTIONS`].
 extern "C" fn bridge_log_cb(
     level: libddwaf_sys::DDWAF_LOG_LEVEL,
     file: *const std::os::raw::c_char,
@@ -114,20 +294,188 @@ extern "C" fn bridge_log_cb(
     unsafe {
         #[allow(static_mut_refs)]
         if let Some(cb) = &LOG_CB {
-            let file = CStr::from_ptr(file);
-            let function = CStr::from_ptr(function);
+            #[allow(static_mut_refs)]
+            let options = &LOG_OPTIONS;
             let message = if message.is_null() {
                 b"<null>\0"
             } else {
                 slice::from_raw_parts(message.cast(), message_len.try_into().unwrap_or(usize::MAX))
             };
-            cb(
+            forward(
+                cb,
+                options,
+                &RATE_LIMITER,
+                now_millis(),
                 Level::try_from(level).unwrap_or(Level::Error),
-                file,
-                function,
+                CStr::from_ptr(file),
+                CStr::from_ptr(function),
                 line,
                 message,
             );
         }
     }
 }
+
+/// Forwards a message to `cb` if it is allowed by `options`, using `limiter` to enforce the
+/// [`LogOptions::max_messages_per_second`] limit as of `now`.
+#[allow(clippy::too_many_arguments)]
+fn forward(
+    cb: &LogCallback,
+    options: &LogOptions,
+    limiter: &RateLimiter,
+    now: u64,
+    level: Level,
+    file: &'static CStr,
+    function: &'static CStr,
+    line: u32,
+    message: &[u8],
+) {
+    if let Some(filter) = &options.file_filter {
+        let filter = filter.as_bytes();
+        if !filter.is_empty() && !file.to_bytes().windows(filter.len()).any(|w| w == filter) {
+            return;
+        }
+    }
+    if let Some(max) = options.max_messages_per_second {
+        let (admitted, dropped) = limiter.admit(now, max);
+        report_dropped(cb, dropped);
+        if !admitted {
+            return;
+        }
+    }
+    cb(level, file, function, line, message);
+}
+
+/// Reports the number of messages dropped by the rate limiter, if any, with a single
+/// [`Level::Warn`] message.
+fn report_dropped(cb: &LogCallback, dropped: u64) {
+    if dropped == 0 {
+        return;
+    }
+    let mut buf = [0u8; 64];
+    let mut writer = BufWriter {
+        buf: &mut buf,
+        len: 0,
+    };
+    let _ = write!(writer, "dropped {dropped} messages");
+    let len = writer.len;
+    cb(
+        Level::Warn,
+        c"libddwaf-rust",
+        c"bridge_log_cb",
+        0,
+        &buf[..len],
+    );
+}
+
+#[cfg(test)]
+#[cfg_attr(coverage_nightly, coverage(off))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    type Received = Arc<Mutex<Vec<(Level, String)>>>;
+
+    /// Returns a callback recording the messages it receives.
+    fn recorder() -> (LogCallback, Received) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let cb: LogCallback = Box::new(move |level, _, _, _, message| {
+            let message = String::from_utf8_lossy(message).into_owned();
+            sink.lock().unwrap().push((level, message));
+        });
+        (cb, received)
+    }
+
+    fn messages(received: &Mutex<Vec<(Level, String)>>) -> Vec<String> {
+        let received = received.lock().unwrap();
+        received.iter().map(|(_, m)| m.clone()).collect()
+    }
+
+    #[test]
+    fn token_bucket() {
+        let limiter = RateLimiter::new();
+        limiter.reset(1_000, 4);
+
+        // A full bucket allows a burst, then drops messages until it is refilled.
+        for _ in 0..4 {
+            assert_eq!(limiter.admit(1_000, 4), (true, 0));
+        }
+        assert_eq!(limiter.admit(1_000, 4), (false, 0));
+        assert_eq!(limiter.admit(1_200, 4), (false, 0));
+        // One token is added every 250ms, and the drops are reported on refill.
+        assert_eq!(limiter.admit(1_250, 4), (true, 2));
+        assert_eq!(limiter.admit(1_250, 4), (false, 0));
+        // Partial progress towards the next token is kept across refills.
+        assert_eq!(limiter.admit(1_400, 4), (false, 0));
+        assert_eq!(limiter.admit(1_500, 4), (true, 2));
+
+        // After a burst, messages are only admitted at the refill rate.
+        limiter.reset(10_000, 4);
+        let admitted = (0..1_000_u64)
+            .filter(|ms| limiter.admit(10_000 + ms, 4).0)
+            .count();
+        assert_eq!(admitted, 4 + 3);
+        // The bucket never holds more than `rate` tokens, however long it was idle.
+        let admitted = (0..100_u64).filter(|_| limiter.admit(60_000, 4).0).count();
+        assert_eq!(admitted, 4);
+
+        // A zero rate drops everything.
+        limiter.reset(0, 0);
+        assert!(!limiter.admit(5_000, 0).0);
+        assert_eq!(limiter.take_dropped(), 1);
+    }
+
+    #[test]
+    fn filtering_and_rate_limiting() {
+        let (cb, received) = recorder();
+        let options = LogOptions {
+            max_messages_per_second: Some(3),
+            file_filter: Some("interesting".to_string()),
+        };
+        let limiter = RateLimiter::new();
+        limiter.reset(0, 3);
+        let emit = |now, file, message: &str| {
+            forward(
+                &cb,
+                &options,
+                &limiter,
+                now,
+                Level::Debug,
+                file,
+                c"function",
+                42,
+                message.as_bytes(),
+            );
+        };
+
+        for i in 0..10 {
+            emit(0, c"src/boring.cpp", &format!("boring {i}"));
+            emit(0, c"src/interesting.cpp", &format!("interesting {i}"));
+        }
+        assert_eq!(
+            messages(&received),
+            ["interesting 0", "interesting 1", "interesting 2"]
+        );
+
+        emit(1_000, c"src/interesting.cpp", "after burst");
+        assert_eq!(
+            received.lock().unwrap()[3..],
+            [
+                (Level::Warn, "dropped 7 messages".to_string()),
+                (Level::Debug, "after burst".to_string()),
+            ]
+        );
+
+        // Drops that are not followed by a refill are reported when the callback is reset.
+        for _ in 0..3 {
+            emit(1_000, c"src/interesting.cpp", "last");
+        }
+        report_dropped(&cb, limiter.take_dropped());
+        assert_eq!(
+            messages(&received)[5..],
+            ["last", "last", "dropped 1 messages"]
+        );
+    }
+}
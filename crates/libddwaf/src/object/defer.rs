@@ -0,0 +1,75 @@
+use std::any::Any;
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+
+/// The maximum number of values waiting to be dropped by the drop thread; values deferred while
+/// it is full are dropped inline instead.
+const DEFERRED_DROP_CAPACITY: usize = 1024;
+
+type Deferred = Box<dyn Any + Send>;
+
+/// The sending end of the drop thread's queue, or [`None`] if the thread could not be spawned.
+static DROP_QUEUE: OnceLock<Option<SyncSender<Deferred>>> = OnceLock::new();
+
+/// The number of values sent to, and dropped by, the drop thread.
+static DROP_COUNTS: Mutex<(u64, u64)> = Mutex::new((0, 0));
+static DROP_PROGRESS: Condvar = Condvar::new();
+
+/// Drops `value` on a dedicated background thread, so that releasing large values (such as
+/// [`WafObject`](crate::object::WafObject)s holding a big request body, or a
+/// [`Context`](crate::Context)) does not take time on the calling thread.
+///
+/// The drop thread is spawned on first use. If it cannot be spawned, or if too many values are
+/// already waiting to be dropped, `value` is dropped inline instead.
+///
+/// This is safe for all the object types of this crate, including [`WafOwned`](super::WafOwned)
+/// values: each object exclusively owns its contents, and both the Rust allocator and
+/// `libddwaf`'s default allocator may release memory from any thread.
+pub fn defer_drop<T: Send + 'static>(value: T) {
+    let queue = DROP_QUEUE.get_or_init(|| {
+        let (sender, receiver) = sync_channel::<Deferred>(DEFERRED_DROP_CAPACITY);
+        std::thread::Builder::new()
+            .name("libddwaf-drop".to_string())
+            .spawn(move || {
+                for value in receiver {
+                    drop(value);
+                    let mut counts = lock_counts();
+                    counts.1 += 1;
+                    DROP_PROGRESS.notify_all();
+                }
+            })
+            .ok()
+            .map(|_| sender)
+    });
+    let Some(queue) = queue else {
+        drop(value);
+        return;
+    };
+
+    // The count is updated before sending, so that it covers the value before it is dropped.
+    let mut counts = lock_counts();
+    counts.0 += 1;
+    drop(counts);
+    if let Err(TrySendError::Full(value) | TrySendError::Disconnected(value)) =
+        queue.try_send(Box::new(value))
+    {
+        lock_counts().0 -= 1;
+        drop(value);
+    }
+}
+
+/// Blocks until all values passed to [`defer_drop`] before this call have been dropped.
+pub fn flush_deferred_drops() {
+    let mut counts = lock_counts();
+    let target = counts.0;
+    // Values that ended up being dropped inline are no longer counted as sent.
+    while counts.1 < target.min(counts.0) {
+        counts = DROP_PROGRESS
+            .wait(counts)
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+fn lock_counts() -> MutexGuard<'static, (u64, u64)> {
+    DROP_COUNTS.lock().unwrap_or_else(PoisonError::into_inner)
+}
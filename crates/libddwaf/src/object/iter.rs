@@ -1,21 +1,14 @@
-use std::alloc::Layout;
-
-use crate::object::{Keyed, WafArray, WafMap, WafObject};
+use crate::object::{array_layout, Keyed, WafArray, WafMap, WafObject};
 
 impl IntoIterator for WafArray {
     type Item = WafObject;
     type IntoIter = WafIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
-        let array: *mut Self::Item = unsafe { self.raw.via.array.ptr.cast() };
-        let len = if array.is_null() {
-            0
-        } else {
-            self.len() as usize
-        };
+        let raw = unsafe { self.raw.via.array };
         // Forget about self, since the iterator is now the owner of the memory.
         std::mem::forget(self);
-        WafIter { array, len, pos: 0 }
+        WafIter::new(raw.ptr.cast(), raw.size, raw.capacity)
     }
 }
 
@@ -34,15 +27,10 @@ impl IntoIterator for WafMap {
     type IntoIter = WafIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
-        let array: *mut Keyed<WafObject> = unsafe { self.raw.via.map.ptr.cast() };
-        let len = if array.is_null() {
-            0
-        } else {
-            self.len() as usize
-        };
+        let raw = unsafe { self.raw.via.map };
         // Forget about self, since the iterator is now the owner of the memory.
         std::mem::forget(self);
-        WafIter { array, len, pos: 0 }
+        WafIter::new(raw.ptr.cast(), raw.size, raw.capacity)
     }
 }
 
@@ -57,10 +45,34 @@ impl IntoIterator for Keyed<WafMap> {
 }
 
 /// An iterator over an [`WafArray`] or [`WafMap`].
+///
+/// The iterator owns the storage of the container: it is released once the iterator is dropped,
+/// along with the elements that were not consumed.
 pub struct WafIter<T> {
     array: *mut T,
     len: usize,
     pos: usize,
+    /// The number of elements the storage was allocated for, which may be greater than `len` (for
+    /// example after [`WafArray::truncate`]).
+    capacity: usize,
+}
+impl<T> WafIter<T> {
+    fn new(array: *mut T, size: u16, capacity: u16) -> Self {
+        if array.is_null() || capacity == 0 {
+            return Self {
+                array: std::ptr::null_mut(),
+                len: 0,
+                pos: 0,
+                capacity: 0,
+            };
+        }
+        Self {
+            array,
+            len: usize::from(size),
+            pos: 0,
+            capacity: usize::from(capacity),
+        }
+    }
 }
 impl<T: Default> Iterator for WafIter<T> {
     type Item = T;
@@ -82,10 +94,11 @@ impl<T> Drop for WafIter<T> {
             let elem = unsafe { self.array.add(i) };
             unsafe { elem.drop_in_place() };
         }
-        if self.len != 0 {
-            // Finally, drop the array itself.
-            let layout = Layout::array::<T>(self.len).unwrap();
-            unsafe { std::alloc::dealloc(self.array.cast(), layout) }
+        if self.capacity != 0 {
+            // Finally, drop the array itself, which was allocated for its full capacity.
+            let layout = array_layout::<T>(self.capacity);
+            unsafe { std::alloc::dealloc(self.array.cast(), layout) };
+            track!(RUST_ALLOCATIONS, -1);
         }
     }
 }
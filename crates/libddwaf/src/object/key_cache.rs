@@ -0,0 +1,124 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
+
+use crate::object::{Keyed, WafObject, WafString, SMALL_STRING_SIZE};
+
+/// A dictionary of map keys that are used over and over again when building
+/// [`WafMap`](crate::object::WafMap)s, such as the addresses and header names submitted with each
+/// request.
+///
+/// The first time a key is requested, a copy of it is stored in the cache; the keys it hands out
+/// then point at that copy (like [`Keyed::with_static_key`] does for static strings), so that
+/// building an entry with a cached key does not allocate. Keys short enough to be stored inline
+/// never allocate, and are not cached.
+///
+/// Since the keys handed out are not tracked by the objects referencing them, the cache must live
+/// for the rest of the program, and its keys are never released: its methods require a `'static`
+/// reference, typically to a `static` item. To bound its memory usage, a [`KeyCache`] holds at most
+/// [`KeyCache::DEFAULT_LIMIT`] keys (or the limit given to [`KeyCache::with_limit`]); further keys
+/// are copied into each entry, as if no cache was used.
+///
+/// ```rust
+/// use libddwaf::object::{KeyCache, WafMap};
+///
+/// static KEYS: KeyCache = KeyCache::new();
+///
+/// let mut map = WafMap::new(0);
+/// map.insert_cached(&KEYS, "server.request.method", "GET");
+/// map.insert_cached(&KEYS, "server.request.uri.raw", "/");
+/// assert_eq!(map.get_str("server.request.method").and_then(|e| e.to_str()), Some("GET"));
+/// assert_eq!(KEYS.len(), 2);
+/// ```
+pub struct KeyCache {
+    keys: RwLock<BTreeSet<Box<str>>>,
+    limit: usize,
+}
+impl KeyCache {
+    /// The number of keys a [`KeyCache`] created with [`KeyCache::new`] holds at most.
+    pub const DEFAULT_LIMIT: usize = 4096;
+
+    /// Creates a new, empty [`KeyCache`] holding at most [`KeyCache::DEFAULT_LIMIT`] keys.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_limit(Self::DEFAULT_LIMIT)
+    }
+
+    /// Creates a new, empty [`KeyCache`] holding at most `limit` keys.
+    #[must_use]
+    pub const fn with_limit(limit: usize) -> Self {
+        Self {
+            keys: RwLock::new(BTreeSet::new()),
+            limit,
+        }
+    }
+
+    /// Returns the number of distinct keys held by this [`KeyCache`].
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Returns `true` if this [`KeyCache`] does not hold any key yet.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `key` encoded as a [`WafString`] pointing at the copy held by this [`KeyCache`],
+    /// adding it to the cache if it was not already there.
+    ///
+    /// Short keys, and keys that do not fit in the cache anymore, are copied instead.
+    #[must_use]
+    pub fn key(&'static self, key: &str) -> WafString {
+        if key.len() <= SMALL_STRING_SIZE {
+            return WafString::from(key);
+        }
+        if let Some(cached) = self.read().get(key) {
+            return Self::literal(cached);
+        }
+
+        let mut keys = self.keys.write().unwrap_or_else(PoisonError::into_inner);
+        if !keys.contains(key) {
+            if keys.len() >= self.limit {
+                return WafString::from(key);
+            }
+            keys.insert(key.into());
+        }
+        match keys.get(key) {
+            Some(cached) => Self::literal(cached),
+            None => WafString::from(key),
+        }
+    }
+
+    /// Creates a new [`Keyed`] with the provided value, and the cached `key` (see
+    /// [`KeyCache::key`]).
+    #[must_use]
+    pub fn keyed(&'static self, key: &str, value: impl Into<WafObject>) -> Keyed<WafObject> {
+        Keyed::new(self.key(key), value.into())
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, BTreeSet<Box<str>>> {
+        self.keys.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn literal(cached: &str) -> WafString {
+        // SAFETY: the contents of the boxed keys do not move when the set is modified, and keys
+        // are never removed from a cache, which lives for the rest of the program.
+        let cached: &'static str = unsafe { &*std::ptr::from_ref(cached) };
+        WafString::new_literal(cached.as_bytes())
+    }
+}
+impl Default for KeyCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl fmt::Debug for KeyCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyCache")
+            .field("len", &self.len())
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+
+use crate::object::{WafArray, WafObject, WafView};
+use crate::LengthError;
+
+/// A builder for [`WafArray`]s that stages homogeneous scalar values compactly, and only lays out
+/// the [`WafObject`]s expected by `libddwaf` when [`LazyWafArray::materialize`] is called.
+///
+/// While all the values pushed are unsigned integers, signed integers, floats or booleans (and all
+/// of the same type), they are stored in a plain [`Vec`] of that type, which takes half (or, for
+/// booleans, a sixteenth) of the memory a [`WafObject`] does. As soon as a value of another type is
+/// pushed, the staged values are converted into [`WafObject`]s, and the builder behaves like a
+/// [`Vec<WafObject>`].
+///
+/// Strings are always staged as [`WafObject`]s: short strings are stored inline, and staging
+/// longer ones separately would require copying them again when materializing the array.
+///
+/// ```rust
+/// use libddwaf::object::{LazyWafArray, WafView};
+/// use libddwaf::waf_array;
+///
+/// let mut ports = LazyWafArray::new();
+/// for port in [80_u64, 443, 8080] {
+///     ports.push(port);
+/// }
+/// assert!(ports.is_compact());
+/// assert_eq!(ports.get(1), Some(WafView::Unsigned(443)));
+/// assert_eq!(ports.materialize().unwrap(), waf_array![80_u64, 443_u64, 8080_u64]);
+/// ```
+#[derive(Default)]
+pub struct LazyWafArray {
+    items: Items,
+    /// The capacity requested by [`LazyWafArray::with_capacity`], reserved once the type of the
+    /// staged values is known.
+    capacity: usize,
+}
+
+enum Items {
+    Unsigned(Vec<u64>),
+    Signed(Vec<i64>),
+    Float(Vec<f64>),
+    Bool(Vec<bool>),
+    Objects(Vec<WafObject>),
+}
+impl Items {
+    fn len(&self) -> usize {
+        match self {
+            Self::Unsigned(values) => values.len(),
+            Self::Signed(values) => values.len(),
+            Self::Float(values) => values.len(),
+            Self::Bool(values) => values.len(),
+            Self::Objects(values) => values.len(),
+        }
+    }
+
+    /// Calls `f` with each of the values, converted into a [`WafObject`], in order.
+    fn for_each(self, f: impl FnMut(WafObject)) {
+        match self {
+            Self::Unsigned(values) => values.into_iter().map(WafObject::from).for_each(f),
+            Self::Signed(values) => values.into_iter().map(WafObject::from).for_each(f),
+            Self::Float(values) => values.into_iter().map(WafObject::from).for_each(f),
+            Self::Bool(values) => values.into_iter().map(WafObject::from).for_each(f),
+            Self::Objects(values) => values.into_iter().for_each(f),
+        }
+    }
+}
+impl Default for Items {
+    fn default() -> Self {
+        Self::Objects(Vec::new())
+    }
+}
+
+/// A scalar value that can be staged compactly.
+#[derive(Clone, Copy)]
+enum Scalar {
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    Bool(bool),
+}
+impl Scalar {
+    fn of(obj: &WafObject) -> Option<Self> {
+        match obj.view() {
+            WafView::Unsigned(value) => Some(Self::Unsigned(value)),
+            WafView::Signed(value) => Some(Self::Signed(value)),
+            WafView::Float(value) => Some(Self::Float(value)),
+            WafView::Bool(value) => Some(Self::Bool(value)),
+            _ => None,
+        }
+    }
+}
+
+impl LazyWafArray {
+    /// Creates a new, empty [`LazyWafArray`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, empty [`LazyWafArray`] with room for at least `capacity` values.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: Items::default(),
+            capacity,
+        }
+    }
+
+    /// Returns the number of values in this [`LazyWafArray`].
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if this [`LazyWafArray`] contains no values.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the values of this [`LazyWafArray`] are currently staged compactly, which
+    /// requires all the values pushed so far to be scalars of the same type.
+    #[must_use]
+    pub fn is_compact(&self) -> bool {
+        !matches!(self.items, Items::Objects(_))
+    }
+
+    /// Appends a value to this [`LazyWafArray`].
+    pub fn push(&mut self, value: impl Into<WafObject>) {
+        let value = value.into();
+        let Some(scalar) = Scalar::of(&value) else {
+            self.push_object(value);
+            return;
+        };
+        match (&mut self.items, scalar) {
+            (Items::Unsigned(values), Scalar::Unsigned(value)) => values.push(value),
+            (Items::Signed(values), Scalar::Signed(value)) => values.push(value),
+            (Items::Float(values), Scalar::Float(value)) => values.push(value),
+            (Items::Bool(values), Scalar::Bool(value)) => values.push(value),
+            (Items::Objects(values), scalar) if values.is_empty() => {
+                let capacity = self.capacity.max(1);
+                self.items = match scalar {
+                    Scalar::Unsigned(value) => Items::Unsigned(staged(capacity, value)),
+                    Scalar::Signed(value) => Items::Signed(staged(capacity, value)),
+                    Scalar::Float(value) => Items::Float(staged(capacity, value)),
+                    Scalar::Bool(value) => Items::Bool(staged(capacity, value)),
+                };
+            }
+            _ => self.push_object(value),
+        }
+    }
+
+    /// Returns a view of the value at the provided index, or [`None`] if it is out of bounds.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<WafView<'_>> {
+        match &self.items {
+            Items::Unsigned(values) => values.get(index).copied().map(WafView::Unsigned),
+            Items::Signed(values) => values.get(index).copied().map(WafView::Signed),
+            Items::Float(values) => values.get(index).copied().map(WafView::Float),
+            Items::Bool(values) => values.get(index).copied().map(WafView::Bool),
+            Items::Objects(values) => values.get(index).map(WafObject::view),
+        }
+    }
+
+    /// Returns an iterator over views of the values of this [`LazyWafArray`].
+    pub fn iter(&self) -> impl Iterator<Item = WafView<'_>> + '_ {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+
+    /// Shortens this [`LazyWafArray`], keeping its first `len` values and dropping the rest. This
+    /// has no effect if it already has `len` values or fewer.
+    pub fn truncate(&mut self, len: usize) {
+        match &mut self.items {
+            Items::Unsigned(values) => values.truncate(len),
+            Items::Signed(values) => values.truncate(len),
+            Items::Float(values) => values.truncate(len),
+            Items::Bool(values) => values.truncate(len),
+            Items::Objects(values) => values.truncate(len),
+        }
+    }
+
+    /// Lays out the values of this [`LazyWafArray`] into a new [`WafArray`], with a single
+    /// allocation.
+    ///
+    /// # Errors
+    /// Returns an error if there are more than [`u16::MAX`] values.
+    pub fn materialize(self) -> Result<WafArray, LengthError> {
+        let size = LengthError::checked_len("array", self.len())?;
+        let mut array = WafArray::new(size);
+        let mut slots = array.iter_mut();
+        self.items.for_each(|value| {
+            if let Some(slot) = slots.next() {
+                *slot = value;
+            }
+        });
+        Ok(array)
+    }
+
+    /// Appends a value that cannot be staged compactly, converting the staged scalars into
+    /// [`WafObject`]s first if needed.
+    fn push_object(&mut self, value: WafObject) {
+        if let Items::Objects(objects) = &mut self.items {
+            objects.push(value);
+            return;
+        }
+        let staged = std::mem::take(&mut self.items);
+        let mut objects = Vec::with_capacity(self.capacity.max(staged.len() + 1));
+        staged.for_each(|value| objects.push(value));
+        objects.push(value);
+        self.items = Items::Objects(objects);
+    }
+}
+impl fmt::Debug for LazyWafArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+fn staged<T>(capacity: usize, first: T) -> Vec<T> {
+    let mut values = Vec::with_capacity(capacity);
+    values.push(first);
+    values
+}
@@ -1,19 +1,41 @@
 #![doc = "Data model for exchanging data with the in-app WAF."]
+#![deny(clippy::unwrap_used, clippy::expect_used)]
 
 use std::alloc::Layout;
+use std::collections::HashMap;
 use std::mem::ManuallyDrop;
 use std::ops::{Deref, DerefMut, Index, IndexMut};
 use std::ptr::null_mut;
 use std::sync::OnceLock;
 use std::{cmp, fmt};
 
+use crate::{Length, LengthError};
+
+mod defer;
 mod iter;
+mod key_cache;
+mod lazy_array;
+pub mod raw;
+#[cfg(feature = "leak-check")]
+pub mod stats;
+mod visit;
+#[doc(inline)]
+pub use defer::*;
 #[doc(inline)]
 pub use iter::*;
+#[doc(inline)]
+pub use key_cache::*;
+#[doc(inline)]
+pub use lazy_array::*;
+#[doc(inline)]
+pub use visit::*;
+// Kept at its historical location for compatibility; new code should use `raw::AsRawMutObject`.
+#[doc(hidden)]
+pub use raw::AsRawMutObject;
 
 /// Identifies the type of the value stored in a [`WafObject`].
 #[non_exhaustive]
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum WafObjectType {
     /// An invalid value. This can be used as a placeholder to retain the key
     /// associated with an object that was only partially encoded.
@@ -100,7 +122,9 @@ impl std::fmt::Display for ObjectTypeError {
 
 /// The error that is returned when a value's length exceeds the maximum allowed.
 ///
-/// This applies to strings (max [`u32::MAX`]) and arrays/maps (max [`u16::MAX`]).
+/// This applies to strings (at most [`MAX_STRING_BYTES`]) and arrays/maps (at most
+/// [`MAX_CONTAINER_ENTRIES`]).
+#[deprecated(note = "use `LengthError`, which all the APIs of this crate now return")]
 #[derive(Copy, Clone, Debug)]
 pub struct LengthTooLargeError {
     /// The length that was too large.
@@ -108,7 +132,9 @@ pub struct LengthTooLargeError {
     /// The maximum allowed length.
     pub max_length: usize,
 }
+#[allow(deprecated)]
 impl std::error::Error for LengthTooLargeError {}
+#[allow(deprecated)]
 impl std::fmt::Display for LengthTooLargeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -118,27 +144,104 @@ impl std::fmt::Display for LengthTooLargeError {
         )
     }
 }
+#[allow(deprecated)]
+impl From<LengthTooLargeError> for LengthError {
+    fn from(value: LengthTooLargeError) -> Self {
+        Self {
+            what: "value",
+            len: value.length,
+            max: value.max_length,
+        }
+    }
+}
 
-/// This trait allow obtaining direct mutable access to the underlying memory
-/// backing a [`WafObject`] or [`TypedWafObject`] value.
-#[doc(hidden)]
-pub trait AsRawMutObject: crate::private::Sealed + AsRef<libddwaf_sys::ddwaf_object> {
-    /// Obtains a mutable reference to the underlying raw [`libddwaf_sys::ddwaf_object`].
-    ///
-    /// # Safety
-    /// The caller must ensure that:
-    /// - it does not change the [`libddwaf_sys::ddwaf_object::type_`] field,
-    /// - it does not change the pointers to values that don't outlive the [`libddwaf_sys::ddwaf_object`]
-    ///   itself, or whose memory cannot be recclaimed byt the destructor in the same way as the
-    ///   current value,
-    /// - it does not change the lengths in such a way that the object is no longer valid.
-    ///
-    /// Additionally, the caller would incur a memory leak if it dropped the value through the
-    /// returned reference (e.g, by calling [`std::mem::replace`]), since [`libddwaf_sys::ddwaf_object`] is
-    /// not [`Drop`] (see swapped destructors in
-    /// [this playground](https://play.rust-lang.org/?version=stable&mode=debug&edition=2021&gist=aeea4aba8f960bf0c63f6185f016a94d).
-    #[doc(hidden)]
-    unsafe fn as_raw_mut(&mut self) -> &mut libddwaf_sys::ddwaf_object;
+/// The error that is returned by [`WafObject::try_from_json`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FromJsonError {
+    /// The JSON document is larger than [`u32::MAX`] bytes.
+    TooLarge {
+        /// The length of the JSON document, in bytes.
+        len: usize,
+    },
+    /// The JSON document could not be parsed.
+    Invalid,
+    /// The `libddwaf` release this crate was built against does not support parsing JSON.
+    Unsupported,
+}
+impl std::error::Error for FromJsonError {}
+impl std::fmt::Display for FromJsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLarge { len } => write!(
+                f,
+                "JSON document is too large ({len} bytes, at most {} are supported)",
+                u32::MAX
+            ),
+            Self::Invalid => write!(f, "Invalid JSON document"),
+            Self::Unsupported => write!(
+                f,
+                "JSON parsing is not supported by the libddwaf release in use"
+            ),
+        }
+    }
+}
+
+/// The error that is returned by [`WafString::from_base64`] and [`WafString::from_hex`].
+#[cfg(feature = "encoding")]
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input is not validly encoded.
+    Invalid,
+    /// The decoded data is larger than [`u32::MAX`] bytes.
+    TooLarge {
+        /// The length of the decoded data, in bytes.
+        len: usize,
+    },
+}
+#[cfg(feature = "encoding")]
+impl std::error::Error for DecodeError {}
+#[cfg(feature = "encoding")]
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid => write!(f, "Invalid encoded data"),
+            Self::TooLarge { len } => write!(
+                f,
+                "Decoded data is too large ({len} bytes, at most {} are supported)",
+                u32::MAX
+            ),
+        }
+    }
+}
+
+/// The error that is returned by [`WafMap::set_path`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SetPathError {
+    /// The path is empty.
+    EmptyPath,
+    /// A value along the path (other than the last one) exists and is not a map.
+    NotAMap {
+        /// The number of path segments leading to the offending value.
+        depth: usize,
+        /// The type of the offending value.
+        actual: WafObjectType,
+    },
+}
+impl std::error::Error for SetPathError {}
+impl std::fmt::Display for SetPathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyPath => write!(f, "Empty path"),
+            Self::NotAMap { depth, actual } => write!(
+                f,
+                "Invalid object type at path segment {depth} (expected {:?}, got {actual:?})",
+                WafObjectType::Map
+            ),
+        }
+    }
 }
 
 /// This trait is implemented by type-safe interfaces to the [`WafObject`], with
@@ -167,12 +270,30 @@ impl WafObject {
     /// # Returns
     /// Returns [`None`] if parsing the JSON string into a [`WafObject`] was not
     /// possible, or if the input JSON string is larger than [`u32::MAX`] bytes.
+    /// See [`WafObject::try_from_json`] to distinguish these cases.
     pub fn from_json(json: impl AsRef<[u8]>) -> Option<WafOwnedOutputAllocator<Self>> {
-        let mut output = WafOwnedOutputAllocator::<Self>::default();
+        Self::try_from_json(json).ok()
+    }
+
+    /// Creates a new [`WafObject`] from a JSON string, like [`WafObject::from_json`].
+    ///
+    /// # Errors
+    /// Returns [`FromJsonError::TooLarge`] if the input JSON string is larger than [`u32::MAX`]
+    /// bytes, and [`FromJsonError::Invalid`] if it could not be parsed. Returns
+    /// [`FromJsonError::Unsupported`] if the `libddwaf` release this crate was built against does
+    /// not provide a JSON parser.
+    pub fn try_from_json(
+        json: impl AsRef<[u8]>,
+    ) -> Result<WafOwnedOutputAllocator<Self>, FromJsonError> {
         let data = json.as_ref();
-        let Ok(len) = u32::try_from(data.len()) else {
-            return None;
-        };
+        let len = LengthError::checked_len("JSON document", data.len())
+            .map_err(|e| FromJsonError::TooLarge { len: e.len })?;
+        Self::parse_json(data, len)
+    }
+
+    #[cfg(ddwaf_has_from_json)]
+    fn parse_json(data: &[u8], len: u32) -> Result<WafOwnedOutputAllocator<Self>, FromJsonError> {
+        let mut output = WafOwnedOutputAllocator::<Self>::default();
         if !unsafe {
             let alloc = WafOwnedOutputAllocator::<Self>::allocator();
             libddwaf_sys::ddwaf_object_from_json(
@@ -182,9 +303,14 @@ impl WafObject {
                 alloc,
             )
         } {
-            return None;
+            return Err(FromJsonError::Invalid);
         }
-        Some(output)
+        Ok(output)
+    }
+
+    #[cfg(not(ddwaf_has_from_json))]
+    fn parse_json(_: &[u8], _: u32) -> Result<WafOwnedOutputAllocator<Self>, FromJsonError> {
+        Err(FromJsonError::Unsupported)
     }
 
     /// Returns the [`WafObjectType`] of the underlying value.
@@ -274,6 +400,333 @@ impl WafObject {
     pub fn to_str(&self) -> Option<&str> {
         self.as_type::<WafString>().and_then(|x| x.as_str().ok())
     }
+
+    /// Returns a [`WafView`] of this [`WafObject`], which allows pattern-matching on its value.
+    ///
+    /// String values are presented as [`WafView::Str`] when they are valid UTF-8, and as
+    /// [`WafView::Bytes`] otherwise.
+    #[must_use]
+    pub fn view(&self) -> WafView<'_> {
+        match self.object_type() {
+            WafObjectType::Invalid => WafView::Invalid,
+            WafObjectType::Null => WafView::Null,
+            WafObjectType::Bool => {
+                WafView::Bool(unsafe { self.as_type_unchecked::<WafBool>() }.value())
+            }
+            WafObjectType::Signed => {
+                WafView::Signed(unsafe { self.as_type_unchecked::<WafSigned>() }.value())
+            }
+            WafObjectType::Unsigned => {
+                WafView::Unsigned(unsafe { self.as_type_unchecked::<WafUnsigned>() }.value())
+            }
+            WafObjectType::Float => {
+                WafView::Float(unsafe { self.as_type_unchecked::<WafFloat>() }.value())
+            }
+            WafObjectType::String => {
+                let bytes = unsafe { self.as_type_unchecked::<WafString>() }.as_bytes();
+                match std::str::from_utf8(bytes) {
+                    Ok(s) => WafView::Str(s),
+                    Err(_) => WafView::Bytes(bytes),
+                }
+            }
+            WafObjectType::Array => WafView::Array(unsafe { self.as_type_unchecked() }),
+            WafObjectType::Map => WafView::Map(unsafe { self.as_type_unchecked() }),
+        }
+    }
+
+    /// Returns statistics about the tree rooted at this [`WafObject`], such as its number of values
+    /// and its depth.
+    #[must_use]
+    pub fn stats(&self) -> WafObjectStats {
+        let mut stats = WafObjectStats::default();
+        let _ = walk(self, &mut stats);
+        stats
+    }
+
+    /// Calls `f` with each value of the tree rooted at this [`WafObject`] that is neither a
+    /// [`WafArray`] nor a [`WafMap`], in depth-first order (see [`walk_mut`]).
+    ///
+    /// The values may be modified or replaced in place; values replaced with a [`WafArray`] or a
+    /// [`WafMap`] are not walked.
+    pub fn for_each_scalar_mut(&mut self, f: impl FnMut(&mut WafObject)) {
+        struct Scalars<F>(F);
+        impl<F: FnMut(&mut WafObject)> WafVisitorMut for Scalars<F> {
+            fn visit_scalar_mut(
+                &mut self,
+                _: &WafPath<'_>,
+                obj: &mut WafObject,
+            ) -> std::ops::ControlFlow<()> {
+                (self.0)(obj);
+                std::ops::ControlFlow::Continue(())
+            }
+        }
+
+        let _ = walk_mut(self, &mut Scalars(f));
+    }
+
+    /// Calls `f` with each [`WafString`] of the tree rooted at this [`WafObject`], in depth-first
+    /// order, allowing them to be modified in place. Map keys are not visited.
+    ///
+    /// ```rust
+    /// use libddwaf::object::WafObject;
+    /// use libddwaf::{waf_array, waf_map};
+    ///
+    /// let mut obj: WafObject = waf_map!(("user", "alice"), ("tags", waf_array!["a", 1u64])).into();
+    /// obj.for_each_string_mut(|string| {
+    ///     let upper = string.as_bytes().to_ascii_uppercase();
+    ///     string.set(upper);
+    /// });
+    /// assert_eq!(obj, waf_map!(("user", "ALICE"), ("tags", waf_array!["A", 1u64])));
+    /// ```
+    pub fn for_each_string_mut(&mut self, mut f: impl FnMut(&mut WafString)) {
+        self.for_each_scalar_mut(|obj| {
+            if let Some(string) = obj.as_type_mut::<WafString>() {
+                f(string);
+            }
+        });
+    }
+
+    /// Returns the number of direct children of this [`WafObject`]: the number of items of an
+    /// array, the number of entries of a map, and `0` for any other type.
+    ///
+    /// Strings are not containers and also have no children; use [`WafString::len`] to obtain
+    /// their length.
+    #[must_use]
+    pub fn child_count(&self) -> usize {
+        match self.object_type() {
+            WafObjectType::Array => {
+                usize::from(unsafe { self.as_type_unchecked::<WafArray>() }.len())
+            }
+            WafObjectType::Map => usize::from(unsafe { self.as_type_unchecked::<WafMap>() }.len()),
+            _ => 0,
+        }
+    }
+
+    /// Returns the structural differences between this [`WafObject`] and `other`, which is empty
+    /// if and only if both are equal.
+    ///
+    /// Arrays are compared item by item, and maps entry by entry, pairing entries with the same
+    /// key in order of appearance. Map entries only present on one side are reported as such,
+    /// and maps that only differ by the order of their entries are reported as a single
+    /// difference. Any other difference is reported at the deepest path where it occurs.
+    #[must_use]
+    pub fn diff<'a>(&'a self, other: &'a WafObject) -> Vec<WafDifference<'a>> {
+        fn collect<'a>(
+            left: &'a WafObject,
+            right: &'a WafObject,
+            path: &mut String,
+            out: &mut Vec<WafDifference<'a>>,
+        ) {
+            if left == right {
+                return;
+            }
+            let len = path.len();
+            match (left.view(), right.view()) {
+                (WafView::Array(l), WafView::Array(r)) => {
+                    let (mut l, mut r) = (l.iter(), r.iter());
+                    for i in 0.. {
+                        let (l, r) = match (l.next(), r.next()) {
+                            (None, None) => break,
+                            pair => pair,
+                        };
+                        path.push('[');
+                        path.push_str(&i.to_string());
+                        path.push(']');
+                        match (l, r) {
+                            (Some(l), Some(r)) => collect(l, r, path, out),
+                            (l, r) => out.push(WafDifference::new(path, l, r)),
+                        }
+                        path.truncate(len);
+                    }
+                }
+                (WafView::Map(l), WafView::Map(r)) => {
+                    let found = out.len();
+                    let mut paired = vec![false; usize::from(r.len())];
+                    for entry in l.iter() {
+                        let key = entry.key_bytes().unwrap_or_default();
+                        path.push('.');
+                        path.push_str(&String::from_utf8_lossy(key));
+                        let other = r.iter().enumerate().find(|(i, other)| {
+                            !paired[*i] && other.key_bytes().unwrap_or_default() == key
+                        });
+                        if let Some((i, other)) = other {
+                            paired[i] = true;
+                            collect(entry, other, path, out);
+                        } else {
+                            out.push(WafDifference::new(path, Some(entry), None));
+                        }
+                        path.truncate(len);
+                    }
+                    for (entry, _) in r.iter().zip(paired).filter(|(_, paired)| !paired) {
+                        path.push('.');
+                        path.push_str(&String::from_utf8_lossy(
+                            entry.key_bytes().unwrap_or_default(),
+                        ));
+                        out.push(WafDifference::new(path, None, Some(entry)));
+                        path.truncate(len);
+                    }
+                    if out.len() == found {
+                        // All entries are equal, but appear in a different order
+                        out.push(WafDifference::new(path, Some(left), Some(right)));
+                    }
+                }
+                _ => out.push(WafDifference::new(path, Some(left), Some(right))),
+            }
+        }
+
+        let mut out = Vec::new();
+        collect(self, other, &mut String::from("$"), &mut out);
+        out
+    }
+
+    /// Returns true if this [`WafObject`] is equal to `other`, considering that a map entry whose
+    /// value is null is equal to no entry at all, for schemas in which an absent key is equivalent
+    /// to an explicit `null`.
+    ///
+    /// This only applies to the values of map entries, at any nesting level: `{"a": null}` equals
+    /// `{}`, but `[null]` does not equal `[]`, and a top-level null does not equal anything but
+    /// null. Since entries may be missing on either side, maps are compared by looking up each
+    /// entry's key on the other side (each key being matched to the first entry holding it), so
+    /// that the order of their entries does not matter. Other values are compared as with
+    /// [`PartialEq`].
+    ///
+    /// A null entry only matches a missing one: `{"a": null}` does not equal `{"a": {}}` nor
+    /// `{"a": ""}`. Objects that are equal in this sense may not be equal according to
+    /// [`PartialEq`], and may have a different [`WafObject::content_hash`].
+    ///
+    /// ```rust
+    /// use libddwaf::object::{WafNull, WafObject};
+    /// use libddwaf::waf_map;
+    ///
+    /// let explicit: WafObject = waf_map!(("id", 42_u64), ("name", WafNull::new())).into();
+    /// let absent: WafObject = waf_map!(("id", 42_u64)).into();
+    /// assert!(explicit.eq_null_coalescing(&absent));
+    /// assert_ne!(explicit, absent);
+    /// ```
+    #[must_use]
+    pub fn eq_null_coalescing(&self, other: &WafObject) -> bool {
+        /// Returns true if each entry of `map` is null or has an equal counterpart in `other`.
+        fn covers(map: &WafMap, other: &WafMap) -> bool {
+            map.iter().all(|entry| match other.get(entry.key()) {
+                Some(counterpart) => entry.eq_null_coalescing(counterpart),
+                None => entry.object_type() == WafObjectType::Null,
+            })
+        }
+
+        match (self.view(), other.view()) {
+            (WafView::Array(left), WafView::Array(right)) => {
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|(left, right)| left.eq_null_coalescing(right))
+            }
+            (WafView::Map(left), WafView::Map(right)) => covers(left, right) && covers(right, left),
+            _ => self == other,
+        }
+    }
+
+    /// Returns a hash of the content of this [`WafObject`], suitable for content-addressing (for
+    /// example, as a cache key).
+    ///
+    /// Unlike hashes obtained through [`std::hash::Hash`], the result is stable: it is the same
+    /// across process runs, platforms and versions of this crate, as it is computed using 64-bit
+    /// FNV-1a over a canonical encoding of the value. In particular, it does not depend on how
+    /// strings are stored. Equal objects have the same hash; since map entries are compared in
+    /// order, maps with the same entries in a different order usually hash differently.
+    ///
+    /// This is not a cryptographic hash, and must not be relied upon when collisions could be
+    /// engineered by an adversary.
+    #[must_use]
+    pub fn content_hash(&self) -> u64 {
+        /// Type tags of the canonical encoding; these must never change.
+        const INVALID: u8 = 0;
+        const NULL: u8 = 1;
+        const BOOL: u8 = 2;
+        const SIGNED: u8 = 3;
+        const UNSIGNED: u8 = 4;
+        const FLOAT: u8 = 5;
+        const STRING: u8 = 6;
+        const ARRAY: u8 = 7;
+        const MAP: u8 = 8;
+
+        struct Fnv1a(u64);
+        impl Fnv1a {
+            fn write(&mut self, bytes: &[u8]) {
+                for &b in bytes {
+                    self.0 ^= u64::from(b);
+                    self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+                }
+            }
+        }
+
+        fn hash(obj: &WafObject, state: &mut Fnv1a) {
+            match obj.view() {
+                WafView::Null => state.write(&[NULL]),
+                WafView::Bool(v) => state.write(&[BOOL, u8::from(v)]),
+                WafView::Signed(v) => {
+                    state.write(&[SIGNED]);
+                    state.write(&v.to_le_bytes());
+                }
+                WafView::Unsigned(v) => {
+                    state.write(&[UNSIGNED]);
+                    state.write(&v.to_le_bytes());
+                }
+                WafView::Float(v) => {
+                    state.write(&[FLOAT]);
+                    state.write(&v.to_bits().to_le_bytes());
+                }
+                WafView::Str(v) => hash_bytes(v.as_bytes(), state),
+                WafView::Bytes(v) => hash_bytes(v, state),
+                WafView::Array(arr) => {
+                    state.write(&[ARRAY]);
+                    state.write(&arr.len().to_le_bytes());
+                    for item in arr.iter() {
+                        hash(item, state);
+                    }
+                }
+                WafView::Map(map) => {
+                    state.write(&[MAP]);
+                    state.write(&map.len().to_le_bytes());
+                    for entry in map.iter() {
+                        hash(entry.key(), state);
+                        hash(entry, state);
+                    }
+                }
+                WafView::Invalid => state.write(&[INVALID]),
+            }
+        }
+
+        fn hash_bytes(bytes: &[u8], state: &mut Fnv1a) {
+            state.write(&[STRING]);
+            state.write(&u64::try_from(bytes.len()).unwrap_or(u64::MAX).to_le_bytes());
+            state.write(bytes);
+        }
+
+        let mut state = Fnv1a(0xcbf2_9ce4_8422_2325);
+        hash(self, &mut state);
+        state.0
+    }
+
+    /// Returns a human-readable description of the differences between this [`WafObject`] and
+    /// `other`, with one line per difference reported by [`WafObject::diff`], or [`None`] if they
+    /// are equal.
+    ///
+    /// This is intended for test failure messages, where it is easier to read than the full
+    /// [`fmt::Debug`] representation of both objects.
+    #[must_use]
+    pub fn diff_string(&self, other: &WafObject) -> Option<String> {
+        let diff = self.diff(other);
+        if diff.is_empty() {
+            return None;
+        }
+        Some(
+            diff.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
 }
 impl AsRef<libddwaf_sys::ddwaf_object> for WafObject {
     fn as_ref(&self) -> &libddwaf_sys::ddwaf_object {
@@ -290,35 +743,35 @@ impl fmt::Debug for WafObject {
         match self.object_type() {
             WafObjectType::Invalid => write!(f, "WafInvalid"),
             WafObjectType::Unsigned => {
-                let obj: &WafUnsigned = self.as_type().unwrap();
+                let obj: &WafUnsigned = unsafe { self.as_type_unchecked() };
                 obj.fmt(f)
             }
             WafObjectType::Signed => {
-                let obj: &WafSigned = self.as_type().unwrap();
+                let obj: &WafSigned = unsafe { self.as_type_unchecked() };
                 obj.fmt(f)
             }
             WafObjectType::Float => {
-                let obj: &WafFloat = self.as_type().unwrap();
+                let obj: &WafFloat = unsafe { self.as_type_unchecked() };
                 obj.fmt(f)
             }
             WafObjectType::Bool => {
-                let obj: &WafBool = self.as_type().unwrap();
+                let obj: &WafBool = unsafe { self.as_type_unchecked() };
                 obj.fmt(f)
             }
             WafObjectType::Null => {
-                let obj: &WafNull = self.as_type().unwrap();
+                let obj: &WafNull = unsafe { self.as_type_unchecked() };
                 obj.fmt(f)
             }
             WafObjectType::String => {
-                let obj: &WafString = self.as_type().unwrap();
+                let obj: &WafString = unsafe { self.as_type_unchecked() };
                 obj.fmt(f)
             }
             WafObjectType::Array => {
-                let obj: &WafArray = self.as_type().unwrap();
+                let obj: &WafArray = unsafe { self.as_type_unchecked() };
                 obj.fmt(f)
             }
             WafObjectType::Map => {
-                let obj: &WafMap = self.as_type().unwrap();
+                let obj: &WafMap = unsafe { self.as_type_unchecked() };
                 obj.fmt(f)
             }
         }
@@ -396,6 +849,13 @@ impl From<f64> for WafObject {
         WafFloat::new(value).into()
     }
 }
+/// Widens the value to an [`f64`], which is exact: every [`f32`] value (including infinities) is
+/// represented identically.
+impl From<f32> for WafObject {
+    fn from(value: f32) -> Self {
+        WafFloat::from(value).into()
+    }
+}
 impl From<bool> for WafObject {
     fn from(value: bool) -> Self {
         WafBool::new(value).into()
@@ -411,6 +871,11 @@ impl From<&[u8]> for WafObject {
         WafString::from(value).into()
     }
 }
+impl From<String> for WafObject {
+    fn from(value: String) -> Self {
+        WafString::from(value).into()
+    }
+}
 impl From<()> for WafObject {
     fn from((): ()) -> Self {
         WafNull::new().into()
@@ -425,13 +890,116 @@ impl<T: TypedWafObject> From<T> for WafObject {
         res
     }
 }
+/// Compares the value of two objects. Map entries are compared in order, including their keys.
 impl<T: AsRef<libddwaf_sys::ddwaf_object>> cmp::PartialEq<T> for WafObject {
     fn eq(&self, other: &T) -> bool {
         self.raw == *other.as_ref()
     }
 }
+/// Compares this object with the value of a [`Keyed`] entry, ignoring its key.
+impl<T: AsRawMutObject> cmp::PartialEq<Keyed<T>> for WafObject {
+    fn eq(&self, other: &Keyed<T>) -> bool {
+        self.raw == other.raw.val
+    }
+}
 impl crate::private::Sealed for WafObject {}
 
+/// A borrowed view over the value of a [`WafObject`], obtained by calling [`WafObject::view`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WafView<'a> {
+    /// An invalid value.
+    Invalid,
+    /// The null value.
+    Null,
+    /// A boolean value.
+    Bool(bool),
+    /// A signed integer value.
+    Signed(i64),
+    /// An unsigned integer value.
+    Unsigned(u64),
+    /// A floating point value.
+    Float(f64),
+    /// A string value that is valid UTF-8.
+    Str(&'a str),
+    /// A string value that is not valid UTF-8.
+    Bytes(&'a [u8]),
+    /// An array value.
+    Array(&'a WafArray),
+    /// A map value.
+    Map(&'a WafMap),
+}
+
+/// A difference between two [`WafObject`]s, as reported by [`WafObject::diff`].
+#[derive(Clone, Debug)]
+pub struct WafDifference<'a> {
+    /// The path at which the difference occurs, starting with `$` for the compared objects
+    /// themselves, followed by `.key` for map entries and `[index]` for array items.
+    pub path: String,
+    /// The value on the left-hand side, or [`None`] if it is missing.
+    pub left: Option<&'a WafObject>,
+    /// The value on the right-hand side, or [`None`] if it is missing.
+    pub right: Option<&'a WafObject>,
+}
+impl<'a> WafDifference<'a> {
+    fn new(path: &str, left: Option<&'a WafObject>, right: Option<&'a WafObject>) -> Self {
+        Self {
+            path: path.to_string(),
+            left,
+            right,
+        }
+    }
+}
+impl fmt::Display for WafDifference<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match self.left {
+            Some(left) => write!(f, "{left:?}")?,
+            None => write!(f, "<missing>")?,
+        }
+        write!(f, " != ")?;
+        match self.right {
+            Some(right) => write!(f, "{right:?}"),
+            None => write!(f, "<missing>"),
+        }
+    }
+}
+
+/// A step of a path into nested [`WafObject`]s, as used by [`waf_get!`](crate::waf_get): string
+/// keys select entries of [`WafMap`]s, and indices select elements of [`WafArray`]s.
+pub trait PathSegment: crate::private::Sealed {
+    /// Returns the child of `obj` selected by this segment, or [`None`] if there is none (including
+    /// when `obj` is not of the right type).
+    fn select<'a>(&self, obj: &'a WafObject) -> Option<&'a WafObject>;
+}
+impl crate::private::Sealed for &str {}
+impl PathSegment for &str {
+    fn select<'a>(&self, obj: &'a WafObject) -> Option<&'a WafObject> {
+        let entry = obj.as_type::<WafMap>()?.get_str(self)?;
+        Some(entry)
+    }
+}
+impl crate::private::Sealed for String {}
+impl PathSegment for String {
+    fn select<'a>(&self, obj: &'a WafObject) -> Option<&'a WafObject> {
+        self.as_str().select(obj)
+    }
+}
+impl crate::private::Sealed for usize {}
+impl PathSegment for usize {
+    fn select<'a>(&self, obj: &'a WafObject) -> Option<&'a WafObject> {
+        obj.as_type::<WafArray>()?.get(*self)
+    }
+}
+
+/// Returns `obj` as a [`WafObject`], as the starting point of [`waf_get!`](crate::waf_get).
+///
+/// Not intended for use outside of this crate, but must be exported as it is used by macros in this crate.
+#[doc(hidden)]
+pub fn __path_root<T: AsRef<libddwaf_sys::ddwaf_object> + ?Sized>(obj: &T) -> &WafObject {
+    obj.as_ref().as_object_ref()
+}
+
 /// Trait to encode which allocator should be used for deallocation in the type system.
 pub trait AllocatorType: 'static {
     /// Get the allocator to use for deallocation.
@@ -464,11 +1032,34 @@ pub struct WafOwned<T: AsRawMutObject, A: AllocatorType = RustAllocator> {
     _phantom: std::marker::PhantomData<A>,
 }
 impl<T: AsRawMutObject, A: AllocatorType> WafOwned<T, A> {
+    #[cfg_attr(not(ddwaf_has_from_json), allow(dead_code))] // Only used to parse JSON
     pub(crate) fn allocator() -> libddwaf_sys::ddwaf_allocator {
         A::allocator()
     }
 }
 
+impl<T: AsRawMutObject + Default, A: AllocatorType> WafOwned<T, A> {
+    /// Releases the current contents of this [`WafOwned`] using its allocator, and resets it to
+    /// its default value; so that it can be reused without allocating a new one.
+    ///
+    /// This is a no-op on values that were never populated, or that were already reset.
+    pub fn reset(&mut self) {
+        unsafe { libddwaf_sys::ddwaf_object_destroy(self.inner.as_raw_mut(), A::allocator()) };
+        // The previous value's contents were released above, so it must not be dropped.
+        self.inner = std::mem::ManuallyDrop::new(T::default());
+    }
+}
+impl<T: AsRawMutObject + Clone, A: AllocatorType> WafOwned<T, A> {
+    /// Returns a deep copy of the value of this [`WafOwned`], allocated by Rust, and releases the
+    /// original using its allocator.
+    ///
+    /// This allows retaining the value (for example, the output of an evaluation) beyond the
+    /// lifetime of the WAF instance or context it was obtained from, as a regular Rust-owned value.
+    #[must_use]
+    pub fn into_owned(self) -> T {
+        self.inner.deref().clone()
+    }
+}
 impl<T: AsRawMutObject + fmt::Debug, A: AllocatorType> fmt::Debug for WafOwned<T, A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.inner.deref().fmt(f)
@@ -476,6 +1067,7 @@ impl<T: AsRawMutObject + fmt::Debug, A: AllocatorType> fmt::Debug for WafOwned<T
 }
 impl<T: AsRawMutObject + Default, A: AllocatorType> Default for WafOwned<T, A> {
     fn default() -> Self {
+        track!(WAF_OWNED_OBJECTS, 1);
         Self {
             inner: std::mem::ManuallyDrop::new(Default::default()),
             _phantom: std::marker::PhantomData,
@@ -498,8 +1090,17 @@ impl<T: AsRawMutObject, A: AllocatorType> Drop for WafOwned<T, A> {
         unsafe {
             libddwaf_sys::ddwaf_object_destroy(self.inner.as_raw_mut(), A::allocator());
         }
+        track!(WAF_OWNED_OBJECTS, -1);
     }
 }
+/// Safety: a [`WafOwned`] exclusively owns its value, like a [`Box`] would, so it can be sent to
+/// another thread whenever the value can. Dropping it there releases the value through
+/// `ddwaf_object_destroy`, which only calls into the (thread-safe) allocator it is given and does
+/// not depend on any thread-local state.
+unsafe impl<T: AsRawMutObject + Send, A: AllocatorType> Send for WafOwned<T, A> {}
+/// Safety: a shared reference to a [`WafOwned`] only gives access to a shared reference to its
+/// value, so it can be shared between threads whenever the value can.
+unsafe impl<T: AsRawMutObject + Sync, A: AllocatorType> Sync for WafOwned<T, A> {}
 impl<T: AsRawMutObject, A: AllocatorType> PartialEq<T> for WafOwned<T, A>
 where
     T: PartialEq<T>,
@@ -528,9 +1129,36 @@ unsafe fn no_fail_alloc(layout: Layout) -> *mut u8 {
     if ptr.is_null() {
         std::alloc::handle_alloc_error(layout);
     }
+    track!(RUST_ALLOCATIONS, 1);
     ptr
 }
 
+/// Returns the [`Layout`] of an array of `len` values of type `T`.
+///
+/// This is only used with the lengths of [`u16`]-sized containers or of existing allocations, for
+/// which computing the layout cannot overflow. Should it overflow regardless, this is reported as
+/// an allocation failure by [`std::alloc::handle_alloc_error`] instead of panicking.
+fn array_layout<T>(len: usize) -> Layout {
+    Layout::array::<T>(len).unwrap_or_else(|_| std::alloc::handle_alloc_error(Layout::new::<T>()))
+}
+
+/// The maximum length, in bytes, of a [`WafString`] (including map keys), as supported by
+/// `libddwaf`.
+///
+/// Longer inputs are rejected by the fallible constructors (such as [`WafString::try_new`]),
+/// truncated by the [`From`] conversions, and cause a panic in the functions that document it
+/// (such as [`WafString::set`]).
+pub const MAX_STRING_BYTES: usize = u32::MAX as usize;
+
+/// The maximum number of items a [`WafArray`] or a [`WafMap`] can hold, as supported by
+/// `libddwaf`.
+///
+/// Containers built from larger collections using the [`From`] conversions are truncated.
+pub const MAX_CONTAINER_ENTRIES: usize = u16::MAX as usize;
+
+/// The maximum length of a string that can be stored inline in a [`WafString`].
+const SMALL_STRING_SIZE: usize = 14;
+
 macro_rules! typed_object {
     (@defaults $type:expr, $name:ident) => {
         #[doc = concat!("Returns true if this [", stringify!($name), "] is indeed [", stringify!($type), "].")]
@@ -601,6 +1229,16 @@ macro_rules! typed_object {
                 self.raw == *other.as_ref()
             }
         }
+        impl<T: AsRawMutObject> cmp::PartialEq<Keyed<T>> for $name {
+            fn eq(&self, other: &Keyed<T>) -> bool {
+                self.raw == other.raw.val
+            }
+        }
+        impl<T: AsRawMutObject> cmp::PartialEq<$name> for Keyed<T> {
+            fn eq(&self, other: &$name) -> bool {
+                self.raw.val == other.raw
+            }
+        }
         impl crate::private::Sealed for $name {}
         impl TypedWafObject for $name {
             const TYPE: WafObjectType = $type;
@@ -632,6 +1270,11 @@ typed_object!(WafObjectType::Signed => WafSigned derive(Copy, Clone) {
     pub const fn value(&self) -> i64 {
         unsafe { self.raw.via.i64_.val }
     }
+
+    /// Replaces the value of this [`WafSigned`].
+    pub fn set(&mut self, val: i64) {
+        *self = Self::new(val);
+    }
 });
 
 typed_object!(WafObjectType::Unsigned => WafUnsigned derive(Copy, Clone) {
@@ -656,6 +1299,11 @@ typed_object!(WafObjectType::Unsigned => WafUnsigned derive(Copy, Clone) {
     pub const fn value(&self) -> u64 {
         unsafe { self.raw.via.u64_.val }
     }
+
+    /// Replaces the value of this [`WafUnsigned`].
+    pub fn set(&mut self, val: u64) {
+        *self = Self::new(val);
+    }
 });
 
 typed_object!(WafObjectType::String => WafString
@@ -666,23 +1314,34 @@ typed_object!(WafObjectType::String => WafString
     }
     {
     /// Creates a new [`WafString`] with the provided value.
-    /// Only returns none if the string is larger than [`u32::MAX`] bytes.
+    /// Only returns none if the string is larger than [`MAX_STRING_BYTES`]; see
+    /// [`WafString::try_new`] for a variant reporting the length of the value.
     ///
     /// # Panics
     /// Panics if memory allocation fails (out of memory).
-    #[allow(clippy::cast_possible_truncation, clippy::items_after_statements)]
     pub fn new(val: impl AsRef<[u8]>) -> Option<Self> {
-        let val = val.as_ref();
-        if val.len() > (u32::MAX as usize) {
-            return None;
-        }
+        Self::try_new(val).ok()
+    }
 
-        const SMALL_STRING_SIZE: usize = 14;
+    /// Creates a new [`WafString`] with the provided value, like [`WafString::new`].
+    ///
+    /// # Errors
+    /// Returns an error if the value is larger than [`MAX_STRING_BYTES`].
+    ///
+    /// # Panics
+    /// Panics if memory allocation fails (out of memory).
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn try_new(val: impl AsRef<[u8]>) -> Result<Self, crate::LengthError> {
+        let val = val.as_ref();
+        let size = LengthError::checked_len("string", val.len())?;
 
-        if val.len() <= SMALL_STRING_SIZE {
+        let small_size = u8::try_from(val.len())
+            .ok()
+            .filter(|&len| usize::from(len) <= SMALL_STRING_SIZE);
+        if let Some(small_size) = small_size {
             let mut ss = libddwaf_sys::_ddwaf_object_small_string {
                 type_: libddwaf_sys::DDWAF_OBJ_SMALL_STRING as u8,
-                size: val.len() as u8,
+                size: small_size,
                 data: [0; 14],
             };
             let valcast = unsafe {
@@ -690,7 +1349,7 @@ typed_object!(WafObjectType::String => WafString
             };
             ss.data[..valcast.len()].copy_from_slice(valcast);
 
-            return Some(Self {
+            return Ok(Self {
                 raw: libddwaf_sys::ddwaf_object {
                     via: libddwaf_sys::_ddwaf_object__bindgen_ty_1 {
                         sstr: ss,
@@ -702,17 +1361,17 @@ typed_object!(WafObjectType::String => WafString
         let ptr: *mut ::std::os::raw::c_char = if val.is_empty() {
             null_mut()
         } else {
-            unsafe { no_fail_alloc(Layout::array::<::std::os::raw::c_char>(val.len()).unwrap()).cast() }
+            unsafe { no_fail_alloc(array_layout::<::std::os::raw::c_char>(val.len())).cast() }
         };
         unsafe {
             std::ptr::copy_nonoverlapping(val.as_ptr(), ptr.cast(), val.len());
         }
-        Some(Self {
+        Ok(Self {
             raw: libddwaf_sys::ddwaf_object {
                 via: libddwaf_sys::_ddwaf_object__bindgen_ty_1 {
                     str_: libddwaf_sys::_ddwaf_object_string {
                         type_: libddwaf_sys::DDWAF_OBJ_STRING as u8,
-                        size: val.len() as u32,
+                        size,
                         ptr,
                     },
                 },
@@ -724,10 +1383,11 @@ typed_object!(WafObjectType::String => WafString
     ///
     /// # Panics
     /// Panics if the string is larger than [`u32::MAX`] bytes.
-    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_possible_truncation, clippy::expect_used)] // Documented panic
     pub fn new_literal(val: impl Into<&'static [u8]>) -> Self {
         let val = val.into();
-        let len = u32::try_from(val.len()).expect("string is too large for this platform");
+        let len: u32 = LengthError::checked_len("string", val.len())
+            .expect("string is too large for this platform");
 
         Self {
             raw: libddwaf_sys::ddwaf_object {
@@ -743,11 +1403,92 @@ typed_object!(WafObjectType::String => WafString
 
     }
 
+    /// Replaces the contents of this [`WafString`] with a copy of the provided bytes, releasing
+    /// the previous contents.
+    ///
+    /// # Panics
+    /// Panics if `val` is larger than [`u32::MAX`] bytes, or if memory allocation fails (out of
+    /// memory).
+    #[allow(clippy::expect_used)] // Documented panic
+    pub fn set(&mut self, val: impl AsRef<[u8]>) {
+        *self = Self::new(val).expect("string is too large for this platform");
+    }
+
+    /// Creates a new [`WafString`] taking ownership of the provided boxed bytes.
+    ///
+    /// Unlike [`WafString::new`], the data is not copied into a new allocation (unless it is short
+    /// enough to be stored inline). Only returns none if the string is larger than [`u32::MAX`]
+    /// bytes.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn new_boxed(val: Box<[u8]>) -> Option<Self> {
+        let size = LengthError::checked_len("string", val.len()).ok()?;
+        if val.len() <= SMALL_STRING_SIZE {
+            // Small strings are stored inline, the box is simply dropped.
+            return Self::new(val);
+        }
+
+        // The allocation of a `Box<[u8]>` has the same layout as the one `drop_string` expects.
+        let ptr: *mut ::std::os::raw::c_char = Box::into_raw(val).cast();
+        track!(RUST_ALLOCATIONS, 1);
+        Some(Self {
+            raw: libddwaf_sys::ddwaf_object {
+                via: libddwaf_sys::_ddwaf_object__bindgen_ty_1 {
+                    str_: libddwaf_sys::_ddwaf_object_string {
+                        type_: libddwaf_sys::DDWAF_OBJ_STRING as u8,
+                        size,
+                        ptr,
+                    },
+                },
+            },
+        })
+    }
+
+    /// Creates a new [`WafString`] holding the bytes decoded from the provided base64 string
+    /// (using the standard alphabet, with padding), such as an encoded token.
+    ///
+    /// # Errors
+    /// Returns an error if `encoded` is not valid base64, or if the decoded data is larger than
+    /// [`u32::MAX`] bytes.
+    #[cfg(feature = "encoding")]
+    pub fn from_base64(encoded: &str) -> Result<Self, DecodeError> {
+        use base64::Engine;
+
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|_| DecodeError::Invalid)?;
+        Self::from_decoded(bytes)
+    }
+
+    /// Creates a new [`WafString`] holding the bytes decoded from the provided hexadecimal string,
+    /// in which each byte is represented by two digits (in either case).
+    ///
+    /// # Errors
+    /// Returns an error if `encoded` is not valid hexadecimal, or if the decoded data is larger
+    /// than [`u32::MAX`] bytes.
+    #[cfg(feature = "encoding")]
+    pub fn from_hex(encoded: &str) -> Result<Self, DecodeError> {
+        Self::from_decoded(decode_hex(encoded).ok_or(DecodeError::Invalid)?)
+    }
+
+    #[cfg(feature = "encoding")]
+    fn from_decoded(bytes: Vec<u8>) -> Result<Self, DecodeError> {
+        let len = bytes.len();
+        Self::new_boxed(bytes.into_boxed_slice()).ok_or(DecodeError::TooLarge { len })
+    }
+
     /// Returns the length of this [`WafString`], in bytes.
     #[must_use]
     pub fn len(&self) -> u32 {
         if self.raw.obj_type() == libddwaf_sys::DDWAF_OBJ_SMALL_STRING {
-            u32::from(unsafe { self.raw.via.sstr.size })
+            let size = unsafe { self.raw.via.sstr.size };
+            strict_assert!(
+                usize::from(size) <= SMALL_STRING_SIZE,
+                "small string size exceeds its inline storage"
+            );
+            // Saturate to the inline storage size, so an invalid size cannot cause reads past it
+            #[allow(clippy::cast_possible_truncation)]
+            u32::from(size.min(SMALL_STRING_SIZE as u8))
         } else {
             unsafe { self.raw.via.str_.size }
         }
@@ -763,7 +1504,7 @@ typed_object!(WafObjectType::String => WafString
     #[must_use]
     #[allow(clippy::cast_possible_truncation)]
     pub fn as_bytes(&self) -> &[u8] {
-        debug_assert!(self.is_valid());
+        strict_assert!(self.is_valid());
         let len = self.len();
         if len == 0 {
             return &[];
@@ -773,17 +1514,15 @@ typed_object!(WafObjectType::String => WafString
             unsafe {
                 std::slice::from_raw_parts(
                     self.raw.via.sstr.data.as_ptr().cast(),
-                    len as usize,
+                    len.to_usize(),
                 )
             }
         } else {
-            debug_assert!(!unsafe{ self.raw.via.str_.ptr }.is_null());
-            unsafe {
-                std::slice::from_raw_parts(
-                    self.raw.via.str_.ptr.cast(),
-                    len as usize,
-                )
+            let ptr = unsafe { self.raw.via.str_.ptr };
+            if ptr.is_null() {
+                return &[];
             }
+            unsafe { std::slice::from_raw_parts(ptr.cast(), len.to_usize()) }
         }
     }
 
@@ -795,6 +1534,48 @@ typed_object!(WafObjectType::String => WafString
     pub fn as_str(&self) -> Result<&str, std::str::Utf8Error> {
         std::str::from_utf8(self.as_bytes())
     }
+
+    /// Returns an iterator over the sub-slices of the bytes of this [`WafString`] separated by
+    /// `byte`; unlike splitting [`WafString::as_str`], this also works on non-UTF-8 data.
+    ///
+    /// Like [`slice::split`], this produces empty sub-slices for adjacent, leading or trailing
+    /// separators, and a single empty sub-slice for an empty [`WafString`].
+    pub fn split(&self, byte: u8) -> impl Iterator<Item = &[u8]> {
+        self.as_bytes().split(move |b| *b == byte)
+    }
+
+    /// Returns the byte index of the first occurrence of `needle` in this [`WafString`], if any.
+    ///
+    /// An empty `needle` is found at index `0`.
+    #[must_use]
+    pub fn find(&self, needle: &[u8]) -> Option<usize> {
+        if needle.is_empty() {
+            return Some(0);
+        }
+        self.as_bytes()
+            .windows(needle.len())
+            .position(|window| window == needle)
+    }
+
+    /// Returns true if all bytes of this [`WafString`] are within the ASCII range.
+    #[must_use]
+    pub fn is_ascii(&self) -> bool {
+        self.as_bytes().is_ascii()
+    }
+
+    /// Returns true if this [`WafString`] is a valid UTF-8 string, i.e, if [`WafString::as_str`]
+    /// would succeed.
+    #[must_use]
+    pub fn is_valid_utf8(&self) -> bool {
+        self.as_str().is_ok()
+    }
+
+    /// Returns true if this [`WafString`] contains any ASCII control character (`0x00` to `0x1F`,
+    /// and `0x7F`), including `NUL`, tabs, and line terminators.
+    #[must_use]
+    pub fn contains_control_chars(&self) -> bool {
+        self.as_bytes().iter().any(u8::is_ascii_control)
+    }
 });
 typed_object!(WafObjectType::Array => WafArray {
     /// Creates a new [`WafArray`] with the provided size. All values in the array are initialized
@@ -805,7 +1586,7 @@ typed_object!(WafObjectType::Array => WafArray {
     #[must_use]
     pub fn new(nb_entries: u16) -> Self {
         let size = usize::from(nb_entries);
-        let layout = Layout::array::<libddwaf_sys::ddwaf_object>(size).unwrap();
+        let layout = array_layout::<libddwaf_sys::ddwaf_object>(size);
         let ptr = unsafe { no_fail_alloc(layout).cast() };
         unsafe { std::ptr::write_bytes(ptr, 0, size)};
         Self {
@@ -823,6 +1604,20 @@ typed_object!(WafObjectType::Array => WafArray {
         }
     }
 
+    /// Creates a new [`WafArray`] by converting each item of the provided slice using `f`.
+    ///
+    /// This allows converting slices of items that are not [`Clone`], or that do not implement
+    /// [`Into<WafObject>`], which the [`From<&[T]>`](#impl-From%3C%26%5BT%5D%3E-for-WafArray)
+    /// implementation requires. Only the first [`u16::MAX`] items are converted.
+    pub fn from_slice_with<T>(slice: &[T], mut f: impl FnMut(&T) -> WafObject) -> Self {
+        let effective_length = LengthError::checked_len("array", slice.len()).unwrap_or(u16::MAX);
+        let mut array = Self::new(effective_length);
+        for (i, item) in slice.iter().take(usize::from(effective_length)).enumerate() {
+            array[i] = f(item);
+        }
+        array
+    }
+
     /// Returns the length of this [`WafArray`].
     #[must_use]
     pub const fn len(&self) -> u16 {
@@ -857,12 +1652,84 @@ typed_object!(WafObjectType::Array => WafArray {
         let arr: *mut WafObject = unsafe { self.raw.via.array.ptr.cast() };
         for i in new_size..self.len() {
             unsafe {
-                std::ptr::drop_in_place(arr.add(i as usize));
+                std::ptr::drop_in_place(arr.add(usize::from(i)));
             }
         }
         self.raw.via.array.size = new_size;
     }
 
+    /// Drops all elements of this [`WafArray`], leaving it empty.
+    ///
+    /// Like [`WafArray::truncate`], this does not free the memory backing this [`WafArray`], which
+    /// can be re-filled up to its [`WafArray::capacity`].
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Swaps the elements at indices `a` and `b` of this [`WafArray`].
+    ///
+    /// # Panics
+    /// Panics if `a` or `b` is out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let slice: &mut [WafObject] = AsMut::as_mut(self);
+        slice.swap(a, b);
+    }
+
+    /// Removes the element at `index` from this [`WafArray`] and returns it, replacing it with the
+    /// last element. This does not preserve the order of the remaining elements, nor change the
+    /// array's capacity.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> WafObject {
+        let len = self.len();
+        assert!(
+            index < usize::from(len),
+            "swap_remove index (is {index}) should be < len (is {len})"
+        );
+        let last = usize::from(len - 1);
+        self.swap(index, last);
+        // The slot left past the end holds an invalid object, which need not be dropped.
+        let removed = std::mem::take(&mut self[last]);
+        self.raw.via.array.size = len - 1;
+        removed
+    }
+
+    /// Sorts the elements of this [`WafArray`] with the provided comparator, without preserving
+    /// the order of equal elements.
+    pub fn sort_unstable_by(&mut self, cmp: impl FnMut(&WafObject, &WafObject) -> cmp::Ordering) {
+        let slice : &mut [WafObject] = AsMut::as_mut(self);
+        slice.sort_unstable_by(cmp);
+    }
+
+    /// Sorts the elements of this [`WafArray`] by value, which is intended for arrays of scalars or
+    /// strings of a single type (strings are compared byte-wise, and floats with
+    /// [`f64::total_cmp`]).
+    ///
+    /// Elements of different types are ordered by type first, in the order of the
+    /// [`WafObjectType`] variants, then by value. Arrays, maps and null values are not compared
+    /// further, so their relative order is unspecified.
+    pub fn sort_scalars(&mut self) {
+        self.sort_unstable_by(cmp_scalars);
+    }
+
+    /// Removes consecutive equal elements from this [`WafArray`] (as compared by [`PartialEq`]),
+    /// keeping the first of each run; sorting the array first removes all duplicates. The
+    /// removed elements are dropped, and the array's capacity is unchanged.
+    pub fn dedup_consecutive(&mut self) {
+        let slice : &mut [WafObject] = AsMut::as_mut(self);
+        let mut kept = 0;
+        for i in 0..slice.len() {
+            if kept > 0 && slice[i] == slice[kept - 1] {
+                continue;
+            }
+            // The elements between `kept` and `i` are duplicates, which end up past `kept`.
+            slice.swap(kept, i);
+            kept += 1;
+        }
+        self.truncate(u16::try_from(kept).unwrap_or(u16::MAX));
+    }
+
     /// Returns an iterator over the [`Keyed<WafObject>`]s in this [`WafMap`].
     pub fn iter(&self) -> impl Iterator<Item = &WafObject> {
         let slice : &[WafObject] = self.as_ref();
@@ -874,7 +1741,76 @@ typed_object!(WafObjectType::Array => WafArray {
         let slice : &mut [WafObject] = AsMut::as_mut(self);
         slice.iter_mut()
     }
+
+    /// Returns a reference to the element at `index`, or [`None`] if it is out of bounds.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&WafObject> {
+        let slice : &[WafObject] = self.as_ref();
+        slice.get(index)
+    }
+
+    /// Returns a mutable reference to the element at `index`, or [`None`] if it is out of bounds.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut WafObject> {
+        let slice : &mut [WafObject] = AsMut::as_mut(self);
+        slice.get_mut(index)
+    }
+
+    /// Returns a reference to the first element of this [`WafArray`], or [`None`] if it is empty.
+    #[must_use]
+    pub fn first(&self) -> Option<&WafObject> {
+        self.get(0)
+    }
+
+    /// Returns a reference to the last element of this [`WafArray`], or [`None`] if it is empty.
+    #[must_use]
+    pub fn last(&self) -> Option<&WafObject> {
+        let slice : &[WafObject] = self.as_ref();
+        slice.last()
+    }
+
+    /// Returns a reference to the element at `index` as a `T`, or [`None`] if it is out of bounds
+    /// or is not a `T`.
+    #[must_use]
+    pub fn index_as<T: TypedWafObject>(&self, index: usize) -> Option<&T> {
+        self.get(index).and_then(WafObject::as_type)
+    }
 });
+/// Decodes a hexadecimal string, for [`WafString::from_hex`].
+#[cfg(feature = "encoding")]
+fn decode_hex(encoded: &str) -> Option<Vec<u8>> {
+    fn digit(c: u8) -> Option<u8> {
+        char::from(c)
+            .to_digit(16)
+            .and_then(|d| u8::try_from(d).ok())
+    }
+
+    let encoded = encoded.as_bytes();
+    if encoded.len() % 2 != 0 {
+        return None;
+    }
+    encoded
+        .chunks_exact(2)
+        .map(|pair| Some((digit(pair[0])? << 4) | digit(pair[1])?))
+        .collect()
+}
+
+/// The comparator used by [`WafArray::sort_scalars`].
+fn cmp_scalars(left: &WafObject, right: &WafObject) -> cmp::Ordering {
+    let (left_type, right_type) = (left.object_type(), right.object_type());
+    if left_type != right_type {
+        return (left_type as u8).cmp(&(right_type as u8));
+    }
+    match (left.view(), right.view()) {
+        (WafView::Bool(left), WafView::Bool(right)) => left.cmp(&right),
+        (WafView::Signed(left), WafView::Signed(right)) => left.cmp(&right),
+        (WafView::Unsigned(left), WafView::Unsigned(right)) => left.cmp(&right),
+        (WafView::Float(left), WafView::Float(right)) => left.total_cmp(&right),
+        _ => match (left.as_type::<WafString>(), right.as_type::<WafString>()) {
+            (Some(left), Some(right)) => left.as_bytes().cmp(right.as_bytes()),
+            _ => cmp::Ordering::Equal,
+        },
+    }
+}
 typed_object!(WafObjectType::Map => WafMap {
     /// Creates a new [`WafMap`] with the provided size. All values in the map are initialized
     /// to an invalid [`WafObject`] instance with a blank key.
@@ -884,7 +1820,7 @@ typed_object!(WafObjectType::Map => WafMap {
     #[must_use]
     pub fn new(nb_entries: u16) -> Self {
         let size = usize::from(nb_entries);
-        let layout = Layout::array::<libddwaf_sys::_ddwaf_object_kv>(size).unwrap();
+        let layout = array_layout::<libddwaf_sys::_ddwaf_object_kv>(size);
         let ptr = unsafe { no_fail_alloc(layout).cast() };
         unsafe { std::ptr::write_bytes(ptr, 0, size)};
         Self {
@@ -936,12 +1872,48 @@ typed_object!(WafObjectType::Map => WafMap {
         let entries: *mut Keyed<WafObject> = unsafe { self.raw.via.map.ptr.cast() };
         for i in new_size..self.len() {
             unsafe {
-                std::ptr::drop_in_place(entries.add(i as usize));
+                std::ptr::drop_in_place(entries.add(usize::from(i)));
             }
         }
         self.raw.via.map.size = new_size;
     }
 
+    /// Drops all entries (keys and values) of this [`WafMap`], leaving it empty.
+    ///
+    /// Like [`WafMap::truncate`], this does not free the memory backing this [`WafMap`], which can
+    /// be re-filled up to its [`WafMap::capacity`] (for example using [`WafMap::insert`]).
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Swaps the entries (keys and values) at indices `a` and `b` of this [`WafMap`].
+    ///
+    /// # Panics
+    /// Panics if `a` or `b` is out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let slice: &mut [Keyed<WafObject>] = AsMut::as_mut(self);
+        slice.swap(a, b);
+    }
+
+    /// Removes the entry at `index` from this [`WafMap`] and returns it along with its key,
+    /// replacing it with the last entry. This does not preserve the order of the remaining
+    /// entries, nor change the map's capacity.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> Keyed<WafObject> {
+        let len = self.len();
+        assert!(
+            index < usize::from(len),
+            "swap_remove index (is {index}) should be < len (is {len})"
+        );
+        let last = usize::from(len - 1);
+        self.swap(index, last);
+        // The slot left past the end holds an invalid key and value, which need not be dropped.
+        let removed = std::mem::take(&mut self[last]);
+        self.raw.via.map.size = len - 1;
+        removed
+    }
     /// Returns an iterator over the [`Keyed<WafObject>`]s in this [`WafMap`].
     pub fn iter(&self) -> impl Iterator<Item = &Keyed<WafObject>> {
         let slice : &[Keyed<WafObject>] = self.as_ref();
@@ -954,6 +1926,21 @@ typed_object!(WafObjectType::Map => WafMap {
         slice.iter_mut()
     }
 
+    /// Returns a reference to the [`Keyed<WafObject>`] at `index`, or [`None`] if it is out of
+    /// bounds.
+    #[must_use]
+    pub fn get_index(&self, index: usize) -> Option<&Keyed<WafObject>> {
+        let slice : &[Keyed<WafObject>] = self.as_ref();
+        slice.get(index)
+    }
+
+    /// Returns a mutable reference to the [`Keyed<WafObject>`] at `index`, or [`None`] if it is
+    /// out of bounds.
+    pub fn get_index_mut(&mut self, index: usize) -> Option<&mut Keyed<WafObject>> {
+        let slice : &mut [Keyed<WafObject>] = AsMut::as_mut(self);
+        slice.get_mut(index)
+    }
+
     /// Returns a reference to the [`Keyed<WafObject>`] with the provided key, if one exists.
     ///
     /// If multiple such objects exist in the receiver, the first match is returned.
@@ -998,6 +1985,246 @@ typed_object!(WafObjectType::Map => WafMap {
     pub fn get_str_mut(&mut self, key: &'_ str) -> Option<&mut Keyed<WafObject>> {
         self.get_mut(key.as_bytes())
     }
+
+    /// Returns a reference to the entry at `index`, with its value as a `T`, or [`None`] if it is
+    /// out of bounds or its value is not a `T`.
+    #[must_use]
+    pub fn index_as<T: TypedWafObject>(&self, index: usize) -> Option<&Keyed<T>> {
+        let slice : &[Keyed<WafObject>] = self.as_ref();
+        slice.get(index).and_then(Keyed::<WafObject>::as_type)
+    }
+
+    /// Appends a new entry with the provided key and value to this [`WafMap`], growing its storage
+    /// as needed, and returns a mutable reference to it.
+    ///
+    /// Existing entries with the same key are left untouched; see [`WafMap::get_or_insert_with`]
+    /// to avoid creating duplicate keys.
+    ///
+    /// This must not be used on maps whose storage was allocated by `libddwaf` (such as those in a
+    /// [`WafOwnedDefaultAllocator`]).
+    ///
+    /// # Panics
+    /// Panics if this [`WafMap`] already contains [`u16::MAX`] entries.
+    pub fn insert(
+        &mut self,
+        key: impl Into<WafObject>,
+        value: impl Into<WafObject>,
+    ) -> &mut Keyed<WafObject> {
+        let len = self.len();
+        assert!(len < u16::MAX, "map cannot hold more than {} entries", u16::MAX);
+        if len == self.capacity() {
+            self.grow();
+        }
+        let entries: *mut Keyed<WafObject> = unsafe { self.raw.via.map.ptr.cast() };
+        let entry = unsafe { entries.add(usize::from(len)) };
+        unsafe { entry.write(Keyed::new(key, value.into())) };
+        self.raw.via.map.size = len + 1;
+        unsafe { &mut *entry }
+    }
+
+    /// Appends a new entry to this [`WafMap`] like [`WafMap::insert`] does, with the key held by
+    /// the provided [`KeyCache`] (see [`KeyCache::key`]).
+    ///
+    /// The same restrictions as for [`WafMap::insert`] apply.
+    ///
+    /// # Panics
+    /// Panics if this [`WafMap`] already contains [`u16::MAX`] entries.
+    pub fn insert_cached(
+        &mut self,
+        cache: &'static KeyCache,
+        key: &str,
+        value: impl Into<WafObject>,
+    ) -> &mut Keyed<WafObject> {
+        self.insert(cache.key(key), value)
+    }
+
+    /// Returns a mutable reference to the first entry with the provided key, inserting a new one
+    /// with the value returned by `f` if none exists.
+    ///
+    /// The same restrictions as for [`WafMap::insert`] apply.
+    ///
+    /// # Panics
+    /// Panics if a new entry needs to be inserted, but this [`WafMap`] already contains
+    /// [`u16::MAX`] entries.
+    pub fn get_or_insert_with(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        f: impl FnOnce() -> WafObject,
+    ) -> &mut Keyed<WafObject> {
+        let key = key.as_ref();
+        let position = self.iter().position(|o| o.key_bytes().ok() == Some(key));
+        match position {
+            Some(idx) => &mut self[idx],
+            None => self.insert(key, f()),
+        }
+    }
+
+    /// Removes the first entry with the provided key from this [`WafMap`], and returns its value.
+    ///
+    /// The key of the removed entry is released, and the order of the remaining entries is
+    /// preserved.
+    pub fn remove(&mut self, key: impl AsRef<[u8]>) -> Option<WafObject> {
+        let key = key.as_ref();
+        let idx = self.iter().position(|o| o.key_bytes().ok() == Some(key))?;
+        let value = std::mem::take(self[idx].value_mut());
+        let len = self.len();
+        let entries: *mut Keyed<WafObject> = unsafe { self.raw.via.map.ptr.cast() };
+        unsafe {
+            std::ptr::drop_in_place(entries.add(idx));
+            std::ptr::copy(entries.add(idx + 1), entries.add(idx), usize::from(len) - idx - 1);
+        }
+        self.raw.via.map.size = len - 1;
+        Some(value)
+    }
+
+    /// Sets the value at the provided `path` of keys, creating intermediate maps as needed.
+    ///
+    /// An existing value at the end of the `path` is replaced; a single-segment `path` therefore
+    /// behaves like [`WafMap::get_or_insert_with`] followed by an assignment. When several entries
+    /// have the same key, the first one is used. The same restrictions as for [`WafMap::insert`]
+    /// apply.
+    ///
+    /// # Errors
+    /// Returns an error, leaving this [`WafMap`] unchanged, if the `path` is empty or if one of
+    /// the values along it (other than the last one) exists and is not a map.
+    ///
+    /// # Panics
+    /// Panics if an entry needs to be inserted into a map that already contains [`u16::MAX`]
+    /// entries.
+    pub fn set_path(
+        &mut self,
+        path: &[&str],
+        value: impl Into<WafObject>,
+    ) -> Result<(), SetPathError> {
+        let Some((leaf, parents)) = path.split_last() else {
+            return Err(SetPathError::EmptyPath);
+        };
+
+        // Check the existing maps first, so that nothing is modified in case of error.
+        let mut map = &*self;
+        for (depth, segment) in parents.iter().enumerate() {
+            let Some(entry) = map.get_str(segment) else {
+                break;
+            };
+            map = entry.as_type::<WafMap>().ok_or(SetPathError::NotAMap {
+                depth: depth + 1,
+                actual: entry.object_type(),
+            })?;
+        }
+
+        let mut map = self;
+        for (depth, segment) in parents.iter().enumerate() {
+            let entry = map.get_or_insert_with(segment, || WafMap::new(0).into());
+            let actual = entry.object_type();
+            map = entry
+                .value_mut()
+                .as_type_mut::<WafMap>()
+                .ok_or(SetPathError::NotAMap {
+                    depth: depth + 1,
+                    actual,
+                })?;
+        }
+        *map.get_or_insert_with(leaf, WafObject::default).value_mut() = value.into();
+        Ok(())
+    }
+
+    /// Removes the value at the provided `path` of keys, and returns it along with its nested
+    /// values.
+    ///
+    /// Returns [`None`] if the `path` is empty, or if there is no value at the `path`. When several
+    /// entries have the same key, the first one is used.
+    pub fn remove_path(&mut self, path: &[&str]) -> Option<WafObject> {
+        let (leaf, parents) = path.split_last()?;
+        let mut map = self;
+        for segment in parents {
+            map = map
+                .get_str_mut(segment)?
+                .value_mut()
+                .as_type_mut::<WafMap>()?;
+        }
+        map.remove(leaf)
+    }
+
+    /// Grows the storage of this [`WafMap`] to hold at least one more entry.
+    fn grow(&mut self) {
+        let capacity = self.capacity();
+        let new_capacity = capacity.saturating_mul(2).max(4);
+        let new_layout = array_layout::<libddwaf_sys::_ddwaf_object_kv>(usize::from(new_capacity));
+        let ptr = if capacity == 0 {
+            unsafe { no_fail_alloc(new_layout) }
+        } else {
+            let old_layout = array_layout::<libddwaf_sys::_ddwaf_object_kv>(usize::from(capacity));
+            let ptr = unsafe {
+                std::alloc::realloc(self.raw.via.map.ptr.cast(), old_layout, new_layout.size())
+            };
+            if ptr.is_null() {
+                std::alloc::handle_alloc_error(new_layout);
+            }
+            ptr
+        };
+        self.raw.via.map.ptr = ptr.cast();
+        self.raw.via.map.capacity = new_capacity;
+    }
+
+    /// Returns a single-level copy of this [`WafMap`], where values nested in maps and arrays are
+    /// keyed by the path leading to them, joined with the provided `separator`. Map entries
+    /// contribute their key to the path, and array items their index; so that flattening
+    /// `{"a": {"b": [1, 2]}}` with `"."` produces `{"a.b.0": 1, "a.b.1": 2}`.
+    ///
+    /// Entries are produced in iteration order. Empty nested maps and arrays produce no entries,
+    /// and non-string keys contribute an empty path segment.
+    ///
+    /// Flattening can produce several entries with the same key (for example, `{"a.b": 1}` and
+    /// `{"a": {"b": 2}}` both flatten to `"a.b"`). All such entries are retained, and lookups such
+    /// as [`WafMap::get_str`] return the first one. If flattening produces more than [`u16::MAX`]
+    /// entries, the extra entries are discarded.
+    #[must_use]
+    pub fn flatten(&self, separator: &str) -> WafMap {
+        fn collect<'a>(
+            obj: &'a WafObject,
+            path: &mut Vec<u8>,
+            separator: &[u8],
+            out: &mut Vec<(Vec<u8>, &'a WafObject)>,
+        ) {
+            let mut visit = |segment: &[u8], obj: &'a WafObject, path: &mut Vec<u8>| {
+                let len = path.len();
+                if len != 0 {
+                    path.extend_from_slice(separator);
+                }
+                path.extend_from_slice(segment);
+                collect(obj, path, separator, out);
+                path.truncate(len);
+            };
+            match obj.view() {
+                WafView::Map(map) => {
+                    for entry in map.iter() {
+                        visit(entry.key_bytes().unwrap_or_default(), entry, path);
+                    }
+                }
+                WafView::Array(arr) => {
+                    for (i, item) in arr.iter().enumerate() {
+                        visit(i.to_string().as_bytes(), item, path);
+                    }
+                }
+                _ => out.push((path.clone(), obj)),
+            }
+        }
+
+        let mut entries = Vec::new();
+        let mut path = Vec::new();
+        for entry in self.iter() {
+            path.clear();
+            path.extend_from_slice(entry.key_bytes().unwrap_or_default());
+            collect(entry, &mut path, separator.as_bytes(), &mut entries);
+        }
+
+        let effective_length = LengthError::checked_len("map", entries.len()).unwrap_or(u16::MAX);
+        let mut map = WafMap::new(effective_length);
+        for (i, (key, value)) in entries.into_iter().take(usize::from(effective_length)).enumerate() {
+            map[i] = (key.as_slice(), value.clone()).into();
+        }
+        map
+    }
 });
 typed_object!(WafObjectType::Bool => WafBool derive(Copy, Clone) {
     /// Creates a new [`WafBool`] with the provided value.
@@ -1021,6 +2248,11 @@ typed_object!(WafObjectType::Bool => WafBool derive(Copy, Clone) {
     pub const fn value(&self) -> bool {
         unsafe { self.raw.via.b8.val }
     }
+
+    /// Replaces the value of this [`WafBool`].
+    pub fn set(&mut self, val: bool) {
+        *self = Self::new(val);
+    }
 });
 
 typed_object!(WafObjectType::Float => WafFloat derive(Copy, Clone) {
@@ -1045,6 +2277,11 @@ typed_object!(WafObjectType::Float => WafFloat derive(Copy, Clone) {
     pub const fn value(&self) -> f64 {
         unsafe { self.raw.via.f64_.val }
     }
+
+    /// Replaces the value of this [`WafFloat`].
+    pub fn set(&mut self, val: f64) {
+        *self = Self::new(val);
+    }
 });
 
 typed_object!(WafObjectType::Null => WafNull derive(Copy, Clone) {
@@ -1101,8 +2338,9 @@ impl From<u32> for WafUnsigned {
 impl<T: AsRef<[u8]>> From<T> for WafString {
     fn from(val: T) -> Self {
         let slice = val.as_ref();
-        let slice = &slice[..slice.len().min(u32::MAX as usize)];
-        Self::new(slice).unwrap()
+        let slice = &slice[..slice.len().min(MAX_STRING_BYTES)];
+        // The slice was truncated to a supported length, so this never falls back to the default
+        Self::new(slice).unwrap_or_default()
     }
 }
 impl fmt::Debug for WafString {
@@ -1128,13 +2366,13 @@ impl Clone for WafString {
     fn clone(&self) -> Self {
         if self.raw.obj_type() == libddwaf_sys::DDWAF_OBJ_STRING {
             let len = self.len();
-            let layout = Layout::array::<std::os::raw::c_char>(len as usize).unwrap();
+            let layout = array_layout::<std::os::raw::c_char>(len.to_usize());
             let copied = unsafe { no_fail_alloc(layout).cast::<std::os::raw::c_char>() };
             unsafe {
                 std::ptr::copy_nonoverlapping(
                     self.as_bytes().as_ptr().cast(),
                     copied,
-                    len as usize,
+                    len.to_usize(),
                 );
             }
             return Self {
@@ -1162,7 +2400,7 @@ impl AsRef<[WafObject]> for WafArray {
             return &[];
         }
         let array = unsafe { self.raw.via.array.ptr.cast() };
-        unsafe { std::slice::from_raw_parts(array, self.len() as usize) }
+        unsafe { std::slice::from_raw_parts(array, usize::from(self.len())) }
     }
 }
 impl AsMut<[WafObject]> for WafArray {
@@ -1171,7 +2409,7 @@ impl AsMut<[WafObject]> for WafArray {
             return &mut [];
         }
         let array = unsafe { self.raw.via.array.ptr.cast() };
-        unsafe { std::slice::from_raw_parts_mut(array, self.len() as usize) }
+        unsafe { std::slice::from_raw_parts_mut(array, usize::from(self.len())) }
     }
 }
 impl fmt::Debug for WafArray {
@@ -1202,14 +2440,14 @@ impl Clone for WafArray {
             return Self::new(0);
         }
 
-        let layout = Layout::array::<libddwaf_sys::ddwaf_object>(size as usize).unwrap();
+        let layout = array_layout::<libddwaf_sys::ddwaf_object>(usize::from(size));
         let new_arr: *mut libddwaf_sys::ddwaf_object = unsafe { no_fail_alloc(layout).cast() };
 
         // Clone each element
         for i in 0..size {
-            let src_elem: &WafObject = &self[i as usize];
+            let src_elem: &WafObject = &self[usize::from(i)];
             let cloned_elem = ManuallyDrop::new(src_elem.clone());
-            unsafe { new_arr.add(i as usize).write(cloned_elem.raw) };
+            unsafe { new_arr.add(usize::from(i)).write(cloned_elem.raw) };
         }
 
         Self {
@@ -1229,11 +2467,10 @@ impl Clone for WafArray {
 }
 impl<T: Into<WafObject>, const N: usize> From<[T; N]> for WafArray {
     fn from(value: [T; N]) -> Self {
-        let effective_length = N.min(u16::MAX as usize);
-        #[allow(clippy::cast_possible_truncation)]
-        let mut array = Self::new(effective_length as u16);
+        let effective_length = LengthError::checked_len("array", N).unwrap_or(u16::MAX);
+        let mut array = Self::new(effective_length);
         for (i, obj) in value.into_iter().enumerate() {
-            if i >= effective_length {
+            if i >= usize::from(effective_length) {
                 break;
             }
             array[i] = obj.into();
@@ -1241,16 +2478,55 @@ impl<T: Into<WafObject>, const N: usize> From<[T; N]> for WafArray {
         array
     }
 }
+/// Converts a slice by cloning its items, truncating it to [`u16::MAX`] items.
+///
+/// Items are cloned as the conversion into [`WafObject`] consumes them; see
+/// [`WafArray::from_slice_with`] for converting items by reference instead, and
+/// [`From<&mut [T]>`](#impl-From%3C%26mut+%5BT%5D%3E-for-WafArray) for taking the items out of a
+/// mutable slice.
+impl<T: Clone + Into<WafObject>> From<&[T]> for WafArray {
+    fn from(value: &[T]) -> Self {
+        Self::from_slice_with(value, |item| item.clone().into())
+    }
+}
+/// Collects items into a [`WafArray`], ignoring any items beyond [`u16::MAX`].
+///
+/// Borrowed items can be collected using [`Iterator::cloned`], since a blanket implementation for
+/// `&T` would overlap with this one.
+impl<T: Into<WafObject>> FromIterator<T> for WafArray {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut items: Vec<WafObject> = iter
+            .into_iter()
+            .take(usize::from(u16::MAX))
+            .map(Into::into)
+            .collect();
+        Self::from(items.as_mut_slice())
+    }
+}
+/// Converts a vector by consuming its items, truncating it to [`u16::MAX`] items.
+impl<T: Into<WafObject>> From<Vec<T>> for WafArray {
+    fn from(value: Vec<T>) -> Self {
+        let effective_length = LengthError::checked_len("array", value.len()).unwrap_or(u16::MAX);
+        let mut array = Self::new(effective_length);
+        for (i, obj) in value
+            .into_iter()
+            .take(usize::from(effective_length))
+            .enumerate()
+        {
+            array[i] = obj.into();
+        }
+        array
+    }
+}
 impl<T> From<&mut [T]> for WafArray
 where
     T: Into<WafObject> + Default,
 {
     fn from(value: &mut [T]) -> Self {
-        let effective_length = value.len().min(u16::MAX as usize);
-        #[allow(clippy::cast_possible_truncation)]
-        let mut array = Self::new(effective_length as u16);
+        let effective_length = LengthError::checked_len("array", value.len()).unwrap_or(u16::MAX);
+        let mut array = Self::new(effective_length);
         for (i, obj) in value.iter_mut().enumerate() {
-            if i >= effective_length {
+            if i >= usize::from(effective_length) {
                 break;
             }
             let obj = std::mem::take(obj);
@@ -1262,16 +2538,22 @@ where
 impl Index<usize> for WafArray {
     type Output = WafObject;
     fn index(&self, index: usize) -> &Self::Output {
-        let len = self.len() as usize;
-        assert!(index < len, "index out of bounds ({index} >= {len})");
+        let len = usize::from(self.len());
+        assert!(
+            index < len,
+            "WafArray index out of bounds ({index} >= {len})"
+        );
         let array = unsafe { self.raw.via.array.ptr };
         unsafe { &*(array.add(index) as *const _) }
     }
 }
 impl IndexMut<usize> for WafArray {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        let len = self.len() as usize;
-        assert!(index < len, "index out of bounds ({index} >= {len})");
+        let len = usize::from(self.len());
+        assert!(
+            index < len,
+            "WafArray index out of bounds ({index} >= {len})"
+        );
         let array = unsafe { self.raw.via.array.ptr };
         unsafe { &mut *(array.add(index).cast()) }
     }
@@ -1283,7 +2565,7 @@ impl AsRef<[Keyed<WafObject>]> for WafMap {
             return &[];
         }
         let ptr = unsafe { self.raw.via.map.ptr as *const _ };
-        unsafe { std::slice::from_raw_parts(ptr, self.len() as usize) }
+        unsafe { std::slice::from_raw_parts(ptr, usize::from(self.len())) }
     }
 }
 impl AsMut<[Keyed<WafObject>]> for WafMap {
@@ -1292,7 +2574,7 @@ impl AsMut<[Keyed<WafObject>]> for WafMap {
             return &mut [];
         }
         let ptr = unsafe { self.raw.via.map.ptr.cast() };
-        unsafe { std::slice::from_raw_parts_mut(ptr, self.len() as usize) }
+        unsafe { std::slice::from_raw_parts_mut(ptr, usize::from(self.len())) }
     }
 }
 impl fmt::Debug for WafMap {
@@ -1323,15 +2605,15 @@ impl Clone for WafMap {
             return Self::new(0);
         }
 
-        let layout = Layout::array::<libddwaf_sys::_ddwaf_object_kv>(size as usize).unwrap();
+        let layout = array_layout::<libddwaf_sys::_ddwaf_object_kv>(usize::from(size));
         let new_ptr: *mut libddwaf_sys::_ddwaf_object_kv = unsafe { no_fail_alloc(layout).cast() };
-        unsafe { std::ptr::write_bytes(new_ptr, 0, size as usize) };
+        unsafe { std::ptr::write_bytes(new_ptr, 0, usize::from(size)) };
 
         // Clone each key-value pair
         for i in 0..size {
-            let src_entry: &Keyed<WafObject> = &self[i as usize];
+            let src_entry: &Keyed<WafObject> = &self[usize::from(i)];
             let cloned_entry = ManuallyDrop::new(src_entry.clone());
-            unsafe { new_ptr.add(i as usize).write(cloned_entry.raw) };
+            unsafe { new_ptr.add(usize::from(i)).write(cloned_entry.raw) };
         }
 
         Self {
@@ -1352,27 +2634,26 @@ impl Clone for WafMap {
 impl Index<usize> for WafMap {
     type Output = Keyed<WafObject>;
     fn index(&self, index: usize) -> &Self::Output {
-        let len = self.len() as usize;
-        assert!(index < len, "index out of bounds ({index} >= {len})");
+        let len = usize::from(self.len());
+        assert!(index < len, "WafMap index out of bounds ({index} >= {len})");
         let ptr = unsafe { self.raw.via.map.ptr };
         unsafe { &*ptr.add(index).cast() }
     }
 }
 impl IndexMut<usize> for WafMap {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        let len = self.len() as usize;
-        assert!(index < len, "index out of bounds ({index} >= {len})");
+        let len = usize::from(self.len());
+        assert!(index < len, "WafMap index out of bounds ({index} >= {len})");
         let ptr = unsafe { self.raw.via.map.ptr };
         unsafe { &mut *ptr.add(index).cast() }
     }
 }
 impl<K: AsRef<[u8]>, V: Into<WafObject>, const N: usize> From<[(K, V); N]> for WafMap {
     fn from(vals: [(K, V); N]) -> Self {
-        let effective_length = N.min(u16::MAX as usize);
-        #[allow(clippy::cast_possible_truncation)]
-        let mut map = WafMap::new(effective_length as u16);
+        let effective_length = LengthError::checked_len("map", N).unwrap_or(u16::MAX);
+        let mut map = WafMap::new(effective_length);
         for (i, (k, v)) in vals.into_iter().enumerate() {
-            if i >= effective_length {
+            if i >= usize::from(effective_length) {
                 break;
             }
             map[i] = Keyed::from((k.as_ref(), v.into()));
@@ -1382,11 +2663,10 @@ impl<K: AsRef<[u8]>, V: Into<WafObject>, const N: usize> From<[(K, V); N]> for W
 }
 impl<V: Into<WafObject>, const N: usize> From<[(WafObject, V); N]> for WafMap {
     fn from(vals: [(WafObject, V); N]) -> Self {
-        let effective_length = N.min(u16::MAX as usize);
-        #[allow(clippy::cast_possible_truncation)]
-        let mut map = WafMap::new(effective_length as u16);
+        let effective_length = LengthError::checked_len("map", N).unwrap_or(u16::MAX);
+        let mut map = WafMap::new(effective_length);
         for (i, (k, v)) in vals.into_iter().enumerate() {
-            if i >= effective_length {
+            if i >= usize::from(effective_length) {
                 break;
             }
             map[i] = (k, v.into()).into();
@@ -1400,11 +2680,10 @@ where
     V: Into<WafObject> + Default,
 {
     fn from(value: &mut [(K, V)]) -> Self {
-        let effective_length = value.len().min(u16::MAX as usize);
-        #[allow(clippy::cast_possible_truncation)]
-        let mut map = Self::new(effective_length as u16);
+        let effective_length = LengthError::checked_len("map", value.len()).unwrap_or(u16::MAX);
+        let mut map = Self::new(effective_length);
         for (i, (k, v)) in value.iter_mut().enumerate() {
-            if i >= effective_length {
+            if i >= usize::from(effective_length) {
                 break;
             }
             let k = std::mem::take(k);
@@ -1414,6 +2693,70 @@ where
         map
     }
 }
+/// Converts a slice of key/value pairs by cloning its values, truncating it to [`u16::MAX`]
+/// entries.
+impl<K: AsRef<[u8]>, V: Clone + Into<WafObject>> From<&[(K, V)]> for WafMap {
+    fn from(value: &[(K, V)]) -> Self {
+        let effective_length = LengthError::checked_len("map", value.len()).unwrap_or(u16::MAX);
+        let mut map = Self::new(effective_length);
+        for (i, (k, v)) in value.iter().take(usize::from(effective_length)).enumerate() {
+            map[i] = Keyed::from((k.as_ref(), v.clone().into()));
+        }
+        map
+    }
+}
+/// Converts a vector of key/value pairs, truncating it to [`u16::MAX`] entries.
+impl<K: AsRef<[u8]>, V: Into<WafObject>> From<Vec<(K, V)>> for WafMap {
+    fn from(value: Vec<(K, V)>) -> Self {
+        let effective_length = LengthError::checked_len("map", value.len()).unwrap_or(u16::MAX);
+        let mut map = Self::new(effective_length);
+        for (i, (k, v)) in value
+            .into_iter()
+            .take(usize::from(effective_length))
+            .enumerate()
+        {
+            map[i] = Keyed::from((k.as_ref(), v.into()));
+        }
+        map
+    }
+}
+/// Converts a [`HashMap`] into a [`WafMap`], truncating it to [`u16::MAX`] entries.
+///
+/// The entries appear in the iteration order of the [`HashMap`], which is unspecified; convert an
+/// `IndexMap` (with the `indexmap` feature) or a [`Vec`] of key/value pairs when the order matters.
+impl<K: AsRef<[u8]>, V: Into<WafObject>, S> From<HashMap<K, V, S>> for WafMap {
+    fn from(value: HashMap<K, V, S>) -> Self {
+        let effective_length = LengthError::checked_len("map", value.len()).unwrap_or(u16::MAX);
+        let mut map = Self::new(effective_length);
+        for (i, (k, v)) in value
+            .into_iter()
+            .take(usize::from(effective_length))
+            .enumerate()
+        {
+            map[i] = Keyed::from((k.as_ref(), v.into()));
+        }
+        map
+    }
+}
+/// Converts an [`IndexMap`](indexmap::IndexMap) into a [`WafMap`], preserving the order of its
+/// entries.
+///
+/// Only the first [`u16::MAX`] entries are used; any extra entries are ignored.
+#[cfg(feature = "indexmap")]
+impl<K: AsRef<[u8]>, V: Into<WafObject>, S> From<indexmap::IndexMap<K, V, S>> for WafMap {
+    fn from(value: indexmap::IndexMap<K, V, S>) -> Self {
+        let effective_length = LengthError::checked_len("map", value.len()).unwrap_or(u16::MAX);
+        let mut map = Self::new(effective_length);
+        for (i, (k, v)) in value
+            .into_iter()
+            .take(usize::from(effective_length))
+            .enumerate()
+        {
+            map[i] = Keyed::from((k.as_ref(), v.into()));
+        }
+        map
+    }
+}
 
 impl fmt::Debug for WafBool {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1436,6 +2779,13 @@ impl From<f64> for WafFloat {
         Self::new(value)
     }
 }
+/// Widens the value to an [`f64`], which is exact: every [`f32`] value (including infinities) is
+/// represented identically.
+impl From<f32> for WafFloat {
+    fn from(value: f32) -> Self {
+        Self::new(value.into())
+    }
+}
 
 impl fmt::Debug for WafNull {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -1468,6 +2818,71 @@ impl<T: AsRawMutObject> Keyed<T> {
         ret
     }
 
+    /// Creates a new [`Keyed`] with the provided static key and value.
+    ///
+    /// The key is not copied: the resulting entry points directly at the static data, which makes
+    /// this suitable for encoding well-known keys (such as common header names) in hot loops.
+    ///
+    /// # Panics
+    /// Panics if the key is larger than [`u32::MAX`] bytes.
+    pub fn with_static_key(key: &'static str, value: T) -> Self {
+        Self::new(WafString::new_literal(key.as_bytes()), value)
+    }
+
+    /// Creates a new [`Keyed`] with the provided key and value, taking ownership of the key's
+    /// allocation instead of copying it.
+    ///
+    /// # Panics
+    /// Panics if the key is larger than [`u32::MAX`] bytes.
+    #[allow(clippy::expect_used)] // Documented panic
+    pub fn from_key_bytes(key: Box<[u8]>, value: T) -> Self {
+        Self::new(
+            WafString::new_boxed(key).expect("key is too large for this platform"),
+            value,
+        )
+    }
+
+    /// Replaces the key of this [`Keyed`] with the provided one, taking ownership of its
+    /// allocation instead of copying it. The previous key is dropped.
+    ///
+    /// # Panics
+    /// Panics if the key is larger than [`u32::MAX`] bytes.
+    #[allow(clippy::expect_used)] // Documented panic
+    pub fn set_key_boxed(&mut self, key: Box<[u8]>) {
+        *self.key_mut() = WafString::new_boxed(key)
+            .expect("key is too large for this platform")
+            .into();
+    }
+
+    /// Replaces the key of this [`Keyed`] with a copy of the provided string. The previous key is
+    /// dropped.
+    ///
+    /// # Errors
+    /// Returns an error if the key is larger than [`u32::MAX`] bytes, in which case the previous
+    /// key is left unchanged.
+    pub fn try_set_key_str(&mut self, key: &str) -> Result<&mut Self, LengthError> {
+        *self.key_mut() = WafString::try_new(key)?.into();
+        Ok(self)
+    }
+
+    /// Replaces the key of this [`Keyed`] with a copy of the provided bytes, after checking that
+    /// they are valid UTF-8. The previous key is dropped.
+    ///
+    /// # Errors
+    /// Returns an error if the key is not valid UTF-8, under the same conditions as
+    /// [`std::str::from_utf8`], in which case the previous key is left unchanged.
+    ///
+    /// # Panics
+    /// Panics if the key is larger than [`u32::MAX`] bytes.
+    #[allow(clippy::expect_used)] // Documented panic
+    pub fn set_key_checked(&mut self, key: &[u8]) -> Result<&mut Self, std::str::Utf8Error> {
+        let key = std::str::from_utf8(key)?;
+        *self.key_mut() = WafString::new(key)
+            .expect("key is too large for this platform")
+            .into();
+        Ok(self)
+    }
+
     // Obtains a reference to the map entry key.
     #[must_use]
     pub fn key(&self) -> &WafObject {
@@ -1492,6 +2907,20 @@ impl<T: AsRawMutObject> Keyed<T> {
         unsafe { self.raw.val.unchecked_as_ref_mut() }
     }
 
+    /// Returns `true` if this entry and `other` have equal values, regardless of their keys. This
+    /// is what comparing a [`Keyed`] with an unkeyed object does.
+    #[must_use]
+    pub fn eq_ignore_key<U: AsRawMutObject>(&self, other: &Keyed<U>) -> bool {
+        self.raw.val == other.raw.val
+    }
+
+    /// Returns `true` if this entry and `other` have equal keys and equal values. This is what
+    /// comparing two [`Keyed`]s with `==` does.
+    #[must_use]
+    pub fn eq_with_key<U: AsRawMutObject>(&self, other: &Keyed<U>) -> bool {
+        self.raw.key == other.raw.key && self.raw.val == other.raw.val
+    }
+
     /// Obtains the key associated with this [`Keyed<WafObject>`] as a string.
     ///
     /// # Errors
@@ -1534,6 +2963,28 @@ impl Keyed<WafObject> {
             None
         }
     }
+
+    /// Converts this [`Keyed<WafObject>`] into a [`Keyed<T>`], retaining its key. This is the
+    /// reverse of the [`From<Keyed<T>>`] conversion.
+    ///
+    /// # Errors
+    /// Returns an error if the value is not of type `T`, in which case both the key and the value
+    /// are dropped.
+    pub fn try_into_typed<T: TypedWafObject>(self) -> Result<Keyed<T>, ObjectTypeError> {
+        let actual = self.value().object_type();
+        if actual != T::TYPE {
+            return Err(ObjectTypeError {
+                expected: T::TYPE,
+                actual,
+            });
+        }
+        let res = Keyed {
+            raw: self.raw,
+            _marker: std::marker::PhantomData,
+        };
+        std::mem::forget(self);
+        Ok(res)
+    }
 }
 // Note - We are not implementing DerefMut for Keyed as it'd allow leaking the key if it is used
 // through [std::mem::take] or [std::mem::replace].
@@ -1590,6 +3041,19 @@ impl<T: AsRawMutObject> Deref for Keyed<T> {
         self.value()
     }
 }
+/// Compares both the keys and the values of two entries; see [`Keyed::eq_ignore_key`] to only
+/// compare their values.
+impl<T: AsRawMutObject, U: AsRawMutObject> cmp::PartialEq<Keyed<U>> for Keyed<T> {
+    fn eq(&self, other: &Keyed<U>) -> bool {
+        self.eq_with_key(other)
+    }
+}
+/// Compares the value of this entry with an unkeyed object, ignoring the key.
+impl<T: AsRawMutObject> cmp::PartialEq<WafObject> for Keyed<T> {
+    fn eq(&self, other: &WafObject) -> bool {
+        self.raw.val == other.raw
+    }
+}
 impl<T: AsRawMutObject> std::ops::Drop for Keyed<T> {
     fn drop(&mut self) {
         unsafe { self.raw.key.drop_object() };
@@ -1704,7 +3168,7 @@ impl UncheckedAsRef for libddwaf_sys::ddwaf_object {
         unsafe { &mut *(std::ptr::from_mut(self).cast()) }
     }
 }
-trait UncheckedAsWafObject: crate::private::Sealed {
+pub(crate) trait UncheckedAsWafObject: crate::private::Sealed {
     /// Converts a naked reference to a [`libddwaf_sys::ddwaf_object`] into a reference to an [`WafObject`].
     fn as_object_ref(&self) -> &WafObject;
 }
@@ -1761,7 +3225,11 @@ impl RustDdwafAllocator {
     ) -> *mut ::std::os::raw::c_void {
         let layout = Layout::from_size_align(size, alignment);
         if let Ok(layout) = layout {
-            unsafe { std::alloc::alloc(layout).cast() }
+            let ptr = unsafe { std::alloc::alloc(layout) };
+            if !ptr.is_null() {
+                track!(RUST_ALLOCATIONS, 1);
+            }
+            ptr.cast()
         } else {
             debug_assert!(false, "Invalid layout");
             std::ptr::null_mut()
@@ -1776,7 +3244,10 @@ impl RustDdwafAllocator {
     ) {
         let layout = Layout::from_size_align(size, alignment);
         match layout {
-            Ok(layout) => unsafe { std::alloc::dealloc(ptr.cast(), layout) },
+            Ok(layout) => {
+                unsafe { std::alloc::dealloc(ptr.cast(), layout) };
+                track!(RUST_ALLOCATIONS, -1);
+            }
             Err(_) => {
                 debug_assert!(false, "Invalid layout");
             }
@@ -1802,8 +3273,10 @@ unsafe impl Send for RustDdwafAllocator {}
 
 static DEFAULT_ALLOCATOR: OnceLock<RustDdwafAllocator> = OnceLock::new();
 
+#[allow(clippy::expect_used)] // No WAF evaluation can happen without an allocator
 pub(crate) fn get_default_allocator() -> &'static RustDdwafAllocator {
-    DEFAULT_ALLOCATOR.get_or_init(|| RustDdwafAllocator::new().unwrap())
+    DEFAULT_ALLOCATOR
+        .get_or_init(|| RustDdwafAllocator::new().expect("failed to create the WAF allocator"))
 }
 
 /// Helper macro to create [`WafObject`]s.
@@ -1824,7 +3297,7 @@ macro_rules! waf_array {
     ($($e:expr),* $(,)?) => {
         {
             let size = [$($crate::__repl_expr_with_unit!($e)),*].len();
-            let mut res = $crate::object::WafArray::new(size as u16);
+            let mut res = $crate::object::WafArray::new(u16::try_from(size).unwrap());
             let mut i = usize::MAX;
             $(
                 i = i.wrapping_add(1);
@@ -1855,6 +3328,37 @@ macro_rules! waf_map {
     };
 }
 
+/// Helper macro to look up a value nested in [`WafObject`]s, returning an
+/// `Option<&WafObject>`.
+///
+/// The first argument is the object to start from (any [`WafObject`] or typed object, such as a
+/// [`WafMap`]), followed by the [`PathSegment`]s to follow: string keys select entries of maps,
+/// and `usize` indices select elements of arrays. [`None`] is returned as soon as a segment does
+/// not match.
+///
+/// # Example
+/// ```
+/// use libddwaf::{waf_array, waf_get, waf_map};
+///
+/// let event = waf_map!(("rule", waf_map!(("id", "ua0-600-12x"))), ("rule_matches", waf_array!(42u64)));
+/// assert_eq!(waf_get!(event, "rule", "id").and_then(|id| id.to_str()), Some("ua0-600-12x"));
+/// assert_eq!(waf_get!(event, "rule_matches", 0).and_then(|m| m.to_u64()), Some(42));
+/// assert!(waf_get!(event, "rule", 0).is_none());
+/// ```
+#[macro_export]
+macro_rules! waf_get {
+    ($obj:expr $(, $seg:expr)* $(,)?) => {
+        {
+            let obj: ::core::option::Option<&$crate::object::WafObject> =
+                ::core::option::Option::Some($crate::object::__path_root(&$obj));
+            $(
+                let obj = obj.and_then(|o| $crate::object::PathSegment::select(&$seg, o));
+            )*
+            obj
+        }
+    };
+}
+
 /// Helper macro to facilitate counting token trees within other macros.
 ///
 /// Not intended for use outside of this crate, but must be exported as it is used by macros in this crate.
@@ -1868,6 +3372,7 @@ macro_rules! __repl_expr_with_unit {
 
 #[cfg(test)]
 #[cfg_attr(coverage_nightly, coverage(off))]
+#[allow(clippy::unwrap_used, clippy::expect_used)]
 mod tests {
     use std::str::FromStr;
 
@@ -1948,4 +3453,114 @@ mod tests {
         assert_eq!(*ns, ss);
         assert_eq!(*ns, ls);
     }
+
+    #[test]
+    fn lengths_are_checked() {
+        assert_eq!(<u16 as Length>::MAX, MAX_CONTAINER_ENTRIES);
+        assert_eq!(<u32 as Length>::MAX, MAX_STRING_BYTES);
+        assert_eq!(u32::MAX.to_usize(), MAX_STRING_BYTES);
+        let err = LengthError::checked_len::<u16>("array", usize::MAX).unwrap_err();
+        assert_eq!(err.max, MAX_CONTAINER_ENTRIES);
+
+        let array: WafArray = (0..=u32::from(u16::MAX)).collect();
+        assert_eq!(array.len(), u16::MAX);
+
+        let small = WafString::new([b'a'; SMALL_STRING_SIZE]).unwrap();
+        assert_eq!(small.raw.obj_type(), libddwaf_sys::DDWAF_OBJ_SMALL_STRING);
+        assert_eq!(small.len().to_usize(), SMALL_STRING_SIZE);
+        let large = WafString::new([b'a'; SMALL_STRING_SIZE + 1]).unwrap();
+        assert_eq!(large.raw.obj_type(), libddwaf_sys::DDWAF_OBJ_STRING);
+        assert_eq!(large.len().to_usize(), SMALL_STRING_SIZE + 1);
+        let boxed = WafString::new_boxed(vec![b'a'; 300].into_boxed_slice()).unwrap();
+        assert_eq!(boxed.len(), 300);
+        assert_eq!(WafString::try_new("").map(|s| s.len()), Ok(0));
+        assert_eq!(WafString::try_new([b'a'; 300]).map(|s| s.len()), Ok(300));
+
+        // A string of the maximum length, without allocating it.
+        let longest = libddwaf_sys::ddwaf_object {
+            via: libddwaf_sys::_ddwaf_object__bindgen_ty_1 {
+                str_: libddwaf_sys::_ddwaf_object_string {
+                    #[allow(clippy::cast_possible_truncation)]
+                    type_: libddwaf_sys::DDWAF_OBJ_STRING as u8,
+                    size: u32::MAX,
+                    ptr: std::ptr::NonNull::dangling().as_ptr(),
+                },
+            },
+        };
+        let longest = unsafe { longest.unchecked_as_ref::<WafString>() };
+        assert_eq!(longest.len().to_usize(), MAX_STRING_BYTES);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn length_too_large_converts_to_length_error() {
+        let err = LengthTooLargeError {
+            length: MAX_CONTAINER_ENTRIES + 1,
+            max_length: MAX_CONTAINER_ENTRIES,
+        };
+        assert_eq!(
+            LengthError::from(err),
+            LengthError {
+                what: "value",
+                len: MAX_CONTAINER_ENTRIES + 1,
+                max: MAX_CONTAINER_ENTRIES,
+            }
+        );
+    }
+
+    /// Objects produced by `libddwaf` are trusted, but their accessors must not panic (or read out
+    /// of bounds) when their lengths are invalid.
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn fabricated_lengths_do_not_panic() {
+        // A small string claiming more bytes than its inline storage holds.
+        let bogus_small = libddwaf_sys::ddwaf_object {
+            via: libddwaf_sys::_ddwaf_object__bindgen_ty_1 {
+                sstr: libddwaf_sys::_ddwaf_object_small_string {
+                    type_: libddwaf_sys::DDWAF_OBJ_SMALL_STRING as u8,
+                    size: u8::MAX,
+                    data: [b'a' as std::os::raw::c_char; 14],
+                },
+            },
+        };
+        let bogus_small = unsafe { bogus_small.unchecked_as_ref::<WafString>() };
+        let len = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| bogus_small.len()));
+        if cfg!(any(debug_assertions, feature = "strict-asserts")) {
+            assert!(len.is_err(), "the invalid size was not reported");
+        } else {
+            assert_eq!(len.ok(), Some(SMALL_STRING_SIZE as u32));
+            assert_eq!(bogus_small.as_bytes(), [b'a'; SMALL_STRING_SIZE]);
+        }
+
+        // Containers claiming the maximum number of entries, without allocating them.
+        let bogus_array = libddwaf_sys::ddwaf_object {
+            via: libddwaf_sys::_ddwaf_object__bindgen_ty_1 {
+                array: libddwaf_sys::_ddwaf_object_array {
+                    type_: libddwaf_sys::DDWAF_OBJ_ARRAY as u8,
+                    size: u16::MAX,
+                    capacity: 0,
+                    ptr: std::ptr::NonNull::dangling().as_ptr(),
+                },
+            },
+        };
+        let bogus_array = unsafe { bogus_array.unchecked_as_ref::<WafArray>() };
+        assert_eq!(bogus_array.len(), u16::MAX);
+        assert!(!bogus_array.is_empty());
+        assert_eq!(usize::from(bogus_array.len()), MAX_CONTAINER_ENTRIES);
+
+        let bogus_map = libddwaf_sys::ddwaf_object {
+            via: libddwaf_sys::_ddwaf_object__bindgen_ty_1 {
+                map: libddwaf_sys::_ddwaf_object_map {
+                    type_: libddwaf_sys::DDWAF_OBJ_MAP as u8,
+                    size: u16::MAX,
+                    capacity: 0,
+                    ptr: std::ptr::NonNull::dangling().as_ptr(),
+                },
+            },
+        };
+        let bogus_map = unsafe { bogus_map.unchecked_as_ref::<WafMap>() };
+        assert_eq!(bogus_map.len(), u16::MAX);
+        assert!(!bogus_map.is_empty());
+        assert_eq!(usize::from(bogus_map.len()), MAX_CONTAINER_ENTRIES);
+    }
 }
@@ -0,0 +1,29 @@
+//! Unsafe access to the raw `libddwaf` representation of [`WafObject`]s.
+//!
+//! This is an expert API, intended for interoperating with other code that manipulates
+//! [`libddwaf_sys::ddwaf_object`]s directly. The safe methods of [`WafObject`] and of the
+//! [`TypedWafObject`] implementations (such as [`WafString::set`] or [`WafBool::set`]) should be
+//! preferred whenever they cover the need.
+
+#[cfg(doc)]
+use super::{TypedWafObject, WafBool, WafObject, WafString};
+
+/// This trait allow obtaining direct mutable access to the underlying memory
+/// backing a [`WafObject`] or [`TypedWafObject`] value.
+pub trait AsRawMutObject: crate::private::Sealed + AsRef<libddwaf_sys::ddwaf_object> {
+    /// Obtains a mutable reference to the underlying raw [`libddwaf_sys::ddwaf_object`].
+    ///
+    /// # Safety
+    /// The caller must ensure that:
+    /// - it does not change the [`libddwaf_sys::ddwaf_object::type_`] field,
+    /// - it does not change the pointers to values that don't outlive the [`libddwaf_sys::ddwaf_object`]
+    ///   itself, or whose memory cannot be recclaimed byt the destructor in the same way as the
+    ///   current value,
+    /// - it does not change the lengths in such a way that the object is no longer valid.
+    ///
+    /// Additionally, the caller would incur a memory leak if it dropped the value through the
+    /// returned reference (e.g, by calling [`std::mem::replace`]), since [`libddwaf_sys::ddwaf_object`] is
+    /// not [`Drop`] (see swapped destructors in
+    /// [this playground](https://play.rust-lang.org/?version=stable&mode=debug&edition=2021&gist=aeea4aba8f960bf0c63f6185f016a94d).
+    unsafe fn as_raw_mut(&mut self) -> &mut libddwaf_sys::ddwaf_object;
+}
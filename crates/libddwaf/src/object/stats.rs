@@ -0,0 +1,113 @@
+//! Process-wide counters of the resources managed by this crate that are still alive, available
+//! with the `leak-check` feature.
+//!
+//! These allow applications to assert, at the end of their own tests, that the WAF layer did not
+//! leak anything:
+//!
+//! ```rust
+//! use libddwaf::object::stats;
+//! use libddwaf::waf_map;
+//!
+//! let data = waf_map!(("key", "a value long enough to be stored out of line"));
+//! assert!(stats::live_rust_allocations() > 0);
+//! drop(data);
+//! stats::assert_all_released();
+//! ```
+//!
+//! The counters are shared by all threads, so [`assert_all_released`] must only be used once
+//! nothing else in the process holds resources from this crate (for example, not while other tests
+//! are running concurrently in the same binary), and after any value passed to
+//! [`defer_drop`](crate::object::defer_drop) has been dropped.
+
+use std::fmt;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+pub(crate) use libddwaf_sys::LIVE_RUST_ALLOCATIONS as RUST_ALLOCATIONS;
+pub(crate) static WAF_OWNED_OBJECTS: AtomicIsize = AtomicIsize::new(0);
+pub(crate) static CONTEXTS: AtomicIsize = AtomicIsize::new(0);
+pub(crate) static HANDLES: AtomicIsize = AtomicIsize::new(0);
+
+/// Returns the number of buffers allocated by Rust to back [`WafObject`](crate::object::WafObject)
+/// strings, arrays, and maps (including those allocated by `libddwaf` through the Rust allocator)
+/// that have not been released yet.
+#[must_use]
+pub fn live_rust_allocations() -> isize {
+    RUST_ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of [`WafOwned`](crate::object::WafOwned) values (including the outputs held
+/// by [`RunOutput`](crate::RunOutput)s) that have not been dropped yet.
+#[must_use]
+pub fn live_waf_owned_objects() -> isize {
+    WAF_OWNED_OBJECTS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of [`Context`](crate::Context)s and [`Subcontext`](crate::Subcontext)s that
+/// have not been dropped yet.
+#[must_use]
+pub fn contexts_alive() -> isize {
+    CONTEXTS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of [`Handle`](crate::Handle)s that have not been dropped yet.
+#[must_use]
+pub fn handles_alive() -> isize {
+    HANDLES.load(Ordering::Relaxed)
+}
+
+/// The values of all counters at a given time, as returned by [`snapshot`].
+///
+/// Negative values indicate that something was released more times than it was created, which is
+/// a bug in this crate.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// See [`live_rust_allocations`].
+    pub rust_allocations: isize,
+    /// See [`live_waf_owned_objects`].
+    pub waf_owned_objects: isize,
+    /// See [`contexts_alive`].
+    pub contexts: isize,
+    /// See [`handles_alive`].
+    pub handles: isize,
+}
+impl StatsSnapshot {
+    /// Returns true if all counters are zero.
+    #[must_use]
+    pub fn is_all_released(&self) -> bool {
+        *self == Self::default()
+    }
+}
+impl fmt::Display for StatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} Rust allocations, {} WAF-owned objects, {} contexts, {} handles",
+            self.rust_allocations, self.waf_owned_objects, self.contexts, self.handles
+        )
+    }
+}
+
+/// Returns the current values of all counters.
+#[must_use]
+pub fn snapshot() -> StatsSnapshot {
+    StatsSnapshot {
+        rust_allocations: live_rust_allocations(),
+        waf_owned_objects: live_waf_owned_objects(),
+        contexts: contexts_alive(),
+        handles: handles_alive(),
+    }
+}
+
+/// Asserts that all counters are zero, meaning that everything created by this crate was released.
+///
+/// # Panics
+/// Panics with the values of all counters if any of them is not zero.
+#[track_caller]
+pub fn assert_all_released() {
+    let snapshot = snapshot();
+    assert!(
+        snapshot.is_all_released(),
+        "Some resources were not released: {snapshot}"
+    );
+}
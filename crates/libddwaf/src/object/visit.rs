@@ -0,0 +1,350 @@
+use std::fmt;
+use std::ops::ControlFlow;
+
+use crate::object::{Keyed, WafArray, WafMap, WafObject, WafObjectType, WafString};
+
+/// A step of a [`WafPath`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WafPathSegment<'a> {
+    /// The key of a [`WafMap`] entry (empty if the key is not a string).
+    Key(&'a [u8]),
+    /// The index of a [`WafArray`] element.
+    Index(usize),
+}
+
+/// The location of a value within the tree traversed by [`walk`] or [`walk_mut`], as the chain of
+/// keys and indices leading to it from the root.
+///
+/// Keys are borrowed from the tree, so building a [`WafPath`] does not copy them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WafPath<'a> {
+    segments: Vec<WafPathSegment<'a>>,
+}
+impl<'a> WafPath<'a> {
+    /// Returns the segments of this [`WafPath`], starting from the root.
+    #[must_use]
+    pub fn segments(&self) -> &[WafPathSegment<'a>] {
+        &self.segments
+    }
+
+    /// Returns the depth of the value at this [`WafPath`], which is `0` for the root.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns the last segment of this [`WafPath`], or [`None`] for the root.
+    #[must_use]
+    pub fn last(&self) -> Option<WafPathSegment<'a>> {
+        self.segments.last().copied()
+    }
+}
+impl fmt::Display for WafPath<'_> {
+    /// Formats this [`WafPath`] like [`WafDifference::path`](crate::object::WafDifference::path):
+    /// `$` for the root, followed by `.key` for map entries and `[index]` for array items.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        for segment in &self.segments {
+            match segment {
+                WafPathSegment::Key(key) => write!(f, ".{}", String::from_utf8_lossy(key))?,
+                WafPathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A visitor over the values of a [`WafObject`] tree, driven by [`walk`].
+///
+/// Containers are reported by a call to [`WafVisitor::enter_array`] (or
+/// [`WafVisitor::enter_map`]) before their children, and a call to [`WafVisitor::leave_array`]
+/// (or [`WafVisitor::leave_map`]) after them. Any other value (including strings) is reported by
+/// [`WafVisitor::visit_scalar`]. Returning [`ControlFlow::Break`] from any method stops the walk
+/// immediately, and [`walk`] returns the value it carries (of type `B`), for example the first
+/// value the visitor was looking for.
+///
+/// All methods do nothing by default.
+pub trait WafVisitor<'a, B = ()> {
+    /// Visits a value that is neither a [`WafArray`] nor a [`WafMap`].
+    fn visit_scalar(&mut self, path: &WafPath<'a>, obj: &'a WafObject) -> ControlFlow<B> {
+        let _ = (path, obj);
+        ControlFlow::Continue(())
+    }
+
+    /// Visits a [`WafArray`], before its elements.
+    fn enter_array(&mut self, path: &WafPath<'a>, array: &'a WafArray) -> ControlFlow<B> {
+        let _ = (path, array);
+        ControlFlow::Continue(())
+    }
+
+    /// Visits a [`WafArray`], after its elements.
+    fn leave_array(&mut self, path: &WafPath<'a>, array: &'a WafArray) -> ControlFlow<B> {
+        let _ = (path, array);
+        ControlFlow::Continue(())
+    }
+
+    /// Visits a [`WafMap`], before its entries.
+    fn enter_map(&mut self, path: &WafPath<'a>, map: &'a WafMap) -> ControlFlow<B> {
+        let _ = (path, map);
+        ControlFlow::Continue(())
+    }
+
+    /// Visits a [`WafMap`], after its entries.
+    fn leave_map(&mut self, path: &WafPath<'a>, map: &'a WafMap) -> ControlFlow<B> {
+        let _ = (path, map);
+        ControlFlow::Continue(())
+    }
+}
+
+/// A visitor over the scalar values of a [`WafObject`] tree, driven by [`walk_mut`].
+///
+/// Like for [`WafVisitor`], returning [`ControlFlow::Break`] stops the walk immediately, and
+/// [`walk_mut`] returns the value it carries.
+pub trait WafVisitorMut<B = ()> {
+    /// Visits a value that is neither a [`WafArray`] nor a [`WafMap`].
+    ///
+    /// The value may be modified or replaced; replacing it with a [`WafArray`] or a [`WafMap`] is
+    /// allowed, but the new value is not walked.
+    fn visit_scalar_mut(&mut self, path: &WafPath<'_>, obj: &mut WafObject) -> ControlFlow<B>;
+}
+
+/// Walks the tree rooted at `obj` in depth-first order, reporting each value to the `visitor`.
+///
+/// The walk uses an explicit stack rather than recursion, so it is not limited in depth by the
+/// size of the thread's stack. It returns [`ControlFlow::Break`] if the `visitor` stopped it, in
+/// which case the values that follow are not visited.
+pub fn walk<'a, B, V: WafVisitor<'a, B> + ?Sized>(
+    obj: &'a WafObject,
+    visitor: &mut V,
+) -> ControlFlow<B> {
+    let mut path = WafPath::default();
+    let mut stack = Vec::new();
+    visit_node(obj, &mut path, &mut stack, visitor)?;
+    while let Some(frame) = stack.last_mut() {
+        let index = frame.next;
+        frame.next += 1;
+        let child = match frame.container {
+            Container::Array(array) => array
+                .get(index)
+                .map(|item| (WafPathSegment::Index(index), item)),
+            Container::Map(map) => {
+                let entries: &'a [Keyed<WafObject>] = map.as_ref();
+                entries.get(index).map(|entry| {
+                    let key = entry.key_bytes().unwrap_or_default();
+                    (WafPathSegment::Key(key), entry.value())
+                })
+            }
+        };
+        if let Some((segment, child)) = child {
+            path.segments.push(segment);
+            visit_node(child, &mut path, &mut stack, visitor)?;
+        } else {
+            let container = frame.container;
+            stack.pop();
+            match container {
+                Container::Array(array) => visitor.leave_array(&path, array)?,
+                Container::Map(map) => visitor.leave_map(&path, map)?,
+            }
+            path.segments.pop();
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+/// A container being walked by [`walk`], along with the index of its next child.
+struct Frame<'a> {
+    container: Container<'a>,
+    next: usize,
+}
+#[derive(Clone, Copy)]
+enum Container<'a> {
+    Array(&'a WafArray),
+    Map(&'a WafMap),
+}
+
+/// Reports `obj` to the `visitor`, and pushes a [`Frame`] for it if it is a container. The last
+/// segment of `path`, which leads to `obj`, is removed once `obj` has been completely visited.
+fn visit_node<'a, B, V: WafVisitor<'a, B> + ?Sized>(
+    obj: &'a WafObject,
+    path: &mut WafPath<'a>,
+    stack: &mut Vec<Frame<'a>>,
+    visitor: &mut V,
+) -> ControlFlow<B> {
+    let container = match obj.object_type() {
+        WafObjectType::Array => {
+            let array = unsafe { obj.as_type_unchecked::<WafArray>() };
+            visitor.enter_array(path, array)?;
+            Container::Array(array)
+        }
+        WafObjectType::Map => {
+            let map = unsafe { obj.as_type_unchecked::<WafMap>() };
+            visitor.enter_map(path, map)?;
+            Container::Map(map)
+        }
+        _ => {
+            visitor.visit_scalar(path, obj)?;
+            path.segments.pop();
+            return ControlFlow::Continue(());
+        }
+    };
+    stack.push(Frame { container, next: 0 });
+    ControlFlow::Continue(())
+}
+
+/// Walks the tree rooted at `obj` in depth-first order like [`walk`] does, but only reports its
+/// scalar values, which the `visitor` can modify.
+///
+/// The structure of the tree cannot be changed during the walk: the `visitor` is never given
+/// access to [`WafArray`]s, [`WafMap`]s, or map keys. It returns [`ControlFlow::Break`] if the
+/// `visitor` stopped it.
+pub fn walk_mut<B, V: WafVisitorMut<B> + ?Sized>(
+    obj: &mut WafObject,
+    visitor: &mut V,
+) -> ControlFlow<B> {
+    let mut path = WafPath::default();
+    let mut stack: Vec<FrameMut> = Vec::new();
+    let root: *mut WafObject = obj;
+    // SAFETY: `root` comes from a mutable reference, and the children of the containers in `stack`
+    // remain valid as the visitor cannot modify the containers themselves.
+    unsafe { visit_node_mut(root, &mut path, &mut stack, visitor)? };
+    while let Some(frame) = stack.last_mut() {
+        if frame.next >= frame.len {
+            stack.pop();
+            path.segments.pop();
+            continue;
+        }
+        let index = frame.next;
+        frame.next += 1;
+        let (segment, child) = match frame.container {
+            ContainerMut::Array(items) => {
+                (WafPathSegment::Index(index), unsafe { items.add(index) })
+            }
+            ContainerMut::Map(entries) => unsafe {
+                let entry = entries.add(index);
+                // Only borrow the key of the entry, so it does not alias the value.
+                let key = &*std::ptr::addr_of!((*entry).raw.key).cast::<WafObject>();
+                let value = std::ptr::addr_of_mut!((*entry).raw.val).cast::<WafObject>();
+                let key = key
+                    .as_type::<WafString>()
+                    .map_or(&[][..], WafString::as_bytes);
+                (WafPathSegment::Key(key), value)
+            },
+        };
+        path.segments.push(segment);
+        unsafe { visit_node_mut(child, &mut path, &mut stack, visitor)? };
+    }
+    ControlFlow::Continue(())
+}
+
+/// A container being walked by [`walk_mut`], along with the index of its next child.
+struct FrameMut {
+    container: ContainerMut,
+    len: usize,
+    next: usize,
+}
+#[derive(Clone, Copy)]
+enum ContainerMut {
+    Array(*mut WafObject),
+    Map(*mut Keyed<WafObject>),
+}
+
+/// Reports `obj` to the `visitor` if it is a scalar, or pushes a [`FrameMut`] for it otherwise.
+///
+/// # Safety
+/// `obj` must be valid for reads and writes, and not be aliased, for the duration of the walk.
+unsafe fn visit_node_mut<'p, B, V: WafVisitorMut<B> + ?Sized>(
+    obj: *mut WafObject,
+    path: &mut WafPath<'p>,
+    stack: &mut Vec<FrameMut>,
+    visitor: &mut V,
+) -> ControlFlow<B> {
+    let obj = unsafe { &mut *obj };
+    let frame = if let Some(array) = obj.as_type_mut::<WafArray>() {
+        let items: &mut [WafObject] = array.as_mut();
+        FrameMut {
+            container: ContainerMut::Array(items.as_mut_ptr()),
+            len: items.len(),
+            next: 0,
+        }
+    } else if let Some(map) = obj.as_type_mut::<WafMap>() {
+        let entries: &mut [Keyed<WafObject>] = map.as_mut();
+        FrameMut {
+            container: ContainerMut::Map(entries.as_mut_ptr()),
+            len: entries.len(),
+            next: 0,
+        }
+    } else {
+        visitor.visit_scalar_mut(path, obj)?;
+        path.segments.pop();
+        return ControlFlow::Continue(());
+    };
+    stack.push(frame);
+    ControlFlow::Continue(())
+}
+
+/// Statistics about a [`WafObject`] tree, as returned by [`WafObject::stats`].
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WafObjectStats {
+    /// The number of values in the tree (including the root, but not map keys).
+    pub nodes: usize,
+    /// The number of [`WafArray`]s and [`WafMap`]s in the tree.
+    pub containers: usize,
+    /// The number of string values in the tree (not including map keys).
+    pub strings: usize,
+    /// The total length of the string values in the tree, in bytes.
+    pub string_bytes: usize,
+    /// The depth of the deepest value in the tree, which is `0` if the root is a scalar or an
+    /// empty container.
+    pub max_depth: usize,
+    /// The number of map keys in the tree.
+    pub keys: usize,
+    /// The total length of the map keys in the tree, in bytes.
+    pub key_bytes: usize,
+}
+impl WafObjectStats {
+    /// Returns an estimate of the memory used by the tree, in bytes: the size of its values and
+    /// map keys, plus the length of its strings.
+    ///
+    /// This does not account for the allocator's overhead, nor for short strings that are stored
+    /// inline, so it is an approximation rather than an exact count.
+    #[must_use]
+    pub fn estimated_bytes(&self) -> usize {
+        let objects = self.nodes.saturating_add(self.keys);
+        objects
+            .saturating_mul(std::mem::size_of::<libddwaf_sys::ddwaf_object>())
+            .saturating_add(self.string_bytes)
+            .saturating_add(self.key_bytes)
+    }
+
+    fn count(&mut self, path: &WafPath<'_>) {
+        self.nodes += 1;
+        self.max_depth = self.max_depth.max(path.depth());
+    }
+}
+impl<'a> WafVisitor<'a> for WafObjectStats {
+    fn visit_scalar(&mut self, path: &WafPath<'a>, obj: &'a WafObject) -> ControlFlow<()> {
+        self.count(path);
+        if let Some(string) = obj.as_type::<WafString>() {
+            self.strings += 1;
+            self.string_bytes += string.as_bytes().len();
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn enter_array(&mut self, path: &WafPath<'a>, _: &'a WafArray) -> ControlFlow<()> {
+        self.count(path);
+        self.containers += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn enter_map(&mut self, path: &WafPath<'a>, map: &'a WafMap) -> ControlFlow<()> {
+        self.count(path);
+        self.containers += 1;
+        for entry in map.iter() {
+            self.keys += 1;
+            self.key_bytes += entry.key_bytes().map_or(0, <[u8]>::len);
+        }
+        ControlFlow::Continue(())
+    }
+}
@@ -0,0 +1,373 @@
+//! A [`RequestEvaluation`] driving the evaluation of an HTTP request through the phases documented
+//! by `libddwaf`: the request headers as soon as they are received, the request body once it is
+//! available, and the response status and headers at the end.
+//!
+//! All phases share a single [`Context`], so that rules can match on data submitted in different
+//! phases, and a single time budget. The data of each phase is provided as [`WafMap`]s and
+//! [`WafObject`]s, so that this is not tied to any particular HTTP framework:
+//!
+//! ```no_run
+//! use libddwaf::phases::RequestEvaluation;
+//! use libddwaf::{waf_map, Handle};
+//!
+//! # fn f(handle: &Handle) {
+//! let mut request = RequestEvaluation::new(handle);
+//! let outcome = request.on_request_headers(waf_map!(("user-agent", "Arachni/v1")));
+//! if outcome.is_blocking() {
+//!     // Respond as instructed by `outcome.blocking_action()`, and skip the remaining phases.
+//! }
+//! request.on_response(200, waf_map!(("content-type", "text/html")));
+//! let summary = request.finish();
+//! println!("{} events, blocked: {}", summary.events.len(), summary.blocked());
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use crate::addresses::AddressMapBuilder;
+use crate::object::{Keyed, WafMap, WafObject};
+use crate::{Context, Handle, RunError, RunOutput, RunnableContext};
+
+/// The time budget shared by all the phases of a [`RequestEvaluation`], unless configured with
+/// [`RequestEvaluation::with_budget`].
+pub const DEFAULT_BUDGET: Duration = Duration::from_millis(5);
+
+/// An action produced by the rules that matched.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// The request must be blocked (`block_request`).
+    Block {
+        /// The HTTP status code of the blocking response.
+        status_code: u16,
+        /// The gRPC status code of the blocking response.
+        grpc_status_code: u16,
+        /// The content type of the blocking response (`auto`, `json`, `html` or `none`).
+        response_type: String,
+    },
+    /// The request must be redirected (`redirect_request`).
+    Redirect {
+        /// The HTTP status code of the redirection.
+        status_code: u16,
+        /// The target of the redirection.
+        location: String,
+    },
+    /// A stack trace must be collected and attached to the event (`generate_stack`).
+    GenerateStack {
+        /// The identifier of the stack trace, referenced by the event.
+        stack_id: String,
+    },
+    /// Any other action.
+    Other {
+        /// The type of the action.
+        name: String,
+        /// The parameters of the action.
+        parameters: WafMap,
+    },
+}
+impl Action {
+    /// Returns the type of this action, as reported by `libddwaf` (e.g, `block_request`).
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Block { .. } => "block_request",
+            Self::Redirect { .. } => "redirect_request",
+            Self::GenerateStack { .. } => "generate_stack",
+            Self::Other { name, .. } => name,
+        }
+    }
+
+    /// Returns true if this action blocks or redirects the request.
+    #[must_use]
+    pub fn is_blocking(&self) -> bool {
+        matches!(self, Self::Block { .. } | Self::Redirect { .. })
+    }
+
+    fn from_entry(entry: &Keyed<WafObject>) -> Option<Self> {
+        let name = entry.key_str().ok()?;
+        let parameters = entry.as_type::<WafMap>();
+        let string = |key: &str| {
+            parameters
+                .and_then(|p| p.get_str(key)?.to_str())
+                .map(str::to_string)
+        };
+        // Numeric parameters may be reported either as integers or as strings.
+        let number = |key: &str, default: u16| {
+            parameters
+                .and_then(|p| p.get_str(key))
+                .and_then(|value| match value.to_u64() {
+                    Some(value) => u16::try_from(value).ok(),
+                    None => value.to_str()?.parse().ok(),
+                })
+                .unwrap_or(default)
+        };
+        Some(match name {
+            "block_request" => Self::Block {
+                status_code: number("status_code", 403),
+                grpc_status_code: number("grpc_status_code", 10),
+                response_type: string("type").unwrap_or_else(|| "auto".to_string()),
+            },
+            "redirect_request" => Self::Redirect {
+                status_code: number("status_code", 303),
+                location: string("location").unwrap_or_default(),
+            },
+            "generate_stack" => Self::GenerateStack {
+                stack_id: string("stack_id").unwrap_or_default(),
+            },
+            _ => Self::Other {
+                name: name.to_string(),
+                parameters: parameters.map(|p| p.value().clone()).unwrap_or_default(),
+            },
+        })
+    }
+}
+
+/// The actions produced by an evaluation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ActionSet {
+    actions: Vec<Action>,
+}
+impl ActionSet {
+    /// Returns true if no action was produced.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Returns the number of actions that were produced.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Returns an iterator over the actions, in the order they were produced.
+    pub fn iter(&self) -> impl Iterator<Item = &Action> {
+        self.actions.iter()
+    }
+
+    /// Returns the action of the provided type, if it was produced.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Action> {
+        self.actions.iter().find(|action| action.name() == name)
+    }
+
+    /// Returns the first action blocking or redirecting the request, if any.
+    #[must_use]
+    pub fn blocking_action(&self) -> Option<&Action> {
+        self.actions.iter().find(|action| action.is_blocking())
+    }
+}
+impl From<&RunOutput> for ActionSet {
+    fn from(output: &RunOutput) -> Self {
+        let actions = output
+            .actions()
+            .into_iter()
+            .flat_map(Keyed::<WafMap>::iter)
+            .filter_map(Action::from_entry)
+            .collect();
+        Self { actions }
+    }
+}
+
+/// The outcome of one of the phases of a [`RequestEvaluation`].
+#[derive(Debug, Default)]
+pub struct PhaseOutcome {
+    actions: ActionSet,
+    matched: bool,
+    timeout: bool,
+    duration: Duration,
+    error: Option<RunError>,
+}
+impl PhaseOutcome {
+    /// Returns the actions produced by this phase.
+    #[must_use]
+    pub fn actions(&self) -> &ActionSet {
+        &self.actions
+    }
+
+    /// Returns the action blocking or redirecting the request, if this phase produced one; the
+    /// request is then expected to be interrupted, and the remaining phases skipped.
+    #[must_use]
+    pub fn blocking_action(&self) -> Option<&Action> {
+        self.actions.blocking_action()
+    }
+
+    /// Returns true if this phase produced an action blocking or redirecting the request.
+    #[must_use]
+    pub fn is_blocking(&self) -> bool {
+        self.blocking_action().is_some()
+    }
+
+    /// Returns true if some rules matched the data of this phase.
+    #[must_use]
+    pub fn is_match(&self) -> bool {
+        self.matched
+    }
+
+    /// Returns true if the evaluation of this phase ran out of time, either because the budget of
+    /// the [`RequestEvaluation`] was exhausted, or because the data took too long to evaluate.
+    #[must_use]
+    pub fn timeout(&self) -> bool {
+        self.timeout
+    }
+
+    /// Returns the time spent by the WAF evaluating the data of this phase.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the error that prevented the data of this phase from being evaluated, if any.
+    #[must_use]
+    pub fn error(&self) -> Option<&RunError> {
+        self.error.as_ref()
+    }
+}
+
+/// The summary of all the phases of a [`RequestEvaluation`], as returned by
+/// [`RequestEvaluation::finish`].
+#[non_exhaustive]
+#[derive(Debug, Default)]
+pub struct FinalOutcome {
+    /// The first action that blocked or redirected the request, if any phase produced one.
+    pub blocking_action: Option<Action>,
+    /// The events produced by all phases, to be reported with the trace of the request.
+    pub events: Vec<WafObject>,
+    /// The attributes produced by all phases, to be attached to the trace of the request. When
+    /// several phases produced the same attribute, the latest value is retained.
+    pub attributes: WafMap,
+    /// Whether any phase requested the trace to be kept (see [`RunOutput::keep`]).
+    pub keep: bool,
+    /// Whether any phase ran out of time.
+    pub timeout: bool,
+    /// The time spent by the WAF over all phases.
+    pub total_duration: Duration,
+}
+impl FinalOutcome {
+    /// Returns true if any phase blocked or redirected the request.
+    #[must_use]
+    pub fn blocked(&self) -> bool {
+        self.blocking_action.is_some()
+    }
+}
+
+/// The evaluation of a single HTTP request, through the phases documented by `libddwaf`.
+///
+/// Each phase evaluates its data with the part of the time budget (see
+/// [`RequestEvaluation::with_budget`]) that the previous phases did not use, and reports the
+/// actions it produced in a [`PhaseOutcome`]. Events and attributes are collected across phases,
+/// and reported by [`RequestEvaluation::finish`].
+pub struct RequestEvaluation {
+    context: Context,
+    budget: Duration,
+    outcome: FinalOutcome,
+    attributes: Vec<(Vec<u8>, WafObject)>,
+}
+impl RequestEvaluation {
+    /// Starts the evaluation of a new request, with a fresh [`Context`] created from `handle`, and
+    /// a time budget of [`DEFAULT_BUDGET`].
+    #[must_use]
+    pub fn new(handle: &Handle) -> Self {
+        Self {
+            context: handle.new_context(),
+            budget: DEFAULT_BUDGET,
+            outcome: FinalOutcome::default(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Sets the time budget shared by all the phases of this [`RequestEvaluation`].
+    #[must_use]
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Returns the [`Context`] holding the data of this request, for example to evaluate the
+    /// addresses of other products (such as [`USER_ID`](crate::addresses::USER_ID)) with
+    /// [`RequestEvaluation::on_addresses`].
+    #[must_use]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Returns true if a previous phase blocked or redirected the request.
+    #[must_use]
+    pub fn is_blocked(&self) -> bool {
+        self.outcome.blocked()
+    }
+
+    /// Evaluates the request headers (excluding cookies), as a map from lower-cased header names
+    /// to values.
+    pub fn on_request_headers(&mut self, headers: WafMap) -> PhaseOutcome {
+        self.on_addresses(AddressMapBuilder::new().request_headers(headers).build())
+    }
+
+    /// Evaluates the parsed request body (see the `http` module, with the `http` feature).
+    pub fn on_request_body(&mut self, body: impl Into<WafObject>) -> PhaseOutcome {
+        self.on_addresses(AddressMapBuilder::new().request_body(body).build())
+    }
+
+    /// Evaluates the response status code and headers (excluding cookies), as a map from
+    /// lower-cased header names to values.
+    pub fn on_response(&mut self, status: u16, headers: WafMap) -> PhaseOutcome {
+        self.on_addresses(
+            AddressMapBuilder::new()
+                .response_status(status)
+                .response_headers(headers)
+                .build(),
+        )
+    }
+
+    /// Evaluates arbitrary address data, such as the one built by an [`AddressMapBuilder`], as an
+    /// additional phase of this request.
+    pub fn on_addresses(&mut self, data: WafMap) -> PhaseOutcome {
+        let timeout = self.context.budget_remaining(self.budget);
+        let result = match self.context.run(data, timeout) {
+            Ok(result) => result,
+            Err(error) => {
+                return PhaseOutcome {
+                    error: Some(error),
+                    ..PhaseOutcome::default()
+                }
+            }
+        };
+        let output = result.output();
+        let phase = PhaseOutcome {
+            actions: ActionSet::from(output),
+            matched: result.is_match(),
+            timeout: output.timeout(),
+            duration: output.duration(),
+            error: None,
+        };
+
+        if self.outcome.blocking_action.is_none() {
+            self.outcome.blocking_action = phase.blocking_action().cloned();
+        }
+        self.outcome.keep |= output.keep();
+        self.outcome.timeout |= phase.timeout;
+        self.outcome
+            .events
+            .extend(output.events().into_iter().flat_map(|e| e.iter().cloned()));
+        for entry in output.attributes().into_iter().flat_map(|a| a.iter()) {
+            let Ok(key) = entry.key_bytes() else {
+                continue;
+            };
+            let value = entry.value().clone();
+            match self.attributes.iter_mut().find(|(k, _)| k == key) {
+                Some(existing) => existing.1 = value,
+                None => self.attributes.push((key.to_vec(), value)),
+            }
+        }
+        phase
+    }
+
+    /// Completes the evaluation of this request, and returns the summary of all of its phases.
+    #[must_use]
+    pub fn finish(self) -> FinalOutcome {
+        let mut outcome = self.outcome;
+        outcome.attributes = WafMap::from(self.attributes);
+        outcome.total_duration = self.context.total_duration();
+        outcome
+    }
+}
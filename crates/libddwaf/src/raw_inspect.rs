@@ -0,0 +1,259 @@
+//! Allocation-free inspection of raw [`ddwaf_object`]s, for use in constrained contexts such as
+//! signal handlers (for example, to report the data that was being evaluated when a crash
+//! occurred inside of `libddwaf`).
+//!
+//! The functions in this module do not allocate, do not take locks, do not panic, and do not
+//! recurse. They only read the objects they are given, which must nevertheless remain readable for
+//! the duration of the call.
+
+use std::fmt::{self, Write};
+
+use libddwaf_sys::{_ddwaf_object_kv, ddwaf_object};
+
+/// The maximum number of bytes of a string (or map key) included in a summary.
+pub const SUMMARY_STRING_BYTES: usize = 16;
+
+/// The maximum number of keys of a map included in a summary.
+pub const SUMMARY_MAP_KEYS: usize = 4;
+
+/// The maximum depth [`node_count_bounded`] descends to.
+pub const MAX_DEPTH: usize = 32;
+
+/// Writes a terse ASCII summary of `obj` into `out`, and returns the number of bytes written.
+///
+/// The summary describes the type of `obj` and, depending on it, its value (for scalars), its
+/// length and first [`SUMMARY_STRING_BYTES`] bytes (for strings), or its length and first
+/// [`SUMMARY_MAP_KEYS`] keys (for maps); for example `map(len=2, keys=["a", "b"])`. Bytes of
+/// strings that are not printable ASCII are hex-escaped (e.g, `\x0a`). The summary is silently
+/// truncated if `out` is too small to hold it; nothing is ever written past its end.
+///
+/// # Safety
+/// `obj` must either be null, or point to a [`ddwaf_object`] that can be read from, including the
+/// data it points to (when it is a string, array, or map).
+pub unsafe fn summarize(obj: *const ddwaf_object, out: &mut [u8]) -> usize {
+    let mut writer = BoundedWriter { out, len: 0 };
+    // Errors only indicate that the output was truncated.
+    let _ = unsafe { write_summary(&mut writer, obj) };
+    writer.len
+}
+
+/// Returns the number of nodes (scalars, strings, arrays, and maps; but not map keys) in the tree
+/// rooted at `obj`, counting at most `max` of them.
+///
+/// Nodes nested more than [`MAX_DEPTH`] levels below `obj` are not counted.
+///
+/// # Safety
+/// `obj` must either be null, or point to a [`ddwaf_object`] that can be read from, including all
+/// the data it (transitively) points to.
+#[must_use]
+pub unsafe fn node_count_bounded(obj: *const ddwaf_object, max: usize) -> usize {
+    let Some(obj) = (unsafe { obj.as_ref() }) else {
+        return 0;
+    };
+    if max == 0 {
+        return 0;
+    }
+
+    let mut stack = [Frame::EMPTY; MAX_DEPTH];
+    let mut depth = 0;
+    let mut count = 1;
+    if let (Some(frame), Some(slot)) = (Frame::of(obj), stack.first_mut()) {
+        *slot = frame;
+        depth = 1;
+    }
+    while count < max {
+        let Some(frame) = depth.checked_sub(1).and_then(|top| stack.get_mut(top)) else {
+            break;
+        };
+        let Some(child) = (unsafe { frame.next_child() }) else {
+            depth -= 1;
+            continue;
+        };
+        count += 1;
+        if let (Some(frame), Some(slot)) = (Frame::of(child), stack.get_mut(depth)) {
+            *slot = frame;
+            depth += 1;
+        }
+    }
+    count
+}
+
+/// The position of [`node_count_bounded`] within the children of an array or map.
+#[derive(Clone, Copy)]
+struct Frame {
+    array: *const ddwaf_object,
+    map: *const _ddwaf_object_kv,
+    len: usize,
+    next: usize,
+}
+impl Frame {
+    const EMPTY: Self = Self {
+        array: std::ptr::null(),
+        map: std::ptr::null(),
+        len: 0,
+        next: 0,
+    };
+
+    /// Returns a [`Frame`] over the children of `obj`, if it is a non-empty array or map.
+    fn of(obj: &ddwaf_object) -> Option<Self> {
+        let frame = match obj.obj_type() {
+            libddwaf_sys::DDWAF_OBJ_ARRAY => {
+                let array = unsafe { obj.via.array };
+                Self {
+                    array: array.ptr,
+                    len: usize::from(array.size),
+                    ..Self::EMPTY
+                }
+            }
+            libddwaf_sys::DDWAF_OBJ_MAP => {
+                let map = unsafe { obj.via.map };
+                Self {
+                    map: map.ptr,
+                    len: usize::from(map.size),
+                    ..Self::EMPTY
+                }
+            }
+            _ => return None,
+        };
+        (frame.len != 0 && !(frame.array.is_null() && frame.map.is_null())).then_some(frame)
+    }
+
+    /// Returns the next child of this [`Frame`], if any.
+    ///
+    /// # Safety
+    /// The children of this [`Frame`] must be readable.
+    unsafe fn next_child<'a>(&mut self) -> Option<&'a ddwaf_object> {
+        if self.next >= self.len {
+            return None;
+        }
+        let index = self.next;
+        self.next += 1;
+        if self.array.is_null() {
+            Some(unsafe { &(*self.map.add(index)).val })
+        } else {
+            Some(unsafe { &*self.array.add(index) })
+        }
+    }
+}
+
+/// A [`Write`] implementation over a fixed buffer, which fails once the buffer is full.
+struct BoundedWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+}
+impl Write for BoundedWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let Some(available) = self.out.get_mut(self.len..) else {
+            return Err(fmt::Error);
+        };
+        let mut written = 0;
+        for (dst, src) in available.iter_mut().zip(s.bytes()) {
+            *dst = src;
+            written += 1;
+        }
+        self.len += written;
+        if written < s.len() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+unsafe fn write_summary(w: &mut BoundedWriter<'_>, obj: *const ddwaf_object) -> fmt::Result {
+    let Some(obj) = (unsafe { obj.as_ref() }) else {
+        return w.write_str("<null>");
+    };
+    match obj.obj_type() {
+        libddwaf_sys::DDWAF_OBJ_INVALID => w.write_str("invalid"),
+        libddwaf_sys::DDWAF_OBJ_NULL => w.write_str("null"),
+        libddwaf_sys::DDWAF_OBJ_BOOL => write!(w, "bool({})", unsafe { obj.via.b8.val }),
+        libddwaf_sys::DDWAF_OBJ_SIGNED => write!(w, "signed({})", unsafe { obj.via.i64_.val }),
+        libddwaf_sys::DDWAF_OBJ_UNSIGNED => {
+            write!(w, "unsigned({})", unsafe { obj.via.u64_.val })
+        }
+        libddwaf_sys::DDWAF_OBJ_FLOAT => write!(w, "float({})", unsafe { obj.via.f64_.val }),
+        libddwaf_sys::DDWAF_OBJ_STRING
+        | libddwaf_sys::DDWAF_OBJ_LITERAL_STRING
+        | libddwaf_sys::DDWAF_OBJ_SMALL_STRING => {
+            let (bytes, len) = unsafe { string_prefix(obj) };
+            write!(w, "string(len={len}, ")?;
+            write_bytes(w, bytes, len)?;
+            w.write_str(")")
+        }
+        libddwaf_sys::DDWAF_OBJ_ARRAY => {
+            write!(w, "array(len={})", unsafe { obj.via.array.size })
+        }
+        libddwaf_sys::DDWAF_OBJ_MAP => {
+            let map = unsafe { obj.via.map };
+            write!(w, "map(len={}", map.size)?;
+            if !map.ptr.is_null() && map.size != 0 {
+                w.write_str(", keys=[")?;
+                let keys = usize::from(map.size).min(SUMMARY_MAP_KEYS);
+                for i in 0..keys {
+                    if i != 0 {
+                        w.write_str(", ")?;
+                    }
+                    let key = unsafe { &(*map.ptr.add(i)).key };
+                    if key.is_string() {
+                        let (bytes, len) = unsafe { string_prefix(key) };
+                        write_bytes(w, bytes, len)?;
+                    } else {
+                        w.write_str("?")?;
+                    }
+                }
+                if usize::from(map.size) > keys {
+                    w.write_str(", ...")?;
+                }
+                w.write_str("]")?;
+            }
+            w.write_str(")")
+        }
+        unknown => write!(w, "unknown(0x{unknown:02X})"),
+    }
+}
+
+/// Returns the first [`SUMMARY_STRING_BYTES`] bytes of the string `obj`, along with its length.
+///
+/// # Safety
+/// `obj` must be a string, whose data can be read from.
+unsafe fn string_prefix(obj: &ddwaf_object) -> (&[u8], usize) {
+    if obj.obj_type() == libddwaf_sys::DDWAF_OBJ_SMALL_STRING {
+        let sstr = unsafe { &obj.via.sstr };
+        let len = usize::from(sstr.size);
+        let prefix = len.min(sstr.data.len()).min(SUMMARY_STRING_BYTES);
+        let bytes = unsafe { std::slice::from_raw_parts(sstr.data.as_ptr().cast(), prefix) };
+        return (bytes, len);
+    }
+    let str = unsafe { obj.via.str_ };
+    let len = usize::try_from(str.size).unwrap_or(usize::MAX);
+    if str.ptr.is_null() {
+        return (&[], len);
+    }
+    let prefix = len.min(SUMMARY_STRING_BYTES);
+    (
+        unsafe { std::slice::from_raw_parts(str.ptr.cast(), prefix) },
+        len,
+    )
+}
+
+/// Writes `bytes` as a quoted, escaped string; followed by an ellipsis if they are only the
+/// beginning of a string of `len` bytes.
+fn write_bytes(w: &mut BoundedWriter<'_>, bytes: &[u8], len: usize) -> fmt::Result {
+    w.write_char('"')?;
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                w.write_char('\\')?;
+                w.write_char(char::from(byte))?;
+            }
+            0x20..=0x7E => w.write_char(char::from(byte))?,
+            _ => write!(w, "\\x{byte:02x}")?,
+        }
+    }
+    w.write_char('"')?;
+    if len > bytes.len() {
+        w.write_str("...")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Obfuscation of address data, mirroring what the WAF's obfuscator does to its outputs.
+//!
+//! This is intended for safely logging address data, for example while troubleshooting. It uses
+//! the same regular expressions as configured in the [`Obfuscator`](crate::Obfuscator), which
+//! can be obtained using [`Config::obfuscator`](crate::Config::obfuscator).
+//!
+//! The regular expressions are evaluated using the [`regex`] crate, while `libddwaf` uses RE2.
+//! Both engines support a largely identical syntax, and both lack look-around and
+//! back-references; but some rarely used constructs differ (such as some Unicode classes), and
+//! expressions using them may be rejected or interpreted differently.
+
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::object::{WafArray, WafMap, WafObject, WafObjectType, WafString};
+
+/// The value that replaces redacted data.
+pub const REDACTED: &str = "<Redacted>";
+
+/// Returns a copy of `obj` where sensitive data is replaced by [`REDACTED`].
+///
+/// Data is considered sensitive if:
+/// - it is the value of a map entry whose key matches `key_regex`, in which case the whole value
+///   is replaced, even if it is an array or a map;
+/// - it is a string matching `value_regex`.
+///
+/// Like in `libddwaf`, the regular expressions are matched case-insensitively, and may match any
+/// part of the key or value. The provided object is not modified, and nesting is traversed
+/// iteratively, so arbitrarily large objects can be processed.
+///
+/// # Errors
+/// Returns an error if either of the regular expressions is invalid.
+pub fn redact(
+    obj: &WafObject,
+    key_regex: &str,
+    value_regex: &str,
+) -> Result<WafObject, regex::Error> {
+    let key_regex = compile(key_regex)?;
+    let value_regex = compile(value_regex)?;
+
+    let mut res = obj.clone();
+    let mut stack = vec![&mut res];
+    while let Some(obj) = stack.pop() {
+        match obj.object_type() {
+            WafObjectType::String => {
+                let matched = obj
+                    .as_type::<WafString>()
+                    .is_some_and(|s| value_regex.is_match(s.as_bytes()));
+                if matched {
+                    *obj = REDACTED.into();
+                }
+            }
+            WafObjectType::Array => {
+                if let Some(arr) = obj.as_type_mut::<WafArray>() {
+                    stack.extend(arr.iter_mut());
+                }
+            }
+            WafObjectType::Map => {
+                let Some(map) = obj.as_type_mut::<WafMap>() else {
+                    continue;
+                };
+                for entry in map.iter_mut() {
+                    let matched = entry.key_bytes().is_ok_and(|key| key_regex.is_match(key));
+                    if matched {
+                        *entry.value_mut() = REDACTED.into();
+                    } else {
+                        stack.push(entry.value_mut());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(res)
+}
+
+fn compile(regex: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(regex).case_insensitive(true).build()
+}
@@ -0,0 +1,48 @@
+//! The recommended Datadog ruleset, embedded in the binary with the `bundled-ruleset` feature.
+//!
+//! The ruleset is downloaded from the `DataDog/appsec-event-rules` releases when this crate is
+//! built, and checked against the SHA-256 checksum pinned for the release. Offline and vendored
+//! builds can instead provide a local copy of it, by setting the `LIBDDWAF_RULESET_PATH`
+//! environment variable to the path of the JSON document; a different release can be requested
+//! with `LIBDDWAF_RULESET_VERSION`, along with its checksum in `LIBDDWAF_RULESET_SHA256`.
+//!
+//! ```no_run
+//! use libddwaf::Builder;
+//!
+//! let mut builder = Builder::new(None).unwrap();
+//! if let Err(diagnostics) = builder.with_recommended_ruleset() {
+//!     eprintln!("Failed to load the recommended ruleset: {diagnostics:?}");
+//! }
+//! let handle = builder.build();
+//! ```
+
+use std::sync::LazyLock;
+
+use crate::object::WafMap;
+#[cfg(doc)]
+use crate::Builder;
+
+/// The path under which [`Builder::with_recommended_ruleset`] adds the recommended ruleset.
+pub const RECOMMENDED_PATH: &str = "datadog/0/ASM_DD/0/recommended";
+
+static RECOMMENDED: &str = include_str!(concat!(env!("OUT_DIR"), "/recommended.json"));
+
+static RECOMMENDED_PARSED: LazyLock<WafMap> = LazyLock::new(|| {
+    serde_json::from_str(RECOMMENDED).expect("the bundled ruleset is not a valid JSON object")
+});
+
+/// Returns the JSON document of the recommended ruleset.
+#[must_use]
+pub fn recommended() -> &'static str {
+    RECOMMENDED
+}
+
+/// Returns the recommended ruleset, parsed into a [`WafMap`] the first time this is called.
+///
+/// # Panics
+/// Panics if the bundled document is not a JSON object, which can only happen if a different
+/// document was provided with `LIBDDWAF_RULESET_PATH`.
+#[must_use]
+pub fn recommended_parsed() -> &'static WafMap {
+    &RECOMMENDED_PARSED
+}
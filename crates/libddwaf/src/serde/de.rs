@@ -1,21 +1,10 @@
-//! Implementations of [`serde::Deserialize`] for [`object::WafObject`](crate::object::WafObject) and
-//! [`object::WafMap`](crate::object::WafMap).
-//!
-//! This module also provides [`Limits`] for applying constraints during deserialization,
-//! similar to the PHP extension's `dd_mpack_limits` structure.
+use std::cell::{Cell, RefCell};
+use std::ops::{Deref, DerefMut};
+use std::thread::LocalKey;
 
-use std::cell::Cell;
+use serde::{de::Error, Deserializer};
 
-use serde::{
-    de::Error,
-    ser::{SerializeMap, SerializeSeq},
-    Deserializer,
-};
-
-use crate::object::{
-    Keyed, WafArray, WafBool, WafFloat, WafMap, WafNull, WafObject, WafObjectType, WafSigned,
-    WafString, WafUnsigned,
-};
+use crate::object::{Keyed, LazyWafArray, WafArray, WafMap, WafNull, WafObject, WafString};
 
 impl<'de> serde::Deserialize<'de> for WafObject {
     fn deserialize<D>(deserializer: D) -> Result<WafObject, D::Error>
@@ -101,139 +90,29 @@ impl<'de> serde::de::Visitor<'de> for Visitor {
     where
         A: serde::de::SeqAccess<'de>,
     {
-        let mut vec = seq.size_hint().map(Vec::with_capacity).unwrap_or_default();
+        let mut items = ArrayBuffer::new(seq.size_hint());
         while let Some(value) = seq.next_element()? {
-            vec.push(value);
-        }
-        let mut res = WafArray::new(vec.len().try_into().unwrap());
-        for (i, v) in vec.into_iter().enumerate() {
-            res[i] = v;
+            items.push(value);
         }
-        Ok(res.into())
+        Ok(items.into_array()?.into())
     }
 
     fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
     where
         A: serde::de::MapAccess<'de>,
     {
-        let mut vec: Vec<(WafObject, WafObject)> =
-            map.size_hint().map(Vec::with_capacity).unwrap_or_default();
+        let mut vec = Scratch::take(&ENTRY_BUFFERS, map.size_hint());
         while let Some((key, value)) = map.next_entry::<WafObject, WafObject>()? {
-            vec.push((key, value));
+            vec.push(Keyed::new(key, value));
         }
         let mut res = WafMap::new(vec.len().try_into().map_err(A::Error::custom)?);
-        for (i, (k, v)) in vec.into_iter().enumerate() {
-            res[i] = Keyed::new(k, v);
+        for (i, keyed) in vec.drain(..).enumerate() {
+            res[i] = keyed;
         }
         Ok(res.into())
     }
 }
 
-impl serde::Serialize for WafObject {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        match self.object_type() {
-            WafObjectType::Unsigned => {
-                unsafe { self.as_type_unchecked::<WafUnsigned>() }.serialize(serializer)
-            }
-            WafObjectType::Signed => {
-                unsafe { self.as_type_unchecked::<WafSigned>() }.serialize(serializer)
-            }
-            WafObjectType::Bool => {
-                unsafe { self.as_type_unchecked::<WafBool>() }.serialize(serializer)
-            }
-            WafObjectType::Float => {
-                unsafe { self.as_type_unchecked::<WafFloat>() }.serialize(serializer)
-            }
-            WafObjectType::String => {
-                unsafe { self.as_type_unchecked::<WafString>() }.serialize(serializer)
-            }
-            WafObjectType::Array => {
-                unsafe { self.as_type_unchecked::<WafArray>() }.serialize(serializer)
-            }
-            WafObjectType::Map => {
-                unsafe { self.as_type_unchecked::<WafMap>() }.serialize(serializer)
-            }
-            WafObjectType::Null | WafObjectType::Invalid => serializer.serialize_unit(),
-        }
-    }
-}
-
-impl serde::Serialize for WafUnsigned {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_u64(self.value())
-    }
-}
-
-impl serde::Serialize for WafSigned {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_i64(self.value())
-    }
-}
-
-impl serde::Serialize for WafBool {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_bool(self.value())
-    }
-}
-
-impl serde::Serialize for WafFloat {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_f64(self.value())
-    }
-}
-
-impl serde::Serialize for WafString {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(&String::from_utf8_lossy(self.as_bytes()))
-    }
-}
-
-impl serde::Serialize for WafArray {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let mut seq_serializer = serializer.serialize_seq(Some(self.len() as usize))?;
-        for value in self.iter() {
-            seq_serializer.serialize_element(value)?;
-        }
-        seq_serializer.end()
-    }
-}
-
-impl serde::Serialize for WafMap {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let mut map_serializer = serializer.serialize_map(Some(self.len() as usize))?;
-        for keyed_val in self.iter() {
-            // Key is serialized as WafObject; formats requiring string keys (e.g. JSON)
-            // will error if the key is not a WafString
-            map_serializer.serialize_entry(keyed_val.key(), keyed_val.value())?;
-        }
-        map_serializer.end()
-    }
-}
-
 /// Default maximum string length (4096 bytes).
 pub const DEFAULT_MAX_STRING_LENGTH: u32 = 4096;
 
@@ -486,7 +365,6 @@ impl<'de> serde::de::Visitor<'de> for LimitedVisitor<'_> {
         Ok(WafObject::from(WafString::from(truncated)))
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
     where
         A: serde::de::SeqAccess<'de>,
@@ -507,10 +385,10 @@ impl<'de> serde::de::Visitor<'de> for LimitedVisitor<'_> {
 
         self.state.enter_depth();
 
-        let mut vec = seq.size_hint().map(Vec::with_capacity).unwrap_or_default();
+        let mut items = ArrayBuffer::new(seq.size_hint());
         while self.state.elements_remaining.get() > 0 {
             match seq.next_element_seed(LimitedSeed { state: self.state })? {
-                Some(value) => vec.push(value),
+                Some(value) => items.push(value),
                 None => break,
             }
         }
@@ -523,12 +401,8 @@ impl<'de> serde::de::Visitor<'de> for LimitedVisitor<'_> {
 
         self.state.exit_depth();
 
-        let len = vec.len().min(u16::MAX as usize);
-        let mut res = WafArray::new(len as u16);
-        for (i, v) in vec.into_iter().take(len).enumerate() {
-            res[i] = v;
-        }
-        Ok(res.into())
+        items.truncate(u16::MAX.into());
+        Ok(items.into_array()?.into())
     }
 
     #[allow(clippy::cast_possible_truncation)]
@@ -559,8 +433,7 @@ impl<'de> serde::de::Visitor<'de> for LimitedVisitor<'_> {
 
         self.state.enter_depth();
 
-        let mut vec: Vec<Keyed<WafObject>> =
-            map.size_hint().map(Vec::with_capacity).unwrap_or_default();
+        let mut vec = Scratch::take(&ENTRY_BUFFERS, map.size_hint());
 
         while self.state.elements_remaining.get() > 0 {
             match map.next_entry_seed(
@@ -588,7 +461,7 @@ impl<'de> serde::de::Visitor<'de> for LimitedVisitor<'_> {
 
         let len = vec.len().min(u16::MAX as usize);
         let mut res = WafMap::new(len as u16);
-        for (i, keyed) in vec.into_iter().take(len).enumerate() {
+        for (i, keyed) in vec.drain(..).take(len).enumerate() {
             res[i] = keyed;
         }
         Ok(res.into())
@@ -614,3 +487,310 @@ where
         deserializer.deserialize_any(visitor)
     }
 }
+
+/// Default maximum container depth (20 levels), matching `libddwaf`'s default.
+pub const DEFAULT_MAX_CONTAINER_DEPTH: usize = 20;
+
+/// Default maximum container size (256 elements), matching `libddwaf`'s default.
+pub const DEFAULT_MAX_CONTAINER_SIZE: usize = 256;
+
+/// Limits enforced by [`deserialize_with_container_limits`].
+///
+/// Unlike [`Limits`], which truncates the data that exceeds them, these cause deserialization to
+/// fail. This is intended for untrusted input (such as rulesets from an untrusted source), where
+/// data exceeding the limits indicates a malformed or malicious document.
+///
+/// # Example
+/// ```
+/// use libddwaf::serde::{deserialize_with_container_limits, ContainerLimits};
+///
+/// let limits = ContainerLimits {
+///     max_container_depth: 2,
+///     ..ContainerLimits::default()
+/// };
+/// let mut deserializer = serde_json::Deserializer::from_str(r#"{"a": [1, 2]}"#);
+/// assert!(deserialize_with_container_limits(&mut deserializer, &limits).is_ok());
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(r#"{"a": [[1, 2]]}"#);
+/// assert!(deserialize_with_container_limits(&mut deserializer, &limits).is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ContainerLimits {
+    /// The maximum number of nested containers (arrays and maps); a scalar value has depth 0.
+    pub max_container_depth: usize,
+    /// The maximum number of elements in any single container.
+    pub max_container_size: usize,
+}
+
+impl Default for ContainerLimits {
+    fn default() -> Self {
+        Self {
+            max_container_depth: DEFAULT_MAX_CONTAINER_DEPTH,
+            max_container_size: DEFAULT_MAX_CONTAINER_SIZE,
+        }
+    }
+}
+
+/// Deserialize a [`WafObject`] from a deserializer, failing if it exceeds the specified
+/// [`ContainerLimits`].
+///
+/// Deserialization stops as soon as a limit is exceeded, so that nesting beyond the configured
+/// depth is never descended into.
+///
+/// # Errors
+/// Returns an error if the deserializer returns an error, or if the data exceeds the limits.
+pub fn deserialize_with_container_limits<'de, D>(
+    deserializer: D,
+    limits: &ContainerLimits,
+) -> Result<WafObject, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(StrictSeed {
+        limits,
+        depth_remaining: limits.max_container_depth,
+    })
+}
+
+/// A `DeserializeSeed` that fails when [`ContainerLimits`] are exceeded.
+#[derive(Clone, Copy)]
+struct StrictSeed<'a> {
+    limits: &'a ContainerLimits,
+    depth_remaining: usize,
+}
+
+impl StrictSeed<'_> {
+    fn enter<E: Error>(self) -> Result<Self, E> {
+        if self.depth_remaining == 0 {
+            return Err(E::custom(format_args!(
+                "container depth limit exceeded (at most {} nested containers are allowed)",
+                self.limits.max_container_depth
+            )));
+        }
+        Ok(Self {
+            depth_remaining: self.depth_remaining - 1,
+            ..self
+        })
+    }
+
+    fn check_size<E: Error>(self, len: usize) -> Result<(), E> {
+        if len >= self.limits.max_container_size {
+            return Err(E::custom(format_args!(
+                "container size limit exceeded (at most {} elements are allowed)",
+                self.limits.max_container_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<'de> serde::de::DeserializeSeed<'de> for StrictSeed<'_> {
+    type Value = WafObject;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> serde::de::Visitor<'de> for StrictSeed<'_> {
+    type Value = WafObject;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Visitor.expecting(formatter)
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        Visitor.visit_u64(v)
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        Visitor.visit_i64(v)
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Self::Value, E> {
+        Visitor.visit_f64(v)
+    }
+
+    fn visit_bool<E: Error>(self, v: bool) -> Result<Self::Value, E> {
+        Visitor.visit_bool(v)
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+        Visitor.visit_unit()
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        Visitor.visit_str(v)
+    }
+
+    fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Visitor.visit_bytes(v)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let inner = self.enter()?;
+        let mut items = ArrayBuffer::new(seq.size_hint());
+        while let Some(value) = seq.next_element_seed(inner)? {
+            self.check_size(items.len())?;
+            items.push(value);
+        }
+        Ok(items.into_array()?.into())
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let inner = self.enter()?;
+        let mut vec = Scratch::take(&ENTRY_BUFFERS, map.size_hint());
+        while let Some((key, value)) = map.next_entry_seed(inner, inner)? {
+            self.check_size(vec.len())?;
+            vec.push(Keyed::new(key, value));
+        }
+        let mut res = WafMap::new(vec.len().try_into().map_err(A::Error::custom)?);
+        for (i, keyed) in vec.drain(..).enumerate() {
+            res[i] = keyed;
+        }
+        Ok(res.into())
+    }
+}
+
+/// The maximum number of scratch buffers of each kind retained by each thread.
+const MAX_POOLED_BUFFERS: usize = 16;
+/// The maximum capacity of a retained scratch buffer; larger buffers are released after use, so
+/// that a single large document does not pin memory for the lifetime of the thread.
+const MAX_POOLED_CAPACITY: usize = 1024;
+
+type BufferPool<T> = RefCell<Vec<Vec<T>>>;
+
+thread_local! {
+    /// Reusable buffers for the elements of arrays being deserialized.
+    static VALUE_BUFFERS: BufferPool<WafObject> = const { RefCell::new(Vec::new()) };
+    /// Reusable buffers for the entries of maps being deserialized.
+    static ENTRY_BUFFERS: BufferPool<Keyed<WafObject>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The elements of an array being deserialized.
+///
+/// Elements are first collected in a pooled [`Scratch`] buffer. Arrays that grow beyond
+/// [`MAX_POOLED_CAPACITY`] elements, whose buffers would not be pooled anyway, continue in a
+/// [`LazyWafArray`]; long lists of scalars (such as the numeric data of large rulesets) are then
+/// staged compactly until the array is materialized.
+enum ArrayBuffer {
+    Scratch(Scratch<WafObject>),
+    Lazy(LazyWafArray),
+}
+impl ArrayBuffer {
+    fn new(size_hint: Option<usize>) -> Self {
+        Self::Scratch(Scratch::take(&VALUE_BUFFERS, size_hint))
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Scratch(buf) => buf.len(),
+            Self::Lazy(lazy) => lazy.len(),
+        }
+    }
+
+    fn push(&mut self, value: WafObject) {
+        match self {
+            Self::Scratch(buf) if buf.len() < MAX_POOLED_CAPACITY => buf.push(value),
+            Self::Scratch(buf) => {
+                let mut lazy = LazyWafArray::with_capacity(2 * buf.len());
+                for staged in buf.drain(..) {
+                    lazy.push(staged);
+                }
+                lazy.push(value);
+                *self = Self::Lazy(lazy);
+            }
+            Self::Lazy(lazy) => lazy.push(value),
+        }
+    }
+
+    fn truncate(&mut self, len: usize) {
+        match self {
+            Self::Scratch(buf) => buf.truncate(len),
+            Self::Lazy(lazy) => lazy.truncate(len),
+        }
+    }
+
+    fn into_array<E: Error>(self) -> Result<WafArray, E> {
+        let len: u16 = self.len().try_into().map_err(E::custom)?;
+        match self {
+            Self::Scratch(mut buf) => {
+                let mut res = WafArray::new(len);
+                for (i, v) in buf.drain(..).enumerate() {
+                    res[i] = v;
+                }
+                Ok(res)
+            }
+            Self::Lazy(lazy) => lazy.materialize().map_err(E::custom),
+        }
+    }
+}
+
+/// A buffer collecting the contents of a container while it is being deserialized, before they
+/// are moved into a [`WafArray`] or [`WafMap`] of the right size.
+///
+/// Buffers are taken from a thread-local pool, and given back (emptied) when dropped, so that
+/// documents with many small containers do not allocate a new buffer for each of them. Each
+/// container being deserialized takes its own buffer, so nested containers (and nested
+/// deserializers) on the same thread never share one.
+struct Scratch<T: 'static> {
+    buf: Vec<T>,
+    pool: &'static LocalKey<BufferPool<T>>,
+}
+impl<T> Scratch<T> {
+    fn take(pool: &'static LocalKey<BufferPool<T>>, size_hint: Option<usize>) -> Self {
+        let wanted = size_hint.unwrap_or(0).min(MAX_POOLED_CAPACITY);
+        let pooled = pool
+            .try_with(|pool| {
+                let mut pool = pool.borrow_mut();
+                // Prefer a buffer that is already large enough, or else the most recently used.
+                let idx = pool
+                    .iter()
+                    .position(|buf| buf.capacity() >= wanted)
+                    .or_else(|| pool.len().checked_sub(1))?;
+                Some(pool.swap_remove(idx))
+            })
+            .ok()
+            .flatten();
+        let mut buf = pooled.unwrap_or_default();
+        buf.reserve(wanted);
+        Self { buf, pool }
+    }
+}
+impl<T> Deref for Scratch<T> {
+    type Target = Vec<T>;
+    fn deref(&self) -> &Self::Target {
+        &self.buf
+    }
+}
+impl<T> DerefMut for Scratch<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buf
+    }
+}
+impl<T> Drop for Scratch<T> {
+    fn drop(&mut self) {
+        // Values left behind (when deserialization failed) are dropped before the pool is borrowed.
+        self.buf.clear();
+        if self.buf.capacity() == 0 || self.buf.capacity() > MAX_POOLED_CAPACITY {
+            return;
+        }
+        let buf = std::mem::take(&mut self.buf);
+        // The pool is unavailable while the thread is being torn down; the buffer is then dropped.
+        let _ = self.pool.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.len() < MAX_POOLED_BUFFERS {
+                pool.push(buf);
+            }
+        });
+    }
+}
@@ -0,0 +1,25 @@
+//! Implementations of [`serde::Serialize`] and [`serde::Deserialize`] for
+//! [`object::WafObject`](crate::object::WafObject) and [`object::WafMap`](crate::object::WafMap).
+//!
+//! The serialization and deserialization support are provided by the `serde-serialize` and
+//! `serde-deserialize` features respectively, which the `serde` feature enables together.
+//!
+//! With `serde-deserialize`, this module also provides [`Limits`] for applying constraints during
+//! deserialization, similar to the PHP extension's `dd_mpack_limits` structure, and
+//! [`ContainerLimits`] for rejecting documents that exceed the WAF's own container limits.
+//!
+//! When serializing, [`WafString`](crate::object::WafString) values are converted to strings
+//! lossily. The [`AsText`] and [`AsBytes`] wrappers (provided by `serde-serialize`) allow choosing
+//! the encoding of individual strings instead.
+
+#[cfg(feature = "serde-deserialize")]
+mod de;
+#[cfg(feature = "serde-deserialize")]
+#[doc(inline)]
+pub use de::*;
+
+#[cfg(feature = "serde-serialize")]
+mod ser;
+#[cfg(feature = "serde-serialize")]
+#[doc(inline)]
+pub use ser::*;
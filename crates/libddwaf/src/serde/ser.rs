@@ -0,0 +1,158 @@
+use serde::ser::{SerializeMap, SerializeSeq};
+
+use crate::object::{
+    WafArray, WafBool, WafFloat, WafMap, WafObject, WafObjectType, WafSigned, WafString,
+    WafUnsigned,
+};
+
+impl serde::Serialize for WafObject {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.object_type() {
+            WafObjectType::Unsigned => {
+                unsafe { self.as_type_unchecked::<WafUnsigned>() }.serialize(serializer)
+            }
+            WafObjectType::Signed => {
+                unsafe { self.as_type_unchecked::<WafSigned>() }.serialize(serializer)
+            }
+            WafObjectType::Bool => {
+                unsafe { self.as_type_unchecked::<WafBool>() }.serialize(serializer)
+            }
+            WafObjectType::Float => {
+                unsafe { self.as_type_unchecked::<WafFloat>() }.serialize(serializer)
+            }
+            WafObjectType::String => {
+                unsafe { self.as_type_unchecked::<WafString>() }.serialize(serializer)
+            }
+            WafObjectType::Array => {
+                unsafe { self.as_type_unchecked::<WafArray>() }.serialize(serializer)
+            }
+            WafObjectType::Map => {
+                unsafe { self.as_type_unchecked::<WafMap>() }.serialize(serializer)
+            }
+            WafObjectType::Null | WafObjectType::Invalid => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl serde::Serialize for WafUnsigned {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u64(self.value())
+    }
+}
+
+impl serde::Serialize for WafSigned {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_i64(self.value())
+    }
+}
+
+impl serde::Serialize for WafBool {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_bool(self.value())
+    }
+}
+
+impl serde::Serialize for WafFloat {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_f64(self.value())
+    }
+}
+
+impl serde::Serialize for WafString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&String::from_utf8_lossy(self.as_bytes()))
+    }
+}
+
+/// Serializes a [`WafString`] as a string, replacing invalid UTF-8 sequences with
+/// [`U+FFFD REPLACEMENT CHARACTER`](std::char::REPLACEMENT_CHARACTER). This is what the
+/// [`serde::Serialize`] implementation of [`WafString`] does.
+///
+/// This and [`AsBytes`] allow choosing how each [`WafString`] embedded in a serializable structure
+/// is encoded:
+///
+/// ```rust
+/// use libddwaf::object::WafString;
+/// use libddwaf::serde::{AsBytes, AsText};
+///
+/// let payload = WafString::from(&b"1'\xFF"[..]);
+/// assert_eq!(
+///     serde_json::to_string(&(AsText(&payload), AsBytes(&payload))).unwrap(),
+///     "[\"1'\u{FFFD}\",[49,39,255]]",
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AsText<'a>(pub &'a WafString);
+impl serde::Serialize for AsText<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self.0.as_str() {
+            Ok(text) => serializer.serialize_str(text),
+            Err(_) => serializer.serialize_str(&String::from_utf8_lossy(self.0.as_bytes())),
+        }
+    }
+}
+
+/// Serializes a [`WafString`] as bytes, preserving its contents exactly, even when they are not
+/// valid UTF-8. How bytes are represented depends on the format; for example, `serde_json` encodes
+/// them as an array of numbers.
+///
+/// See [`AsText`] for an example.
+#[derive(Clone, Copy, Debug)]
+pub struct AsBytes<'a>(pub &'a WafString);
+impl serde::Serialize for AsBytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_bytes(self.0.as_bytes())
+    }
+}
+
+impl serde::Serialize for WafArray {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut seq_serializer = serializer.serialize_seq(Some(self.len() as usize))?;
+        for value in self.iter() {
+            seq_serializer.serialize_element(value)?;
+        }
+        seq_serializer.end()
+    }
+}
+
+impl serde::Serialize for WafMap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut map_serializer = serializer.serialize_map(Some(self.len() as usize))?;
+        for keyed_val in self.iter() {
+            // Key is serialized as WafObject; formats requiring string keys (e.g. JSON)
+            // will error if the key is not a WafString
+            map_serializer.serialize_entry(keyed_val.key(), keyed_val.value())?;
+        }
+        map_serializer.end()
+    }
+}
@@ -0,0 +1,124 @@
+//! Rulesets for use in tests.
+//!
+//! These are small, self-contained rulesets in the format expected by
+//! [`Builder::add_or_update_config`](crate::Builder::add_or_update_config), with well-known rule
+//! identifiers that tests can assert on.
+
+use crate::object::WafMap;
+use crate::{waf_array, waf_map};
+
+/// Returns a ruleset with a single rule, `arachni_rule`, which blocks requests whose `User-Agent`
+/// header (in `server.request.headers.no_cookies`) or body (in `server.request.body`) contains
+/// `Arachni`.
+#[must_use]
+pub fn arachni_rule() -> WafMap {
+    waf_map! {
+        ("version", "2.1"),
+        ("rules", waf_array![
+            waf_map!{
+                ("id", "arachni_rule"),
+                ("name", "Block with default action"),
+                ("tags", waf_map!{ ("category", "attack_attempt"), ("type", "security_scanner") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!{
+                                    ("address", "server.request.headers.no_cookies"),
+                                    ("key_path", waf_array!["user-agent"]),
+                                },
+                                waf_map!{
+                                    ("address", "server.request.body"),
+                                },
+                            ]),
+                            ("regex", "Arachni"),
+                        }),
+                    },
+                ]),
+                ("on_match", waf_array!["block"])
+            },
+        ]),
+    }
+}
+
+/// Returns a ruleset modelled after a few rules of the recommended Datadog ruleset, which only
+/// report their matches (none of them blocks):
+/// - `crs-941-110`, detecting XSS in the query, body and path parameters,
+/// - `crs-942-100`, detecting SQL injections in the query, body and path parameters,
+/// - `crs-930-100`, detecting path traversal (`../` or `..\`) in the URI and query,
+/// - `ua0-600-12x`, detecting the Arachni scanner by its `User-Agent` header.
+#[must_use]
+pub fn recommended_subset() -> WafMap {
+    let request_params = || {
+        waf_array![
+            waf_map!(("address", "server.request.query")),
+            waf_map!(("address", "server.request.body")),
+            waf_map!(("address", "server.request.path_params")),
+        ]
+    };
+    waf_map! {
+        ("version", "2.2"),
+        ("metadata", waf_map!(("rules_version", "test"))),
+        ("rules", waf_array![
+            waf_map!{
+                ("id", "crs-941-110"),
+                ("name", "XSS Filter - Category 1: Script Tag Vector"),
+                ("tags", waf_map!{ ("type", "xss"), ("category", "attack_attempt") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "is_xss"),
+                        ("parameters", waf_map!{ ("inputs", request_params()) }),
+                    },
+                ]),
+            },
+            waf_map!{
+                ("id", "crs-942-100"),
+                ("name", "SQL Injection Attack Detected via libinjection"),
+                ("tags", waf_map!{ ("type", "sql_injection"), ("category", "attack_attempt") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "is_sqli"),
+                        ("parameters", waf_map!{ ("inputs", request_params()) }),
+                    },
+                ]),
+            },
+            waf_map!{
+                ("id", "crs-930-100"),
+                ("name", "Obfuscated Path Traversal Attack (/../)"),
+                ("tags", waf_map!{ ("type", "lfi"), ("category", "attack_attempt") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!(("address", "server.request.uri.raw")),
+                                waf_map!(("address", "server.request.query")),
+                            ]),
+                            ("regex", r"\.\.[/\\]"),
+                        }),
+                    },
+                ]),
+            },
+            waf_map!{
+                ("id", "ua0-600-12x"),
+                ("name", "Arachni"),
+                ("tags", waf_map!{ ("type", "attack_tool"), ("category", "attack_attempt") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!{
+                                    ("address", "server.request.headers.no_cookies"),
+                                    ("key_path", waf_array!["user-agent"]),
+                                },
+                            ]),
+                            ("regex", r"^Arachni/v"),
+                        }),
+                    },
+                ]),
+            },
+        ]),
+    }
+}
@@ -0,0 +1,228 @@
+//! Helpers for testing code that creates and manipulates [`WafObject`]s, available with the
+//! `test-util` feature.
+//!
+//! The [`Drop`] implementations of [`WafObject`] and friends rely on invariants that are easily
+//! broken when using the [raw](crate::object::raw) API, or when transferring ownership to and
+//! from `libddwaf`. [`assert_no_leak`] verifies that all memory allocated while creating a value
+//! is released once it is dropped. It requires [`CountingAllocator`] to be installed as the global
+//! allocator of the test binary:
+//!
+//! ```rust
+//! use libddwaf::test_util::{assert_no_leak, CountingAllocator};
+//! use libddwaf::waf_map;
+//!
+//! #[global_allocator]
+//! static GLOBAL: CountingAllocator = CountingAllocator;
+//!
+//! fn main() {
+//!     assert_no_leak(|| waf_map!(("key", "a value long enough to be stored out of line")));
+//! }
+//! ```
+//!
+//! It also provides rulesets for tests in [`fixtures`], and [`TestWaf`] along with
+//! [`assert_matches_rule`], [`assert_blocks`] and [`assert_no_match`] for checking how a ruleset
+//! handles some address data.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
+
+use crate::object::{WafObject, WafView};
+
+pub mod fixtures;
+mod waf;
+#[doc(inline)]
+pub use waf::*;
+
+/// A [`GlobalAlloc`] that delegates to [`System`], while keeping track of the number of bytes
+/// allocated by each thread that are still live.
+///
+/// Allocations are attributed to the thread that performs them, so that tests running concurrently
+/// in the same binary do not affect each other's counts; memory allocated by a thread and released
+/// by another is accounted for in both. Such memory can instead be tracked with
+/// [`CountingAllocator::total_live_bytes`], which covers all threads.
+pub struct CountingAllocator;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static TOTAL_LIVE_BYTES: AtomicIsize = AtomicIsize::new(0);
+thread_local! {
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+    static PEAK_BYTES: Cell<isize> = const { Cell::new(0) };
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+impl CountingAllocator {
+    /// Returns the number of bytes allocated by the current thread that have not been released
+    /// yet, or [`None`] if [`CountingAllocator`] is not the global allocator.
+    #[must_use]
+    pub fn live_bytes() -> Option<isize> {
+        // Make sure at least one allocation went through the global allocator.
+        drop(std::hint::black_box(Box::new(0u8)));
+        if !INSTALLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        LIVE_BYTES.try_with(Cell::get).ok()
+    }
+
+    /// Returns the number of bytes allocated by all threads that have not been released yet, or
+    /// [`None`] if [`CountingAllocator`] is not the global allocator.
+    ///
+    /// Unlike [`CountingAllocator::live_bytes`], this is affected by all the tests running
+    /// concurrently in the same binary.
+    #[must_use]
+    pub fn total_live_bytes() -> Option<isize> {
+        if !INSTALLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(TOTAL_LIVE_BYTES.load(Ordering::SeqCst))
+    }
+
+    /// Returns the highest number of bytes allocated by the current thread that were live at the
+    /// same time, since the last call to [`CountingAllocator::reset_peak`], or [`None`] if
+    /// [`CountingAllocator`] is not the global allocator.
+    #[must_use]
+    pub fn peak_bytes() -> Option<isize> {
+        if !INSTALLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        PEAK_BYTES.try_with(Cell::get).ok()
+    }
+
+    /// Returns the number of allocations performed by the current thread so far (not counting
+    /// reallocations), or [`None`] if [`CountingAllocator`] is not the global allocator.
+    ///
+    /// Unlike [`CountingAllocator::live_bytes`], this does not allocate, so that the number of
+    /// allocations performed by some operation is the difference between two calls.
+    #[must_use]
+    pub fn allocations() -> Option<u64> {
+        if !INSTALLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        ALLOCATIONS.try_with(Cell::get).ok()
+    }
+
+    /// Resets the peak reported by [`CountingAllocator::peak_bytes`] to the number of bytes
+    /// currently live, so that the peak reached by some operation can be measured.
+    pub fn reset_peak() {
+        let live = Self::live_bytes().unwrap_or_default();
+        let _ = PEAK_BYTES.try_with(|peak| peak.set(live));
+    }
+
+    fn record_allocation(layout: Layout) {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        Self::record(size_delta(layout));
+    }
+
+    fn record(delta: isize) {
+        TOTAL_LIVE_BYTES.fetch_add(delta, Ordering::SeqCst);
+        // The thread-local storage is not available while the thread is being torn down, at which
+        // point there is nothing to track anymore.
+        let Ok(live) = LIVE_BYTES.try_with(|live| {
+            live.set(live.get().wrapping_add(delta));
+            live.get()
+        }) else {
+            return;
+        };
+        let _ = PEAK_BYTES.try_with(|peak| peak.set(peak.get().max(live)));
+    }
+}
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            Self::record_allocation(layout);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        INSTALLED.store(true, Ordering::Relaxed);
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            Self::record_allocation(layout);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        Self::record(-size_delta(layout));
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            Self::record(isize::try_from(new_size).unwrap_or(isize::MAX) - size_delta(layout));
+        }
+        new_ptr
+    }
+}
+
+fn size_delta(layout: Layout) -> isize {
+    // Layout sizes never exceed isize::MAX.
+    isize::try_from(layout.size()).unwrap_or(isize::MAX)
+}
+
+/// Creates a value by calling `make`, drops it, and asserts that all the memory the current thread
+/// allocated meanwhile has been released.
+///
+/// The value must not be sent to (or be dropped by) another thread.
+///
+/// # Panics
+/// Panics if some memory was leaked, or if [`CountingAllocator`] is not the global allocator.
+#[allow(clippy::expect_used)] // Documented panic
+pub fn assert_no_leak<T>(make: impl FnOnce() -> T) {
+    const NOT_INSTALLED: &str = "CountingAllocator must be installed as the #[global_allocator]";
+    let before = CountingAllocator::live_bytes().expect(NOT_INSTALLED);
+    drop(make());
+    let after = CountingAllocator::live_bytes().expect(NOT_INSTALLED);
+    assert!(
+        after == before,
+        "{} bytes were leaked by {}",
+        after - before,
+        std::any::type_name::<T>()
+    );
+}
+
+/// Returns whether `left` and `right` are structurally equal, regardless of the order in which
+/// the entries of their maps appear.
+///
+/// Unlike [`WafObject`]'s [`PartialEq`] implementation, maps are equal when they contain the same
+/// entries, with each entry of one being paired with a distinct entry of the other.
+#[must_use]
+pub fn deep_eq(left: &WafObject, right: &WafObject) -> bool {
+    match (left.view(), right.view()) {
+        (WafView::Array(l), WafView::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| deep_eq(l, r))
+        }
+        (WafView::Map(l), WafView::Map(r)) => {
+            if l.len() != r.len() {
+                return false;
+            }
+            let mut paired = vec![false; usize::from(r.len())];
+            l.iter().all(|entry| {
+                let found = r.iter().enumerate().find(|(i, other)| {
+                    !paired[*i] && entry.key() == other.key() && deep_eq(entry, other)
+                });
+                found.is_some_and(|(i, _)| {
+                    paired[i] = true;
+                    true
+                })
+            })
+        }
+        _ => left == right,
+    }
+}
+
+/// Asserts that `left` and `right` are [`deep_eq`], reporting their differences otherwise.
+///
+/// # Panics
+/// Panics if `left` and `right` are not [`deep_eq`].
+pub fn assert_deep_eq(left: &WafObject, right: &WafObject) {
+    assert!(
+        deep_eq(left, right),
+        "objects are not equal:\n{}",
+        left.diff_string(right).unwrap_or_default()
+    );
+}
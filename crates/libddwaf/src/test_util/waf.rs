@@ -0,0 +1,233 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::object::{Keyed, WafArray, WafMap, WafObject, WafOwnedDefaultAllocator, WafView};
+use crate::{evaluate_once, Builder, Handle, RunOutput, RunResult};
+
+/// A [`Handle`] built from a single ruleset, for evaluating address data in tests.
+///
+/// Unlike [`RulesetTester`](crate::RulesetTester), which reports failures as values,
+/// [`TestWaf`] panics with a descriptive message when the ruleset cannot be loaded or an
+/// evaluation fails, and is meant to be used with [`assert_matches_rule`], [`assert_blocks`] and
+/// [`assert_no_match`]:
+///
+/// ```rust
+/// use libddwaf::test_util::{assert_blocks, assert_no_match, fixtures, TestWaf};
+/// use libddwaf::waf_map;
+///
+/// let waf = TestWaf::new(&fixtures::arachni_rule());
+/// assert_blocks(&waf, waf_map!(("server.request.body", "Arachni")));
+/// assert_no_match(&waf, waf_map!(("server.request.body", "Mozilla")));
+/// ```
+pub struct TestWaf {
+    handle: Handle,
+    timeout: Duration,
+}
+impl TestWaf {
+    /// The timeout used by [`TestWaf::run`] unless [`TestWaf::with_timeout`] is used.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Creates a new [`TestWaf`] for the provided ruleset.
+    ///
+    /// # Panics
+    /// Panics, reporting the diagnostics produced by `libddwaf`, if the ruleset cannot be loaded
+    /// or contains no active instructions.
+    #[track_caller]
+    pub fn new(ruleset: &impl AsRef<libddwaf_sys::ddwaf_object>) -> Self {
+        let Some(mut builder) = Builder::new(None) else {
+            panic!("failed to create a WAF builder");
+        };
+        let mut diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+        let loaded = builder.add_or_update_config("ruleset", ruleset, Some(&mut diagnostics));
+        let handle = builder.build().filter(|_| loaded);
+        let Some(handle) = handle else {
+            panic!(
+                "failed to load the ruleset; diagnostics:\n{}",
+                pretty(diagnostics.as_object())
+            );
+        };
+        Self {
+            handle,
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the timeout used by [`TestWaf::run`].
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the [`Handle`] used by this [`TestWaf`].
+    #[must_use]
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Evaluates the provided address data in a fresh [`Context`](crate::Context), and returns
+    /// its output.
+    ///
+    /// # Panics
+    /// Panics if the evaluation fails.
+    #[track_caller]
+    pub fn run(&self, data: WafMap) -> RunOutput {
+        match evaluate_once(&self.handle, data, self.timeout) {
+            Ok(RunResult::Match(output) | RunResult::NoMatch(output)) => output,
+            Err(err) => panic!("the evaluation failed: {err}"),
+        }
+    }
+}
+
+/// Evaluates `data` using `waf`, and asserts that the rule with the provided identifier matched.
+/// Returns the output of the evaluation, for further assertions.
+///
+/// # Panics
+/// Panics if the evaluation fails, or if the rule did not match; the message then lists the rules
+/// that did match, along with their events.
+#[track_caller]
+pub fn assert_matches_rule(waf: &TestWaf, data: WafMap, rule_id: &str) -> RunOutput {
+    let output = waf.run(data);
+    let matched = matched_rules(&output);
+    assert!(
+        matched.contains(&rule_id),
+        "expected rule `{rule_id}` to match, but {}",
+        describe_matches(&output)
+    );
+    output
+}
+
+/// Evaluates `data` using `waf`, and asserts that it produced a `block_request` or
+/// `redirect_request` action. Returns the output of the evaluation, for further assertions.
+///
+/// # Panics
+/// Panics if the evaluation fails, or if it did not produce a blocking action; the message then
+/// lists the actions that were produced and the rules that matched, along with their events.
+#[track_caller]
+pub fn assert_blocks(waf: &TestWaf, data: WafMap) -> RunOutput {
+    const BLOCKING_ACTIONS: [&str; 2] = ["block_request", "redirect_request"];
+
+    let output = waf.run(data);
+    let actions: Vec<&str> = output
+        .actions()
+        .into_iter()
+        .flat_map(Keyed::<WafMap>::iter)
+        .filter_map(|action| action.key_str().ok())
+        .collect();
+    assert!(
+        actions
+            .iter()
+            .any(|action| BLOCKING_ACTIONS.contains(action)),
+        "expected the data to be blocked, but the actions were {actions:?} and {}",
+        describe_matches(&output)
+    );
+    output
+}
+
+/// Evaluates `data` using `waf`, and asserts that no rule matched.
+///
+/// # Panics
+/// Panics if the evaluation fails, or if some rules matched; the message then lists them, along
+/// with their events.
+#[track_caller]
+pub fn assert_no_match(waf: &TestWaf, data: WafMap) -> RunOutput {
+    let output = waf.run(data);
+    assert!(
+        matched_rules(&output).is_empty(),
+        "expected no rule to match, but {}",
+        describe_matches(&output)
+    );
+    output
+}
+
+/// Returns the identifiers of the rules that matched, in the order their events were reported.
+fn matched_rules(output: &RunOutput) -> Vec<&str> {
+    events(output)
+        .filter_map(|event| event.as_type::<WafMap>()?.get_str("rule"))
+        .filter_map(|rule| rule.as_type::<WafMap>()?.get_str("id")?.to_str())
+        .collect()
+}
+
+fn events(output: &RunOutput) -> impl Iterator<Item = &WafObject> {
+    output
+        .events()
+        .into_iter()
+        .flat_map(Keyed::<WafArray>::iter)
+}
+
+/// Describes the rules that matched, and their events, for use in assertion messages.
+fn describe_matches(output: &RunOutput) -> String {
+    let matched = matched_rules(output);
+    if matched.is_empty() {
+        return "no rule matched".to_string();
+    }
+    let mut description = format!("the matched rules were {matched:?}; events:");
+    for event in events(output) {
+        description.push('\n');
+        description.push_str(&pretty(event));
+    }
+    description
+}
+
+/// Formats `obj` as indented JSON (strings that are not valid UTF-8 are formatted lossily).
+fn pretty(obj: &WafObject) -> String {
+    let mut out = String::new();
+    write_pretty(&mut out, obj, 0);
+    out
+}
+
+fn write_pretty(out: &mut String, obj: &WafObject, indent: usize) {
+    const INDENT: usize = 2;
+
+    match obj.view() {
+        WafView::Array(array) if !array.is_empty() => {
+            out.push('[');
+            for (i, item) in array.iter().enumerate() {
+                out.push_str(if i == 0 { "\n" } else { ",\n" });
+                push_indent(out, indent + INDENT);
+                write_pretty(out, item, indent + INDENT);
+            }
+            out.push('\n');
+            push_indent(out, indent);
+            out.push(']');
+        }
+        WafView::Map(map) if !map.is_empty() => {
+            out.push('{');
+            for (i, entry) in map.iter().enumerate() {
+                out.push_str(if i == 0 { "\n" } else { ",\n" });
+                push_indent(out, indent + INDENT);
+                let key = entry.key_bytes().unwrap_or_default();
+                let _ = write!(out, "{:?}: ", String::from_utf8_lossy(key));
+                write_pretty(out, entry.value(), indent + INDENT);
+            }
+            out.push('\n');
+            push_indent(out, indent);
+            out.push('}');
+        }
+        WafView::Array(_) => out.push_str("[]"),
+        WafView::Map(_) => out.push_str("{}"),
+        WafView::Str(string) => {
+            let _ = write!(out, "{string:?}");
+        }
+        WafView::Bytes(bytes) => {
+            let _ = write!(out, "{:?}", String::from_utf8_lossy(bytes));
+        }
+        WafView::Unsigned(value) => {
+            let _ = write!(out, "{value}");
+        }
+        WafView::Signed(value) => {
+            let _ = write!(out, "{value}");
+        }
+        WafView::Float(value) => {
+            let _ = write!(out, "{value}");
+        }
+        WafView::Bool(value) => {
+            let _ = write!(out, "{value}");
+        }
+        WafView::Null | WafView::Invalid => out.push_str("null"),
+    }
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    out.push_str(&" ".repeat(indent));
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use crate::object::{Keyed, WafArray, WafMap, WafOwnedDefaultAllocator};
+use crate::{Builder, Handle, RunError, RunOutput, RunResult, RunnableContext};
+
+/// Evaluates the provided address data against the [`Handle`]'s ruleset in a fresh [`Context`],
+/// which is discarded immediately after.
+///
+/// This is convenient for offline analysis (e.g, replaying logged requests against a ruleset),
+/// where each piece of data is self-contained. Service requests should be processed using a
+/// [`Context`] obtained from [`Handle::new_context`] instead.
+///
+/// [`Context`]: crate::Context
+///
+/// # Errors
+/// Returns an error under the same conditions as [`RunnableContext::run`].
+pub fn evaluate_once(
+    handle: &Handle,
+    data: WafMap,
+    timeout: Duration,
+) -> Result<RunResult, RunError> {
+    handle.new_context().run(data, timeout)
+}
+
+/// A helper for writing unit tests for rulesets.
+///
+/// It owns a [`Handle`] built from a single ruleset, and evaluates address data against it using
+/// [`evaluate_once`], summarizing the result as a [`TestOutcome`].
+pub struct RulesetTester {
+    handle: Handle,
+    timeout: Duration,
+}
+impl RulesetTester {
+    /// The timeout used by [`RulesetTester::test`] unless [`RulesetTester::with_timeout`] is used.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+    /// Creates a new [`RulesetTester`] for the provided ruleset. Returns [`None`] if the ruleset
+    /// could not be loaded, or contains no active instructions.
+    ///
+    /// Any warning/error information is conveyed through the provided diagnostics object.
+    #[must_use]
+    pub fn new(
+        ruleset: &impl AsRef<libddwaf_sys::ddwaf_object>,
+        diagnostics: Option<&mut WafOwnedDefaultAllocator<WafMap>>,
+    ) -> Option<Self> {
+        let mut builder = Builder::new(None)?;
+        if !builder.add_or_update_config("ruleset", ruleset, diagnostics) {
+            return None;
+        }
+        Some(Self {
+            handle: builder.build()?,
+            timeout: Self::DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Sets the timeout used by [`RulesetTester::test`].
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the [`Handle`] used by this [`RulesetTester`].
+    #[must_use]
+    pub fn handle(&self) -> &Handle {
+        &self.handle
+    }
+
+    /// Evaluates the provided address data against the ruleset.
+    ///
+    /// # Errors
+    /// Returns an error under the same conditions as [`RunnableContext::run`].
+    pub fn test(&self, data: WafMap) -> Result<TestOutcome, RunError> {
+        let output = match evaluate_once(&self.handle, data, self.timeout)? {
+            RunResult::Match(output) | RunResult::NoMatch(output) => output,
+        };
+        Ok(TestOutcome::from(&output))
+    }
+}
+
+/// A summary of the evaluation performed by [`RulesetTester::test`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestOutcome {
+    /// The identifiers of the rules that matched, in the order their events were reported.
+    pub matched_rules: Vec<String>,
+    /// The types of the actions that were produced (e.g, `block_request`).
+    pub actions: Vec<String>,
+    /// The time spent evaluating the data, as reported by [`RunOutput::duration`].
+    pub duration: Duration,
+}
+impl TestOutcome {
+    /// Returns true if the rule with the provided identifier matched.
+    #[must_use]
+    pub fn matched(&self, rule_id: &str) -> bool {
+        self.matched_rules.iter().any(|id| id == rule_id)
+    }
+}
+impl From<&RunOutput> for TestOutcome {
+    fn from(output: &RunOutput) -> Self {
+        let matched_rules = output
+            .events()
+            .into_iter()
+            .flat_map(Keyed::<WafArray>::iter)
+            .filter_map(|event| event.as_type::<WafMap>()?.get_str("rule"))
+            .filter_map(|rule| rule.as_type::<WafMap>()?.get_str("id")?.to_str())
+            .map(str::to_string)
+            .collect();
+        let actions = output
+            .actions()
+            .into_iter()
+            .flat_map(Keyed::<WafMap>::iter)
+            .filter_map(|action| action.key_str().ok())
+            .map(str::to_string)
+            .collect();
+        Self {
+            matched_rules,
+            actions,
+            duration: output.duration(),
+        }
+    }
+}
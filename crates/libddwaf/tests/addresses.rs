@@ -0,0 +1,85 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use libddwaf::addresses::{self, AddressMapBuilder};
+use libddwaf::{waf_array, waf_map};
+
+#[test]
+fn builder_matches_hand_built_map() {
+    let data = AddressMapBuilder::new()
+        .request_method("POST")
+        .request_uri("/login?redirect=%2F")
+        .request_headers(waf_map!(("user-agent", "Arachni/v1")))
+        .request_cookies(waf_map!(("session", "abc")))
+        .request_query(waf_map!(("redirect", "/")))
+        .request_path_params(waf_map!(("id", "42")))
+        .request_body(waf_map!(("password", "hunter2")))
+        .response_status(403)
+        .response_headers(waf_map!(("content-type", "text/html")))
+        .response_body("denied")
+        .client_ip(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+        .user_id("user-1")
+        .user_login("admin")
+        .session_id("abc")
+        .build();
+
+    let expected = waf_map!(
+        ("server.request.method", "POST"),
+        ("server.request.uri.raw", "/login?redirect=%2F"),
+        (
+            "server.request.headers.no_cookies",
+            waf_map!(("user-agent", "Arachni/v1"))
+        ),
+        ("server.request.cookies", waf_map!(("session", "abc"))),
+        ("server.request.query", waf_map!(("redirect", "/"))),
+        ("server.request.path_params", waf_map!(("id", "42"))),
+        ("server.request.body", waf_map!(("password", "hunter2"))),
+        ("server.response.status", "403"),
+        (
+            "server.response.headers.no_cookies",
+            waf_map!(("content-type", "text/html"))
+        ),
+        ("server.response.body", "denied"),
+        ("http.client_ip", "192.0.2.1"),
+        ("usr.id", "user-1"),
+        ("usr.login", "admin"),
+        ("usr.session_id", "abc"),
+    );
+    assert_eq!(data, expected);
+}
+
+#[test]
+fn builder_replaces_addresses() {
+    let builder = AddressMapBuilder::new();
+    assert!(builder.is_empty());
+    let data = builder
+        .client_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .response_status(200)
+        .client_ip(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        .set(addresses::SYS_EXEC_CMD, waf_array!("ls", "-l"))
+        .set(addresses::RESPONSE_STATUS, "500")
+        .build();
+    assert_eq!(
+        data,
+        waf_map!(
+            ("http.client_ip", "::1"),
+            ("server.response.status", "500"),
+            ("server.sys.exec.cmd", waf_array!("ls", "-l")),
+        )
+    );
+}
+
+#[cfg(not(miri))]
+#[test]
+fn builder_output_matches_rules() {
+    use libddwaf::test_util::{assert_matches_rule, fixtures, TestWaf};
+
+    let waf = TestWaf::new(&fixtures::arachni_rule());
+    assert!(waf
+        .handle()
+        .uses_address(addresses::REQUEST_HEADERS_NO_COOKIES));
+
+    let data = AddressMapBuilder::new()
+        .request_headers(waf_map!(("user-agent", "Arachni/v1")))
+        .build();
+    assert_matches_rule(&waf, data, "arachni_rule");
+}
@@ -1,27 +1,79 @@
 #![cfg(not(miri))]
 
+mod common;
+
 use libddwaf::{
-    object::{WafMap, WafOwnedDefaultAllocator},
-    waf_array, waf_map, Builder, Config,
+    object::{WafArray, WafMap, WafObject, WafOwnedDefaultAllocator},
+    waf_array, waf_map, AcceptancePolicy, Builder, Config, Obfuscator, RejectionReason,
+    SyncBuilder,
 };
 
 #[test]
 pub fn blank_config() {
     let mut builder = Builder::new(Some(&Config::default())).expect("builder should be created");
+    assert!(!builder.as_raw().is_null());
     // Not adding any rules, so we can't get a handle...
     assert!(builder.build().is_none());
 }
 
+#[test]
+pub fn owned_config() {
+    let mut builder =
+        Builder::with_config(Config::new(Obfuscator::new(Some("password"), None::<&str>)))
+            .expect("builder should be created");
+    let obfuscator_paths = builder.config_paths(None);
+    assert_eq!(obfuscator_paths.len(), 1);
+
+    assert!(builder.add_or_update_config("arachni", &*common::ARACHNI_RULE, None));
+    assert!(builder.build().is_some());
+}
+
 #[test]
 #[cfg_attr(
-    debug_assertions,
+    any(debug_assertions, feature = "strict-asserts"),
     should_panic(
         expected = "path cannot be empty (bindings::ddwaf_builder_add_or_update_config would always fail)"
     )
 )]
 pub fn empty_path() {
     let mut builder = Builder::new(Some(&Config::default())).expect("builder should be created");
-    assert!(!builder.add_or_update_config("", &waf_map! {}, None)); // Panics when debug_assertions is enabled
+    assert!(!builder.add_or_update_config("", &waf_map! {}, None)); // Panics when debug_assertions or strict-asserts is enabled
+}
+
+#[test]
+#[cfg(feature = "serde-deserialize")]
+pub fn ruleset_parsed_as_object() {
+    let ruleset: WafObject = serde_json::from_str(
+        r#"{
+            "version": "2.1",
+            "rules": [{
+                "id": "parsed",
+                "name": "parsed",
+                "tags": {"type": "flow1", "category": "test"},
+                "conditions": [{
+                    "operator": "match_regex",
+                    "parameters": {"inputs": [{"address": "address.1"}], "regex": ".*"}
+                }]
+            }]
+        }"#,
+    )
+    .expect("ruleset should parse");
+
+    let mut builder = Builder::new(None).expect("builder should be created");
+    assert!(builder.add_or_update_config("parsed", &ruleset, None));
+    assert_eq!(loaded_rule_ids(&builder), ["parsed"]);
+    assert!(builder.build().is_some());
+}
+
+#[test]
+#[cfg_attr(
+    any(debug_assertions, feature = "strict-asserts"),
+    should_panic(expected = "ruleset must be a map, but is Array")
+)]
+pub fn non_map_ruleset() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    let ruleset = WafObject::from(waf_array![waf_map! { ("version", "2.1") }]);
+    assert!(!builder.add_or_update_config("array", &ruleset, None)); // Panics when debug_assertions or strict-asserts is enabled
 }
 
 #[test]
@@ -108,7 +160,560 @@ pub fn add_update_remove_config() {
         assert_eq!(path.to_str(), Some("test"));
     }
 
-    assert!(builder.remove_config("test"));
+    assert_eq!(builder.try_config_paths_count(Some("^test$")), Ok(1));
+    assert_eq!(
+        builder.try_config_paths(Some("^other$")).map(|p| p.len()),
+        Ok(0)
+    );
+
+    assert_eq!(builder.try_remove_config("test"), Ok(true));
     assert_eq!(builder.config_paths_count(None), 0);
     assert!(builder.config_paths(None).is_empty());
+    assert_eq!(builder.try_remove_config("test"), Ok(false));
+    assert_eq!(
+        builder.try_add_or_update_config("test", &rules_1, None),
+        Ok(true)
+    );
+}
+
+#[test]
+pub fn operation_timings() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    assert_eq!(builder.last_operation_duration(), None);
+    assert_eq!(builder.total_build_time(), std::time::Duration::ZERO);
+
+    let rules = waf_map! {
+        ("version", "2.1"),
+        ("rules", waf_array![
+            waf_map!{
+                ("id", "1"),
+                ("name", "rule 1"),
+                ("tags", waf_map!{ ("type", "flow1"), ("category", "test") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!{("address", "address.1")},
+                            ]),
+                            ("regex", ".*"),
+                        }),
+                    },
+                ]),
+                ("on_match", waf_array!["block"]),
+            },
+        ]),
+    };
+
+    let mut total = std::time::Duration::ZERO;
+    for path in ["first", "second", "third"] {
+        assert!(builder.add_or_update_config(path, &rules, None));
+        let last = builder
+            .last_operation_duration()
+            .expect("duration should be recorded");
+        assert!(!last.is_zero());
+        assert!(builder.total_build_time() > total);
+        total += last;
+        assert_eq!(builder.total_build_time(), total);
+    }
+
+    let waf = builder.build().expect("handle should be built");
+    assert!(!waf.build_duration().is_zero());
+    assert_eq!(
+        builder.last_operation_duration(),
+        Some(waf.build_duration())
+    );
+    total += waf.build_duration();
+    assert_eq!(builder.total_build_time(), total);
+
+    assert!(builder.remove_config("second"));
+    assert!(builder.total_build_time() > total);
+}
+
+#[test]
+pub fn export_merged_config() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    let rule = |id: &str, address: &str| {
+        waf_map! {
+            ("id", id),
+            ("name", "rule"),
+            ("tags", waf_map!{ ("type", "flow1"), ("category", "test") }),
+            ("conditions", waf_array![
+                waf_map!{
+                    ("operator", "match_regex"),
+                    ("parameters", waf_map!{
+                        ("inputs", waf_array![waf_map!{("address", address)}]),
+                        ("regex", ".*"),
+                    }),
+                },
+            ]),
+        }
+    };
+    let first = waf_map! {
+        ("version", "2.1"),
+        ("metadata", waf_map!{ ("rules_version", "1") }),
+        ("rules", waf_array![rule("1", "address.1")]),
+    };
+    let second = waf_map! {
+        ("version", "2.1"),
+        ("metadata", waf_map!{ ("rules_version", "2") }),
+        ("rules", waf_array![rule("2", "address.2")]),
+    };
+    assert!(builder.add_or_update_config("first", &first, None));
+    assert!(builder.add_or_update_config("second", &second, None));
+
+    let merged = builder.export_merged_config();
+    let rule_ids: Vec<_> = merged
+        .get_str("rules")
+        .and_then(|r| r.as_type::<WafArray>())
+        .expect("rules should be exported")
+        .iter()
+        .filter_map(|r| r.as_type::<WafMap>()?.get_str("id")?.to_str())
+        .collect();
+    assert_eq!(rule_ids, ["1", "2"]);
+    let rules_version = merged
+        .get_str("metadata")
+        .and_then(|m| m.as_type::<WafMap>()?.get_str("rules_version")?.to_str());
+    assert_eq!(rules_version, Some("2"));
+
+    assert!(builder.remove_config("second"));
+    let merged = builder.export_merged_config();
+    let rules = merged
+        .get_str("rules")
+        .and_then(|r| r.as_type::<WafArray>());
+    assert_eq!(rules.map(|r| r.len()), Some(1));
+}
+
+#[test]
+pub fn sync_builder_from_threads() {
+    let builder = std::sync::Arc::new(SyncBuilder::new(None).expect("builder should be created"));
+    assert!(builder.build().is_none());
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let builder = builder.clone();
+            std::thread::spawn(move || {
+                let id = format!("rule-{i}");
+                let rules = waf_map! {
+                    ("version", "2.1"),
+                    ("rules", waf_array![
+                        waf_map!{
+                            ("id", id.as_str()),
+                            ("name", id.as_str()),
+                            ("tags", waf_map!{ ("type", "flow1"), ("category", "test") }),
+                            ("conditions", waf_array![
+                                waf_map!{
+                                    ("operator", "match_regex"),
+                                    ("parameters", waf_map!{
+                                        ("inputs", waf_array![
+                                            waf_map!{("address", "address.1")},
+                                        ]),
+                                        ("regex", ".*"),
+                                    }),
+                                },
+                            ]),
+                        },
+                    ]),
+                };
+                let path = format!("config/{i}");
+                assert!(builder.add_or_update_config(&path, &rules, None));
+                // Building concurrently with other threads' updates is fine, too.
+                assert!(builder.build().is_some());
+                if i % 2 == 1 {
+                    assert!(builder.remove_config(&path));
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().expect("thread should not panic");
+    }
+
+    assert_eq!(builder.config_paths_count(None), 4);
+    let waf = builder.build().expect("handle should be built");
+    let mut ids: Vec<_> = waf.rules().iter().map(|r| r.id.as_str()).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, ["rule-0", "rule-2", "rule-4", "rule-6"]);
+
+    let builder = std::sync::Arc::into_inner(builder)
+        .expect("no other reference should remain")
+        .into_inner();
+    assert_eq!(builder.rules().count(), 4);
+}
+
+#[test]
+pub fn config_inventory() {
+    fn ruleset(ids: &[&str]) -> WafMap {
+        let mut rules = libddwaf::object::WafArray::new(ids.len().try_into().unwrap());
+        for (i, id) in ids.iter().enumerate() {
+            rules[i] = waf_map! {
+                ("id", *id),
+                ("name", *id),
+                ("tags", waf_map!{ ("type", "flow1"), ("category", "test") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!{("address", "address.1")},
+                            ]),
+                            ("regex", ".*"),
+                        }),
+                    },
+                ]),
+            }
+            .into();
+        }
+        waf_map! { ("version", "2.1"), ("rules", rules) }
+    }
+
+    let mut builder = Builder::new(None).expect("builder should be created");
+    assert!(builder.config_inventory().is_empty());
+
+    let before = std::time::SystemTime::now();
+    assert!(builder.add_or_update_config("first", &ruleset(&["1"]), None));
+    assert!(builder.add_or_update_config("second", &ruleset(&["2", "3"]), None));
+    let inventory = builder.config_inventory();
+    assert_eq!(
+        inventory
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>(),
+        ["first", "second"]
+    );
+    assert_eq!(inventory[0].rules_loaded, 1);
+    assert_eq!(inventory[1].rules_loaded, 2);
+    assert!(inventory.iter().all(|e| e.added_at >= before));
+    assert_ne!(inventory[0].fingerprint, inventory[1].fingerprint);
+
+    // Re-adding identical content yields the same fingerprint...
+    assert!(builder.add_or_update_config("first", &ruleset(&["1"]), None));
+    assert_eq!(
+        builder.config_inventory()[0].fingerprint,
+        inventory[0].fingerprint
+    );
+    assert!(builder.config_inventory()[0].added_at >= inventory[0].added_at);
+
+    // ...while updating the content changes it.
+    assert!(builder.add_or_update_config("first", &ruleset(&["1", "4", "5"]), None));
+    let updated = builder.config_inventory();
+    assert_eq!(updated.len(), 2);
+    assert_eq!(updated[0].path, "first");
+    assert_ne!(updated[0].fingerprint, inventory[0].fingerprint);
+    assert_eq!(updated[0].rules_loaded, 3);
+    assert_eq!(updated[1], inventory[1]);
+
+    assert!(builder.remove_config("second"));
+    let remaining = builder.config_inventory();
+    assert_eq!(remaining, &updated[..1]);
+    assert!(!builder.remove_config("second"));
+    assert_eq!(builder.config_inventory(), remaining);
+}
+
+#[test]
+pub fn reused_diagnostics() {
+    fn loaded_and_failed(diagnostics: &WafMap) -> (Vec<&str>, Vec<&str>) {
+        let rules: &WafMap = diagnostics
+            .get_str("rules")
+            .and_then(|o| o.as_type())
+            .expect("rules diagnostics should be present");
+        let ids = |key: &str| -> Vec<&str> {
+            rules
+                .get_str(key)
+                .and_then(|o| o.as_type::<libddwaf::object::WafArray>())
+                .map(|arr| arr.iter().filter_map(|o| o.to_str()).collect())
+                .unwrap_or_default()
+        };
+        (ids("loaded"), ids("failed"))
+    }
+    fn ruleset(id: &str) -> WafMap {
+        waf_map! {
+            ("version", "2.1"),
+            ("rules", waf_array![
+                waf_map!{
+                    ("id", id),
+                    ("name", id),
+                    ("tags", waf_map!{ ("type", "flow1"), ("category", "test") }),
+                    ("conditions", waf_array![
+                        waf_map!{
+                            ("operator", "match_regex"),
+                            ("parameters", waf_map!{
+                                ("inputs", waf_array![
+                                    waf_map!{("address", "address.1")},
+                                ]),
+                                ("regex", ".*"),
+                            }),
+                        },
+                    ]),
+                },
+            ]),
+        }
+    }
+
+    let mut builder = Builder::new(None).expect("builder should be created");
+    let mut diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+
+    assert!(builder.add_or_update_config("first", &ruleset("1"), Some(&mut diagnostics)));
+    assert_eq!(loaded_and_failed(&diagnostics), (vec!["1"], vec![]));
+
+    let invalid = waf_map! {
+        ("version", "2.1"),
+        ("rules", waf_array![ waf_map!{ ("id", "invalid") } ]),
+    };
+    assert!(!builder.add_or_update_config("invalid", &invalid, Some(&mut diagnostics)));
+    assert_eq!(loaded_and_failed(&diagnostics), (vec![], vec!["invalid"]));
+
+    assert!(builder.add_or_update_config("second", &ruleset("2"), Some(&mut diagnostics)));
+    assert_eq!(loaded_and_failed(&diagnostics), (vec!["2"], vec![]));
+
+    diagnostics.reset();
+    assert!(diagnostics.is_empty());
+    diagnostics.reset();
+}
+
+#[test]
+pub fn build_delta() {
+    let monitor = waf_map! {
+        ("version", "2.1"),
+        ("rules", waf_array![
+            waf_map!{
+                ("id", "monitor_rule"),
+                ("name", "Monitor only"),
+                ("tags", waf_map!{ ("type", "flow1"), ("category", "test") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!{("address", "server.request.query")},
+                            ]),
+                            ("regex", ".*"),
+                        }),
+                    },
+                ]),
+            },
+        ]),
+    };
+    let disable_arachni = waf_map! {
+        ("rules_override", waf_array![
+            waf_map!{
+                ("rules_target", waf_array![ waf_map!{ ("rule_id", "arachni_rule") } ]),
+                ("enabled", false),
+            },
+        ]),
+    };
+
+    let mut builder = Builder::new(None).expect("builder should be created");
+    assert!(builder.add_or_update_config("arachni", &*common::ARACHNI_RULE, None));
+    assert!(builder.add_or_update_config("monitor", &monitor, None));
+
+    let (_, delta) = builder.build_with_delta().expect("handle should be built");
+    assert_eq!(
+        delta.added_addresses,
+        [
+            "server.request.body",
+            "server.request.headers.no_cookies",
+            "server.request.query"
+        ]
+    );
+    assert!(delta.removed_addresses.is_empty());
+    assert_eq!(delta.added_actions, ["block_request"]);
+    assert!(delta.removed_actions.is_empty());
+    assert_eq!((delta.previous_rule_count, delta.rule_count), (0, 2));
+
+    // Nothing changed since the last build
+    let (_, delta) = builder.build_with_delta().expect("handle should be built");
+    assert!(delta.is_empty(), "{delta:?}");
+    assert_eq!((delta.previous_rule_count, delta.rule_count), (2, 2));
+
+    assert!(builder.add_or_update_config("override", &disable_arachni, None));
+    let (_, delta) = builder.build_with_delta().expect("handle should be built");
+    assert!(delta.added_actions.is_empty());
+    assert_eq!(delta.removed_actions, ["block_request"]);
+    assert!(!delta.is_empty());
+
+    // A failed build does not affect the next delta
+    assert!(builder.remove_config("arachni"));
+    assert!(builder.remove_config("monitor"));
+    assert!(builder.build_with_delta().is_none());
+    assert!(builder.add_or_update_config("monitor", &monitor, None));
+    let (_, delta) = builder.build_with_delta().expect("handle should be built");
+    assert!(delta.removed_actions.is_empty());
+    assert_eq!(delta.previous_rule_count, 2);
+    assert_eq!(delta.rule_count, 1);
+}
+
+#[test]
+pub fn read_paths_from_threads() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    assert!(builder.add_or_update_config("arachni", &*common::ARACHNI_RULE, None));
+    assert!(builder.add_or_update_config("password", &*common::PASSWORD_RULE, None));
+
+    // Read-only operations only require a shared reference
+    let builder = &builder;
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                s.spawn(move || {
+                    (
+                        builder.config_paths_count(None),
+                        builder.config_paths(Some("^arachni$")).len(),
+                        builder.config_inventory().len(),
+                    )
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), (2, 1, 2));
+        }
+    });
+}
+
+#[test]
+pub fn remove_config_matching() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    for (i, path) in [
+        "datadog/2/ASM/1/config",
+        "datadog/2/ASM/2/config",
+        "datadog/2/ASM/3/config",
+        "datadog/2/ASM_DD/1/config",
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let ruleset = policy_ruleset(vec![policy_rule(&i.to_string(), "match_regex")]);
+        assert!(builder.add_or_update_config(path, &ruleset, None));
+    }
+
+    assert_eq!(builder.remove_config_matching("datadog/2/ASM/"), 3);
+    let paths = builder.config_paths(None);
+    assert_eq!(
+        paths
+            .iter()
+            .filter_map(WafObject::to_str)
+            .collect::<Vec<_>>(),
+        ["datadog/2/ASM_DD/1/config"]
+    );
+    assert_eq!(builder.config_inventory().len(), 1);
+    assert_eq!(builder.remove_config_matching("datadog/2/ASM/"), 0);
+}
+
+/// Returns a rule matching anything on `address.1` using `operator`, which fails to load unless
+/// the operator is known to `libddwaf`.
+fn policy_rule(id: &str, operator: &str) -> WafObject {
+    waf_map! {
+        ("id", id),
+        ("name", id),
+        ("tags", waf_map!{ ("type", "flow1"), ("category", "test") }),
+        ("conditions", waf_array![
+            waf_map!{
+                ("operator", operator),
+                ("parameters", waf_map!{
+                    ("inputs", waf_array![
+                        waf_map!{("address", "address.1")},
+                    ]),
+                    ("regex", ".*"),
+                }),
+            },
+        ]),
+    }
+    .into()
+}
+
+fn policy_ruleset(rules: Vec<WafObject>) -> WafMap {
+    waf_map! {
+        ("version", "2.1"),
+        ("rules", rules.into_iter().collect::<WafArray>()),
+    }
+}
+
+fn loaded_rule_ids(builder: &Builder) -> Vec<&str> {
+    builder.rules().map(|r| r.id.as_str()).collect()
+}
+
+#[test]
+pub fn policy_accepts_config() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    let ruleset = policy_ruleset(vec![
+        policy_rule("good_1", "match_regex"),
+        policy_rule("good_2", "match_regex"),
+        policy_rule("bad_1", "no_such_operator"),
+    ]);
+    let policy = AcceptancePolicy {
+        max_failed_rules: Some(1),
+        required_rule_ids: &["good_1"],
+        ..AcceptancePolicy::default()
+    };
+    let mut diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+    let accepted = builder
+        .add_or_update_config_with_policy("rules", &ruleset, &policy, Some(&mut diagnostics))
+        .expect("config should be accepted");
+    assert_eq!(accepted.report.loaded, ["good_1", "good_2"]);
+    assert_eq!(accepted.report.failed, ["bad_1"]);
+    assert!((accepted.report.failed_ratio() - 1.0 / 3.0).abs() < f32::EPSILON);
+    assert!(diagnostics.get_str("rules").is_some());
+    assert_eq!(loaded_rule_ids(&builder), ["good_1", "good_2"]);
+}
+
+#[test]
+pub fn policy_rejects_failed_ratio_and_restores_previous() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    let previous = policy_ruleset(vec![policy_rule("previous", "match_regex")]);
+    assert!(builder.add_or_update_config("rules", &previous, None));
+
+    let ruleset = policy_ruleset(vec![
+        policy_rule("good_1", "match_regex"),
+        policy_rule("bad_1", "no_such_operator"),
+        policy_rule("bad_2", "no_such_operator"),
+    ]);
+    let policy = AcceptancePolicy {
+        max_failed_ratio: Some(0.5),
+        ..AcceptancePolicy::default()
+    };
+    let rejected = builder
+        .add_or_update_config_with_policy("rules", &ruleset, &policy, None)
+        .expect_err("config should be rejected");
+    assert!(matches!(
+        rejected.reason,
+        RejectionReason::FailedRatioExceeded { max, .. } if (max - 0.5).abs() < f32::EPSILON
+    ));
+    assert_eq!(rejected.report.loaded, ["good_1"]);
+    assert_eq!(rejected.report.failed, ["bad_1", "bad_2"]);
+    assert!(rejected.restored);
+
+    assert_eq!(loaded_rule_ids(&builder), ["previous"]);
+    let inventory = builder.config_inventory();
+    assert_eq!(inventory.len(), 1);
+    assert_eq!(
+        inventory[0].fingerprint,
+        WafObject::from(previous).content_hash()
+    );
+    assert!(builder.build().is_some());
+}
+
+#[test]
+pub fn policy_enforces_required_rules() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    let ruleset = policy_ruleset(vec![
+        policy_rule("good_1", "match_regex"),
+        policy_rule("required", "no_such_operator"),
+    ]);
+    let policy = AcceptancePolicy {
+        required_rule_ids: &["good_1", "required"],
+        ..AcceptancePolicy::default()
+    };
+    let rejected = builder
+        .add_or_update_config_with_policy("rules", &ruleset, &policy, None)
+        .expect_err("config should be rejected");
+    assert_eq!(
+        rejected.reason,
+        RejectionReason::MissingRequiredRules(vec!["required".to_string()])
+    );
+    assert_eq!(
+        rejected.to_string(),
+        "The configuration was rejected: required rules were not loaded: required"
+    );
+    assert!(!rejected.restored);
+    assert_eq!(builder.config_paths_count(None), 0);
+    assert!(loaded_rule_ids(&builder).is_empty());
 }
@@ -3,38 +3,10 @@
 use std::sync::LazyLock;
 
 use libddwaf::object::WafMap;
+use libddwaf::test_util::fixtures;
 use libddwaf::{waf_array, waf_map};
 
-pub static ARACHNI_RULE: LazyLock<WafMap> = LazyLock::new(|| {
-    waf_map! {
-        ("version", "2.1"),
-        ("rules", waf_array![
-            waf_map!{
-                ("id", "arachni_rule"),
-                ("name", "Block with default action"),
-                ("tags", waf_map!{ ("category", "attack_attempt"), ("type", "security_scanner") }),
-                ("conditions", waf_array![
-                    waf_map!{
-                        ("operator", "match_regex"),
-                        ("parameters", waf_map!{
-                            ("inputs", waf_array![
-                                waf_map!{
-                                    ("address", "server.request.headers.no_cookies"),
-                                    ("key_path", waf_array!["user-agent"]),
-                                },
-                                waf_map!{
-                                    ("address", "server.request.body"),
-                                },
-                            ]),
-                            ("regex", "Arachni"),
-                        }),
-                    },
-                ]),
-                ("on_match", waf_array!["block"])
-            },
-        ]),
-    }
-});
+pub static ARACHNI_RULE: LazyLock<WafMap> = LazyLock::new(fixtures::arachni_rule);
 
 pub static PASSWORD_RULE: LazyLock<WafMap> = LazyLock::new(|| {
     waf_map! {
@@ -0,0 +1,54 @@
+#![cfg(all(not(miri), feature = "compat-naming"))]
+#![allow(deprecated)]
+
+use libddwaf::compat::*;
+use libddwaf::object::WafObject;
+use libddwaf::{waf_array, waf_map};
+
+#[test]
+fn aliases_are_interchangeable() {
+    let mut map = WAFMap::new(3);
+    map[0] = ("string", WAFString::new("value").unwrap()).into();
+    map[1] = ("signed", WAFSigned::new(-1)).into();
+    map[2] = ("array", WAFArray::from(vec![WAFUnsigned::new(1)])).into();
+    assert_eq!(
+        map,
+        waf_map!(
+            ("string", "value"),
+            ("signed", -1_i64),
+            ("array", waf_array![1_u64])
+        )
+    );
+
+    let obj: WAFObject = map.into();
+    assert_eq!(obj.object_type(), WAFObjectType::Map);
+    assert!(obj.as_type::<WAFMap>().is_some());
+    let obj: WafObject = obj;
+    assert!(obj.as_type::<WAFArray>().is_none());
+
+    let values: [WAFObject; 4] = [
+        WAFBool::new(true).into(),
+        WAFFloat::new(1.5).into(),
+        WAFNull::new().into(),
+        WAFInvalid::default().into(),
+    ];
+    assert_eq!(
+        values.map(|v| v.object_type()),
+        [
+            WAFObjectType::Bool,
+            WAFObjectType::Float,
+            WAFObjectType::Null,
+            WAFObjectType::Invalid
+        ]
+    );
+}
+
+#[test]
+fn owned_alias() {
+    let Some(parsed): Option<WAFOwned<WAFObject>> = WafObject::from_json(r#"{"key": [1, 2]}"#)
+    else {
+        // Parsing JSON is not supported by all libddwaf releases.
+        return;
+    };
+    assert_eq!(parsed.object_type(), WAFObjectType::Map);
+}
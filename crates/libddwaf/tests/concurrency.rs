@@ -0,0 +1,287 @@
+#![cfg(not(miri))]
+
+use std::sync::{Arc, Barrier, Mutex, RwLock};
+use std::time::Duration;
+
+use libddwaf::object::{Keyed, WafArray, WafMap, WafObject, WafOwned, WafOwnedDefaultAllocator};
+use libddwaf::{
+    waf_array, waf_map, Builder, Context, Handle, RunOutput, RunResult, RunnableContext,
+};
+use static_assertions::assert_impl_all;
+
+use common::ARACHNI_RULE;
+
+mod common;
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+const THREADS: usize = 8;
+
+fn build() -> (Builder, Handle) {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    assert!(builder.add_or_update_config("rules", &*ARACHNI_RULE, None));
+    let handle = builder.build().expect("handle should be built");
+    (builder, handle)
+}
+
+fn attack() -> WafMap {
+    waf_map!(("server.request.body", "Arachni/v1.0"))
+}
+
+assert_impl_all!(WafObject: Send, Sync);
+assert_impl_all!(WafMap: Send, Sync);
+assert_impl_all!(WafArray: Send, Sync);
+assert_impl_all!(Keyed<WafObject>: Send, Sync);
+assert_impl_all!(WafOwned<WafMap>: Send, Sync);
+assert_impl_all!(WafOwnedDefaultAllocator<WafMap>: Send, Sync);
+assert_impl_all!(RunOutput: Send, Sync);
+assert_impl_all!(RunResult: Send, Sync);
+
+#[test]
+fn markers() {
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<Builder>();
+    send_sync::<Handle>();
+    send_sync::<Context>();
+    send_sync::<libddwaf::Subcontext>();
+    send_sync::<libddwaf::SyncBuilder>();
+}
+
+#[test]
+fn many_contexts_from_one_handle() {
+    let (_, handle) = build();
+    let handle = Arc::new(handle);
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            let handle = Arc::clone(&handle);
+            s.spawn(move || {
+                for _ in 0..50 {
+                    let mut ctx = handle.new_context();
+                    assert!(matches!(
+                        ctx.run(attack(), TIMEOUT),
+                        Ok(RunResult::Match(_))
+                    ));
+                    let mut ctx = handle.new_context();
+                    assert!(matches!(
+                        ctx.run(waf_map!(("server.request.body", "harmless")), TIMEOUT),
+                        Ok(RunResult::NoMatch(_))
+                    ));
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn concurrent_first_access_to_known_addresses() {
+    let (_, handle) = build();
+    let barrier = Barrier::new(THREADS);
+    let lists: Vec<_> = std::thread::scope(|s| {
+        let threads: Vec<_> = (0..THREADS)
+            .map(|_| {
+                s.spawn(|| {
+                    barrier.wait();
+                    assert!(handle.uses_address("server.request.headers.no_cookies"));
+                    let list = handle.known_addresses_cached();
+                    (list.as_ptr() as usize, list.len())
+                })
+            })
+            .collect();
+        threads.into_iter().map(|t| t.join().unwrap()).collect()
+    });
+    // All threads observe the same cached list.
+    assert!(lists.iter().all(|list| *list == lists[0]));
+    assert_eq!(lists[0].1, 2);
+}
+
+#[test]
+fn handle_swap_mid_flight() {
+    let (mut builder, handle) = build();
+    let current = RwLock::new(Arc::new(handle));
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..50 {
+                    let handle = Arc::clone(&current.read().unwrap());
+                    let mut ctx = handle.new_context();
+                    // The handle may be swapped (and the previous one dropped) at any point
+                    drop(handle);
+                    assert!(matches!(
+                        ctx.run(attack(), TIMEOUT),
+                        Ok(RunResult::Match(_))
+                    ));
+                    assert!(ctx.generation() <= current.read().unwrap().generation());
+                }
+            });
+        }
+        for _ in 0..20 {
+            let handle = builder.build().expect("handle should be built");
+            *current.write().unwrap() = Arc::new(handle);
+        }
+    });
+    assert_eq!(current.read().unwrap().generation(), 21);
+}
+
+#[test]
+fn context_outlives_handle() {
+    let (builder, handle) = build();
+    let mut ctx = handle.new_context();
+    drop(handle);
+    drop(builder);
+    assert!(matches!(
+        ctx.run(attack(), TIMEOUT),
+        Ok(RunResult::Match(_))
+    ));
+}
+
+#[test]
+fn context_moved_between_threads() {
+    let (_, handle) = build();
+    let mut ctx = handle.new_context();
+    assert!(matches!(
+        ctx.run(waf_map!(("server.request.query", "harmless")), TIMEOUT),
+        Ok(RunResult::NoMatch(_))
+    ));
+    let mut ctx = std::thread::spawn(move || {
+        assert!(matches!(
+            ctx.run(attack(), TIMEOUT),
+            Ok(RunResult::Match(_))
+        ));
+        ctx
+    })
+    .join()
+    .unwrap();
+    assert!(ctx
+        .run(waf_map!(("server.request.query", "harmless")), TIMEOUT)
+        .is_ok());
+    assert_eq!(ctx.run_count(), 3);
+}
+
+#[test]
+fn context_behind_mutex() {
+    let (_, handle) = build();
+    let ctx = Mutex::new(handle.new_context());
+    std::thread::scope(|s| {
+        for i in 0..THREADS {
+            let ctx = &ctx;
+            s.spawn(move || {
+                for j in 0..10 {
+                    let data = waf_map!((
+                        "server.request.query",
+                        waf_array!(format!("{i}-{j}").as_str())
+                    ));
+                    let res = ctx.lock().unwrap().run(data, TIMEOUT);
+                    assert!(matches!(res, Ok(RunResult::NoMatch(_))));
+                }
+            });
+        }
+    });
+    let mut ctx = ctx.into_inner().unwrap();
+    assert_eq!(ctx.run_count(), (THREADS * 10) as u64);
+    assert!(matches!(
+        ctx.run(attack(), TIMEOUT),
+        Ok(RunResult::Match(_))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn context_behind_tokio_mutex() {
+    const TASKS: u64 = 8;
+
+    let (_, handle) = build();
+    let ctx = Arc::new(tokio::sync::Mutex::new(handle.new_context()));
+    let tasks: Vec<_> = (0..TASKS)
+        .map(|i| {
+            let ctx = Arc::clone(&ctx);
+            tokio::spawn(async move {
+                let mut ctx = ctx.lock().await;
+                let before = ctx.run_count();
+                let data = waf_map!(("server.request.query", waf_array!(format!("{i}").as_str())));
+                assert!(matches!(ctx.run(data, TIMEOUT), Ok(RunResult::NoMatch(_))));
+                // The guard is held across the await point, possibly resuming on another thread.
+                tokio::task::yield_now().await;
+                assert!(matches!(
+                    ctx.run(waf_map!(("server.request.method", "GET")), TIMEOUT),
+                    Ok(RunResult::NoMatch(_))
+                ));
+                assert_eq!(ctx.run_count(), before + 2);
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.expect("task should complete");
+    }
+
+    let mut ctx = ctx.lock().await;
+    assert_eq!(ctx.run_count(), TASKS * 2);
+    assert!(matches!(
+        ctx.run(attack(), TIMEOUT),
+        Ok(RunResult::Match(_))
+    ));
+}
+
+#[test]
+fn subcontexts_from_threads() {
+    let (_, handle) = build();
+    let mut ctx = handle.new_context();
+    assert!(ctx
+        .run(waf_map!(("server.request.query", "harmless")), TIMEOUT)
+        .is_ok());
+    let ctx = &ctx;
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(move || {
+                let mut sub = ctx.new_subcontext().expect("subcontext should be created");
+                assert!(matches!(
+                    sub.run(attack(), TIMEOUT),
+                    Ok(RunResult::Match(_))
+                ));
+            });
+        }
+    });
+    assert_eq!(ctx.run_count(), 1);
+}
+
+#[test]
+fn run_result_sent_to_another_thread() {
+    let (_, handle) = build();
+    let mut ctx = handle.new_context();
+    let res = ctx.run(attack(), TIMEOUT).expect("run should succeed");
+
+    let (tx, rx) = std::sync::mpsc::channel::<RunResult>();
+    let receiver = std::thread::spawn(move || {
+        let res = rx.recv().expect("result should be received");
+        let RunResult::Match(output) = &res else {
+            panic!("Unexpected result: {res:?}");
+        };
+        let events = output.events().expect("events should be present");
+        assert_eq!(events.len(), 1);
+        // The result (and the WAF-owned data it holds) is released on this thread.
+        drop(res);
+    });
+    tx.send(res).expect("result should be sent");
+    receiver.join().expect("receiver thread should not panic");
+
+    // The context remains usable after its result was released on another thread.
+    assert!(ctx
+        .run(waf_map!(("server.request.query", "q")), TIMEOUT)
+        .is_ok());
+}
+
+#[test]
+fn diagnostics_sent_to_another_thread() {
+    let mut builder = Builder::new(None).expect("builder should be created");
+    let mut diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+    assert!(builder.add_or_update_config("rules", &*ARACHNI_RULE, Some(&mut diagnostics)));
+
+    let loaded = std::thread::spawn(move || {
+        diagnostics
+            .get_str("rules")
+            .and_then(|rules| rules.as_type::<WafMap>())
+            .and_then(|rules| rules.get_str("loaded"))
+            .and_then(|loaded| loaded.as_type::<WafArray>())
+            .map(WafArray::len)
+    })
+    .join()
+    .expect("thread should not panic");
+    assert_eq!(loaded, Some(1));
+}
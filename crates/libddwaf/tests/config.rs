@@ -20,7 +20,7 @@ pub fn key_only_obfuscator() {
     assert_eq!(
         obfuscator
             .key_regex()
-            .map(|r| unsafe { std::str::from_utf8_unchecked(r) }),
+            .map(|r| std::str::from_utf8(r).unwrap()),
         Some(".*")
     );
     assert!(obfuscator.value_regex().is_none());
@@ -33,7 +33,7 @@ pub fn value_only_obfuscator() {
     assert_eq!(
         obfuscator
             .value_regex()
-            .map(|r| unsafe { std::str::from_utf8_unchecked(r) }),
+            .map(|r| std::str::from_utf8(r).unwrap()),
         Some(".*")
     );
 }
@@ -48,17 +48,37 @@ pub fn clone_validity() {
     assert_eq!(
         obfuscator
             .key_regex()
-            .map(|r| unsafe { std::str::from_utf8_unchecked(r) }),
+            .map(|r| std::str::from_utf8(r).unwrap()),
         Some("a")
     );
     assert_eq!(
         obfuscator
             .value_regex()
-            .map(|r| unsafe { std::str::from_utf8_unchecked(r) }),
+            .map(|r| std::str::from_utf8(r).unwrap()),
         Some("b")
     );
 }
 
+#[test]
+#[cfg(feature = "regex")]
+pub fn obfuscator_from_regex() {
+    let key = regex::Regex::new(r"(?i)pass(word)?").unwrap();
+    let value = regex::Regex::new(r"\d{4,}").unwrap();
+    let obfuscator = Obfuscator::from_regex(Some(&key), Some(&value));
+    assert_eq!(obfuscator.key_regex(), Some(key.as_str().as_bytes()));
+    assert_eq!(obfuscator.value_regex(), Some(value.as_str().as_bytes()));
+    assert_eq!(
+        obfuscator
+            .key_regex()
+            .map(|r| std::str::from_utf8(r).unwrap()),
+        Some(r"(?i)pass(word)?")
+    );
+
+    let obfuscator = Obfuscator::from_regex(None, Some(&value));
+    assert!(obfuscator.key_regex().is_none());
+    assert_eq!(obfuscator.value_regex(), Some(&b"\\d{4,}"[..]));
+}
+
 #[cfg(not(miri))]
 fn get_match_value(rr: &RunResult) -> &str {
     match rr {
@@ -13,16 +13,20 @@ use std::{sync::Arc, time::Duration};
 use libddwaf::object::WafOwnedDefaultAllocator;
 use libddwaf::{
     object::{WafArray, WafMap, WafObject},
-    waf_array, waf_map, Builder, Config, RunResult, RunnableContext,
+    waf_array, waf_map, waf_object, Builder, Config, RunResult, RunnableContext, SamplingDecision,
 };
 
-static ARACHNI_RULE: LazyLock<WafMap> = LazyLock::new(|| {
+use common::ARACHNI_RULE;
+
+mod common;
+
+static SPLIT_ADDRESS_RULE: LazyLock<WafMap> = LazyLock::new(|| {
     waf_map! {
         ("version", "2.1"),
         ("rules", waf_array![
             waf_map!{
-                ("id", "arachni_rule"),
-                ("name", "Block with default action"),
+                ("id", "split_address_rule"),
+                ("name", "Block when inputs are spread across batches"),
                 ("tags", waf_map!{ ("category", "attack_attempt"), ("type", "security_scanner") }),
                 ("conditions", waf_array![
                     waf_map!{
@@ -30,14 +34,21 @@ static ARACHNI_RULE: LazyLock<WafMap> = LazyLock::new(|| {
                         ("parameters", waf_map!{
                             ("inputs", waf_array![
                                 waf_map!{
-                                    ("address", "server.request.headers.no_cookies"),
-                                    ("key_path", waf_array!["user-agent"]),
+                                    ("address", "test.first"),
                                 },
+                            ]),
+                            ("regex", "first-value"),
+                        }),
+                    },
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
                                 waf_map!{
-                                    ("address", "server.request.body"),
+                                    ("address", "test.second"),
                                 },
                             ]),
-                            ("regex", "Arachni"),
+                            ("regex", "second-value"),
                         }),
                     },
                 ]),
@@ -47,13 +58,15 @@ static ARACHNI_RULE: LazyLock<WafMap> = LazyLock::new(|| {
     }
 });
 
-static SPLIT_ADDRESS_RULE: LazyLock<WafMap> = LazyLock::new(|| {
+/// A rule that only tags the trace with an attribute, without producing events; whether it
+/// requests the trace to be kept is set by `keep`.
+fn trace_tagging_rule(keep: bool) -> WafMap {
     waf_map! {
         ("version", "2.1"),
         ("rules", waf_array![
             waf_map!{
-                ("id", "split_address_rule"),
-                ("name", "Block when inputs are spread across batches"),
+                ("id", "trace_tagging_rule"),
+                ("name", "Tag traces from scanners"),
                 ("tags", waf_map!{ ("category", "attack_attempt"), ("type", "security_scanner") }),
                 ("conditions", waf_array![
                     waf_map!{
@@ -61,29 +74,25 @@ static SPLIT_ADDRESS_RULE: LazyLock<WafMap> = LazyLock::new(|| {
                         ("parameters", waf_map!{
                             ("inputs", waf_array![
                                 waf_map!{
-                                    ("address", "test.first"),
-                                },
-                            ]),
-                            ("regex", "first-value"),
-                        }),
-                    },
-                    waf_map!{
-                        ("operator", "match_regex"),
-                        ("parameters", waf_map!{
-                            ("inputs", waf_array![
-                                waf_map!{
-                                    ("address", "test.second"),
+                                    ("address", "server.request.headers.no_cookies"),
+                                    ("key_path", waf_array!["user-agent"]),
                                 },
                             ]),
-                            ("regex", "second-value"),
+                            ("regex", "Arachni"),
                         }),
                     },
                 ]),
-                ("on_match", waf_array!["block"])
+                ("output", waf_map!{
+                    ("event", false),
+                    ("keep", keep),
+                    ("attributes", waf_map!{
+                        ("_dd.appsec.trace.scanner", waf_map!{ ("value", "arachni") }),
+                    }),
+                }),
             },
         ]),
     }
-});
+}
 
 #[test]
 fn basic_run_rule_with_match() {
@@ -134,6 +143,10 @@ fn basic_run_rule_with_match() {
                 rule_first_event.get_str("id").unwrap().to_str().unwrap(),
                 "arachni_rule"
             );
+            assert_eq!(
+                result.matched_addresses(),
+                ["server.request.headers.no_cookies"]
+            );
 
             let actions = result.actions().expect("Expected some actions");
             assert_eq!(actions.len(), 1);
@@ -202,6 +215,42 @@ fn basic_run_rule_with_no_match() {
     }
 }
 
+#[test]
+fn run_result_output_accessors() {
+    let mut builder = Builder::new(Some(&Config::default())).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+
+    let run = |user_agent: &str| {
+        let mut ctx = waf.new_context();
+        let data = waf_map!((
+            "server.request.headers.no_cookies",
+            waf_map!(("user-agent", user_agent))
+        ));
+        ctx.run(data, Duration::from_secs(1)).unwrap()
+    };
+
+    let matched = run("Arachni");
+    assert!(matched.is_match());
+    assert!(!matched.is_no_match());
+    assert_eq!(matched.output().events().unwrap().len(), 1);
+    assert!(matched.output().keep());
+    let output = matched.into_output();
+    assert_eq!(
+        output.matched_addresses(),
+        ["server.request.headers.no_cookies"]
+    );
+
+    let not_matched = run("JDatabaseDriverMysqli");
+    assert!(not_matched.is_no_match());
+    assert!(!not_matched.is_match());
+    if let Some(events) = not_matched.output().events() {
+        assert!(events.is_empty());
+    }
+    assert!(!not_matched.output().keep());
+    assert!(!not_matched.into_output().timeout());
+}
+
 #[test]
 fn run_batches_context_matches_across_batches() {
     let mut builder = Builder::new(Some(&Config::default())).expect("Failed to create builder");
@@ -237,6 +286,43 @@ fn run_batches_subcontext_matches_across_batches() {
     assert_split_address_match(res);
 }
 
+#[test]
+fn run_pairs_matches() {
+    let mut builder = Builder::new(Some(&Config::default())).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&SPLIT_ADDRESS_RULE), None));
+    let waf = builder.build().unwrap();
+
+    let mut ctx = waf.new_context();
+    let res = ctx.run_pairs(
+        [
+            ("test.first", "first-value"),
+            ("test.second", "second-value"),
+        ],
+        Duration::from_secs(1),
+    );
+    match res {
+        Ok(RunResult::Match(result)) => {
+            assert_eq!(result.evaluated(), 1);
+            let events = result.events().expect("Expected some events");
+            assert_eq!(events.len(), 1);
+        }
+        _ => panic!("Unexpected result: {res:?}"),
+    }
+
+    let mut ctx = waf.new_context();
+    let res = ctx.run_pairs(
+        [("test.first", waf_object!("first-value"))],
+        Duration::from_secs(1),
+    );
+    assert!(matches!(res, Ok(RunResult::NoMatch(_))), "{res:?}");
+    let mut subctx = ctx.new_subcontext().unwrap();
+    let res = subctx.run_pairs(
+        vec![("test.second", waf_object!("second-value"))],
+        Duration::from_secs(1),
+    );
+    assert!(matches!(res, Ok(RunResult::Match(_))), "{res:?}");
+}
+
 fn assert_split_address_match(res: Result<RunResult, libddwaf::RunError>) {
     match res {
         Ok(RunResult::Match(result)) => {
@@ -328,6 +414,10 @@ fn test_run_error_display() {
         format!("{}", RunError::InvalidArgument),
         "The WAF encountered an invalid argument"
     );
+    assert_eq!(
+        format!("{}", RunError::CircuitOpen),
+        "The WAF circuit breaker is open"
+    );
 }
 
 #[test]
@@ -357,3 +447,474 @@ fn test_run_output_debug() {
         other => panic!("Expected match result, got: {other:?}"),
     }
 }
+
+#[test]
+fn test_raw_pointers() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+    let ctx = waf.new_context();
+    let other = waf.new_context();
+
+    assert!(!ctx.as_raw().is_null());
+    assert!(!other.as_raw().is_null());
+    assert_ne!(ctx.as_raw(), other.as_raw());
+    // Ownership is not transferred
+    assert_eq!(ctx.as_raw(), ctx.as_raw());
+}
+
+#[test]
+fn test_run_count() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+    let mut ctx = waf.new_context();
+    assert!(!ctx.has_run());
+    assert_eq!(ctx.run_count(), 0);
+
+    let data = || waf_map!(("server.request.body", "Arachni"));
+    assert!(matches!(
+        ctx.run(data(), Duration::from_secs(1)),
+        Ok(RunResult::Match(_))
+    ));
+    assert!(ctx.has_run());
+    assert_eq!(ctx.run_count(), 1);
+
+    assert!(ctx
+        .run_batches(waf_array!(data(), waf_map!()), Duration::from_secs(1))
+        .is_ok());
+    assert_eq!(ctx.run_count(), 2);
+
+    // Evaluations on subcontexts are not counted
+    let mut sub = ctx.new_subcontext().expect("Failed to create subcontext");
+    assert!(sub.run(data(), Duration::from_secs(1)).is_ok());
+    assert_eq!(ctx.run_count(), 2);
+}
+
+#[test]
+fn test_budget_remaining() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+    let mut ctx = waf.new_context();
+    let budget = Duration::from_secs(1);
+    assert_eq!(ctx.total_duration(), Duration::ZERO);
+    assert_eq!(ctx.budget_remaining(budget), budget);
+
+    let data = waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni"))
+    ));
+    let first = ctx.run(data, ctx.budget_remaining(budget)).unwrap();
+    let timeout = ctx.budget_remaining(budget);
+    assert_eq!(timeout, budget - first.output().duration());
+    let second = ctx
+        .run(waf_map!(("server.request.body", "Arachni")), timeout)
+        .unwrap();
+
+    let spent = first.output().duration() + second.output().duration();
+    assert_eq!(ctx.total_duration(), spent);
+    assert_eq!(ctx.budget_remaining(budget), budget - spent);
+    assert_eq!(ctx.budget_remaining(spent / 2), Duration::ZERO);
+
+    // Evaluations on subcontexts are not included
+    let mut sub = ctx.new_subcontext().expect("Failed to create subcontext");
+    assert!(sub
+        .run(waf_map!(("server.request.body", "Arachni")), budget)
+        .is_ok());
+    assert_eq!(ctx.total_duration(), spent);
+}
+
+#[test]
+fn test_persistent_addresses() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+    let mut ctx = waf.new_context();
+    // Addresses are only tracked once enabled.
+    assert!(ctx
+        .run(
+            waf_map!(("server.request.query", "untracked")),
+            Duration::from_secs(1)
+        )
+        .is_ok());
+    assert!(ctx.persistent_addresses().is_empty());
+    ctx.track_persistent_addresses(true);
+    assert!(ctx.persistent_addresses().is_empty());
+
+    assert!(ctx
+        .run(
+            waf_map!(
+                ("server.request.body", "harmless"),
+                ("server.request.method", "GET")
+            ),
+            Duration::from_secs(1)
+        )
+        .is_ok());
+    assert!(ctx
+        .run(
+            waf_map!(
+                (
+                    "server.request.headers.no_cookies",
+                    waf_map!(("user-agent", "curl"))
+                ),
+                ("server.request.body", "still harmless")
+            ),
+            Duration::from_secs(1)
+        )
+        .is_ok());
+    assert_eq!(
+        ctx.persistent_addresses(),
+        [
+            "server.request.body",
+            "server.request.method",
+            "server.request.headers.no_cookies"
+        ]
+    );
+
+    // Batches are reported, but not the data of subcontexts
+    assert!(ctx
+        .run_batches(
+            waf_array!(waf_map!(("server.response.status", "200"))),
+            Duration::from_secs(1)
+        )
+        .is_ok());
+    let mut sub = ctx.new_subcontext().expect("Failed to create subcontext");
+    assert!(sub
+        .run(
+            waf_map!(("server.request.query", "q")),
+            Duration::from_secs(1)
+        )
+        .is_ok());
+    assert_eq!(ctx.persistent_addresses().len(), 4);
+    assert_eq!(ctx.persistent_addresses()[3], "server.response.status");
+
+    ctx.track_persistent_addresses(false);
+    assert!(ctx.persistent_addresses().is_empty());
+}
+
+#[test]
+fn test_keepalive_limit() {
+    const LIMIT: usize = 3;
+
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+    let mut ctx = waf.new_context();
+    ctx.track_persistent_addresses(true);
+    assert_eq!(ctx.keepalive_limit(), None);
+    ctx.set_keepalive_limit(Some(LIMIT));
+    assert_eq!(ctx.keepalive_limit(), Some(LIMIT));
+
+    for i in 0..50 {
+        let address = format!("server.request.query.{i}");
+        let res = if i % 2 == 0 {
+            ctx.run(
+                waf_map!((address.as_str(), "harmless")),
+                Duration::from_secs(1),
+            )
+        } else {
+            ctx.run_batches(
+                waf_array!(waf_map!((address.as_str(), "harmless"))),
+                Duration::from_secs(1),
+            )
+        };
+        assert!(res.is_ok(), "{res:?}");
+        assert!(ctx.keepalive_len() <= LIMIT);
+    }
+    assert_eq!(ctx.keepalive_len(), LIMIT);
+    assert_eq!(ctx.run_count(), 50);
+    // Only the data of the retained evaluations is persistent.
+    assert_eq!(
+        ctx.persistent_addresses(),
+        [
+            "server.request.query.0",
+            "server.request.query.1",
+            "server.request.query.2"
+        ]
+    );
+
+    // Evaluations beyond the limit still produce matches.
+    let res = ctx.run(
+        waf_map!((
+            "server.request.headers.no_cookies",
+            waf_map!(("user-agent", "Arachni"))
+        )),
+        Duration::from_secs(1),
+    );
+    assert!(matches!(res, Ok(RunResult::Match(_))), "{res:?}");
+    assert_eq!(ctx.keepalive_len(), LIMIT);
+
+    // Removing the limit retains data again.
+    ctx.set_keepalive_limit(None);
+    assert!(ctx
+        .run(
+            waf_map!(("server.request.body", "harmless")),
+            Duration::from_secs(1)
+        )
+        .is_ok());
+    assert_eq!(ctx.keepalive_len(), LIMIT + 1);
+}
+
+#[test]
+fn test_max_context_memory() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let mut waf = builder.build().unwrap();
+    assert_eq!(waf.max_context_memory(), None);
+
+    let chunk = |i: usize| {
+        waf_map!((
+            format!("server.request.query.{i}").as_str(),
+            "x".repeat(100)
+        ))
+    };
+    let chunk_bytes = WafObject::from(chunk(0)).stats().estimated_bytes();
+
+    // Without a limit, the retained data is accounted for as it is submitted.
+    let mut ctx = waf.new_context();
+    assert_eq!(ctx.max_memory(), None);
+    assert_eq!(ctx.keepalive_bytes(), 0);
+    for i in 0..5 {
+        assert!(ctx.run(chunk(i), Duration::from_secs(1)).is_ok());
+    }
+    assert!(ctx
+        .run_batches(waf_array!(chunk(5), chunk(6)), Duration::from_secs(1))
+        .is_ok());
+    let batch_bytes = WafObject::from(waf_array!(chunk(5), chunk(6)))
+        .stats()
+        .estimated_bytes();
+    assert_eq!(ctx.keepalive_len(), 6);
+    assert_eq!(ctx.keepalive_bytes(), 5 * chunk_bytes + batch_bytes);
+
+    // The limit applies to the contexts created after it is set.
+    waf.set_max_context_memory(Some(3 * chunk_bytes));
+    assert_eq!(waf.max_context_memory(), Some(3 * chunk_bytes));
+    assert_eq!(ctx.max_memory(), None);
+    let mut ctx = waf.new_context();
+    assert_eq!(ctx.max_memory(), Some(3 * chunk_bytes));
+    for i in 0..3 {
+        assert!(ctx.run(chunk(i), Duration::from_secs(1)).is_ok());
+    }
+    assert_eq!(ctx.keepalive_bytes(), 3 * chunk_bytes);
+    let res = ctx.run(chunk(3), Duration::from_secs(1));
+    assert!(
+        matches!(
+            res,
+            Err(libddwaf::RunError::MemoryLimitExceeded { held, incoming })
+                if held == 3 * chunk_bytes && incoming == chunk_bytes
+        ),
+        "{res:?}"
+    );
+    assert_eq!((ctx.keepalive_len(), ctx.run_count()), (3, 3));
+    assert_eq!(ctx.keepalive_bytes(), 3 * chunk_bytes);
+
+    // The rejected data may still be evaluated as ephemeral data.
+    let mut subcontext = ctx.new_subcontext().unwrap();
+    let res = subcontext.run(
+        waf_map!(("server.request.body", "Arachni")),
+        Duration::from_secs(1),
+    );
+    assert!(matches!(res, Ok(RunResult::Match(_))), "{res:?}");
+}
+
+#[test]
+fn test_run_batch() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+    let mut ctx = waf.new_context();
+
+    let results = ctx.run_batch(
+        Some(waf_map!(("server.request.query", "harmless"))),
+        vec![
+            waf_map!(("server.request.body", "Arachni/v1.0")),
+            waf_map!(("server.request.body", "harmless")),
+        ],
+        Duration::from_secs(1),
+    );
+    assert_eq!(results.len(), 3);
+    assert!(matches!(results[0], Ok(RunResult::NoMatch(_))));
+    assert!(matches!(results[1], Ok(RunResult::Match(_))));
+    assert!(matches!(results[2], Ok(RunResult::NoMatch(_))));
+
+    // Ephemeral data was not retained by the context
+    assert_eq!(ctx.run_count(), 1);
+    let results = ctx.run_batch(
+        None,
+        vec![waf_map!(("server.request.query", "harmless"))],
+        Duration::from_secs(1),
+    );
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0], Ok(RunResult::NoMatch(_))));
+}
+
+#[test]
+fn test_effective_timeout() {
+    use libddwaf::Context;
+
+    assert_eq!(Context::effective_timeout(Duration::ZERO), 0);
+    assert_eq!(Context::effective_timeout(Duration::from_nanos(500)), 1);
+    assert_eq!(Context::effective_timeout(Duration::from_nanos(1_500)), 1);
+    assert_eq!(Context::effective_timeout(Duration::from_millis(1)), 1_000);
+    assert_eq!(Context::effective_timeout(Duration::MAX), u64::MAX);
+}
+
+#[test]
+fn context_into_deferred_drop() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+
+    let mut ctx = waf.new_context();
+    let data = waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni/v1"))
+    ));
+    assert!(matches!(
+        ctx.run(data, Duration::from_secs(1)),
+        Ok(RunResult::Match(_))
+    ));
+    ctx.into_deferred_drop();
+    // The context may outlive the handle, and is destroyed on the drop thread.
+    drop(waf);
+    libddwaf::object::flush_deferred_drops();
+}
+
+#[test]
+fn test_sampling_decision() {
+    fn run(ruleset: &WafMap, user_agent: &str) -> RunResult {
+        let mut builder = Builder::new(None).expect("Failed to create builder");
+        assert!(builder.add_or_update_config("rules", ruleset, None));
+        let waf = builder.build().unwrap();
+        let mut ctx = waf.new_context();
+        let data = waf_map!((
+            "server.request.headers.no_cookies",
+            waf_map!(("user-agent", user_agent))
+        ));
+        ctx.run(data, Duration::from_secs(1)).unwrap()
+    }
+
+    let res = run(&ARACHNI_RULE, "Arachni/v1");
+    assert!(matches!(res, RunResult::Match(_)));
+    assert_eq!(
+        res.sampling_decision(),
+        SamplingDecision::Keep { due_to_match: true }
+    );
+    assert!(res.sampling_decision().is_keep());
+
+    let res = run(&ARACHNI_RULE, "Mozilla/5.0");
+    assert!(matches!(res, RunResult::NoMatch(_)));
+    assert_eq!(res.sampling_decision(), SamplingDecision::NoOverride);
+    assert!(!res.sampling_decision().is_keep());
+
+    let res = run(&trace_tagging_rule(true), "Arachni/v1");
+    let (RunResult::Match(output) | RunResult::NoMatch(output)) = &res;
+    assert!(output.keep());
+    assert!(output.events().is_none_or(|events| events.is_empty()));
+    assert!(output
+        .attributes()
+        .is_some_and(|attributes| attributes.get_str("_dd.appsec.trace.scanner").is_some()));
+    assert_eq!(
+        output.sampling_decision(),
+        SamplingDecision::Keep {
+            due_to_match: false
+        }
+    );
+    assert_eq!(res.sampling_decision(), output.sampling_decision());
+
+    let res = run(&trace_tagging_rule(false), "Arachni/v1");
+    let (RunResult::Match(output) | RunResult::NoMatch(output)) = &res;
+    assert!(output.attributes().is_some());
+    assert_eq!(res.sampling_decision(), SamplingDecision::NoOverride);
+}
+
+#[test]
+fn run_output_to_tags() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+
+    let mut ctx = waf.new_context();
+    let data = waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni/v1"))
+    ));
+    let Ok(RunResult::Match(output)) = ctx.run(data, Duration::from_secs(1)) else {
+        panic!("Expected a match");
+    };
+    let tags = output.to_tags();
+    let keys: Vec<&str> = tags.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(
+        keys,
+        ["timeout", "keep", "duration_ns", "event_count", "rule_ids"]
+    );
+    let tag = |key: &str| {
+        tags.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    };
+    assert_eq!(tag("timeout"), Some("false"));
+    assert_eq!(tag("keep"), Some(output.keep().to_string().as_str()));
+    assert_eq!(
+        tag("duration_ns"),
+        Some(output.duration().as_nanos().to_string().as_str())
+    );
+    assert_eq!(tag("event_count"), Some("1"));
+    assert_eq!(tag("rule_ids"), Some("arachni_rule"));
+}
+
+#[test]
+fn run_output_into_owned_outlives_context() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+
+    let mut ctx = waf.new_context();
+    let data = waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni/v1"))
+    ));
+    let Ok(RunResult::Match(output)) = ctx.run(data, Duration::from_secs(1)) else {
+        panic!("Expected a match");
+    };
+    let owned: WafMap = output.into_data().into_owned();
+    drop(ctx);
+    drop(waf);
+    drop(builder);
+
+    let events: &WafArray = owned.get_str("events").unwrap().as_type().unwrap();
+    assert_eq!(events.len(), 1);
+    let rule: &WafMap = events[0]
+        .as_type::<WafMap>()
+        .unwrap()
+        .get_str("rule")
+        .unwrap()
+        .as_type()
+        .unwrap();
+    assert_eq!(
+        rule.get_str("id").unwrap().to_str().unwrap(),
+        "arachni_rule"
+    );
+}
+
+#[test]
+fn run_raw_result_contains_all_keys() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+
+    let mut ctx = waf.new_context();
+    let data = waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni/v1"))
+    ));
+    let raw = ctx
+        .run_raw_result(data, Duration::from_secs(1))
+        .expect("run should succeed");
+    assert!(raw.get_str("duration").is_some());
+    assert_eq!(raw.get_str("keep").and_then(|k| k.to_bool()), Some(true));
+    let events: &WafArray = raw.get_str("events").unwrap().as_type().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(ctx.run_count(), 1);
+}
@@ -0,0 +1,198 @@
+#![cfg(not(miri))]
+
+use std::time::Duration;
+
+use libddwaf::addresses::HTTP_CLIENT_IP;
+use libddwaf::data_configs::{DenyList, DenyListType};
+use libddwaf::object::{WafArray, WafMap};
+use libddwaf::{evaluate_once, waf_array, waf_map, Builder, Handle, LengthError, RunResult};
+
+mod common;
+
+const BLOCK_IP_RULE: &str = "blk-001-001";
+
+fn ip_match_rule() -> WafMap {
+    waf_map! {
+        ("version", "2.1"),
+        ("rules", waf_array![
+            waf_map!{
+                ("id", BLOCK_IP_RULE),
+                ("name", "Block IP Addresses"),
+                ("tags", waf_map!{ ("type", "block_ip"), ("category", "security_response") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "ip_match"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![waf_map!(("address", HTTP_CLIENT_IP))]),
+                            ("data", "blocked_ips"),
+                        }),
+                    },
+                ]),
+                ("on_match", waf_array!["block"])
+            },
+        ]),
+    }
+}
+
+fn matched_rule(handle: &Handle, client_ip: &str) -> Option<String> {
+    let data = waf_map!((HTTP_CLIENT_IP, client_ip));
+    let RunResult::Match(output) = evaluate_once(handle, data, Duration::from_secs(1)).unwrap()
+    else {
+        return None;
+    };
+    let event = output.events()?.iter().next()?;
+    let rule = event.as_type::<WafMap>()?.get_str("rule")?;
+    let id = rule.as_type::<WafMap>()?.get_str("id")?.to_str()?;
+    Some(id.to_string())
+}
+
+#[test]
+fn config_shape() {
+    let mut list = DenyList::new("blocked_users", DenyListType::Data);
+    assert!(list.insert("zoe", None).unwrap());
+    assert!(list.insert("alice", Some(1_700_000_000)).unwrap());
+    assert!(!list.insert("zoe", Some(1_800_000_000)).unwrap());
+    assert_eq!(list.len(), 2);
+
+    // Entries are sorted by value.
+    let expected = waf_map!((
+        "rules_data",
+        waf_array![waf_map!(
+            ("id", "blocked_users"),
+            ("type", "data_with_expiration"),
+            (
+                "data",
+                waf_array![
+                    waf_map!(("value", "alice"), ("expiration", 1_700_000_000_u64)),
+                    waf_map!(("value", "zoe"), ("expiration", 1_800_000_000_u64)),
+                ]
+            )
+        )]
+    ));
+    assert_eq!(list.to_config(), expected);
+    assert_eq!(
+        list.to_exclusion_config()
+            .get_str("exclusion_data")
+            .map(|data| data.value()),
+        expected.get_str("rules_data").map(|data| data.value())
+    );
+
+    assert!(list.remove("alice"));
+    assert!(!list.remove("alice"));
+    assert!(!list.contains("alice"));
+    assert_eq!(
+        list.to_data(),
+        waf_map!(
+            ("id", "blocked_users"),
+            ("type", "data_with_expiration"),
+            (
+                "data",
+                waf_array![waf_map!(
+                    ("value", "zoe"),
+                    ("expiration", 1_800_000_000_u64)
+                )]
+            )
+        )
+    );
+}
+
+#[test]
+fn diff_against() {
+    let mut list = DenyList::new("blocked_ips", DenyListType::Ip);
+    list.insert("192.168.1.1", None).unwrap();
+    list.insert("10.0.0.0/8", Some(1_900_000_000)).unwrap();
+
+    // The insertion order does not matter.
+    let mut same = DenyList::new("blocked_ips", DenyListType::Ip);
+    same.insert("10.0.0.0/8", Some(1_900_000_000)).unwrap();
+    same.insert("192.168.1.1", None).unwrap();
+    assert!(!same.diff_against(&list));
+    assert_eq!(same.to_config(), list.to_config());
+
+    let previous = list.clone();
+    list.insert("10.0.0.0/8", Some(2_000_000_000)).unwrap();
+    assert!(list.diff_against(&previous));
+
+    let mut reinserted = previous.clone();
+    reinserted.remove("192.168.1.1");
+    reinserted.insert("192.168.1.1", None).unwrap();
+    assert!(!reinserted.diff_against(&previous));
+
+    let empty = DenyList::new("blocked_ips", DenyListType::Ip);
+    assert!(DenyList::new("other_ips", DenyListType::Ip).diff_against(&empty));
+    assert!(DenyList::new("blocked_ips", DenyListType::Data).diff_against(&empty));
+}
+
+#[test]
+fn too_many_values() {
+    let mut list = DenyList::new("blocked_users", DenyListType::Data);
+    for i in 0..u16::MAX {
+        list.insert(&i.to_string(), None).unwrap();
+    }
+    let err = list.insert("one too many", None).unwrap_err();
+    assert_eq!(
+        err,
+        LengthError {
+            what: "deny list",
+            len: usize::from(u16::MAX) + 1,
+            max: usize::from(u16::MAX),
+        }
+    );
+    // Existing values can still be updated.
+    assert!(!list.insert("0", Some(1)).unwrap());
+    let data = list.to_data();
+    let values = data
+        .get_str("data")
+        .and_then(|data| data.as_type::<WafArray>());
+    assert_eq!(values.map(|values| values.len()), Some(u16::MAX));
+}
+
+#[test]
+fn blocks_listed_ips() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules/arachni", &*common::ARACHNI_RULE, None));
+    assert!(builder.add_or_update_config("rules/ip_match", &ip_match_rule(), None));
+
+    let mut blocked_ips = DenyList::new("blocked_ips", DenyListType::Ip);
+    blocked_ips.insert("192.168.1.1", None).unwrap();
+    blocked_ips.insert("10.0.0.0/8", None).unwrap();
+    assert!(builder.add_or_update_config("ASM_DATA/blocked_ips", &blocked_ips.to_config(), None));
+    let handle = builder.build().unwrap();
+
+    assert_eq!(
+        matched_rule(&handle, "192.168.1.1").as_deref(),
+        Some(BLOCK_IP_RULE)
+    );
+    assert_eq!(
+        matched_rule(&handle, "10.1.2.3").as_deref(),
+        Some(BLOCK_IP_RULE)
+    );
+    assert_eq!(matched_rule(&handle, "192.168.1.2"), None);
+
+    // Updating the list takes effect once a new handle is built.
+    let previous = blocked_ips.clone();
+    blocked_ips.remove("192.168.1.1");
+    blocked_ips.insert("192.168.1.2", None).unwrap();
+    assert!(blocked_ips.diff_against(&previous));
+    assert!(builder.add_or_update_config("ASM_DATA/blocked_ips", &blocked_ips.to_config(), None));
+    assert_eq!(
+        matched_rule(&handle, "192.168.1.1").as_deref(),
+        Some(BLOCK_IP_RULE)
+    );
+    let handle = builder.build().unwrap();
+    assert_eq!(matched_rule(&handle, "192.168.1.1"), None);
+    assert_eq!(
+        matched_rule(&handle, "192.168.1.2").as_deref(),
+        Some(BLOCK_IP_RULE)
+    );
+    assert_eq!(
+        matched_rule(&handle, "10.1.2.3").as_deref(),
+        Some(BLOCK_IP_RULE)
+    );
+
+    // Expired values are ignored.
+    blocked_ips.insert("192.168.1.2", Some(1)).unwrap();
+    assert!(builder.add_or_update_config("ASM_DATA/blocked_ips", &blocked_ips.to_config(), None));
+    let handle = builder.build().unwrap();
+    assert_eq!(matched_rule(&handle, "192.168.1.2"), None);
+}
@@ -0,0 +1,48 @@
+#![cfg(not(miri))]
+
+use std::sync::Mutex;
+
+use libddwaf::object::{defer_drop, flush_deferred_drops, WafArray, WafObject};
+use libddwaf::test_util::CountingAllocator;
+use libddwaf::waf_map;
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+static DROPPED_ON: Mutex<Option<std::thread::ThreadId>> = Mutex::new(None);
+
+struct DropProbe;
+impl Drop for DropProbe {
+    fn drop(&mut self) {
+        *DROPPED_ON.lock().unwrap() = Some(std::thread::current().id());
+    }
+}
+
+// Everything is done in a single test, as other tests would skew the allocation counts.
+#[test]
+fn deferred_drops_release_memory() {
+    // Values are dropped on another thread.
+    defer_drop(DropProbe);
+    flush_deferred_drops();
+    let dropped_on = DROPPED_ON.lock().unwrap().take();
+    assert!(dropped_on.is_some());
+    assert_ne!(dropped_on, Some(std::thread::current().id()));
+
+    // The memory is released by the drop thread, so the counts of all threads are needed.
+    let baseline = CountingAllocator::total_live_bytes().unwrap();
+    let mut arr = WafArray::new(10_000);
+    for (i, item) in arr.iter_mut().enumerate() {
+        *item = waf_map!(
+            ("value", "a string long enough to be stored out of line"),
+            ("index", i as u64)
+        )
+        .into();
+    }
+    assert!(CountingAllocator::total_live_bytes().unwrap() > baseline);
+    defer_drop(WafObject::from(arr));
+    flush_deferred_drops();
+    assert_eq!(CountingAllocator::total_live_bytes(), Some(baseline));
+
+    // Flushing with nothing left to drop returns immediately.
+    flush_deferred_drops();
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "encoding")]
+
+use std::error::Error as _;
+
+use libddwaf::object::{DecodeError, WafString};
+use libddwaf::Error;
+
+#[test]
+fn from_base64() {
+    let token = WafString::from_base64("YWRtaW46cGFzc3dvcmQ=").unwrap();
+    assert_eq!(token.as_bytes(), b"admin:password");
+
+    // The decoded bytes do not need to be valid UTF-8.
+    let binary = WafString::from_base64("/wCAJw==").unwrap();
+    assert_eq!(binary.as_bytes(), b"\xFF\x00\x80'");
+    assert!(binary.as_str().is_err());
+
+    // Long values are stored out of line.
+    let long = WafString::from_base64("PHNjcmlwdD5hbGVydCgnWFNTJyk8L3NjcmlwdD4=").unwrap();
+    assert_eq!(long.as_bytes(), b"<script>alert('XSS')</script>");
+
+    assert_eq!(WafString::from_base64("").unwrap().as_bytes(), b"");
+    assert_eq!(
+        WafString::from_base64("YWRtaW4").err(),
+        Some(DecodeError::Invalid)
+    );
+    assert_eq!(
+        WafString::from_base64("not base64!").err(),
+        Some(DecodeError::Invalid)
+    );
+}
+
+#[test]
+fn from_hex() {
+    let token = WafString::from_hex("61646d696e").unwrap();
+    assert_eq!(token.as_bytes(), b"admin");
+
+    let binary = WafString::from_hex("FF00807a").unwrap();
+    assert_eq!(binary.as_bytes(), b"\xFF\x00\x80z");
+
+    let long =
+        WafString::from_hex("3c7363726970743e616c657274282758535327293c2f7363726970743e").unwrap();
+    assert_eq!(long.as_bytes(), b"<script>alert('XSS')</script>");
+
+    assert_eq!(WafString::from_hex("").unwrap().as_bytes(), b"");
+    assert_eq!(WafString::from_hex("abc").err(), Some(DecodeError::Invalid));
+    assert_eq!(WafString::from_hex("zz").err(), Some(DecodeError::Invalid));
+    assert_eq!(WafString::from_hex("+1").err(), Some(DecodeError::Invalid));
+}
+
+#[test]
+fn decode_errors_convert_into_error() {
+    fn decode(token: &str) -> libddwaf::Result<WafString> {
+        Ok(WafString::from_base64(token)?)
+    }
+
+    let err = decode("not base64!").unwrap_err();
+    assert!(matches!(err, Error::Decode(DecodeError::Invalid)));
+    assert_eq!(err.to_string(), "Failed to decode encoded data");
+    assert!(err.source().unwrap().is::<DecodeError>());
+}
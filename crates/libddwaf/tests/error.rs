@@ -0,0 +1,105 @@
+#![cfg(not(miri))]
+
+use std::error::Error as _;
+use std::fmt;
+
+use libddwaf::object::{Keyed, SetPathError, WafMap, WafObject, WafObjectType, WafString};
+use libddwaf::{Error, RunError};
+
+/// Returns the messages of `err` and of its sources, outermost first.
+fn chain(err: &(dyn std::error::Error + 'static)) -> Vec<String> {
+    std::iter::successors(Some(err), |err| err.source())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// An error of an application, wrapping a [`libddwaf::Error`].
+#[derive(Debug)]
+struct LoadUserError {
+    source: Error,
+}
+impl fmt::Display for LoadUserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to load the user data")
+    }
+}
+impl std::error::Error for LoadUserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+fn set_user_id(map: &mut WafMap, id: &str) -> libddwaf::Result<()> {
+    map.set_path(&["usr", "id"], id)?;
+    Ok(())
+}
+
+fn load_user(map: &mut WafMap) -> Result<(), LoadUserError> {
+    set_user_id(map, "admin").map_err(|source| LoadUserError { source })
+}
+
+#[test]
+fn nested_source_chain() {
+    let mut map = WafMap::new(0);
+    map.insert("usr", 42_u64);
+    let err = load_user(&mut map).unwrap_err();
+    assert_eq!(
+        chain(&err),
+        [
+            "Failed to load the user data",
+            "Failed to set a value at a path",
+            "Invalid object type at path segment 1 (expected Map, got Unsigned)",
+        ]
+    );
+    let set_path = err
+        .source()
+        .and_then(|err| err.source())
+        .and_then(|err| err.downcast_ref::<SetPathError>());
+    assert_eq!(
+        set_path,
+        Some(&SetPathError::NotAMap {
+            depth: 1,
+            actual: WafObjectType::Unsigned
+        })
+    );
+}
+
+#[test]
+fn conversions() {
+    fn typed(keyed: Keyed<WafObject>) -> libddwaf::Result<Keyed<WafString>> {
+        Ok(keyed.try_into_typed()?)
+    }
+    let err = typed(("key", 1_u64).into()).unwrap_err();
+    assert!(matches!(err, Error::ObjectType(_)));
+    assert_eq!(
+        chain(&err),
+        [
+            "An object does not have the expected type",
+            "Invalid object type (expected String, got Unsigned)",
+        ]
+    );
+
+    fn as_str(string: &WafString) -> libddwaf::Result<&str> {
+        Ok(string.as_str()?)
+    }
+    let err = as_str(&WafString::from(&b"\xFF"[..])).unwrap_err();
+    assert!(matches!(err, Error::Utf8(_)));
+    assert_eq!(err.to_string(), "A string is not valid UTF-8");
+
+    let err = Error::from(RunError::CircuitOpen);
+    assert!(matches!(err, Error::Run(RunError::CircuitOpen)));
+    assert_eq!(
+        chain(&err),
+        [
+            "Failed to evaluate address data",
+            "The WAF circuit breaker is open"
+        ]
+    );
+
+    let err = Error::from(SetPathError::EmptyPath);
+    assert!(err.source().unwrap().is::<SetPathError>());
+
+    // Errors can also be boxed like any other.
+    let boxed: Box<dyn std::error::Error + Send + Sync> = Box::new(err);
+    assert_eq!(boxed.to_string(), "Failed to set a value at a path");
+}
@@ -1,6 +1,7 @@
 #![cfg(not(miri))]
 
-use libddwaf::Builder;
+use libddwaf::test_util::{fixtures, TestWaf};
+use libddwaf::{waf_array, waf_map, Builder, RunnableContext};
 
 use common::ARACHNI_RULE;
 
@@ -8,9 +9,9 @@ mod common;
 
 #[test]
 fn test_known_actions() {
-    let mut builder = Builder::new(None).expect("Failed to create builder");
-    assert!(builder.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
-    let waf = builder.build().unwrap();
+    let waf = TestWaf::new(&fixtures::arachni_rule());
+    let waf = waf.handle();
+    assert!(!waf.as_raw().is_null());
 
     let actions = waf.known_actions();
     assert!(!actions.is_empty());
@@ -20,9 +21,8 @@ fn test_known_actions() {
 
 #[test]
 fn test_known_addresses() {
-    let mut builder = Builder::new(None).expect("Failed to create builder");
-    assert!(builder.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
-    let waf = builder.build().unwrap();
+    let waf = TestWaf::new(&fixtures::arachni_rule());
+    let waf = waf.handle();
 
     let addresses = waf.known_addresses();
     assert!(!addresses.is_empty());
@@ -33,3 +33,183 @@ fn test_known_addresses() {
         Ok("server.request.headers.no_cookies")
     );
 }
+
+#[test]
+fn test_known_addresses_cached() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+
+    let cached = waf.known_addresses_cached();
+    assert_eq!(
+        cached,
+        ["server.request.body", "server.request.headers.no_cookies"]
+    );
+    // The list is only obtained once, and then borrowed by every call.
+    assert!(std::ptr::eq(cached, waf.known_addresses_cached()));
+    let uncached: Vec<_> = waf
+        .known_addresses()
+        .into_iter()
+        .map(|addr| addr.to_str().unwrap())
+        .collect();
+    assert_eq!(cached, uncached);
+}
+
+#[test]
+fn test_warm() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
+
+    // Lazy work happens on first use...
+    let cold = builder.build().unwrap();
+    assert_eq!(cold.lazy_init_duration(), None);
+    assert!(cold.uses_address("server.request.body"));
+    assert!(cold.lazy_init_duration().is_some());
+
+    // ...unless it was performed ahead of time.
+    let waf = builder.build().unwrap();
+    waf.warm();
+    let init_duration = waf
+        .lazy_init_duration()
+        .expect("warm() should populate the caches");
+    let cached = waf.known_addresses_cached();
+    waf.warm();
+
+    let mut ctx = waf.new_context();
+    assert!(ctx
+        .run(
+            waf_map!(("server.request.body", "harmless")),
+            std::time::Duration::from_secs(1)
+        )
+        .is_ok());
+    assert!(waf.uses_address("server.request.body"));
+    assert_eq!(waf.known_actions().len(), 1);
+    assert!(waf.address_phases().request);
+    assert_eq!(waf.lazy_init_duration(), Some(init_duration));
+    assert!(std::ptr::eq(cached, waf.known_addresses_cached()));
+}
+
+#[test]
+fn test_rules() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
+    assert_eq!(builder.rules().count(), 1);
+    let waf = builder.build().unwrap();
+
+    let rules = waf.rules();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].id, "arachni_rule");
+    assert_eq!(rules[0].name, "Block with default action");
+    assert_eq!(
+        rules[0].tags.get("category").map(String::as_str),
+        Some("attack_attempt")
+    );
+    assert_eq!(
+        rules[0].tags.get("type").map(String::as_str),
+        Some("security_scanner")
+    );
+
+    assert!(builder.remove_config("rules"));
+    assert_eq!(builder.rules().count(), 0);
+    // Existing handles are not affected
+    assert_eq!(waf.rules().len(), 1);
+}
+
+#[test]
+fn test_address_phases() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
+    let waf = builder.build().unwrap();
+
+    assert!(waf.uses_address("server.request.body"));
+    assert!(waf.uses_address("server.request.headers.no_cookies"));
+    assert!(!waf.uses_address("server.response.status"));
+    let phases = waf.address_phases();
+    assert!(phases.request);
+    assert!(!phases.response);
+    assert!(!phases.grpc);
+    assert!(!phases.graphql);
+
+    let response_rule = waf_map! {
+        ("version", "2.1"),
+        ("rules", waf_array![
+            waf_map!{
+                ("id", "response_rule"),
+                ("name", "Response status"),
+                ("tags", waf_map!{ ("category", "attack_attempt"), ("type", "security_scanner") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!{ ("address", "server.response.status") },
+                            ]),
+                            ("regex", "^5"),
+                        }),
+                    },
+                ]),
+            },
+        ]),
+    };
+    assert!(builder.add_or_update_config("response", &response_rule, None));
+    let updated = builder.build().unwrap();
+    assert!(updated.uses_address("server.response.status"));
+    assert!(updated.address_phases().response);
+    assert!(updated.address_phases().request);
+    // Existing handles are not affected
+    assert!(!waf.address_phases().response);
+}
+
+#[test]
+fn test_generation() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.build().is_none());
+    assert!(builder.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
+
+    // Failed builds do not consume a generation
+    let first = builder.build().unwrap();
+    assert_eq!(first.generation(), 1);
+    let ctx = first.new_context();
+    assert_eq!(ctx.generation(), 1);
+
+    let second = builder.build().unwrap();
+    assert_eq!(second.generation(), 2);
+    assert_eq!(second.new_context().generation(), 2);
+    // Contexts created from the previous instance are outdated
+    assert!(ctx.generation() < second.generation());
+    assert_eq!(first.new_context().generation(), 1);
+
+    // Generations are specific to each builder
+    let mut other = Builder::new(None).expect("Failed to create builder");
+    assert!(other.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
+    assert_eq!(other.build().unwrap().generation(), 1);
+}
+
+#[test]
+fn test_circuit_breaker() {
+    use libddwaf::{CircuitBreakerPolicy, CircuitState, RunnableContext};
+    use std::time::Duration;
+
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", std::sync::LazyLock::force(&ARACHNI_RULE), None));
+    let mut waf = builder.build().unwrap();
+    assert_eq!(waf.circuit_breaker_state(), None);
+
+    waf.set_circuit_breaker(CircuitBreakerPolicy::new(
+        1,
+        Duration::from_secs(30),
+        Duration::from_secs(30),
+    ));
+    assert_eq!(waf.circuit_breaker_state(), Some(CircuitState::Closed));
+
+    // Successful evaluations leave the circuit breaker closed.
+    let mut ctx = waf.new_context();
+    let data = waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni/v1"))
+    ));
+    assert!(ctx.run(data, Duration::from_secs(1)).is_ok());
+    let mut subctx = ctx.new_subcontext().unwrap();
+    assert!(subctx.run(waf_map!(), Duration::from_secs(1)).is_ok());
+    assert_eq!(waf.circuit_breaker_state(), Some(CircuitState::Closed));
+}
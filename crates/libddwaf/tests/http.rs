@@ -0,0 +1,184 @@
+#![cfg(feature = "http")]
+
+use std::error::Error as _;
+
+use libddwaf::http::{body_json_to_waf, body_multipart_to_waf, BodyError, ConversionLimits};
+use libddwaf::object::{WafMap, WafNull, WafObject};
+use libddwaf::{waf_array, waf_map, Error};
+
+const BOUNDARY: &str = "----WebKitFormBoundary7MA4YWxkTrZu0gW";
+
+#[test]
+fn nested_json() {
+    let body = br#"{"user": {"name": "admin", "roles": ["read", "write"]}, "age": 42}"#;
+    let object = body_json_to_waf(body, &ConversionLimits::default()).unwrap();
+    let map = object.as_type::<WafMap>().unwrap();
+    assert_eq!(
+        map,
+        &waf_map!(
+            (
+                "user",
+                waf_map!(("name", "admin"), ("roles", waf_array!["read", "write"]))
+            ),
+            ("age", 42_u64)
+        )
+    );
+
+    assert_eq!(
+        body_json_to_waf(br#"{"user": "#, &ConversionLimits::default()).err(),
+        Some(BodyError::InvalidJson)
+    );
+    assert_eq!(
+        body_json_to_waf(br"[1] [2]", &ConversionLimits::default()).err(),
+        Some(BodyError::InvalidJson)
+    );
+}
+
+#[test]
+fn nested_json_limits() {
+    let mut limits = ConversionLimits::default();
+    limits.values.max_depth = 2;
+    limits.values.max_string_length = 3;
+    // Strings are truncated, and containers nested too deeply are replaced with null.
+    let object = body_json_to_waf(br#"[["abcdef", [1]]]"#, &limits).unwrap();
+    assert_eq!(object, waf_array![waf_array!["abc", WafNull::new()]]);
+}
+
+#[test]
+fn multipart_fields_and_files() {
+    let body = format!(
+        "--{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"username\"\r\n\
+         \r\n\
+         admin\r\n\
+         --{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"comment\"\r\n\
+         \r\n\
+         <script>alert('XSS')</script>\r\n\
+         --{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"avatar\"; filename=\"../../etc/passwd\"\r\n\
+         Content-Type: image/png\r\n\
+         \r\n\
+         \x00\x01binary data\r\n\
+         --{BOUNDARY}--\r\n"
+    );
+    let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+    let map = body_multipart_to_waf(&content_type, body.as_bytes(), &ConversionLimits::default())
+        .unwrap();
+    assert_eq!(
+        map,
+        waf_map!(
+            ("username", "admin"),
+            ("comment", "<script>alert('XSS')</script>"),
+            (
+                "avatar",
+                waf_map!(
+                    ("filename", "../../etc/passwd"),
+                    ("content_type", "image/png")
+                )
+            )
+        )
+    );
+
+    // The boundary may be quoted.
+    let quoted = format!("Multipart/Form-Data; charset=utf-8; boundary=\"{BOUNDARY}\"");
+    assert_eq!(
+        body_multipart_to_waf(&quoted, body.as_bytes(), &ConversionLimits::default()).unwrap(),
+        map
+    );
+}
+
+#[test]
+fn multipart_limits() {
+    let body = format!(
+        "--{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"a\"\r\n\
+         \r\n\
+         first value\r\n\
+         --{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"b\"\r\n\
+         \r\n\
+         second value\r\n\
+         --{BOUNDARY}--"
+    );
+    let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+    let mut limits = ConversionLimits::default();
+    limits.values.max_elements = 1;
+    limits.values.max_string_length = 5;
+    assert_eq!(
+        body_multipart_to_waf(&content_type, body.as_bytes(), &limits).unwrap(),
+        waf_map!(("a", "first"))
+    );
+}
+
+#[test]
+fn invalid_multipart() {
+    let limits = ConversionLimits::default();
+    assert_eq!(
+        body_multipart_to_waf("application/json", b"{}", &limits).err(),
+        Some(BodyError::MissingBoundary)
+    );
+    assert_eq!(
+        body_multipart_to_waf("multipart/form-data", b"", &limits).err(),
+        Some(BodyError::MissingBoundary)
+    );
+
+    let content_type = format!("multipart/form-data; boundary={BOUNDARY}");
+    // No closing delimiter
+    let unterminated = format!(
+        "--{BOUNDARY}\r\n\
+         Content-Disposition: form-data; name=\"a\"\r\n\
+         \r\n\
+         value"
+    );
+    assert_eq!(
+        body_multipart_to_waf(&content_type, unterminated.as_bytes(), &limits).err(),
+        Some(BodyError::InvalidMultipart)
+    );
+    // No field name
+    let unnamed = format!(
+        "--{BOUNDARY}\r\n\
+         Content-Type: text/plain\r\n\
+         \r\n\
+         value\r\n\
+         --{BOUNDARY}--"
+    );
+    assert_eq!(
+        body_multipart_to_waf(&content_type, unnamed.as_bytes(), &limits).err(),
+        Some(BodyError::InvalidMultipart)
+    );
+}
+
+#[test]
+fn oversized_body() {
+    let limits = ConversionLimits {
+        max_body_size: 16,
+        ..ConversionLimits::default()
+    };
+    let body = br#"{"key": "a value that is too long"}"#;
+    let expected = BodyError::TooLarge {
+        len: body.len(),
+        max_len: 16,
+    };
+    assert_eq!(body_json_to_waf(body, &limits).err(), Some(expected));
+    assert_eq!(
+        body_multipart_to_waf("multipart/form-data; boundary=x", body, &limits).err(),
+        Some(expected)
+    );
+    assert_eq!(
+        expected.to_string(),
+        "Request body is too large (35 bytes, at most 16 are supported)"
+    );
+}
+
+#[test]
+fn body_errors_convert_into_error() {
+    fn convert(body: &[u8]) -> libddwaf::Result<WafObject> {
+        Ok(body_json_to_waf(body, &ConversionLimits::default())?)
+    }
+
+    let err = convert(b"{not json").unwrap_err();
+    assert!(matches!(err, Error::Body(BodyError::InvalidJson)));
+    assert_eq!(err.to_string(), "Failed to convert a request body");
+    assert!(err.source().unwrap().is::<BodyError>());
+}
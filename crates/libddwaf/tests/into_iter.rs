@@ -0,0 +1,92 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::{Keyed, WafArray, WafMap, WafObject};
+use libddwaf::test_util::{assert_no_leak, CountingAllocator};
+use libddwaf::{waf_array, waf_map};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const LONG: &str = "a string long enough to be stored out of line";
+const LONG_KEY: &str = "a key long enough to be stored out of line";
+
+#[test]
+fn partially_consumed_array() {
+    assert_no_leak(|| {
+        let array = waf_array![LONG, waf_map!((LONG_KEY, LONG)), 1_u64, LONG];
+        let mut iter = array.into_iter();
+        let first = iter.next().unwrap();
+        assert_eq!(first.to_str(), Some(LONG));
+        // The remaining elements are dropped along with the iterator.
+        (first, iter)
+    });
+
+    assert_no_leak(|| {
+        let array = waf_array![LONG, LONG];
+        assert_eq!(array.into_iter().count(), 2);
+    });
+}
+
+#[test]
+fn partially_consumed_map() {
+    assert_no_leak(|| {
+        let map = waf_map!(
+            (LONG_KEY, LONG),
+            ("short", 1_u64),
+            (LONG_KEY, waf_array![LONG])
+        );
+        let mut iter = map.into_iter();
+        let first = iter.next().unwrap();
+        assert_eq!(first.key_str().unwrap(), LONG_KEY);
+        (first, iter)
+    });
+
+    // Keyed containers release their own key as well.
+    assert_no_leak(|| {
+        let keyed: Keyed<WafMap> = (LONG_KEY, waf_map!((LONG_KEY, LONG), (LONG_KEY, LONG))).into();
+        let mut iter = keyed.into_iter();
+        iter.next().unwrap()
+    });
+    assert_no_leak(|| {
+        let keyed: Keyed<WafArray> = (LONG_KEY, waf_array![LONG, LONG]).into();
+        let mut iter = keyed.into_iter();
+        (iter.next().unwrap(), iter)
+    });
+}
+
+#[test]
+fn storage_larger_than_contents() {
+    // Truncated containers release the storage allocated for their full capacity.
+    assert_no_leak(|| {
+        let mut array = WafArray::new(4);
+        array[0] = LONG.into();
+        array[1] = LONG.into();
+        array.truncate(2);
+        assert_eq!((array.len(), array.capacity()), (2, 4));
+        let mut iter = array.into_iter();
+        iter.next().unwrap()
+    });
+
+    // Maps grown by insertion usually have spare capacity.
+    assert_no_leak(|| {
+        let mut map = WafMap::new(0);
+        for key in ["a", "b", LONG_KEY] {
+            map.insert(key, LONG);
+        }
+        assert!(map.capacity() > map.len());
+        let mut iter = map.into_iter();
+        iter.next().unwrap()
+    });
+
+    // Cleared containers still own their storage.
+    assert_no_leak(|| {
+        let mut array = waf_array![LONG, LONG];
+        array.clear();
+        assert!(array.into_iter().next().is_none());
+        let mut map = waf_map!((LONG_KEY, LONG));
+        map.clear();
+        map.into_iter().collect::<Vec<_>>()
+    });
+
+    assert_no_leak(|| WafArray::new(0).into_iter().collect::<Vec<WafObject>>());
+}
@@ -0,0 +1,113 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::{KeyCache, Keyed, WafMap, WafObject};
+use libddwaf::test_util::{assert_no_leak, CountingAllocator};
+use libddwaf::waf_map;
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const LONG_KEY: &str = "server.request.headers.no_cookies";
+
+/// Returns a new [`KeyCache`] that lives for the rest of the test binary.
+fn leaked_cache(limit: usize) -> &'static KeyCache {
+    Box::leak(Box::new(KeyCache::with_limit(limit)))
+}
+
+#[test]
+fn keys_match_uncached_keys() {
+    let cache = leaked_cache(KeyCache::DEFAULT_LIMIT);
+    assert!(cache.is_empty());
+
+    for key in ["", "short", LONG_KEY] {
+        let cached = cache.key(key);
+        assert_eq!(cached.as_str(), Ok(key));
+        assert_eq!(cache.key(key).as_bytes(), cached.as_bytes());
+    }
+    // Keys stored inline are not cached.
+    assert_eq!(cache.len(), 1);
+
+    let keyed = cache.keyed(LONG_KEY, 42_u64);
+    assert_eq!(keyed.key_str().unwrap(), LONG_KEY);
+    assert_eq!(keyed.value(), &WafObject::from(42_u64));
+    let mut map = WafMap::new(1);
+    map[0] = keyed;
+    assert_eq!(map, waf_map!((LONG_KEY, 42_u64)));
+    assert_eq!(cache.len(), 1);
+}
+
+#[test]
+fn insert_cached() {
+    static CACHE: KeyCache = KeyCache::new();
+
+    let mut cached = WafMap::new(0);
+    for round in 0..3_u64 {
+        cached.insert_cached(&CACHE, "short", round);
+        cached.insert_cached(&CACHE, LONG_KEY, "value");
+    }
+    assert_eq!(CACHE.len(), 1);
+    assert_eq!(cached.len(), 6);
+
+    let mut uncached = WafMap::new(0);
+    for round in 0..3_u64 {
+        uncached.insert("short", round);
+        uncached.insert(LONG_KEY, "value");
+    }
+    assert_eq!(cached, uncached);
+
+    let keys: Vec<_> = cached.iter().map(|e| e.key_str().unwrap()).collect();
+    assert_eq!(
+        keys,
+        ["short", LONG_KEY, "short", LONG_KEY, "short", LONG_KEY]
+    );
+}
+
+#[test]
+fn limit() {
+    let cache = leaked_cache(2);
+    let keys: Vec<String> = (0..3).map(|i| format!("{LONG_KEY}-{i}")).collect();
+    for key in &keys {
+        assert_eq!(cache.key(key).as_str(), Ok(key.as_str()));
+    }
+    assert_eq!(cache.len(), 2);
+
+    // Keys past the limit are still encoded, with their own copy.
+    let mut map = WafMap::new(3);
+    for (i, key) in keys.iter().enumerate() {
+        map[i] = cache.keyed(key, 1_u64);
+    }
+    let expected: Vec<_> = keys.iter().map(|key| (key.as_str(), 1_u64)).collect();
+    assert_eq!(map, WafMap::from(expected));
+    assert_eq!(cache.len(), 2);
+}
+
+#[test]
+fn cached_keys_do_not_allocate() {
+    let cache = leaked_cache(KeyCache::DEFAULT_LIMIT);
+    let keys: Vec<String> = (0..40)
+        .map(|i| format!("server.request.headers.x-header-{i}"))
+        .collect();
+    let build = |cached: bool| {
+        let mut map = WafMap::new(40);
+        for (i, key) in keys.iter().enumerate() {
+            map[i] = if cached {
+                cache.keyed(key, 1_u64)
+            } else {
+                Keyed::from((key.as_str(), 1_u64))
+            };
+        }
+        map
+    };
+    drop(build(true));
+    assert_eq!(cache.len(), 40);
+
+    for (cached, expected) in [(false, 1 + 40), (true, 1)] {
+        let before = CountingAllocator::allocations().unwrap();
+        let map = build(cached);
+        assert_eq!(CountingAllocator::allocations().unwrap() - before, expected);
+        drop(map);
+    }
+
+    // Nothing but the map's storage is allocated, and it is released with the map.
+    assert_no_leak(|| build(true));
+}
@@ -0,0 +1,228 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::{LazyWafArray, WafArray, WafObject, WafView};
+use libddwaf::test_util::{assert_no_leak, CountingAllocator};
+use libddwaf::{waf_array, waf_map, LengthError};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn lazy<T: Into<WafObject>>(values: impl IntoIterator<Item = T>) -> LazyWafArray {
+    let mut array = LazyWafArray::new();
+    for value in values {
+        array.push(value);
+    }
+    array
+}
+
+fn eager<T: Into<WafObject>>(values: impl IntoIterator<Item = T>) -> WafArray {
+    let values: Vec<WafObject> = values.into_iter().map(Into::into).collect();
+    let mut array = WafArray::new(values.len().try_into().unwrap());
+    for (slot, value) in array.iter_mut().zip(values) {
+        *slot = value;
+    }
+    array
+}
+
+#[test]
+fn homogeneous_scalars_are_compact() {
+    let unsigned = lazy(0..1_000_u64);
+    assert!(unsigned.is_compact());
+    assert_eq!(unsigned.len(), 1_000);
+    assert_eq!(unsigned.get(42), Some(WafView::Unsigned(42)));
+    assert_eq!(unsigned.get(1_000), None);
+    assert_eq!(unsigned.materialize().unwrap(), eager(0..1_000_u64));
+
+    let signed = lazy(-500..500_i64);
+    assert!(signed.is_compact());
+    assert_eq!(signed.materialize().unwrap(), eager(-500..500_i64));
+
+    let floats = [0.5, -1.25, f64::MAX];
+    let float = lazy(floats);
+    assert!(float.is_compact());
+    assert_eq!(float.iter().collect::<Vec<_>>(), floats.map(WafView::Float));
+    assert_eq!(float.materialize().unwrap(), eager(floats));
+
+    let bools = lazy([true, false, true]);
+    assert!(bools.is_compact());
+    assert_eq!(bools.materialize().unwrap(), waf_array![true, false, true]);
+
+    let empty = LazyWafArray::with_capacity(16);
+    assert!(empty.is_empty());
+    assert!(!empty.is_compact());
+    assert_eq!(empty.materialize().unwrap(), WafArray::new(0));
+}
+
+#[test]
+fn other_values_are_staged_as_objects() {
+    // A value of another type converts the staged scalars.
+    let mut mixed = lazy([1_u64, 2, 3]);
+    mixed.push(-4_i64);
+    assert!(!mixed.is_compact());
+    mixed.push("five");
+    mixed.push(waf_map!(("six", 6_u64)));
+    assert_eq!(
+        mixed.iter().take(4).collect::<Vec<_>>(),
+        [
+            WafView::Unsigned(1),
+            WafView::Unsigned(2),
+            WafView::Unsigned(3),
+            WafView::Signed(-4)
+        ]
+    );
+    assert_eq!(mixed.get(4), Some(WafView::Str("five")));
+    assert_eq!(
+        mixed.materialize().unwrap(),
+        waf_array![
+            1_u64,
+            2_u64,
+            3_u64,
+            -4_i64,
+            "five",
+            waf_map!(("six", 6_u64))
+        ]
+    );
+
+    let strings = lazy(["10.0.0.1", "a string long enough to be stored out of line"]);
+    assert!(!strings.is_compact());
+    assert_eq!(
+        strings.materialize().unwrap(),
+        waf_array!["10.0.0.1", "a string long enough to be stored out of line"]
+    );
+
+    let mut truncated = lazy(0..10_u64);
+    truncated.truncate(3);
+    assert_eq!(
+        truncated.materialize().unwrap(),
+        waf_array![0_u64, 1_u64, 2_u64]
+    );
+}
+
+#[test]
+fn too_many_values() {
+    let err = lazy(0..=u64::from(u16::MAX)).materialize().unwrap_err();
+    assert_eq!(
+        err,
+        LengthError {
+            what: "array",
+            len: usize::from(u16::MAX) + 1,
+            max: usize::from(u16::MAX),
+        }
+    );
+
+    assert_eq!(
+        lazy(0..u64::from(u16::MAX)).materialize().unwrap().len(),
+        u16::MAX
+    );
+}
+
+#[test]
+fn no_leak() {
+    const LONG: &str = "a string long enough to be stored out of line";
+    assert_no_leak(|| lazy(0..100_u64));
+    assert_no_leak(|| lazy(0..100_u64).materialize());
+    assert_no_leak(|| {
+        let mut array = lazy([true, false]);
+        array.push(LONG);
+        array.push(waf_array![LONG]);
+        array
+    });
+    assert_no_leak(|| {
+        let mut array = lazy([LONG, LONG]);
+        array.truncate(1);
+        array.materialize()
+    });
+}
+
+#[cfg(feature = "serde-deserialize")]
+mod serde {
+    use libddwaf::serde::{deserialize_with_container_limits, ContainerLimits};
+
+    use super::*;
+
+    /// A configuration in the style of an IP denylist, with IPv4 addresses encoded as integers.
+    fn denylist(count: u32) -> (String, WafObject) {
+        let addresses = (0..count).map(|i| u64::from(0x0A00_0000 + i));
+        let json = format!(
+            r#"{{"rules_data":[{{"id":"blocked_ips","type":"ip_list","data":[{}]}}]}}"#,
+            addresses
+                .clone()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let expected = waf_map!((
+            "rules_data",
+            waf_array![waf_map!(
+                ("id", "blocked_ips"),
+                ("type", "ip_list"),
+                ("data", eager(addresses))
+            )]
+        ));
+        (json, expected.into())
+    }
+
+    #[test]
+    fn matches_eager_deserialization() {
+        for count in [0, 1, 1_024, 1_025, 20_000] {
+            let (json, expected) = denylist(count);
+            let obj: WafObject = serde_json::from_str(&json).unwrap();
+            assert_eq!(obj, expected, "{count} addresses");
+
+            let mut de = serde_json::Deserializer::from_str(&json);
+            let limits = ContainerLimits {
+                max_container_size: u16::MAX.into(),
+                ..ContainerLimits::default()
+            };
+            let obj = deserialize_with_container_limits(&mut de, &limits).unwrap();
+            assert_eq!(obj, expected, "{count} addresses, with limits");
+        }
+
+        // Large arrays that are not homogeneous are preserved as well.
+        let values: Vec<_> = (0..5_000_u64)
+            .map(|i| match i % 3 {
+                0 => serde_json::json!(i),
+                1 => serde_json::json!(-i64::try_from(i).unwrap()),
+                _ => serde_json::json!(i.to_string()),
+            })
+            .collect();
+        let obj: WafObject =
+            serde_json::from_value(serde_json::Value::from(values.clone())).unwrap();
+        let expected = eager(values.iter().map(|value| {
+            match value {
+                serde_json::Value::String(s) => WafObject::from(s.as_str()),
+                value => value
+                    .as_u64()
+                    .map_or_else(|| WafObject::from(value.as_i64().unwrap()), WafObject::from),
+            }
+        }));
+        assert_eq!(obj, WafObject::from(expected));
+    }
+
+    #[test]
+    fn large_scalar_arrays_lower_peak_memory() {
+        const COUNT: u32 = 64_000;
+        let (json, expected) = denylist(COUNT);
+
+        // The eager path collects the elements as `WafObject`s before copying them into the array.
+        CountingAllocator::reset_peak();
+        let baseline = CountingAllocator::live_bytes().unwrap();
+        drop(eager((0..COUNT).map(|i| u64::from(0x0A00_0000 + i))));
+        let eager_peak = CountingAllocator::peak_bytes().unwrap() - baseline;
+
+        CountingAllocator::reset_peak();
+        let baseline = CountingAllocator::live_bytes().unwrap();
+        let obj: WafObject = serde_json::from_str(&json).unwrap();
+        let lazy_peak = CountingAllocator::peak_bytes().unwrap() - baseline;
+        assert_eq!(obj, expected);
+
+        // The array itself takes 16 bytes per element, and the eager path needs at least as much
+        // again for its staging vector, while integers are staged with 8 bytes each.
+        let array_size = 16 * isize::try_from(COUNT).unwrap();
+        assert!(eager_peak >= 2 * array_size, "eager peak: {eager_peak}");
+        assert!(
+            lazy_peak < eager_peak * 4 / 5,
+            "lazy peak: {lazy_peak}, eager peak: {eager_peak}"
+        );
+    }
+}
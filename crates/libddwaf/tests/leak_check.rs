@@ -0,0 +1,215 @@
+#![cfg(all(not(miri), feature = "leak-check"))]
+
+use std::collections::BTreeSet;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use libddwaf::object::stats::{self, StatsSnapshot};
+use libddwaf::object::{WafArray, WafMap, WafObject, WafString};
+use libddwaf::test_util::fixtures;
+use libddwaf::{waf_array, waf_map, Builder, Config, RunResult, RunnableContext};
+
+/// The counters are process-wide, so the tests of this file must not run concurrently.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn attack() -> WafMap {
+    waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni/v1.5.1 (a long enough user agent)"))
+    ))
+}
+
+#[test]
+fn run_rule_threaded() {
+    let _guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    stats::assert_all_released();
+
+    let mut builder = Builder::new(Some(&Config::default())).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", &fixtures::arachni_rule(), None));
+    let waf = Arc::new(builder.build().unwrap());
+    drop(builder);
+    assert_eq!(stats::handles_alive(), 1);
+
+    let threads: Vec<_> = (0..2)
+        .map(|_| {
+            let waf = waf.clone();
+            std::thread::spawn(move || {
+                let mut ctx = waf.new_context();
+                let mut subctx = ctx.new_subcontext().unwrap();
+                let res = subctx.run(attack(), Duration::from_secs(1));
+                assert!(matches!(res, Ok(RunResult::Match(_))));
+                let res = ctx.run(attack(), Duration::from_secs(1));
+                assert!(matches!(res, Ok(RunResult::Match(_))));
+                // The outputs are only released once the results are dropped.
+                assert!(stats::live_waf_owned_objects() > 0);
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    drop(waf);
+    stats::assert_all_released();
+}
+
+#[test]
+fn outputs_and_contexts() {
+    let _guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    stats::assert_all_released();
+
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", &fixtures::arachni_rule(), None));
+    let waf = builder.build().unwrap();
+    drop(builder);
+
+    let mut ctx = waf.new_context();
+    let sub = ctx.new_subcontext().unwrap();
+    assert_eq!(stats::contexts_alive(), 2);
+    drop(sub);
+    assert_eq!(stats::contexts_alive(), 1);
+
+    let RunResult::Match(output) = ctx.run(attack(), Duration::from_secs(1)).unwrap() else {
+        panic!("Expected a match");
+    };
+    assert_eq!(stats::live_waf_owned_objects(), 1);
+    // Moving the output out of the result does not count it twice.
+    let data = output.into_data();
+    assert_eq!(stats::live_waf_owned_objects(), 1);
+    // Nor does copying it into a Rust-owned value.
+    let owned = data.into_owned();
+    assert_eq!(stats::live_waf_owned_objects(), 0);
+    drop(owned);
+
+    drop(ctx);
+    drop(waf);
+    stats::assert_all_released();
+}
+
+#[test]
+fn conversions() {
+    let _guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    stats::assert_all_released();
+
+    // Strings allocated by copying or by taking ownership of a box, and moved between wrappers.
+    let copied = WafString::new("a value long enough to be stored out of line").unwrap();
+    let boxed = WafString::new_boxed(vec![b'a'; 300].into_boxed_slice()).unwrap();
+    assert_eq!(stats::live_rust_allocations(), 2);
+    let object: WafObject = copied.into();
+    let mut map = WafMap::new(0);
+    map.insert("boxed", boxed);
+    map.insert("copied", object);
+    // The storage of the map is allocated, but its keys are short enough to be stored inline.
+    assert_eq!(stats::live_rust_allocations(), 3);
+
+    // Arrays consumed by iterators, partially or completely.
+    let array: WafArray = waf_array!["a value long enough to be stored out of line", map];
+    let mut iter = array.into_iter();
+    let first = iter.next().unwrap();
+    drop(iter);
+    drop(first);
+
+    let array = waf_array![waf_map!(("key", "value")), 1_u64, "short"];
+    assert_eq!(array.into_iter().count(), 3);
+
+    // Clones, and values released by the WAF.
+    let clone = attack().clone();
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", &fixtures::arachni_rule(), None));
+    let waf = builder.build().unwrap();
+    let mut ctx = waf.new_context();
+    assert!(ctx.run(clone, Duration::from_secs(1)).is_ok());
+    drop(ctx);
+    drop(waf);
+    drop(builder);
+
+    if let Some(parsed) = WafObject::from_json(r#"{"key": ["a value long enough", 1]}"#) {
+        assert_eq!(stats::live_waf_owned_objects(), 1);
+        drop(parsed);
+    }
+
+    assert_eq!(stats::snapshot(), StatsSnapshot::default());
+}
+
+#[test]
+fn assert_all_released_reports_counts() {
+    let _guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    stats::assert_all_released();
+
+    let leaked = WafString::new("a value long enough to be stored out of line").unwrap();
+    let message = std::panic::catch_unwind(stats::assert_all_released)
+        .unwrap_err()
+        .downcast::<String>()
+        .unwrap();
+    assert_eq!(
+        *message,
+        "Some resources were not released: 1 Rust allocations, 0 WAF-owned objects, 0 contexts, \
+         0 handles"
+    );
+    drop(leaked);
+    stats::assert_all_released();
+}
+
+#[test]
+fn config_paths_chunked_releases_array_first() {
+    const PATHS: usize = 5000;
+    let _guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    stats::assert_all_released();
+
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    for i in 0..PATHS {
+        let id = format!("rule-{i}");
+        let ruleset = waf_map!(
+            ("version", "2.1"),
+            (
+                "rules",
+                waf_array![waf_map!(
+                    ("id", id.as_str()),
+                    ("name", id.as_str()),
+                    ("tags", waf_map!(("type", "flow1"), ("category", "test"))),
+                    (
+                        "conditions",
+                        waf_array![waf_map!(
+                            ("operator", "match_regex"),
+                            (
+                                "parameters",
+                                waf_map!(
+                                    ("inputs", waf_array![waf_map!(("address", "address.1"))]),
+                                    ("regex", ".*")
+                                )
+                            )
+                        )]
+                    )
+                )]
+            )
+        );
+        assert!(builder.add_or_update_config(&format!("tenant/{i}"), &ruleset, None));
+    }
+
+    let mut chunks = Vec::new();
+    let mut seen = BTreeSet::new();
+    let res = builder.config_paths_chunked(Some("^tenant/"), 1000, |chunk| {
+        // The array returned by libddwaf was released before the paths are delivered.
+        assert_eq!(stats::live_waf_owned_objects(), 0);
+        chunks.push(chunk.len());
+        seen.extend(chunk.iter().cloned());
+        ControlFlow::Continue(())
+    });
+    assert_eq!(res, ControlFlow::Continue(()));
+    assert_eq!(chunks, [1000; 5]);
+    assert_eq!(seen.len(), PATHS);
+    assert!(seen.contains("tenant/4999"));
+
+    let mut calls = 0;
+    let res = builder.config_paths_chunked(None, 64, |chunk| {
+        calls += 1;
+        assert_eq!(chunk.len(), 64);
+        ControlFlow::Break(())
+    });
+    assert_eq!(res, ControlFlow::Break(()));
+    assert_eq!(calls, 1);
+
+    drop(builder);
+    stats::assert_all_released();
+}
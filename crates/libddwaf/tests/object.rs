@@ -1,4 +1,6 @@
-use libddwaf::{object::*, waf_array, waf_map, waf_object};
+use std::collections::HashMap;
+
+use libddwaf::{object::*, waf_array, waf_get, waf_map, waf_object};
 
 #[test]
 #[allow(clippy::float_cmp)] // No operations are done on the values, they should be the same.
@@ -179,6 +181,14 @@ fn ddwaf_obj_from_conversions() {
     let obj: WafObject = 3.0.into();
     assert_eq!(obj.to_f64().unwrap(), 3.0f64);
 
+    // f32 values are widened exactly, even when they have no short decimal representation.
+    for value in [0.1f32, -1.5, f32::MAX, f32::MIN_POSITIVE, f32::INFINITY] {
+        let obj: WafObject = value.into();
+        assert_eq!(obj.to_f64().unwrap(), f64::from(value));
+        assert_eq!(WafFloat::from(value).value(), f64::from(value));
+    }
+    assert_ne!(WafFloat::from(0.1f32).value(), 0.1f64);
+
     let obj: WafObject = true.into();
     assert!(obj.to_bool().unwrap());
 
@@ -215,6 +225,15 @@ fn invalid_utf8() {
     assert!(obj.as_str().is_err());
 }
 
+#[test]
+fn string_without_data() {
+    // A string with a length but no data (which the safe API never produces) is empty.
+    let mut obj = WafString::new_literal(b"abc");
+    unsafe { obj.as_raw_mut().via.str_.ptr = std::ptr::null_mut() };
+    assert_eq!(obj.len(), 3);
+    assert_eq!(obj.as_bytes(), b"");
+}
+
 #[test]
 fn empty_key() {
     let map = waf_map!(("", 42_u64));
@@ -223,33 +242,73 @@ fn empty_key() {
 }
 
 #[test]
-#[should_panic(expected = "index out of bounds (3 >= 3)")]
+#[should_panic(expected = "WafArray index out of bounds (3 >= 3)")]
 fn array_index_out_of_bounds() {
     let arr = waf_array!(1u64, "hello", waf_object!(null));
     let _ = arr[3]; // Panics
 }
 
 #[test]
-#[should_panic(expected = "index out of bounds (3 >= 3)")]
+#[should_panic(expected = "WafArray index out of bounds (3 >= 3)")]
 fn array_index_mut_of_bounds() {
     let mut arr = waf_array!(1u64, "hello", waf_object!(null));
     arr[3] = 42u64.into(); // Panics
 }
 
 #[test]
-#[should_panic(expected = "index out of bounds (3 >= 3)")]
+#[should_panic(expected = "WafMap index out of bounds (3 >= 3)")]
 fn map_index_out_of_bounds() {
     let arr = waf_map!(("a", 1u64), ("b", "hello"), ("c", waf_object!(null)));
     let _ = arr[3]; // Panics
 }
 
 #[test]
-#[should_panic(expected = "index out of bounds (3 >= 3)")]
+#[should_panic(expected = "WafMap index out of bounds (3 >= 3)")]
 fn map_index_mut_of_bounds() {
     let mut arr = waf_map!(("a", 1u64), ("b", "hello"), ("c", waf_object!(null)));
     arr[3] = Keyed::from(("d", 42u64)); // Panics
 }
 
+#[test]
+fn array_positional_accessors() {
+    let mut arr = waf_array!(1u64, "hello", waf_object!(null));
+    assert_eq!(arr.get(1).and_then(WafObject::to_str), Some("hello"));
+    assert!(arr.get(3).is_none());
+    assert_eq!(arr.first().and_then(WafObject::to_u64), Some(1));
+    assert_eq!(
+        arr.last().map(WafObject::object_type),
+        Some(WafObjectType::Null)
+    );
+
+    *arr.get_mut(2).unwrap() = 42u64.into();
+    assert_eq!(arr.last().and_then(WafObject::to_u64), Some(42));
+    assert!(arr.get_mut(3).is_none());
+
+    let mut empty = WafArray::new(0);
+    assert!(empty.get(0).is_none());
+    assert!(empty.get_mut(0).is_none());
+    assert!(empty.first().is_none());
+    assert!(empty.last().is_none());
+}
+
+#[test]
+fn map_positional_accessors() {
+    let mut map = waf_map!(("a", 1u64), ("b", "hello"));
+    let entry = map.get_index(1).unwrap();
+    assert_eq!(entry.key_str().unwrap(), "b");
+    assert_eq!(entry.to_str(), Some("hello"));
+    assert!(map.get_index(2).is_none());
+
+    let key_mut = map.get_index_mut(0).unwrap().key_mut();
+    let _ = std::mem::replace(key_mut, "c".into());
+    assert_eq!(map[0].key_str().unwrap(), "c");
+    assert!(map.get_index_mut(2).is_none());
+
+    let mut empty = WafMap::new(0);
+    assert!(empty.get_index(0).is_none());
+    assert!(empty.get_index_mut(0).is_none());
+}
+
 #[test]
 fn keyed_obj_methods() {
     let mut map = waf_map!(("key", 42_u64));
@@ -660,7 +719,7 @@ fn try_from_implementations() {
 }
 
 #[test]
-#[cfg(not(miri))]
+#[cfg(all(not(miri), ddwaf_has_from_json))]
 fn test_from_json() {
     assert_eq!(
         WafObject::from_json(
@@ -688,10 +747,28 @@ fn test_from_json() {
 
     // No data
     assert!(WafObject::from_json("").is_none());
+    assert_eq!(
+        WafObject::try_from_json("").err(),
+        Some(FromJsonError::Invalid)
+    );
     // Invalid JSON (truncated)
     assert!(WafObject::from_json("{").is_none());
-    // Too large (but otherwise valid)
-    assert!(WafObject::from_json(format!(r#""{}""#, "a".repeat(u32::MAX as usize + 1))).is_none());
+    assert_eq!(
+        WafObject::try_from_json("{").err(),
+        Some(FromJsonError::Invalid)
+    );
+    // Documents larger than u32::MAX bytes are rejected by `LengthError::checked_len`, which is
+    // unit tested with fabricated lengths rather than allocating such a document here.
+}
+
+#[test]
+#[cfg(not(ddwaf_has_from_json))]
+fn test_from_json_unsupported() {
+    assert!(WafObject::from_json("{}").is_none());
+    assert_eq!(
+        WafObject::try_from_json("{}").err(),
+        Some(FromJsonError::Unsupported)
+    );
 }
 
 #[test]
@@ -738,6 +815,66 @@ fn test_array_from_large_array_truncates() {
         .unwrap();
 }
 
+#[test]
+fn test_map_from_runtime_slice_and_vec() {
+    let entries: Vec<(String, u64)> = (0..3).map(|i| (format!("key{i}"), i)).collect();
+
+    let map = WafMap::from(entries.as_slice());
+    assert_eq!(map.len(), 3);
+    for (i, (key, value)) in entries.iter().enumerate() {
+        assert_eq!(map[i].key_str().unwrap(), key);
+        assert_eq!(map.get_str(key).unwrap().to_u64(), Some(*value));
+    }
+
+    let map = WafMap::from(entries);
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get_str("key0").unwrap().to_u64(), Some(0));
+    assert_eq!(map.get_str("key2").unwrap().to_u64(), Some(2));
+
+    let empty: &[(&str, u64)] = &[];
+    assert!(WafMap::from(empty).is_empty());
+}
+
+#[test]
+fn test_array_from_vec() {
+    assert_eq!(
+        WafArray::from(vec![-1_i64, 0, i64::MAX]),
+        waf_array!(-1_i64, 0_i64, i64::MAX)
+    );
+    assert_eq!(WafArray::from(vec![-1_i32, 7]), waf_array!(-1_i64, 7_i64));
+    assert_eq!(WafArray::from(vec![1_u64, 2]), waf_array!(1_u64, 2_u64));
+    assert_eq!(WafArray::from(vec![1_u32, 2]), waf_array!(1_u64, 2_u64));
+    assert_eq!(WafArray::from(vec![0.5_f64, -2.0]), waf_array!(0.5, -2.0));
+    assert_eq!(WafArray::from(vec![true, false]), waf_array!(true, false));
+    assert_eq!(WafArray::from(vec!["a", "bc"]), waf_array!("a", "bc"));
+    assert_eq!(
+        WafArray::from(vec![String::from("a"), String::from("bc")]),
+        waf_array!("a", "bc")
+    );
+    assert_eq!(
+        WafArray::from(vec![WafObject::from(1_u64), waf_object!(null)]),
+        waf_array!(1_u64, waf_object!(null))
+    );
+    assert!(WafArray::from(Vec::<i64>::new()).is_empty());
+}
+
+#[test]
+fn test_map_from_hashmap() {
+    let signed: HashMap<String, i64> = [("a".to_string(), -1), ("b".to_string(), 2)].into();
+    let map = WafMap::from(signed);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get_str("a").unwrap().to_i64(), Some(-1));
+    assert_eq!(map.get_str("b").unwrap().to_i64(), Some(2));
+
+    let strings: HashMap<String, String> = [("k".to_string(), "v".to_string())].into();
+    assert_eq!(WafMap::from(strings), waf_map!(("k", "v")));
+
+    let floats: HashMap<&str, f64> = [("pi", 3.5)].into();
+    assert_eq!(WafMap::from(floats), waf_map!(("pi", 3.5)));
+
+    assert!(WafMap::from(HashMap::<String, i64>::new()).is_empty());
+}
+
 #[test]
 #[cfg(not(miri))] // takes too long
 fn test_map_from_large_slice_truncates() {
@@ -788,3 +925,790 @@ fn test_map_from_large_array_truncates() {
         .join()
         .unwrap();
 }
+
+#[test]
+fn keyed_pre_encoded_keys() {
+    let static_key = Keyed::with_static_key("user-agent", WafObject::from("Arachni"));
+    assert_eq!(static_key.key_str().unwrap(), "user-agent");
+    assert_eq!(static_key.value().to_str(), Some("Arachni"));
+
+    let boxed: Box<[u8]> = Box::from(&b"x-forwarded-for-header"[..]);
+    let boxed_key = Keyed::from_key_bytes(boxed, WafUnsigned::new(42));
+    assert_eq!(boxed_key.key_str().unwrap(), "x-forwarded-for-header");
+    assert_eq!(boxed_key.value().value(), 42);
+
+    let short_key = Keyed::from_key_bytes(Box::from(&b"host"[..]), WafObject::from(()));
+    assert_eq!(short_key.key_str().unwrap(), "host");
+
+    let empty_key = Keyed::from_key_bytes(Box::default(), WafObject::from(()));
+    assert_eq!(empty_key.key_bytes().unwrap(), b"");
+
+    let mut map = WafMap::new(3);
+    map[0] = static_key;
+    map[1] = boxed_key.into();
+    map[2] = short_key;
+    map[2].set_key_boxed(Box::from(&b"a-much-longer-replacement-key"[..]));
+    assert_eq!(map[2].key_str().unwrap(), "a-much-longer-replacement-key");
+    assert!(map.get_str("user-agent").is_some());
+    assert!(map.get_str("x-forwarded-for-header").is_some());
+    assert!(map.get_str("host").is_none());
+}
+
+#[test]
+fn checked_key_setters() {
+    let mut entry = Keyed::new("key", WafObject::from(1_u64));
+    *entry
+        .try_set_key_str("a-key-long-enough-to-be-allocated")
+        .unwrap()
+        .value_mut() = WafObject::from(2_u64);
+    assert_eq!(
+        entry.key_str().unwrap(),
+        "a-key-long-enough-to-be-allocated"
+    );
+    assert_eq!(entry.value().to_u64(), Some(2));
+
+    entry.set_key_checked("clé".as_bytes()).unwrap();
+    assert_eq!(entry.key_str().unwrap(), "clé");
+
+    // Invalid UTF-8 is rejected, and the previous key is kept.
+    let err = entry.set_key_checked(b"cl\xC3").unwrap_err();
+    assert_eq!(err.valid_up_to(), 2);
+    assert_eq!(entry.key_str().unwrap(), "clé");
+    assert!(entry.set_key_checked(b"\xFF\xFE").is_err());
+    assert_eq!(entry.key_bytes().unwrap(), "clé".as_bytes());
+
+    // The empty key is valid UTF-8.
+    entry.set_key_checked(b"").unwrap();
+    assert_eq!(entry.key_bytes().unwrap(), b"");
+}
+
+#[test]
+fn view_variants() {
+    let root = waf_array!(
+        42_u64,
+        -42_i64,
+        "Hello, world!",
+        WafString::from(&b"\xFF\xFE"[..]),
+        waf_array!(123_u64),
+        waf_map!(("key", 5.2)),
+        true,
+        waf_object!(null),
+        WafObject::default(),
+    );
+
+    for (i, obj) in root.iter().enumerate() {
+        match (i, obj.view()) {
+            (0, WafView::Unsigned(v)) => assert_eq!(v, 42),
+            (1, WafView::Signed(v)) => assert_eq!(v, -42),
+            (2, WafView::Str(v)) => assert_eq!(v, "Hello, world!"),
+            (3, WafView::Bytes(v)) => assert_eq!(v, b"\xFF\xFE"),
+            (4, WafView::Array(arr)) => assert_eq!(arr[0].view(), WafView::Unsigned(123)),
+            (5, WafView::Map(map)) => {
+                assert_eq!(map.get_str("key").unwrap().view(), WafView::Float(5.2));
+            }
+            (6, WafView::Bool(v)) => assert!(v),
+            (7, WafView::Null) | (8, WafView::Invalid) => {}
+            (i, view) => panic!("unexpected view at index {i}: {view:?}"),
+        }
+    }
+}
+
+#[test]
+fn string_encoding_checks() {
+    let ascii = WafString::from("Hello, world!");
+    assert!(ascii.is_ascii());
+    assert!(ascii.is_valid_utf8());
+    assert!(!ascii.contains_control_chars());
+
+    let utf8 = WafString::from("Grüße, 世界!");
+    assert!(!utf8.is_ascii());
+    assert!(utf8.is_valid_utf8());
+    assert!(!utf8.contains_control_chars());
+
+    let control = WafString::from("line one\r\nline two\0");
+    assert!(control.is_ascii());
+    assert!(control.is_valid_utf8());
+    assert!(control.contains_control_chars());
+    assert!(WafString::from("\x7F").contains_control_chars());
+
+    let invalid = WafString::from(&b"\xFF\xFE\x01"[..]);
+    assert!(!invalid.is_ascii());
+    assert!(!invalid.is_valid_utf8());
+    assert!(invalid.contains_control_chars());
+
+    let empty = WafString::from("");
+    assert!(empty.is_ascii());
+    assert!(empty.is_valid_utf8());
+    assert!(!empty.contains_control_chars());
+}
+
+#[test]
+fn map_flatten() {
+    let map = waf_map!(
+        ("a", waf_map!(("b", 1_u64), ("c", waf_array!("x", "y")))),
+        ("d", true),
+        ("e", waf_map!()),
+        ("a.b", 2_u64),
+    );
+
+    let flat = map.flatten(".");
+    let keys: Vec<_> = flat.iter().map(|e| e.key_str().unwrap()).collect();
+    assert_eq!(keys, ["a.b", "a.c.0", "a.c.1", "d", "a.b"]);
+    assert_eq!(flat.get_str("a.b").unwrap().to_u64(), Some(1));
+    assert_eq!(flat.get_str("a.c.1").unwrap().to_str(), Some("y"));
+    assert_eq!(flat.get_str("d").unwrap().to_bool(), Some(true));
+    assert_eq!(flat[4].to_u64(), Some(2));
+
+    let flat = map.flatten("/");
+    assert_eq!(flat.get_str("a/c/0").unwrap().to_str(), Some("x"));
+    assert!(flat.get_str("a.c.0").is_none());
+}
+
+#[test]
+fn map_get_or_insert_with() {
+    let mut map = WafMap::new(0);
+    let entry = map.get_or_insert_with("headers", || WafMap::new(0).into());
+    entry
+        .as_type_mut::<WafMap>()
+        .expect("entry should be a map")
+        .value_mut()
+        .insert("user-agent", "Arachni");
+    assert_eq!(map.len(), 1);
+
+    let entry = map.get_or_insert_with("headers", || panic!("entry should already exist"));
+    let headers = entry.as_type_mut::<WafMap>().unwrap().value_mut();
+    headers.get_or_insert_with(b"accept", || "*/*".into());
+    assert_eq!(map.len(), 1);
+    assert_eq!(
+        map,
+        waf_map!((
+            "headers",
+            waf_map!(("user-agent", "Arachni"), ("accept", "*/*"))
+        ))
+    );
+
+    for i in 0..100_u64 {
+        map.insert(i.to_string().as_str(), i);
+    }
+    assert_eq!(map.len(), 101);
+    assert!(map.capacity() >= 101);
+    assert_eq!(map.get_str("42").unwrap().to_u64(), Some(42));
+    assert_eq!(map.clone(), map);
+}
+
+#[test]
+fn object_diff_string() {
+    let left: WafObject = waf_map!(
+        ("user", "alice"),
+        (
+            "headers",
+            waf_map!(("host", "example.com"), ("accept", "*/*"))
+        ),
+        ("tags", waf_array!(1u64, 2u64)),
+    )
+    .into();
+    assert_eq!(left.diff_string(&left.clone()), None);
+
+    let right: WafObject = waf_map!(
+        ("user", "alice"),
+        ("headers", waf_map!(("host", "example.org"))),
+        ("tags", waf_array!(1u64, 3i64, 4u64)),
+        ("extra", ()),
+    )
+    .into();
+    let diff = left.diff(&right);
+    assert_eq!(
+        diff.iter().map(|d| d.path.as_str()).collect::<Vec<_>>(),
+        [
+            "$.headers.host",
+            "$.headers.accept",
+            "$.tags[1]",
+            "$.tags[2]",
+            "$.extra"
+        ]
+    );
+
+    let diff = left.diff_string(&right).expect("objects should differ");
+    assert!(
+        diff.contains(r#"$.headers.host: WafString("example.com") != WafString("example.org")"#),
+        "{diff}"
+    );
+    assert!(
+        diff.contains(r#"$.headers.accept: WafString("*/*") != <missing>"#),
+        "{diff}"
+    );
+    assert!(
+        diff.contains("$.tags[1]: WafUnsigned(2) != WafSigned(3)"),
+        "{diff}"
+    );
+    assert!(
+        diff.contains("$.tags[2]: <missing> != WafUnsigned(4)"),
+        "{diff}"
+    );
+    assert!(diff.contains("$.extra: <missing> != WafNull"), "{diff}");
+    assert!(!diff.contains("user"), "{diff}");
+
+    // Maps only differing by the order of their entries are reported as a whole
+    let left: WafObject = waf_map!(("a", 1u64), ("b", 2u64)).into();
+    let right: WafObject = waf_map!(("b", 2u64), ("a", 1u64)).into();
+    let diff = left.diff(&right);
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff[0].path, "$");
+}
+
+#[test]
+fn object_content_hash() {
+    let obj: WafObject = waf_map!(
+        ("name", "a string that is stored out of line"),
+        ("short", "inline"),
+        ("values", waf_array!(1u64, -1i64, 1.5, true, ())),
+    )
+    .into();
+    let copy = obj.clone();
+    assert_eq!(obj.content_hash(), copy.content_hash());
+
+    // The hash does not depend on how strings are stored
+    let small = WafObject::from(WafString::new("inline").unwrap());
+    let literal = WafObject::from(WafString::new_literal(b"inline".as_slice()));
+    assert_eq!(small.content_hash(), literal.content_hash());
+
+    let modified: WafObject = waf_map!(
+        ("name", "a string that is stored out of line"),
+        ("short", "inline"),
+        ("values", waf_array!(1u64, -1i64, 1.5, false, ())),
+    )
+    .into();
+    assert_ne!(obj.content_hash(), modified.content_hash());
+    assert_ne!(
+        WafObject::from(1u64).content_hash(),
+        WafObject::from(1i64).content_hash()
+    );
+    assert_ne!(
+        WafObject::from(waf_array!("ab", "c")).content_hash(),
+        WafObject::from(waf_array!("a", "bc")).content_hash()
+    );
+
+    // The hash is stable across runs and platforms
+    assert_eq!(obj.content_hash(), 194_171_032_435_260_329);
+}
+
+#[test]
+fn keyed_try_into_typed() {
+    let typed: Keyed<WafString> = ("user-agent", "Arachni").into();
+    let obj: Keyed<WafObject> = typed.into();
+    assert_eq!(obj.key_str().unwrap(), "user-agent");
+
+    let typed = obj
+        .try_into_typed::<WafString>()
+        .expect("value should be a string");
+    assert_eq!(typed.key_str().unwrap(), "user-agent");
+    assert_eq!(typed.value().as_str(), Ok("Arachni"));
+
+    let obj = Keyed::<WafObject>::from(typed);
+    assert_eq!(obj.key_str().unwrap(), "user-agent");
+    assert_eq!(obj.to_str(), Some("Arachni"));
+
+    let err = obj
+        .try_into_typed::<WafMap>()
+        .expect_err("value should not be a map");
+    assert_eq!(err.expected, WafObjectType::Map);
+    assert_eq!(err.actual, WafObjectType::String);
+}
+
+#[test]
+fn array_from_slices() {
+    let numbers: &[u64] = &[1, 2, 3];
+    assert_eq!(WafArray::from(numbers), waf_array!(1u64, 2u64, 3u64));
+
+    let strs: &[&str] = &["a", "b"];
+    assert_eq!(WafArray::from(strs), waf_array!("a", "b"));
+
+    let strings = vec!["foo".to_string(), "bar".to_string()];
+    assert_eq!(WafArray::from(strings.as_slice()), waf_array!("foo", "bar"));
+    // The slice is left untouched
+    assert_eq!(strings, ["foo", "bar"]);
+
+    let collected: WafArray = strings.iter().cloned().collect();
+    assert_eq!(collected, waf_array!("foo", "bar"));
+    let collected: WafArray = numbers.iter().map(|n| n * 2).collect();
+    assert_eq!(collected, waf_array!(2u64, 4u64, 6u64));
+
+    struct Header {
+        name: &'static str,
+        value: &'static str,
+    }
+    let headers = [
+        Header {
+            name: "accept",
+            value: "*/*",
+        },
+        Header {
+            name: "host",
+            value: "localhost",
+        },
+    ];
+    let arr = WafArray::from_slice_with(&headers, |h| {
+        waf_map!(("name", h.name), ("value", h.value)).into()
+    });
+    assert_eq!(
+        arr,
+        waf_array!(
+            waf_map!(("name", "accept"), ("value", "*/*")),
+            waf_map!(("name", "host"), ("value", "localhost")),
+        )
+    );
+
+    let empty: &[u64] = &[];
+    assert!(WafArray::from(empty).is_empty());
+}
+
+#[test]
+fn truncate_and_clear() {
+    let long = "a string long enough to be stored out of line";
+    let mut arr = waf_array!(
+        long,
+        1u64,
+        waf_array!(long),
+        waf_map!(("a key long enough to be stored out of line", long)),
+        long
+    );
+    arr.truncate(2);
+    assert_eq!(arr.len(), 2);
+    assert_eq!(arr.capacity(), 5);
+    assert_eq!(arr, waf_array!(long, 1u64));
+    // Growing is not possible through truncate
+    arr.truncate(4);
+    assert_eq!(arr.len(), 2);
+    arr.clear();
+    assert!(arr.is_empty());
+    arr.clear();
+    assert!(arr.is_empty());
+
+    let mut map = waf_map!(
+        ("first", long),
+        (
+            "a key long enough to be stored out of line",
+            waf_array!(long)
+        ),
+        ("third", 3u64)
+    );
+    map.clear();
+    assert!(map.is_empty());
+    assert_eq!(map.capacity(), 3);
+    // The map remains usable
+    map.insert(long, long);
+    assert_eq!(map.get_str(long).and_then(|v| v.to_str()), Some(long));
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn swap_and_swap_remove() {
+    let long = "a string long enough to be stored out of line";
+    let mut arr = waf_array!(1u64, long, waf_array!(long), 4u64);
+    arr.swap(0, 2);
+    assert_eq!(arr, waf_array!(waf_array!(long), long, 1u64, 4u64));
+    arr.swap(1, 1);
+    assert_eq!(arr, waf_array!(waf_array!(long), long, 1u64, 4u64));
+    assert_eq!(arr.swap_remove(1), WafObject::from(long));
+    assert_eq!(arr, waf_array!(waf_array!(long), 4u64, 1u64));
+    // Removing the last element does not swap anything
+    assert_eq!(arr.swap_remove(2), WafObject::from(1u64));
+    assert_eq!(arr.swap_remove(0), WafObject::from(waf_array!(long)));
+    assert_eq!(arr.swap_remove(0), WafObject::from(4u64));
+    assert!(arr.is_empty());
+    assert_eq!(arr.capacity(), 4);
+    let out_of_bounds = std::panic::AssertUnwindSafe(|| arr.swap_remove(0));
+    assert!(std::panic::catch_unwind(out_of_bounds).is_err());
+
+    let long_key = "a key long enough to be stored out of line";
+    let mut map = waf_map!(
+        ("first", long),
+        (long_key, waf_array!(long)),
+        ("third", 3u64)
+    );
+    map.swap(0, 1);
+    assert_eq!(
+        map,
+        waf_map!(
+            (long_key, waf_array!(long)),
+            ("first", long),
+            ("third", 3u64)
+        )
+    );
+    // The removed entry owns its key and value
+    let removed = map.swap_remove(0);
+    assert_eq!(removed.key_str().unwrap(), long_key);
+    assert_eq!(removed.value(), &WafObject::from(waf_array!(long)));
+    assert_eq!(map, waf_map!(("third", 3u64), ("first", long)));
+    drop(removed);
+    // The remaining entries are still usable
+    map.insert(long_key, long);
+    assert_eq!(map.get_str(long_key).and_then(|v| v.to_str()), Some(long));
+    assert_eq!(map.get_str("first").and_then(|v| v.to_str()), Some(long));
+}
+
+#[test]
+fn object_child_count() {
+    let map: WafObject = waf_map!(("a", 1_u64), ("b", waf_array!(1_u64, 2_u64, 3_u64))).into();
+    assert_eq!(map.child_count(), 2);
+    let arr: WafObject = waf_array!(1_u64, "two", waf_map!()).into();
+    assert_eq!(arr.child_count(), 3);
+    assert_eq!(WafObject::from(waf_array!()).child_count(), 0);
+
+    // Scalars, strings included, have no children.
+    assert_eq!(WafObject::from(42_u64).child_count(), 0);
+    assert_eq!(WafObject::from("a string value").child_count(), 0);
+    assert_eq!(waf_object!(null).child_count(), 0);
+    assert_eq!(WafObject::default().child_count(), 0);
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn map_from_indexmap() {
+    let mut index = indexmap::IndexMap::new();
+    index.insert("zeta", WafObject::from(1_u64));
+    index.insert("alpha", WafObject::from("two"));
+    index.insert("mu", waf_array!(3_u64).into());
+    index.shift_remove("alpha");
+    index.insert("beta", waf_object!(null));
+
+    let map = WafMap::from(index);
+    let keys: Vec<_> = map.iter().map(|e| e.key_str().unwrap()).collect();
+    assert_eq!(keys, ["zeta", "mu", "beta"]);
+    assert_eq!(
+        map,
+        waf_map!(
+            ("zeta", 1_u64),
+            ("mu", waf_array!(3_u64)),
+            ("beta", waf_object!(null))
+        )
+    );
+}
+
+#[test]
+fn map_set_and_remove_path() {
+    let mut map = WafMap::new(0);
+    for (key, value) in [
+        ("server.request.query.utm_source", "x"),
+        ("server.request.query.utm_medium", "y"),
+        ("server.request.method", "GET"),
+        ("usr.id", "admin"),
+    ] {
+        let path: Vec<_> = key.split('.').collect();
+        map.set_path(&path, value).unwrap();
+    }
+    // Overwriting an existing leaf replaces it.
+    map.set_path(&["usr", "id"], "user").unwrap();
+    assert_eq!(
+        map,
+        waf_map!(
+            (
+                "server",
+                waf_map!((
+                    "request",
+                    waf_map!(
+                        ("query", waf_map!(("utm_source", "x"), ("utm_medium", "y"))),
+                        ("method", "GET")
+                    )
+                ))
+            ),
+            ("usr", waf_map!(("id", "user")))
+        )
+    );
+
+    assert_eq!(map.set_path(&[], "x"), Err(SetPathError::EmptyPath));
+    assert_eq!(
+        map.set_path(&["server", "request", "method", "verb"], "x"),
+        Err(SetPathError::NotAMap {
+            depth: 3,
+            actual: WafObjectType::String
+        })
+    );
+
+    let removed = map.remove_path(&["server", "request", "query"]);
+    assert_eq!(
+        removed,
+        Some(waf_map!(("utm_source", "x"), ("utm_medium", "y")).into())
+    );
+    assert_eq!(
+        map,
+        waf_map!(
+            ("server", waf_map!(("request", waf_map!(("method", "GET"))))),
+            ("usr", waf_map!(("id", "user")))
+        )
+    );
+    assert_eq!(map.remove_path(&["server", "request", "query"]), None);
+    assert_eq!(map.remove_path(&["usr", "id", "nested"]), None);
+    assert_eq!(map.remove_path(&[]), None);
+
+    // Single-segment paths behave like remove.
+    assert_eq!(
+        map.remove_path(&["usr"]),
+        Some(waf_map!(("id", "user")).into())
+    );
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn string_split_and_find() {
+    let cookies = WafString::new(b"session=abc; theme=dark;; lang=\xff\xfe").unwrap();
+    let parts: Vec<_> = cookies.split(b';').collect();
+    assert_eq!(
+        parts,
+        [
+            b"session=abc".as_slice(),
+            b" theme=dark",
+            b"",
+            b" lang=\xff\xfe"
+        ]
+    );
+    assert_eq!(cookies.find(b"theme="), Some(13));
+    assert_eq!(cookies.find(b"\xff"), Some(31));
+    assert_eq!(cookies.find(b"missing"), None);
+    assert_eq!(cookies.find(b""), Some(0));
+
+    let empty = WafString::new("").unwrap();
+    assert_eq!(empty.split(b';').collect::<Vec<_>>(), [b"".as_slice()]);
+    assert_eq!(empty.find(b"a"), None);
+}
+
+#[test]
+fn nested_lookup_macro() {
+    let output = waf_map!((
+        "events",
+        waf_array!(waf_map!(
+            (
+                "rule",
+                waf_map!(
+                    ("id", "ua0-600-12x"),
+                    ("tags", waf_map!(("type", "attack_tool")))
+                )
+            ),
+            (
+                "rule_matches",
+                waf_array!(waf_map!(("operator", "match_regex")))
+            )
+        ))
+    ));
+
+    let manual = output
+        .get_str("events")
+        .and_then(|events| events.as_type::<WafArray>()?.get(0))
+        .and_then(|event| event.as_type::<WafMap>()?.get_str("rule"))
+        .and_then(|rule| rule.as_type::<WafMap>()?.get_str("tags"))
+        .and_then(|tags| tags.as_type::<WafMap>()?.get_str("type"));
+    let via_macro = waf_get!(output, "events", 0, "rule", "tags", "type");
+    assert_eq!(via_macro.and_then(WafObject::to_str), Some("attack_tool"));
+    assert_eq!(via_macro, manual.map(|keyed| &**keyed));
+
+    let key = String::from("operator");
+    let operator = waf_get!(output, "events", 0, "rule_matches", 0, key);
+    assert_eq!(operator.and_then(WafObject::to_str), Some("match_regex"));
+
+    let root = waf_get!(output);
+    assert_eq!(root.map(WafObject::object_type), Some(WafObjectType::Map));
+    assert!(waf_get!(output, "events", 1).is_none());
+    assert!(waf_get!(output, "events", "rule").is_none());
+    assert!(waf_get!(output, "missing", 0).is_none());
+}
+
+#[test]
+#[allow(clippy::float_cmp)] // No operations are done on the values, they should be the same.
+fn test_setters() {
+    let mut unsigned = WafUnsigned::default();
+    unsigned.set(1);
+    assert_eq!(unsigned.value(), 1);
+
+    let mut signed = WafSigned::default();
+    signed.set(-1);
+    assert_eq!(signed.value(), -1);
+
+    let mut float = WafFloat::default();
+    float.set(1.5);
+    assert_eq!(float.value(), 1.5);
+
+    let mut boolean = WafBool::default();
+    boolean.set(true);
+    assert!(boolean.value());
+
+    let mut string = WafString::default();
+    string.set("foobar");
+    assert_eq!(string.as_str().unwrap(), "foobar");
+    string.set("a string long enough to be stored out of line");
+    assert_eq!(
+        string.as_str().unwrap(),
+        "a string long enough to be stored out of line"
+    );
+    string.set(b"");
+    assert!(string.is_empty());
+
+    // Setters can be used on values nested in containers.
+    let mut map = waf_map!(("key", "value"), ("count", 1u64));
+    map.get_str_mut("key")
+        .and_then(|v| v.value_mut().as_type_mut::<WafString>())
+        .unwrap()
+        .set("other");
+    map.get_str_mut("count")
+        .and_then(|v| v.value_mut().as_type_mut::<WafUnsigned>())
+        .unwrap()
+        .set(2);
+    assert_eq!(map, waf_map!(("key", "other"), ("count", 2u64)));
+}
+
+#[test]
+fn test_index_as() {
+    let array = waf_array![1u64, "two", waf_array![3u64]];
+    assert_eq!(
+        array.index_as::<WafUnsigned>(0).map(WafUnsigned::value),
+        Some(1)
+    );
+    assert_eq!(
+        array.index_as::<WafString>(1).map(WafString::as_str),
+        Some(Ok("two"))
+    );
+    assert_eq!(array.index_as::<WafArray>(2).map(WafArray::len), Some(1));
+    // Type mismatches and out of bounds indices
+    assert!(array.index_as::<WafSigned>(0).is_none());
+    assert!(array.index_as::<WafMap>(2).is_none());
+    assert!(array.index_as::<WafUnsigned>(3).is_none());
+
+    let map = waf_map![("count", 1u64), ("name", "value")];
+    let count = map.index_as::<WafUnsigned>(0).unwrap();
+    assert_eq!(count.key_str().unwrap(), "count");
+    assert_eq!(count.value().value(), 1);
+    assert_eq!(
+        map.index_as::<WafString>(1).map(|e| e.as_str()),
+        Some(Ok("value"))
+    );
+    assert!(map.index_as::<WafString>(0).is_none());
+    assert!(map.index_as::<WafUnsigned>(2).is_none());
+}
+
+#[test]
+fn keyed_eq() {
+    let entry: Keyed<WafObject> = ("key", 42_u64).into();
+    let same: Keyed<WafUnsigned> = ("key", 42_u64).into();
+    let other_key: Keyed<WafObject> = ("other", 42_u64).into();
+    let other_value: Keyed<WafObject> = ("key", 43_u64).into();
+
+    assert_eq!(entry, same);
+    assert!(entry.eq_with_key(&same));
+    assert!(entry.eq_ignore_key(&same));
+
+    // Same value, different key
+    assert_ne!(entry, other_key);
+    assert!(!entry.eq_with_key(&other_key));
+    assert!(entry.eq_ignore_key(&other_key));
+
+    // Same key, different value
+    assert_ne!(entry, other_value);
+    assert!(!entry.eq_with_key(&other_value));
+    assert!(!entry.eq_ignore_key(&other_value));
+
+    // Keyed vs unkeyed, in both directions: only the values are compared.
+    let value = WafObject::from(42_u64);
+    let typed = WafUnsigned::new(42);
+    assert_eq!(value, entry);
+    assert_eq!(entry, value);
+    assert_eq!(value, other_key);
+    assert_eq!(other_key, value);
+    assert_eq!(typed, same);
+    assert_eq!(same, typed);
+    assert_eq!(typed, other_key);
+    assert_eq!(other_key, typed);
+    assert_ne!(value, other_value);
+    assert_ne!(other_value, value);
+    assert_ne!(typed, other_value);
+    assert_ne!(other_value, typed);
+}
+
+#[test]
+fn nested_map_keys_eq() {
+    let map = waf_map!((
+        "outer",
+        waf_map!(("inner", waf_array![waf_map!(("deep", 1_u64))]))
+    ));
+    let same = waf_map!((
+        "outer",
+        waf_map!(("inner", waf_array![waf_map!(("deep", 1_u64))]))
+    ));
+    let deep_key = waf_map!((
+        "outer",
+        waf_map!(("inner", waf_array![waf_map!(("peed", 1_u64))]))
+    ));
+    let deep_value = waf_map!((
+        "outer",
+        waf_map!(("inner", waf_array![waf_map!(("deep", 2_u64))]))
+    ));
+
+    assert_eq!(map, same);
+    assert_ne!(map, deep_key);
+    assert_ne!(map, deep_value);
+    assert_eq!(WafObject::from(map.clone()), same);
+    assert_ne!(WafObject::from(map.clone()), deep_key);
+
+    // Keys nested in the value of an entry are part of the value.
+    assert_eq!(map[0], same[0]);
+    assert_ne!(map[0], deep_key[0]);
+    assert!(!map[0].eq_ignore_key(&deep_key[0]));
+    assert_ne!(*map[0], *deep_key[0]);
+
+    // Entries are compared in order.
+    let ab = waf_map!(("a", 1_u64), ("b", 2_u64));
+    let ba = waf_map!(("b", 2_u64), ("a", 1_u64));
+    assert_ne!(ab, ba);
+}
+
+#[test]
+fn eq_null_coalescing() {
+    let eq = |left: WafMap, right: WafMap| {
+        let (left, right) = (WafObject::from(left), WafObject::from(right));
+        let result = left.eq_null_coalescing(&right);
+        assert_eq!(result, right.eq_null_coalescing(&left));
+        result
+    };
+
+    // A null entry is equal to a missing one, on either side.
+    assert!(eq(
+        waf_map!(("a", 1_u64), ("b", WafNull::new())),
+        waf_map!(("a", 1_u64))
+    ));
+    assert!(eq(waf_map!(("a", WafNull::new())), waf_map!()));
+    assert!(eq(
+        waf_map!(("a", WafNull::new()), ("b", 2_u64)),
+        waf_map!(("b", 2_u64), ("c", WafNull::new()))
+    ));
+    // Including in nested maps, and maps nested in arrays.
+    assert!(eq(
+        waf_map!(("outer", waf_array![waf_map!(("inner", WafNull::new()))])),
+        waf_map!(("outer", waf_array![waf_map!()]))
+    ));
+    // Entries are matched by key, regardless of their order.
+    assert!(eq(
+        waf_map!(("a", 1_u64), ("b", 2_u64)),
+        waf_map!(("b", 2_u64), ("a", 1_u64))
+    ));
+    // Entries that are present on both sides are still compared.
+    assert!(eq(
+        waf_map!(("a", WafNull::new())),
+        waf_map!(("a", WafNull::new()))
+    ));
+
+    // A null entry is not equal to any other value.
+    assert!(!eq(waf_map!(("a", WafNull::new())), waf_map!(("a", 1_u64))));
+    assert!(!eq(waf_map!(("a", WafNull::new())), waf_map!(("a", ""))));
+    assert!(!eq(
+        waf_map!(("a", WafNull::new())),
+        waf_map!(("a", waf_map!()))
+    ));
+    // A missing entry is only equal to a null one.
+    assert!(!eq(waf_map!(("a", 1_u64)), waf_map!()));
+    assert!(!eq(waf_map!(("a", 1_u64)), waf_map!(("b", 1_u64))));
+
+    // Nulls are not coalesced outside of map values.
+    let array = |items: WafArray| WafObject::from(items);
+    assert!(!array(waf_array![WafNull::new()]).eq_null_coalescing(&array(waf_array![])));
+    assert!(!array(waf_array![1_u64, WafNull::new()]).eq_null_coalescing(&array(waf_array![1_u64])));
+    assert!(WafObject::from(WafNull::new()).eq_null_coalescing(&WafNull::new().into()));
+    assert!(!WafObject::from(WafNull::new()).eq_null_coalescing(&WafObject::default()));
+}
@@ -0,0 +1,42 @@
+#![cfg(all(not(miri), ddwaf_has_from_json))]
+
+use libddwaf::object::{WafMap, WafObject, WafOwnedDefaultAllocator, WafOwnedOutputAllocator};
+use libddwaf::test_util::CountingAllocator;
+
+// The global allocator is also used for `WafOwnedOutputAllocator` values.
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn live_bytes() -> isize {
+    CountingAllocator::live_bytes().unwrap()
+}
+
+#[test]
+fn reset_releases_contents() {
+    // Never-populated values can be reset, any number of times.
+    let mut diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+    diagnostics.reset();
+    diagnostics.reset();
+    assert!(diagnostics.is_empty());
+
+    let mut empty = WafOwnedOutputAllocator::<WafObject>::default();
+    // Ensures the output allocator is initialized before measuring.
+    drop(WafObject::from_json("null"));
+    let baseline = live_bytes();
+    for json in [
+        r#"{"key": "a rather long string value", "list": [1, 2, 3]}"#,
+        r#"["another long string value, stored out of line"]"#,
+    ] {
+        let mut owned = WafObject::from_json(json).expect("valid JSON");
+        std::mem::swap(&mut empty, &mut owned);
+        drop(owned);
+        assert!(live_bytes() > baseline);
+        assert!(empty.is_valid());
+
+        empty.reset();
+        assert!(!empty.is_valid());
+        assert_eq!(live_bytes(), baseline);
+        empty.reset();
+        assert_eq!(live_bytes(), baseline);
+    }
+}
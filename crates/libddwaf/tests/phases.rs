@@ -0,0 +1,109 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::{WafMap, WafObject};
+use libddwaf::phases::{Action, RequestEvaluation};
+use libddwaf::{waf_array, waf_map, Builder, Config};
+
+fn ruleset() -> WafMap {
+    waf_map! {
+        ("version", "2.1"),
+        ("rules", waf_array![
+            waf_map!{
+                ("id", "scanner_rule"),
+                ("name", "Tag traces from scanners"),
+                ("tags", waf_map!{ ("category", "attack_attempt"), ("type", "security_scanner") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!{
+                                    ("address", "server.request.headers.no_cookies"),
+                                    ("key_path", waf_array!["user-agent"]),
+                                },
+                            ]),
+                            ("regex", "Arachni"),
+                        }),
+                    },
+                ]),
+                ("output", waf_map!{
+                    ("event", true),
+                    ("keep", true),
+                    ("attributes", waf_map!{
+                        ("_dd.appsec.trace.scanner", waf_map!{ ("value", "arachni") }),
+                    }),
+                }),
+            },
+            waf_map!{
+                ("id", "not_found_rule"),
+                ("name", "Block requests for missing resources"),
+                ("tags", waf_map!{ ("category", "attack_attempt"), ("type", "security_scanner") }),
+                ("conditions", waf_array![
+                    waf_map!{
+                        ("operator", "match_regex"),
+                        ("parameters", waf_map!{
+                            ("inputs", waf_array![
+                                waf_map!{ ("address", "server.response.status") },
+                            ]),
+                            ("regex", "^404$"),
+                        }),
+                    },
+                ]),
+                ("on_match", waf_array!["block"]),
+            },
+        ]),
+    }
+}
+
+#[test]
+fn request_phases() {
+    let mut builder = Builder::new(Some(&Config::default())).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", &ruleset(), None));
+    let waf = builder.build().unwrap();
+
+    let mut request = RequestEvaluation::new(&waf).with_budget(std::time::Duration::from_secs(1));
+
+    let headers = request.on_request_headers(waf_map!(("user-agent", "Arachni/v1.5.1")));
+    assert!(headers.error().is_none());
+    assert!(headers.is_match());
+    assert!(!headers.is_blocking());
+    assert!(!request.is_blocked());
+
+    let body = request.on_request_body(waf_map!(("comment", "hello")));
+    assert!(body.error().is_none());
+    assert!(!body.is_match());
+    assert!(body.actions().is_empty());
+
+    let response = request.on_response(404, waf_map!(("content-type", "text/html")));
+    assert!(response.is_match());
+    assert!(response.is_blocking());
+    assert!(matches!(
+        response.blocking_action(),
+        Some(Action::Block {
+            status_code: 403,
+            ..
+        })
+    ));
+    assert!(request.is_blocked());
+
+    let outcome = request.finish();
+    assert!(outcome.blocked());
+    assert_eq!(
+        outcome.blocking_action.as_ref().map(Action::name),
+        Some("block_request")
+    );
+    assert_eq!(outcome.events.len(), 2);
+    assert!(outcome.keep);
+    assert!(!outcome.timeout);
+    assert_eq!(
+        outcome
+            .attributes
+            .get_str("_dd.appsec.trace.scanner")
+            .map(|a| a.value()),
+        Some(&WafObject::from(waf_map!(("value", "arachni"))))
+    );
+    assert_eq!(
+        outcome.total_duration,
+        headers.duration() + body.duration() + response.duration()
+    );
+}
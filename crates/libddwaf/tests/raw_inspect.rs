@@ -0,0 +1,119 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::{WafArray, WafMap, WafObject};
+use libddwaf::raw_inspect::{node_count_bounded, summarize, MAX_DEPTH};
+use libddwaf::{waf_array, waf_map, waf_object};
+use libddwaf_sys::ddwaf_object;
+
+fn summary(obj: &impl AsRef<ddwaf_object>) -> String {
+    let mut buf = [0u8; 256];
+    let len = unsafe { summarize(obj.as_ref(), &mut buf) };
+    String::from_utf8(buf[..len].to_vec()).expect("summary should be ASCII")
+}
+
+fn fixtures() -> Vec<WafObject> {
+    vec![
+        WafObject::default(),
+        waf_object!(null),
+        true.into(),
+        (-7i64).into(),
+        42u64.into(),
+        1.5f64.into(),
+        "hello".into(),
+        "0123456789abcdefXYZ".into(),
+        "a\"b\\c\n\u{e9}".into(),
+        waf_array![1u64, "two", waf_array![3u64]].into(),
+        waf_map![("a", "hello"), ("b", waf_array![1u64, 2u64])].into(),
+        waf_map![
+            ("k1", 1u64),
+            ("k2", 2u64),
+            ("k3", 3u64),
+            ("k4", 4u64),
+            ("k5", 5u64)
+        ]
+        .into(),
+        WafArray::new(0).into(),
+        WafMap::new(0).into(),
+    ]
+}
+
+#[test]
+fn summarize_fixtures() {
+    let expected = [
+        "invalid",
+        "null",
+        "bool(true)",
+        "signed(-7)",
+        "unsigned(42)",
+        "float(1.5)",
+        r#"string(len=5, "hello")"#,
+        r#"string(len=19, "0123456789abcdef"...)"#,
+        r#"string(len=8, "a\"b\\c\x0a\xc3\xa9")"#,
+        "array(len=3)",
+        r#"map(len=2, keys=["a", "b"])"#,
+        r#"map(len=5, keys=["k1", "k2", "k3", "k4", ...])"#,
+        "array(len=0)",
+        "map(len=0)",
+    ];
+    for (obj, expected) in fixtures().iter().zip(expected) {
+        assert_eq!(summary(obj), expected);
+    }
+
+    let mut buf = [0u8; 16];
+    let len = unsafe { summarize(std::ptr::null(), &mut buf) };
+    assert_eq!(&buf[..len], b"<null>");
+}
+
+#[test]
+fn summarize_never_writes_past_the_buffer() {
+    const SENTINEL: u8 = 0xA5;
+    for obj in fixtures() {
+        let full = summary(&obj);
+        for size in 0..=full.len() + 8 {
+            let mut buf = vec![SENTINEL; size + 8];
+            let len = unsafe { summarize(obj.as_ref(), &mut buf[..size]) };
+            assert_eq!(len, full.len().min(size));
+            assert_eq!(&buf[..len], &full.as_bytes()[..len]);
+            assert!(buf[size..].iter().all(|&b| b == SENTINEL));
+        }
+    }
+}
+
+#[test]
+fn node_count() {
+    let obj: WafObject = waf_map![
+        ("a", "hello"),
+        ("b", waf_array![1u64, 2u64, waf_array![3u64]])
+    ]
+    .into();
+    let count = |max| unsafe { node_count_bounded(obj.as_ref(), max) };
+    assert_eq!(count(usize::MAX), 7);
+    assert_eq!(count(7), 7);
+    assert_eq!(count(3), 3);
+    assert_eq!(count(1), 1);
+    assert_eq!(count(0), 0);
+
+    let scalar = WafObject::from(42u64);
+    assert_eq!(
+        unsafe { node_count_bounded(scalar.as_ref(), usize::MAX) },
+        1
+    );
+    assert_eq!(
+        unsafe { node_count_bounded(std::ptr::null(), usize::MAX) },
+        0
+    );
+}
+
+#[test]
+fn node_count_is_bounded_in_depth() {
+    let mut obj: WafObject = WafArray::new(0).into();
+    for _ in 0..100 {
+        let mut array = WafArray::new(1);
+        array[0] = obj;
+        obj = array.into();
+    }
+    assert_eq!(
+        unsafe { node_count_bounded(obj.as_ref(), usize::MAX) },
+        MAX_DEPTH + 1
+    );
+}
@@ -0,0 +1,37 @@
+#![cfg(all(not(miri), feature = "bundled-ruleset"))]
+
+use std::time::Duration;
+
+use libddwaf::ruleset;
+use libddwaf::{waf_map, Builder, RunResult, RunnableContext};
+
+#[test]
+fn recommended_ruleset() {
+    assert!(ruleset::recommended().trim_start().starts_with('{'));
+    let parsed = ruleset::recommended_parsed();
+    assert!(parsed.get_str("rules").is_some());
+    // The ruleset is only parsed once.
+    assert!(std::ptr::eq(parsed, ruleset::recommended_parsed()));
+}
+
+#[test]
+fn scanner_user_agent_matches() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    let diagnostics = builder
+        .with_recommended_ruleset()
+        .expect("Failed to load the recommended ruleset");
+    assert!(diagnostics.get_str("rules").is_some());
+    assert!(builder
+        .config_paths(None)
+        .iter()
+        .any(|path| path.to_str() == Some(ruleset::RECOMMENDED_PATH)));
+    let waf = builder.build().expect("Failed to build the WAF");
+
+    let mut ctx = waf.new_context();
+    let data = waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni/v1.5.1"))
+    ));
+    let res = ctx.run(data, Duration::from_secs(1));
+    assert!(matches!(res, Ok(RunResult::Match(_))), "{res:?}");
+}
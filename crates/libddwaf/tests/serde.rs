@@ -2,7 +2,7 @@
 
 use libddwaf::{
     object::{WafArray, WafMap, WafObject, WafObjectType, WafString},
-    serde::{deserialize_with_limits, Limits},
+    serde::{deserialize_with_container_limits, deserialize_with_limits, ContainerLimits, Limits},
     waf_array, waf_map, waf_object,
 };
 use serde_json::from_str;
@@ -77,6 +77,26 @@ fn map_deserialization_wrong_type() {
     );
 }
 
+#[test]
+fn oversized_containers_deserialization() {
+    let json = format!("[{}0]", "0,".repeat(usize::from(u16::MAX) - 1));
+    let arr: WafArray = from_str::<WafObject>(&json)
+        .expect("array should fit")
+        .try_into()
+        .unwrap();
+    assert_eq!(arr.len(), u16::MAX);
+
+    let json = format!("[{}0]", "0,".repeat(usize::from(u16::MAX)));
+    let err = from_str::<WafObject>(&json).expect_err("array should be too large");
+    assert!(err.to_string().contains("out of range"), "{err}");
+
+    let entries: Vec<_> = (0..=u32::from(u16::MAX))
+        .map(|i| format!("\"{i}\":0"))
+        .collect();
+    let json = format!("{{{}}}", entries.join(","));
+    assert!(from_str::<WafObject>(&json).is_err());
+}
+
 #[test]
 fn sample_json_serialization() {
     let root = waf_array!(
@@ -593,3 +613,89 @@ fn limits_visit_bytes_with_element_exhaustion() {
     assert!(result.truncated);
     assert_eq!(result.value, waf_object!(null));
 }
+
+#[test]
+fn container_limits_within_limits() {
+    let limits = ContainerLimits {
+        max_container_depth: 3,
+        max_container_size: 3,
+    };
+    let json = r#"{"a": [1, {"b": "c"}], "d": null}"#;
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let obj = deserialize_with_container_limits(&mut deserializer, &limits)
+        .expect("document is within limits");
+    assert_eq!(
+        obj,
+        WafObject::from(waf_map!(
+            ("a", waf_array!(1_u64, waf_map!(("b", "c")))),
+            ("d", waf_object!(null))
+        ))
+    );
+}
+
+#[test]
+fn container_limits_over_depth() {
+    let limits = ContainerLimits {
+        max_container_depth: 3,
+        ..ContainerLimits::default()
+    };
+    let json = r#"{"a": [1, {"b": ["c"]}]}"#;
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let err = deserialize_with_container_limits(&mut deserializer, &limits)
+        .expect_err("document is too deep");
+    assert!(err.to_string().contains("depth"), "{err}");
+
+    // Scalars have no depth, and are accepted even with a zero depth limit.
+    let limits = ContainerLimits {
+        max_container_depth: 0,
+        ..ContainerLimits::default()
+    };
+    let mut deserializer = serde_json::Deserializer::from_str("42");
+    assert!(deserialize_with_container_limits(&mut deserializer, &limits).is_ok());
+    let mut deserializer = serde_json::Deserializer::from_str("[]");
+    assert!(deserialize_with_container_limits(&mut deserializer, &limits).is_err());
+}
+
+#[test]
+fn container_limits_over_size() {
+    let limits = ContainerLimits {
+        max_container_size: 2,
+        ..ContainerLimits::default()
+    };
+    for json in [
+        r#"[1, 2, 3]"#,
+        r#"{"a": 1, "b": 2, "c": 3}"#,
+        r#"[[1, 2, 3]]"#,
+    ] {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let err = deserialize_with_container_limits(&mut deserializer, &limits)
+            .expect_err("container is too large");
+        assert!(err.to_string().contains("size"), "{json}: {err}");
+    }
+}
+
+#[test]
+fn string_encoding_wrappers() {
+    use libddwaf::serde::{AsBytes, AsText};
+
+    let invalid = WafString::from(&b"caf\xC3\xA9 \xFF\xFE"[..]);
+    assert_eq!(
+        serde_json::to_value(AsText(&invalid)).unwrap(),
+        serde_json::json!("café \u{FFFD}\u{FFFD}")
+    );
+    assert_eq!(
+        serde_json::to_value(AsBytes(&invalid)).unwrap(),
+        serde_json::json!([99, 97, 102, 0xC3, 0xA9, 32, 0xFF, 0xFE])
+    );
+    // `AsText` is what `WafString` does by default.
+    assert_eq!(
+        serde_json::to_string(&invalid).unwrap(),
+        serde_json::to_string(&AsText(&invalid)).unwrap()
+    );
+
+    let valid = WafString::from("hello");
+    assert_eq!(
+        serde_json::to_string(&(AsText(&valid), AsBytes(&valid))).unwrap(),
+        r#"["hello",[104,101,108,108,111]]"#
+    );
+}
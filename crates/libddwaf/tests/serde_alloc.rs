@@ -0,0 +1,75 @@
+#![cfg(all(feature = "serde-deserialize", not(miri)))]
+
+use libddwaf::object::{WafArray, WafObject};
+use libddwaf::test_util::CountingAllocator;
+use libddwaf::{waf_array, waf_map};
+use serde_json::from_str;
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn count_allocations<T>(f: impl FnOnce() -> T) -> (T, u64) {
+    let before = CountingAllocator::allocations().unwrap();
+    let res = f();
+    (res, CountingAllocator::allocations().unwrap() - before)
+}
+
+#[test]
+fn small_arrays_reuse_scratch_buffers() {
+    const COUNT: u64 = 10_000;
+    let json = format!(
+        "[{}]",
+        (0..COUNT)
+            .map(|i| format!("[{i},{}]", i + 1))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+
+    let mut expected = WafArray::new(COUNT.try_into().unwrap());
+    for (i, item) in expected.iter_mut().enumerate() {
+        let i = i as u64;
+        *item = waf_array!(i, i + 1).into();
+    }
+
+    // The first document warms up this thread's scratch buffers.
+    let first: WafObject = from_str(&json).unwrap();
+    assert_eq!(first, expected);
+    drop(first);
+
+    let (second, allocations) = count_allocations(|| from_str::<WafObject>(&json).unwrap());
+    assert_eq!(second, expected);
+    // Each small array only allocates its own storage; allocating an intermediate buffer for each
+    // of them would at least double this.
+    assert!(
+        allocations < COUNT + 64,
+        "{allocations} allocations for {COUNT} arrays"
+    );
+}
+
+#[test]
+fn nested_containers_use_distinct_buffers() {
+    let json = r#"{"a": [1, {"b": [2, 3], "c": {"d": [4]}}, [5, [6, 7]]], "e": {}}"#;
+    let expected = waf_map!(
+        (
+            "a",
+            waf_array!(
+                1u64,
+                waf_map!(
+                    ("b", waf_array!(2u64, 3u64)),
+                    ("c", waf_map!(("d", waf_array!(4u64))))
+                ),
+                waf_array!(5u64, waf_array!(6u64, 7u64))
+            )
+        ),
+        ("e", waf_map!())
+    );
+    for _ in 0..3 {
+        let obj: WafObject = from_str(json).unwrap();
+        assert_eq!(obj, expected);
+    }
+
+    // Failed documents leave the scratch buffers empty.
+    assert!(from_str::<WafObject>(r#"{"a": [1, 2, {"b": [3,"#).is_err());
+    let obj: WafObject = from_str(json).unwrap();
+    assert_eq!(obj, expected);
+}
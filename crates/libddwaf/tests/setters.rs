@@ -0,0 +1,52 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::{WafBool, WafObject, WafString, WafUnsigned};
+use libddwaf::test_util::CountingAllocator;
+use libddwaf::waf_map;
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn live_bytes() -> isize {
+    CountingAllocator::live_bytes().unwrap()
+}
+
+const LONG: &str = "a string long enough to be stored out of line";
+const LONGER: &str = "another string, even longer, that is also stored out of line";
+
+#[test]
+fn setters_release_previous_contents() {
+    let baseline = live_bytes();
+
+    let mut string = WafString::from(LONG);
+    let after_new = live_bytes();
+    assert_eq!(after_new - baseline, LONG.len() as isize);
+
+    // Replacing out-of-line contents releases the previous buffer.
+    string.set(LONGER);
+    assert_eq!(live_bytes() - baseline, LONGER.len() as isize);
+    // Inline contents do not need a buffer at all.
+    string.set("short");
+    assert_eq!(live_bytes(), baseline);
+    string.set(LONG);
+    assert_eq!(live_bytes(), after_new);
+    drop(string);
+    assert_eq!(live_bytes(), baseline);
+
+    // The same holds for values nested in containers.
+    let mut map = waf_map!(("key", LONG), ("flag", false), ("count", 1u64));
+    let with_map = live_bytes();
+    for _ in 0..10 {
+        let value = map.get_str_mut("key").unwrap().value_mut();
+        value.as_type_mut::<WafString>().unwrap().set(LONGER);
+        value.as_type_mut::<WafString>().unwrap().set(LONG);
+        let flag = map.get_str_mut("flag").unwrap().value_mut();
+        flag.as_type_mut::<WafBool>().unwrap().set(true);
+        let count = map.get_str_mut("count").unwrap().value_mut();
+        count.as_type_mut::<WafUnsigned>().unwrap().set(2);
+    }
+    assert_eq!(live_bytes(), with_map);
+    let map = WafObject::from(map);
+    drop(map);
+    assert_eq!(live_bytes(), baseline);
+}
@@ -0,0 +1,97 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::{WafArray, WafObject};
+use libddwaf::test_util::{assert_no_leak, CountingAllocator};
+use libddwaf::{waf_array, waf_map};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const LONG: &str = "a string long enough to be stored out of line";
+
+#[test]
+fn sort_strings() {
+    let mut phrases = waf_array!["select", "union", LONG, "drop table", "Union", "select"];
+    phrases.sort_scalars();
+    assert_eq!(
+        phrases,
+        waf_array!["Union", LONG, "drop table", "select", "select", "union"]
+    );
+
+    // Strings compare byte-wise, whether or not they are valid UTF-8.
+    let mut bytes = waf_array![&b"\xFF"[..], "b", &b"a\x80"[..]];
+    bytes.sort_scalars();
+    assert_eq!(bytes, waf_array![&b"a\x80"[..], "b", &b"\xFF"[..]]);
+}
+
+#[test]
+fn sort_mixed_types() {
+    // Values are ordered by type first (signed, unsigned, string, bool, float), then by value.
+    let mut mixed = waf_array![2.5, "b", true, 3_u64, -1_i64, "a", false, 1_u64, -0.5];
+    mixed.sort_scalars();
+    assert_eq!(
+        mixed,
+        waf_array![-1_i64, 1_u64, 3_u64, "a", "b", false, true, -0.5, 2.5]
+    );
+
+    let mut custom = waf_array![1_u64, 3_u64, 2_u64];
+    custom.sort_unstable_by(|left, right| right.to_u64().cmp(&left.to_u64()));
+    assert_eq!(custom, waf_array![3_u64, 2_u64, 1_u64]);
+}
+
+#[test]
+fn dedup_header_values() {
+    let mut values = waf_array![
+        "text/html",
+        "text/html",
+        "application/json",
+        "text/html",
+        LONG,
+        LONG,
+        LONG,
+    ];
+    values.dedup_consecutive();
+    assert_eq!(
+        values,
+        waf_array!["text/html", "application/json", "text/html", LONG]
+    );
+    assert_eq!(values.capacity(), 7);
+
+    values.sort_scalars();
+    values.dedup_consecutive();
+    assert_eq!(values, waf_array![LONG, "application/json", "text/html"]);
+
+    // Containers are compared by content.
+    let mut nested = waf_array![
+        waf_map!(("a", 1_u64)),
+        waf_map!(("a", 1_u64)),
+        waf_map!(("b", 1_u64)),
+    ];
+    nested.dedup_consecutive();
+    assert_eq!(
+        nested,
+        waf_array![waf_map!(("a", 1_u64)), waf_map!(("b", 1_u64))]
+    );
+
+    let mut empty = WafArray::new(0);
+    empty.dedup_consecutive();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn no_leak() {
+    assert_no_leak(|| {
+        let mut values = waf_array![
+            LONG,
+            "short",
+            LONG,
+            waf_array![LONG],
+            waf_array![LONG],
+            LONG
+        ];
+        values.sort_scalars();
+        values.dedup_consecutive();
+        assert_eq!(values.len(), 3);
+        WafObject::from(values)
+    });
+}
@@ -0,0 +1,107 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::{Keyed, WafMap, WafObject};
+use libddwaf::test_util::CountingAllocator;
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// The names of the headers of a typical request, most of which are too long to be stored inline.
+const HEADERS: [&str; 40] = [
+    "accept",
+    "accept-encoding",
+    "accept-language",
+    "authorization",
+    "cache-control",
+    "cf-connecting-ip",
+    "connection",
+    "content-length",
+    "content-type",
+    "cookie",
+    "dnt",
+    "forwarded",
+    "host",
+    "if-modified-since",
+    "if-none-match",
+    "origin",
+    "pragma",
+    "referer",
+    "sec-ch-ua",
+    "sec-ch-ua-mobile",
+    "sec-ch-ua-platform",
+    "sec-fetch-dest",
+    "sec-fetch-mode",
+    "sec-fetch-site",
+    "sec-fetch-user",
+    "true-client-ip",
+    "upgrade-insecure-requests",
+    "user-agent",
+    "via",
+    "x-amzn-trace-id",
+    "x-client-ip",
+    "x-cluster-client-ip",
+    "x-datadog-parent-id",
+    "x-datadog-trace-id",
+    "x-forwarded",
+    "x-forwarded-for",
+    "x-forwarded-proto",
+    "x-real-ip",
+    "x-request-id",
+    "x-requested-with",
+];
+
+/// Returns the number of allocations performed by `make`, excluding the release of its result.
+fn allocations<T>(make: impl FnOnce() -> T) -> u64 {
+    let before = CountingAllocator::allocations().unwrap();
+    let value = make();
+    let count = CountingAllocator::allocations().unwrap() - before;
+    drop(value);
+    count
+}
+
+#[test]
+fn static_and_boxed_keys_save_allocations() {
+    // Keys of at most 14 bytes are stored inline by all constructors.
+    let out_of_line = HEADERS.iter().filter(|name| name.len() > 14).count();
+    let out_of_line = u64::try_from(out_of_line).unwrap();
+    assert_eq!(out_of_line, 14);
+
+    let copied = allocations(|| {
+        let mut map = WafMap::new(40);
+        for (i, name) in HEADERS.into_iter().enumerate() {
+            map[i] = Keyed::from((name, 1_u64));
+        }
+        map
+    });
+    let static_keys = allocations(|| {
+        let mut map = WafMap::new(40);
+        for (i, name) in HEADERS.into_iter().enumerate() {
+            map[i] = Keyed::with_static_key(name, WafObject::from(1_u64));
+        }
+        map
+    });
+    // Only the map's storage is allocated.
+    assert_eq!(static_keys, 1);
+    assert_eq!(copied - static_keys, out_of_line);
+
+    // Keys computed at runtime (here, normalized header names) are moved rather than copied.
+    let received: Vec<String> = HEADERS.iter().map(|name| name.to_uppercase()).collect();
+    let copied = allocations(|| {
+        let mut map = WafMap::new(40);
+        for (i, name) in received.iter().enumerate() {
+            let key = name.to_ascii_lowercase();
+            map[i] = Keyed::from((key.as_str(), 1_u64));
+        }
+        map
+    });
+    let boxed = allocations(|| {
+        let mut map = WafMap::new(40);
+        for (i, name) in received.iter().enumerate() {
+            let key = name.to_ascii_lowercase().into_bytes().into_boxed_slice();
+            map[i] = Keyed::from_key_bytes(key, WafObject::from(1_u64));
+        }
+        map
+    });
+    assert_eq!(boxed, 1 + 40);
+    assert_eq!(copied - boxed, out_of_line);
+}
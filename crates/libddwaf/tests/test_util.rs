@@ -0,0 +1,58 @@
+#![cfg(not(miri))]
+
+use libddwaf::object::raw::AsRawMutObject;
+use libddwaf::object::{Keyed, WafMap, WafObject, WafString};
+use libddwaf::test_util::{assert_deep_eq, assert_no_leak, deep_eq, CountingAllocator};
+use libddwaf::{waf_array, waf_map};
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const LONG: &str = "a string long enough to be stored out of line";
+
+#[test]
+fn well_formed_objects_do_not_leak() {
+    assert_no_leak(|| WafObject::from(LONG));
+    assert_no_leak(|| waf_array![LONG, 1u64, waf_array![LONG]]);
+    assert_no_leak(|| waf_map!((LONG, LONG), ("nested", waf_map!((LONG, true)))));
+    assert_no_leak(|| {
+        let mut map = waf_map!((LONG, 1u64));
+        map[0].set_key_boxed(LONG.as_bytes().into());
+        map
+    });
+}
+
+#[test]
+#[should_panic(expected = "bytes were leaked")]
+fn leaked_key_is_detected() {
+    assert_no_leak(|| {
+        let mut map = WafMap::new(1);
+        map[0] = Keyed::new(WafString::from(LONG), WafObject::from(1u64));
+        // Overwriting the key without releasing it first leaks its out-of-line contents.
+        let short = WafString::from("short");
+        unsafe { *map[0].key_mut().as_raw_mut() = *short.as_ref() };
+        map
+    });
+}
+
+#[test]
+fn deep_eq_ignores_map_order() {
+    let left: WafObject = waf_map!(("a", 1u64), ("b", waf_array![LONG, 2u64])).into();
+    let right: WafObject = waf_map!(("b", waf_array![LONG, 2u64]), ("a", 1u64)).into();
+    assert_ne!(left, right);
+    assert!(deep_eq(&left, &right));
+    assert_deep_eq(&left, &right);
+
+    let reordered: WafObject = waf_map!(("a", 1u64), ("b", waf_array![2u64, LONG])).into();
+    assert!(!deep_eq(&left, &reordered));
+    let duplicated: WafObject = waf_map!(("a", 1u64), ("a", 1u64)).into();
+    assert!(!deep_eq(&left, &duplicated));
+}
+
+#[test]
+#[should_panic(expected = "$.b[0]")]
+fn assert_deep_eq_reports_differences() {
+    let left: WafObject = waf_map!(("a", 1u64), ("b", waf_array![LONG])).into();
+    let right: WafObject = waf_map!(("b", waf_array!["other"]), ("a", 1u64)).into();
+    assert_deep_eq(&left, &right);
+}
@@ -0,0 +1,126 @@
+#![cfg(not(miri))]
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use libddwaf::test_util::{assert_blocks, assert_matches_rule, assert_no_match, fixtures, TestWaf};
+use libddwaf::{waf_array, waf_map};
+
+/// Returns the message of the panic caused by `f`.
+fn panic_message(f: impl FnOnce()) -> String {
+    let payload = catch_unwind(AssertUnwindSafe(f)).expect_err("expected a panic");
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .unwrap_or_default(),
+    }
+}
+
+#[test]
+fn arachni_rule() {
+    let waf = TestWaf::new(&fixtures::arachni_rule());
+    let output = assert_matches_rule(
+        &waf,
+        waf_map!((
+            "server.request.headers.no_cookies",
+            waf_map!(("user-agent", "Arachni/v1.5"))
+        )),
+        "arachni_rule",
+    );
+    assert!(output.keep());
+    assert_blocks(&waf, waf_map!(("server.request.body", "Arachni")));
+    assert_no_match(&waf, waf_map!(("server.request.body", "Mozilla/5.0")));
+}
+
+#[test]
+fn recommended_subset() {
+    let waf = TestWaf::new(&fixtures::recommended_subset());
+    assert_matches_rule(
+        &waf,
+        waf_map!((
+            "server.request.query",
+            waf_map!(("q", "<script>alert(1)</script>"))
+        )),
+        "crs-941-110",
+    );
+    assert_matches_rule(
+        &waf,
+        waf_map!(("server.request.body", waf_map!(("id", "1' OR '1'='1' --")))),
+        "crs-942-100",
+    );
+    assert_matches_rule(
+        &waf,
+        waf_map!(("server.request.uri.raw", "/static/../../etc/passwd")),
+        "crs-930-100",
+    );
+    assert_matches_rule(
+        &waf,
+        waf_map!((
+            "server.request.headers.no_cookies",
+            waf_map!(("user-agent", waf_array!["Arachni/v1.5"]))
+        )),
+        "ua0-600-12x",
+    );
+    assert_no_match(
+        &waf,
+        waf_map!(
+            ("server.request.query", waf_map!(("q", "shoes"))),
+            ("server.request.uri.raw", "/products?q=shoes")
+        ),
+    );
+}
+
+#[test]
+fn failure_messages() {
+    let waf = TestWaf::new(&fixtures::arachni_rule());
+    let harmless = || waf_map!(("server.request.body", "harmless"));
+    let attack = || waf_map!(("server.request.body", "Arachni"));
+
+    assert_eq!(
+        panic_message(|| {
+            assert_matches_rule(&waf, harmless(), "arachni_rule");
+        }),
+        "expected rule `arachni_rule` to match, but no rule matched"
+    );
+    assert_eq!(
+        panic_message(|| {
+            assert_blocks(&waf, harmless());
+        }),
+        "expected the data to be blocked, but the actions were [] and no rule matched"
+    );
+
+    // The events of unexpected matches are pretty-printed.
+    let message = panic_message(|| {
+        assert_no_match(&waf, attack());
+    });
+    assert!(
+        message.starts_with(
+            "expected no rule to match, but the matched rules were [\"arachni_rule\"]; events:\n{\n"
+        ),
+        "{message}"
+    );
+    assert!(
+        message.contains("\n    \"id\": \"arachni_rule\",\n"),
+        "{message}"
+    );
+    assert!(
+        message.contains("\"address\": \"server.request.body\""),
+        "{message}"
+    );
+    let message = panic_message(|| {
+        assert_matches_rule(&waf, attack(), "another_rule");
+    });
+    assert!(
+        message.starts_with(
+            "expected rule `another_rule` to match, but the matched rules were [\"arachni_rule\"]"
+        ),
+        "{message}"
+    );
+}
+
+#[test]
+#[should_panic(expected = "failed to load the ruleset; diagnostics:")]
+fn invalid_ruleset() {
+    let _ = TestWaf::new(&waf_map!(("rules", waf_array![])));
+}
@@ -0,0 +1,71 @@
+#![cfg(not(miri))]
+
+use std::time::Duration;
+
+use libddwaf::{
+    evaluate_once,
+    object::{WafMap, WafOwnedDefaultAllocator},
+    waf_array, waf_map, RulesetTester, RunResult,
+};
+
+mod common;
+
+#[test]
+fn ruleset_tester() {
+    let mut diagnostics = WafOwnedDefaultAllocator::<WafMap>::default();
+    let tester = RulesetTester::new(&*common::ARACHNI_RULE, Some(&mut diagnostics))
+        .expect("ruleset should load")
+        .with_timeout(Duration::from_secs(2));
+    assert!(diagnostics.get_str("rules").is_some());
+
+    let outcome = tester
+        .test(waf_map! {
+            ("server.request.headers.no_cookies", waf_map!{ ("user-agent", "Arachni/v1") }),
+        })
+        .expect("evaluation should succeed");
+    assert_eq!(outcome.matched_rules, ["arachni_rule"]);
+    assert!(outcome.matched("arachni_rule"));
+    assert_eq!(outcome.actions, ["block_request"]);
+    assert!(outcome.duration > Duration::ZERO);
+
+    let outcome = tester
+        .test(waf_map! {
+            ("server.request.body", waf_array!["Arachni"]),
+        })
+        .expect("evaluation should succeed");
+    assert_eq!(outcome.matched_rules, ["arachni_rule"]);
+
+    // Each evaluation uses a fresh context, so the same data matches again.
+    let outcome = tester
+        .test(waf_map! {
+            ("server.request.body", waf_array!["Arachni"]),
+        })
+        .expect("evaluation should succeed");
+    assert_eq!(outcome.matched_rules, ["arachni_rule"]);
+
+    let outcome = tester
+        .test(waf_map! {
+            ("server.request.headers.no_cookies", waf_map!{ ("user-agent", "Mozilla/5.0") }),
+        })
+        .expect("evaluation should succeed");
+    assert!(outcome.matched_rules.is_empty());
+    assert!(!outcome.matched("arachni_rule"));
+    assert!(outcome.actions.is_empty());
+}
+
+#[test]
+fn ruleset_tester_invalid_ruleset() {
+    assert!(RulesetTester::new(&waf_map! { ("rules", waf_array![]) }, None).is_none());
+}
+
+#[test]
+fn evaluate_once_with_handle() {
+    let tester = RulesetTester::new(&*common::ARACHNI_RULE, None).expect("ruleset should load");
+    let data = waf_map! {
+        ("server.request.headers.no_cookies", waf_map!{ ("user-agent", "Arachni") }),
+    };
+    match evaluate_once(tester.handle(), data, Duration::from_secs(1)) {
+        Ok(RunResult::Match(output)) => assert!(output.events().is_some()),
+        res => panic!("Unexpected result: {res:?}"),
+    }
+}
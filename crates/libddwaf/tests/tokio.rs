@@ -0,0 +1,33 @@
+#![cfg(all(feature = "tokio", not(miri)))]
+
+use std::time::Duration;
+
+use libddwaf::{waf_map, Builder, RunResult};
+
+use common::ARACHNI_RULE;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn run_async() {
+    let mut builder = Builder::new(None).expect("Failed to create builder");
+    assert!(builder.add_or_update_config("rules", &*ARACHNI_RULE, None));
+    let waf = builder.build().unwrap();
+
+    let ctx = waf.new_context();
+    let data = waf_map!((
+        "server.request.headers.no_cookies",
+        waf_map!(("user-agent", "Arachni/v1"))
+    ));
+    // The future is Send, so it can be spawned on the runtime.
+    let (ctx, res) = tokio::spawn(ctx.run_async(data, Duration::from_secs(1)))
+        .await
+        .unwrap();
+    assert!(matches!(res, Ok(RunResult::Match(_))), "{res:?}");
+    assert_eq!(ctx.run_count(), 1);
+
+    // The context keeps its data across evaluations.
+    let (ctx, res) = ctx.run_async(waf_map!(), Duration::from_secs(1)).await;
+    assert!(matches!(res, Ok(RunResult::NoMatch(_))), "{res:?}");
+    assert_eq!(ctx.run_count(), 2);
+}
@@ -0,0 +1,292 @@
+#![cfg(not(miri))]
+
+use std::ops::ControlFlow;
+
+use libddwaf::object::{
+    walk, walk_mut, WafArray, WafMap, WafObject, WafPath, WafPathSegment, WafString, WafVisitor,
+    WafVisitorMut,
+};
+use libddwaf::{waf_array, waf_map};
+
+/// Records every event of the walk, as `<event> <path>`.
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+}
+impl<'a> WafVisitor<'a> for Recorder {
+    fn visit_scalar(&mut self, path: &WafPath<'a>, _: &'a WafObject) -> ControlFlow<()> {
+        self.events.push(format!("scalar {path}"));
+        ControlFlow::Continue(())
+    }
+
+    fn enter_array(&mut self, path: &WafPath<'a>, _: &'a WafArray) -> ControlFlow<()> {
+        self.events.push(format!("enter_array {path}"));
+        ControlFlow::Continue(())
+    }
+
+    fn leave_array(&mut self, path: &WafPath<'a>, _: &'a WafArray) -> ControlFlow<()> {
+        self.events.push(format!("leave_array {path}"));
+        ControlFlow::Continue(())
+    }
+
+    fn enter_map(&mut self, path: &WafPath<'a>, _: &'a WafMap) -> ControlFlow<()> {
+        self.events.push(format!("enter_map {path}"));
+        ControlFlow::Continue(())
+    }
+
+    fn leave_map(&mut self, path: &WafPath<'a>, _: &'a WafMap) -> ControlFlow<()> {
+        self.events.push(format!("leave_map {path}"));
+        ControlFlow::Continue(())
+    }
+}
+
+#[test]
+fn walk_reports_paths() {
+    let obj: WafObject = waf_map![
+        ("a", "hello"),
+        ("b", waf_array![1u64, waf_map![("c", true)]]),
+        ("d", WafMap::new(0))
+    ]
+    .into();
+    let mut recorder = Recorder::default();
+    assert_eq!(walk(&obj, &mut recorder), ControlFlow::Continue(()));
+    assert_eq!(
+        recorder.events,
+        [
+            "enter_map $",
+            "scalar $.a",
+            "enter_array $.b",
+            "scalar $.b[0]",
+            "enter_map $.b[1]",
+            "scalar $.b[1].c",
+            "leave_map $.b[1]",
+            "leave_array $.b",
+            "enter_map $.d",
+            "leave_map $.d",
+            "leave_map $",
+        ]
+    );
+
+    let mut recorder = Recorder::default();
+    let _ = walk(&WafObject::from(42u64), &mut recorder);
+    assert_eq!(recorder.events, ["scalar $"]);
+}
+
+#[test]
+fn walk_stops_on_break() {
+    struct StopAfter {
+        remaining: usize,
+        seen: Vec<String>,
+    }
+    impl<'a> WafVisitor<'a> for StopAfter {
+        fn visit_scalar(&mut self, path: &WafPath<'a>, _: &'a WafObject) -> ControlFlow<()> {
+            self.seen.push(path.to_string());
+            self.remaining -= 1;
+            if self.remaining == 0 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    let obj: WafObject = waf_array![1u64, waf_map![("key", 2u64), ("other", 3u64)], 4u64].into();
+    let mut visitor = StopAfter {
+        remaining: 2,
+        seen: Vec::new(),
+    };
+    assert_eq!(walk(&obj, &mut visitor), ControlFlow::Break(()));
+    assert_eq!(visitor.seen, ["$[0]", "$[1].key"]);
+}
+
+#[test]
+fn walk_breaks_with_value() {
+    struct FirstString {
+        visited: usize,
+    }
+    impl<'a> WafVisitor<'a, (String, &'a WafString)> for FirstString {
+        fn visit_scalar(
+            &mut self,
+            path: &WafPath<'a>,
+            obj: &'a WafObject,
+        ) -> ControlFlow<(String, &'a WafString)> {
+            self.visited += 1;
+            match obj.as_type::<WafString>() {
+                Some(string) => ControlFlow::Break((path.to_string(), string)),
+                None => ControlFlow::Continue(()),
+            }
+        }
+    }
+
+    let obj: WafObject = waf_map![
+        ("id", 1u64),
+        ("user", waf_map![("admin", false), ("name", "alice")]),
+        ("tags", waf_array!["a", "b", "c"])
+    ]
+    .into();
+    let mut visitor = FirstString { visited: 0 };
+    let ControlFlow::Break((path, string)) = walk(&obj, &mut visitor) else {
+        panic!("Expected a string to be found");
+    };
+    assert_eq!(path, "$.user.name");
+    assert_eq!(string.as_bytes(), b"alice");
+    // The tags were never visited.
+    assert_eq!(visitor.visited, 3);
+}
+
+#[test]
+fn walk_mut_modifies_scalars() {
+    struct Redact {
+        visited: Vec<String>,
+    }
+    impl WafVisitorMut for Redact {
+        fn visit_scalar_mut(&mut self, path: &WafPath<'_>, obj: &mut WafObject) -> ControlFlow<()> {
+            self.visited.push(path.to_string());
+            if path.last() == Some(WafPathSegment::Key(b"password")) {
+                *obj = "<redacted>".into();
+            } else if let Some(string) = obj.as_type_mut::<WafString>() {
+                let upper = string.as_bytes().to_ascii_uppercase();
+                string.set(upper);
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    let mut obj: WafObject = waf_map![
+        ("user", "alice"),
+        ("password", "hunter2"),
+        ("tags", waf_array!["a", 1u64, waf_map![("password", 42u64)]])
+    ]
+    .into();
+    let mut visitor = Redact {
+        visited: Vec::new(),
+    };
+    assert_eq!(walk_mut(&mut obj, &mut visitor), ControlFlow::Continue(()));
+    assert_eq!(
+        visitor.visited,
+        [
+            "$.user",
+            "$.password",
+            "$.tags[0]",
+            "$.tags[1]",
+            "$.tags[2].password"
+        ]
+    );
+    let expected: WafObject = waf_map![
+        ("user", "ALICE"),
+        ("password", "<redacted>"),
+        (
+            "tags",
+            waf_array!["A", 1u64, waf_map![("password", "<redacted>")]]
+        )
+    ]
+    .into();
+    assert_eq!(obj, expected);
+}
+
+#[test]
+fn for_each_mut_helpers() {
+    let mut obj: WafObject = waf_map![
+        ("name", "alice"),
+        ("bytes", WafString::from(&b"caf\xE9"[..])),
+        (
+            "nested",
+            waf_array!["a", 1u64, waf_map![("deeper", waf_array!["b", true])]]
+        ),
+        ("empty", WafMap::new(0))
+    ]
+    .into();
+
+    let mut count = 0;
+    obj.for_each_string_mut(|string| {
+        count += 1;
+        let upper = string.as_bytes().to_ascii_uppercase();
+        string.set(upper);
+    });
+    assert_eq!(count, 4);
+
+    // Scalars of any type can be replaced.
+    obj.for_each_scalar_mut(|obj| {
+        if let Some(value) = obj.to_u64() {
+            *obj = (value + 1).into();
+        } else if obj.to_bool().is_some() {
+            *obj = "was a bool".into();
+        }
+    });
+
+    let expected: WafObject = waf_map![
+        ("name", "ALICE"),
+        ("bytes", WafString::from(&b"CAF\xE9"[..])),
+        (
+            "nested",
+            waf_array![
+                "A",
+                2u64,
+                waf_map![("deeper", waf_array!["B", "was a bool"])]
+            ]
+        ),
+        ("empty", WafMap::new(0))
+    ]
+    .into();
+    assert_eq!(obj, expected);
+}
+
+#[test]
+fn stats() {
+    let obj: WafObject = waf_map![
+        ("a", "hello"),
+        ("b", waf_array![1u64, waf_map![("c", "world!")]]),
+        ("d", WafMap::new(0))
+    ]
+    .into();
+    let stats = obj.stats();
+    assert_eq!(stats.nodes, 7);
+    assert_eq!(stats.containers, 4);
+    assert_eq!(stats.strings, 2);
+    assert_eq!(stats.string_bytes, 11);
+    assert_eq!(stats.max_depth, 3);
+    assert_eq!((stats.keys, stats.key_bytes), (4, 4));
+    assert_eq!(
+        stats.estimated_bytes(),
+        (7 + 4) * std::mem::size_of::<WafObject>() + 11 + 4
+    );
+
+    let stats = WafObject::from(1.5f64).stats();
+    assert_eq!((stats.nodes, stats.containers, stats.max_depth), (1, 0, 0));
+}
+
+#[test]
+fn walk_deep_tree() {
+    const DEPTH: usize = 100_000;
+
+    let mut obj: WafObject = WafArray::new(0).into();
+    for _ in 0..DEPTH {
+        let mut array = WafArray::new(1);
+        array[0] = obj;
+        obj = array.into();
+    }
+
+    let stats = obj.stats();
+    assert_eq!(stats.nodes, DEPTH + 1);
+    assert_eq!(stats.containers, DEPTH + 1);
+    assert_eq!(stats.max_depth, DEPTH);
+
+    struct Count(usize);
+    impl WafVisitorMut for Count {
+        fn visit_scalar_mut(&mut self, _: &WafPath<'_>, _: &mut WafObject) -> ControlFlow<()> {
+            self.0 += 1;
+            ControlFlow::Continue(())
+        }
+    }
+    let mut count = Count(0);
+    assert_eq!(walk_mut(&mut obj, &mut count), ControlFlow::Continue(()));
+    assert_eq!(count.0, 0);
+
+    // Dropping is recursive, so the tree is taken apart one level at a time.
+    while let Some(array) = obj.as_type_mut::<WafArray>() {
+        if array.is_empty() {
+            break;
+        }
+        obj = std::mem::take(&mut array[0]);
+    }
+}